@@ -92,6 +92,15 @@ pub struct CompilationCtx<'a, F: FeatureProvider, V: VariationInfo> {
     conditionset_defs: ConditionSetMap,
     mark_attach_class_id: HashMap<GlyphSet, u16>,
     mark_filter_sets: HashMap<GlyphSet, FilterSetId>,
+    /// For features containing an insertion marker comment, the id the next
+    /// lookup in the feature's table had at the marker.
+    ///
+    /// Lookups added by the feature writer for these features are inserted at
+    /// this position, rather than appended.
+    insert_markers: HashMap<Tag, LookupId>,
+    /// The next (GSUB, GPOS) lookup ids at an insertion marker in the active
+    /// feature; which one applies is known once the feature has ended.
+    pending_marker: Option<(LookupId, LookupId)>,
 }
 
 impl<'a, F: FeatureProvider, V: VariationInfo> CompilationCtx<'a, F, V> {
@@ -124,6 +133,8 @@ impl<'a, F: FeatureProvider, V: VariationInfo> CompilationCtx<'a, F, V> {
             script: Default::default(),
             mark_attach_class_id: Default::default(),
             mark_filter_sets: Default::default(),
+            insert_markers: Default::default(),
+            pending_marker: Default::default(),
             opts,
         }
     }
@@ -268,10 +279,18 @@ impl<'a, F: FeatureProvider, V: VariationInfo> CompilationCtx<'a, F, V> {
             return;
         };
 
+        let existing_features = self
+            .features
+            .iter()
+            .map(|(key, _)| key.feature)
+            .chain(self.insert_markers.keys().copied())
+            .collect();
         let mut builder = FeatureBuilder::new(
             &self.default_lang_systems,
             &mut self.tables,
             self.mark_filter_sets.len(),
+            existing_features,
+            self.insert_markers.keys().copied().collect(),
         );
         writer.add_features(&mut builder);
 
@@ -281,7 +300,49 @@ impl<'a, F: FeatureProvider, V: VariationInfo> CompilationCtx<'a, F, V> {
             .features
             .values_mut()
             .for_each(|feat| feat.base.iter_mut().for_each(|id| *id = id_map.get(*id)));
+
+        // lookups for features with an insertion marker move to the marker.
+        // A lookup shared by several features goes to the first marker.
+        let mut moves = HashMap::new();
+        for (key, feat) in builder.features.iter() {
+            // the feature writer only adds GPOS lookups
+            let Some(LookupId::Gpos(marker)) = self.insert_markers.get(&key.feature) else {
+                continue;
+            };
+            for id in feat.base.iter() {
+                if let LookupId::Gpos(idx) = id {
+                    moves.entry(*idx).or_insert(*marker);
+                }
+            }
+        }
         self.features.merge_external_features(builder.features);
+        if !moves.is_empty() {
+            let id_map = self.lookups.move_gpos_lookups(&moves);
+            self.features.remap_lookup_ids(&id_map);
+        }
+    }
+
+    /// Note the position of an insertion marker in the active feature
+    ///
+    /// ufo2ft allows a comment beginning "# Automatic Code" in a feature block
+    /// to indicate where generated lookups should go.
+    /// See <https://github.com/googlefonts/ufo2ft/blob/main/Lib/ufo2ft/featureWriters/baseFeatureWriter.py>
+    fn add_insertion_marker(&mut self, tag: Tag, range: Range<usize>) {
+        // lookups so far precede the generated ones, so finish any in progress
+        if let Some((id, _name)) = self.lookups.finish_current() {
+            self.add_lookup_to_current_feature_if_present(id);
+        }
+        if self.insert_markers.contains_key(&tag) || self.pending_marker.is_some() {
+            self.warning(
+                range,
+                format!("ignoring additional insertion marker in '{tag}'"),
+            );
+            return;
+        }
+        self.pending_marker = Some((
+            LookupId::Gsub(self.lookups.next_gsub_index()),
+            LookupId::Gpos(self.lookups.next_gpos_index()),
+        ));
     }
 
     /// Infer/update GDEF table as required.
@@ -377,6 +438,19 @@ impl<'a, F: FeatureProvider, V: VariationInfo> CompilationCtx<'a, F, V> {
             self.add_lookup_to_current_feature_if_present(id);
         }
         let active = self.active_feature.take().expect("always present");
+        if let Some((gsub, gpos)) = self.pending_marker.take() {
+            // the marker indexes the table the feature's lookups are in. A
+            // feature without lookups of its own can only get them from the
+            // feature writer, which writes GPOS.
+            let is_gsub = active
+                .lookup_ids()
+                .any(|id| matches!(id, LookupId::Gsub(_)))
+                && !active
+                    .lookup_ids()
+                    .any(|id| matches!(id, LookupId::Gpos(_)));
+            let marker = if is_gsub { gsub } else { gpos };
+            self.insert_markers.insert(active.tag(), marker);
+        }
         active.add_to_features(&mut self.features);
 
         self.vertical_feature.end_feature();
@@ -1245,8 +1319,12 @@ impl<'a, F: FeatureProvider, V: VariationInfo> CompilationCtx<'a, F, V> {
         } else if tags::is_character_variant(tag_raw) {
             self.resolve_character_variant_feature(tag_raw, &feature);
         } else {
-            for item in feature.statements() {
-                self.resolve_statement(item);
+            for item in feature.statements_and_comments() {
+                if item.kind() != Kind::Comment {
+                    self.resolve_statement(item);
+                } else if item.token_text().is_some_and(is_insertion_marker) {
+                    self.add_insertion_marker(tag_raw, item.range());
+                }
             }
         }
         self.end_feature();
@@ -2101,8 +2179,23 @@ fn get_reasonable_length_span(node: &NodeOrToken) -> Range<usize> {
     }
 }
 
+/// Whether a comment is a ufo2ft feature writer insertion marker
+fn is_insertion_marker(comment: &str) -> bool {
+    comment
+        .trim_start_matches('#')
+        .trim_start()
+        .starts_with("Automatic Code")
+}
+
 #[cfg(test)]
 mod tests {
+    use std::ffi::OsStr;
+
+    use crate::{
+        compile::{NopFeatureProvider, NopVariationInfo},
+        parse::ParseContext,
+    };
+
     use super::*;
 
     fn glyph_id_vec<const N: usize>(ids: [u16; N]) -> Vec<GlyphId> {
@@ -2129,4 +2222,36 @@ mod tests {
             ]
         );
     }
+
+    // a marker indexes the lookup list of the table its feature is in
+    #[test]
+    fn insertion_marker_table() {
+        let fea = "\
+            feature liga {\n sub f i by f_i;\n} liga;\n\
+            feature ccmp {\n sub a by b;\n # Automatic Code\n sub b by a;\n} ccmp;\n\
+            feature kern {\n pos a b 10;\n # Automatic Code\n} kern;\n";
+        let glyph_map = crate::compile::parse_glyph_order(".notdef\na\nb\nf\ni\nf_i").unwrap();
+        let (tree, _) = ParseContext::parse(
+            "features.fea".into(),
+            Some(&glyph_map),
+            Box::new(move |_: &OsStr| Ok(fea.into())),
+        )
+        .unwrap()
+        .generate_parse_tree();
+        let mut ctx = CompilationCtx::<NopFeatureProvider, NopVariationInfo>::new(
+            &glyph_map,
+            tree.source_map(),
+            None,
+            None,
+            Default::default(),
+        );
+        ctx.compile(&tree.typed_root());
+        assert_eq!(
+            HashMap::from([
+                (Tag::new(b"ccmp"), LookupId::Gsub(2)),
+                (Tag::new(b"kern"), LookupId::Gpos(1)),
+            ]),
+            ctx.insert_markers
+        );
+    }
 }
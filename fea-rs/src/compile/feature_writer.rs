@@ -1,6 +1,6 @@
 //! API for the client to manually add additional features
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use write_fonts::{tables::layout::LookupFlag, types::Tag};
 
@@ -35,6 +35,8 @@ pub struct FeatureBuilder<'a> {
     mark_filter_sets: HashMap<GlyphSet, FilterSetId>,
     // because there may already be defined filter sets from the root fea
     filter_set_id_start: usize,
+    existing_features: HashSet<Tag>,
+    insert_markers: HashSet<Tag>,
 }
 
 pub trait GposSubtableBuilder: Sized {
@@ -56,6 +58,8 @@ impl<'a> FeatureBuilder<'a> {
         language_systems: &'a DefaultLanguageSystems,
         tables: &'a mut Tables,
         filter_set_id_start: usize,
+        existing_features: HashSet<Tag>,
+        insert_markers: HashSet<Tag>,
     ) -> Self {
         Self {
             language_systems,
@@ -64,6 +68,8 @@ impl<'a> FeatureBuilder<'a> {
             features: Default::default(),
             mark_filter_sets: Default::default(),
             filter_set_id_start,
+            existing_features,
+            insert_markers,
         }
     }
 
@@ -77,6 +83,20 @@ impl<'a> FeatureBuilder<'a> {
         self.tables.gdef.as_ref()
     }
 
+    /// Whether the FEA defines a feature with this tag
+    pub fn has_feature(&self, tag: Tag) -> bool {
+        self.existing_features.contains(&tag)
+    }
+
+    /// Whether a feature with this tag in the FEA contains an insertion marker.
+    ///
+    /// An insertion marker is a comment beginning `# Automatic Code`, as used
+    /// by ufo2ft. Lookups added to such a feature are placed at the marker
+    /// in the lookup list, instead of after all the lookups in the FEA.
+    pub fn has_insertion_marker(&self, tag: Tag) -> bool {
+        self.insert_markers.contains(&tag)
+    }
+
     /// Create a new lookup.
     ///
     /// The `LookupId` that is returned can then be included in features
@@ -10,7 +10,7 @@ use write_fonts::{
 
 use super::{
     language_system::{DefaultLanguageSystems, LanguageSystem},
-    lookups::{AllLookups, FeatureKey, LookupId},
+    lookups::{AllLookups, FeatureKey, LookupId, LookupIdMap},
     tables::{NameBuilder, NameSpec},
     tags,
 };
@@ -235,6 +235,16 @@ impl AllFeatures {
         }
     }
 
+    /// Update lookup ids after lookups have been reordered
+    pub(crate) fn remap_lookup_ids(&mut self, map: &LookupIdMap) {
+        for feat in self.features.values_mut() {
+            feat.base
+                .iter_mut()
+                .chain(feat.variations.values_mut().flat_map(|x| x.iter_mut()))
+                .for_each(|id| *id = map.get(*id));
+        }
+    }
+
     #[cfg(test)]
     fn get_base(&self, key: &FeatureKey) -> Option<&[LookupId]> {
         self.features.get(key).map(|x| x.base.as_slice())
@@ -354,6 +364,19 @@ impl ActiveFeature {
         }
     }
 
+    pub(crate) fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    /// All the lookups added to this feature, under any language system
+    pub(crate) fn lookup_ids(&self) -> impl Iterator<Item = LookupId> + '_ {
+        self.lookups
+            .values()
+            .chain(self.script_default_lookups.values())
+            .flatten()
+            .copied()
+    }
+
     /// take the lookups for this feature, and add them to the Big List Of Features
     pub(crate) fn add_to_features(mut self, features: &mut AllFeatures) {
         // remove the default lookups; we will add them back later if DFLT dflt
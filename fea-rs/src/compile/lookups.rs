@@ -639,6 +639,68 @@ impl AllLookups {
         lookup_ids
     }
 
+    /// The index the next GPOS lookup will be assigned
+    pub(crate) fn next_gpos_index(&self) -> usize {
+        self.gpos.len()
+    }
+
+    /// The index the next GSUB lookup will be assigned
+    pub(crate) fn next_gsub_index(&self) -> usize {
+        self.gsub.len()
+    }
+
+    /// Move GPOS lookups to new positions in the lookup list.
+    ///
+    /// `moves` maps the index of a lookup to the index of the lookup it should
+    /// precede; an index past the end moves the lookup to the end. Lookups
+    /// moved to the same position keep their relative order.
+    ///
+    /// Returns a map that must be used to remap the ids in any features where
+    /// they were used.
+    pub(crate) fn move_gpos_lookups(&mut self, moves: &HashMap<usize, usize>) -> LookupIdMap {
+        let mut moved: Vec<_> = moves.iter().map(|(from, to)| (*to, *from)).collect();
+        moved.sort_unstable();
+        let mut moved = moved.into_iter().peekable();
+
+        let mut new_order = Vec::with_capacity(self.gpos.len());
+        for idx in (0..self.gpos.len()).filter(|idx| !moves.contains_key(idx)) {
+            while let Some((_, from)) = moved.next_if(|(to, _)| *to <= idx) {
+                new_order.push(from);
+            }
+            new_order.push(idx);
+        }
+        new_order.extend(moved.map(|(_, from)| from));
+
+        let mut map = LookupIdMap::default();
+        for (new_idx, old_idx) in new_order.iter().enumerate() {
+            map.insert(LookupId::Gpos(*old_idx), LookupId::Gpos(new_idx));
+        }
+
+        let mut old_lookups: Vec<_> = std::mem::take(&mut self.gpos)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.gpos = new_order
+            .iter()
+            .map(|idx| old_lookups[*idx].take().unwrap())
+            .collect();
+
+        // contextual lookups refer to other lookups by id
+        self.gpos.iter_mut().for_each(|lookup| match lookup {
+            PositionLookup::Contextual(lookup) => lookup
+                .subtables
+                .iter_mut()
+                .for_each(|sub| sub.remap_lookup_ids(&map)),
+            PositionLookup::ChainedContextual(lookup) => lookup
+                .subtables
+                .iter_mut()
+                .for_each(|sub| sub.remap_lookup_ids(&map)),
+            _ => (),
+        });
+        self.named.values_mut().for_each(|id| *id = map.get(*id));
+        map
+    }
+
     /// Returns a map that must be used to remap the ids in any features where
     /// they were used.
     pub(crate) fn merge_external_lookups(
@@ -1171,6 +1233,11 @@ impl FeatureKey {
             script,
         }
     }
+
+    /// The feature tag
+    pub fn feature(&self) -> Tag {
+        self.feature
+    }
 }
 
 impl Debug for FeatureKey {
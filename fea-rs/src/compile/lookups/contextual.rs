@@ -20,7 +20,7 @@ use write_fonts::{
 use crate::{common::GlyphOrClass, compile::metrics::ValueRecord};
 
 use super::{
-    Builder, ClassDefBuilder2, FilterSetId, LookupBuilder, LookupId, PositionLookup,
+    Builder, ClassDefBuilder2, FilterSetId, LookupBuilder, LookupId, LookupIdMap, PositionLookup,
     SubstitutionLookup,
 };
 
@@ -318,6 +318,15 @@ impl ContextBuilder {
             .for_each(|rule| rule.bump_all_lookup_ids(by))
     }
 
+    // for adjusting ids if we reorder lookups
+    pub(crate) fn remap_lookup_ids(&mut self, map: &LookupIdMap) {
+        self.rules
+            .iter_mut()
+            .flat_map(|rule| rule.context.iter_mut())
+            .flat_map(|(_, lookups)| lookups.iter_mut())
+            .for_each(|id| *id = map.get(*id))
+    }
+
     /// Iterate all referenced lookups
     fn iter_lookups(&self) -> impl Iterator<Item = LookupId> + '_ {
         self.rules
@@ -665,6 +674,18 @@ impl SubContextBuilder {
         self.0.bump_all_lookup_ids(by)
     }
 }
+
+impl PosContextBuilder {
+    pub(crate) fn remap_lookup_ids(&mut self, map: &LookupIdMap) {
+        self.0.remap_lookup_ids(map)
+    }
+}
+
+impl PosChainContextBuilder {
+    pub(crate) fn remap_lookup_ids(&mut self, map: &LookupIdMap) {
+        self.0 .0.remap_lookup_ids(map)
+    }
+}
 impl SubChainContextBuilder {
     pub(crate) fn bump_all_lookup_ids(&mut self, by: usize) {
         self.0 .0.bump_all_lookup_ids(by)
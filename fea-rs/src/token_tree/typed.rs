@@ -651,6 +651,15 @@ impl Feature {
             .filter(|t| !t.kind().is_trivia())
             .take_while(|t| t.kind() != Kind::RBrace)
     }
+
+    /// The statements in the block, interleaved with any comments
+    pub(crate) fn statements_and_comments(&self) -> impl Iterator<Item = &NodeOrToken> {
+        self.iter()
+            .skip_while(|t| t.kind() != Kind::LBrace)
+            .skip(1)
+            .filter(|t| t.kind() == Kind::Comment || !t.kind().is_trivia())
+            .take_while(|t| t.kind() != Kind::RBrace)
+    }
 }
 
 impl LookupBlock {
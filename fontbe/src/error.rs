@@ -1,9 +1,11 @@
 use std::{fmt::Display, io, path::PathBuf};
 
 use fea_rs::compile::error::CompilerError;
-use fontdrasil::{coords::NormalizedLocation, types::GlyphName};
+use fontdrasil::types::GlyphName;
 use fontir::{
-    error::VariationModelError, ir::KernPair, orchestration::WorkId as FeWorkId,
+    error::{VariationModelError, WorkError},
+    ir::KernPair,
+    orchestration::WorkId as FeWorkId,
     variations::DeltaError,
 };
 use smol_str::SmolStr;
@@ -32,6 +34,8 @@ pub enum Error {
         kurbo_problem: MalformedPath,
         context: String,
     },
+    #[error("'{0}' can't be converted to quadratic within {1} units")]
+    Cu2QuError(GlyphName, f64),
     #[error("'{glyph}' references {referenced_glyph}, {problem}")]
     ComponentError {
         glyph: GlyphName,
@@ -85,14 +89,16 @@ pub enum Error {
         new_class: SmolStr,
         glyph: GlyphName,
     },
-    #[error("No variation model for '{0:?}'")]
-    NoVariationModel(NormalizedLocation),
     #[error("Delta error '{0:?}'")]
     DeltaError(DeltaError),
     #[error("No glyph id for '{0}'")]
     MissingGlyphId(GlyphName),
     #[error("Error making CMap: {0}")]
     CmapConflict(#[from] CmapConflict),
+    #[error("Unable to draw the variable components of '{0}': {1}")]
+    VariableComponentError(GlyphName, WorkError),
+    #[error("Unable to compute VARC deltas for '{0}': {1}")]
+    VarcDeltaError(GlyphName, DeltaError),
 }
 
 #[derive(Debug)]
@@ -103,6 +109,7 @@ pub enum GlyphProblem {
     MissingDefault,
     NoComponents,
     NotInGlyphOrder,
+    UnpairedCubicOffCurve,
 }
 
 impl Display for GlyphProblem {
@@ -116,6 +123,7 @@ impl Display for GlyphProblem {
             GlyphProblem::MissingDefault => "has no default master",
             GlyphProblem::NoComponents => "has no components",
             GlyphProblem::NotInGlyphOrder => "has no entry in glyph order",
+            GlyphProblem::UnpairedCubicOffCurve => "has cubic off-curve points that aren't paired",
         };
         f.write_str(message)
     }
@@ -2,7 +2,7 @@
 
 use std::{
     cell::RefCell,
-    collections::HashMap,
+    collections::{hash_map::Entry, BTreeSet, HashMap, HashSet},
     ffi::{OsStr, OsString},
     fmt::Display,
     fs,
@@ -21,9 +21,9 @@ use fea_rs::{
 };
 
 use fontir::{
-    ir::{FeaturesSource, GlyphOrder, StaticMetadata},
+    ir::{FeatureWriterMode, FeaturesSource, GlyphOrder, MiscMetadata, StaticMetadata},
     orchestration::{Flags, WorkId as FeWorkId},
-    variations::DeltaError,
+    variations::{DeltaError, VariationModel, VariationRegion as Region},
 };
 
 use fontdrasil::{
@@ -126,7 +126,7 @@ impl Display for NoIncludePathError {
 
 struct FeaVariationInfo<'a> {
     axes: HashMap<Tag, (usize, &'a Axis)>,
-    static_metadata: &'a StaticMetadata,
+    models: VariationModels<'a>,
 }
 
 impl<'a> FeaVariationInfo<'a> {
@@ -138,75 +138,137 @@ impl<'a> FeaVariationInfo<'a> {
                 .enumerate()
                 .map(|(i, a)| (a.tag, (i, a)))
                 .collect(),
-            static_metadata,
+            models: VariationModels::new(static_metadata),
         }
     }
 }
 
-//NOTE: this is basically identical to the same method on FeaVariationInfo,
-//except they have slightly different inputs?
-pub(crate) fn resolve_variable_metric<'a>(
-    static_metadata: &StaticMetadata,
-    values: impl Iterator<Item = (&'a NormalizedLocation, &'a OrderedFloat<f32>)>,
-) -> Result<(i16, Vec<(VariationRegion, i16)>), DeltaError> {
-    let var_model = &static_metadata.variation_model;
-
-    let point_seqs = values
-        .into_iter()
-        .map(|(pos, value)| (pos.to_owned(), vec![value.0 as f64]))
-        .collect();
-    let raw_deltas: Vec<_> = var_model
-        .deltas(&point_seqs)?
-        .into_iter()
-        .map(|(region, values)| {
-            assert!(values.len() == 1, "{} values?!", values.len());
-            (region, values[0])
-        })
-        .collect();
-
-    let default_value: i16 = raw_deltas
-        .iter()
-        .filter_map(|(region, value)| {
-            let scaler = region.scalar_at(&var_model.default).into_inner();
-            match scaler {
-                scaler if scaler == 0.0 => None,
-                scaler => Some(scaler * *value as f32),
-            }
-        })
-        .sum::<f32>()
-        .ot_round();
-
-    let mut deltas = Vec::with_capacity(raw_deltas.len());
-    for (region, value) in raw_deltas.iter().filter(|(r, _)| !r.is_default()) {
-        // https://learn.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#variation-regions
-        // Array of region axis coordinates records, in the order of axes given in the 'fvar' table.
-        let mut region_axes = Vec::with_capacity(static_metadata.axes.len());
-        for axis in static_metadata.axes.iter() {
-            // all axes in static metadata must be present in region returned
-            // by variation model, which uses those same axes
-            let coords = region.get(&axis.tag).unwrap().to_region_axis_coords();
-            region_axes.push(coords);
+/// The models to compute deltas for values defined at some set of locations
+///
+/// If the values are defined at every master location we can use the global model. If
+/// they are sparse, e.g. an anchor on a glyph with an intermediate layer, or not present
+/// in all masters, we must build a model specific to their locations. Otherwise any master
+/// without a value would act as though its delta was zero.
+pub(crate) struct VariationModels<'a> {
+    static_metadata: &'a StaticMetadata,
+    /// Sparse variation models, keyed by the set of locations they define
+    sparse: RefCell<HashMap<BTreeSet<NormalizedLocation>, VariationModel>>,
+}
+
+impl<'a> VariationModels<'a> {
+    pub(crate) fn new(static_metadata: &'a StaticMetadata) -> Self {
+        VariationModels {
+            static_metadata,
+            sparse: Default::default(),
         }
-        deltas.push((
-            write_fonts::tables::variations::VariationRegion { region_axes },
-            value.ot_round(),
-        ));
     }
 
-    Ok((default_value, deltas))
+    /// The default location, the same for every model
+    fn default_location(&self) -> &NormalizedLocation {
+        &self.static_metadata.variation_model.default
+    }
+
+    fn deltas(
+        &self,
+        point_seqs: &HashMap<NormalizedLocation, Vec<f64>>,
+    ) -> Result<Vec<(Region, Vec<f64>)>, DeltaError> {
+        let global_model = &self.static_metadata.variation_model;
+        if global_model.num_locations() == point_seqs.len()
+            && point_seqs.keys().all(|loc| global_model.supports(loc))
+        {
+            return global_model.deltas(point_seqs);
+        }
+        // A static font has nothing to vary, only the value at the default matters
+        if self.static_metadata.axes.is_empty() {
+            let (default, seq) = point_seqs
+                .iter()
+                .find(|(loc, _)| loc.is_default())
+                .ok_or(DeltaError::DefaultUndefined)?;
+            return global_model.deltas(&HashMap::from([(default.clone(), seq.clone())]));
+        }
+        let mut sparse = self.sparse.borrow_mut();
+        let model = match sparse.entry(point_seqs.keys().cloned().collect()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let model = VariationModel::new(
+                    entry.key().iter().cloned().collect(),
+                    self.static_metadata.axes.clone(),
+                )
+                .map_err(DeltaError::NoModel)?;
+                entry.insert(model)
+            }
+        };
+        model.deltas(point_seqs)
+    }
+
+    /// The default value of a metric and the deltas of its non-default regions
+    pub(crate) fn resolve_variable_metric<'b>(
+        &self,
+        values: impl Iterator<Item = (&'b NormalizedLocation, &'b OrderedFloat<f32>)>,
+    ) -> Result<(i16, Vec<(VariationRegion, i16)>), DeltaError> {
+        // Compute deltas using f64 as 1d point and delta, then ship them home as i16
+        let point_seqs = values
+            .into_iter()
+            .map(|(pos, value)| (pos.to_owned(), vec![value.0 as f64]))
+            .collect();
+        // Only 1 value per region for our input
+        let raw_deltas: Vec<_> = self
+            .deltas(&point_seqs)?
+            .into_iter()
+            .map(|(region, values)| {
+                assert!(values.len() == 1, "{} values?!", values.len());
+                (region, values[0])
+            })
+            .collect();
+
+        // Compute the default on the unrounded deltas
+        let default_value: i16 = raw_deltas
+            .iter()
+            .filter_map(|(region, value)| {
+                let scaler = region.scalar_at(self.default_location()).into_inner();
+                match scaler {
+                    scaler if scaler == 0.0 => None,
+                    scaler => Some(scaler * *value as f32),
+                }
+            })
+            .sum::<f32>()
+            .ot_round();
+
+        // Produce the desired delta type
+        let mut deltas = Vec::with_capacity(raw_deltas.len());
+        for (region, value) in raw_deltas.iter().filter(|(r, _)| !r.is_default()) {
+            deltas.push((
+                region.to_write_fonts_variation_region(&self.static_metadata.axes),
+                value.ot_round(),
+            ));
+        }
+
+        Ok((default_value, deltas))
+    }
 }
 
 struct FeatureWriter<'a> {
     kerning: &'a FeaRsKerns,
     marks: &'a FeaRsMarks,
+    misc: &'a MiscMetadata,
     timing: RefCell<Vec<(&'static str, Instant)>>,
 }
 
+/// Whether to generate a feature, given what the FEA already has
+///
+/// See <https://github.com/googlefonts/ufo2ft/blob/main/Lib/ufo2ft/featureWriters/baseFeatureWriter.py>
+fn should_generate(builder: &FeatureBuilder, mode: FeatureWriterMode, tag: Tag) -> bool {
+    mode == FeatureWriterMode::Append
+        || !builder.has_feature(tag)
+        || builder.has_insertion_marker(tag)
+}
+
 impl<'a> FeatureWriter<'a> {
-    fn new(kerning: &'a FeaRsKerns, marks: &'a FeaRsMarks) -> Self {
+    fn new(kerning: &'a FeaRsKerns, marks: &'a FeaRsMarks, misc: &'a MiscMetadata) -> Self {
         FeatureWriter {
             marks,
             kerning,
+            misc,
             timing: Default::default(),
         }
     }
@@ -216,23 +278,36 @@ impl<'a> FeatureWriter<'a> {
         if self.kerning.is_empty() {
             return Ok(());
         }
-        // convert the lookups into lookup ids
+        let features: Vec<_> = self
+            .kerning
+            .features
+            .iter()
+            .filter(|(feature, _)| {
+                should_generate(builder, self.misc.kern_writer_mode, feature.feature())
+            })
+            .collect();
+
+        // convert the lookups into lookup ids, skipping any no feature uses
+        let used: HashSet<_> = features.iter().flat_map(|(_, ids)| ids.iter()).collect();
         let lookup_ids = self
             .kerning
             .lookups
             .iter()
-            .map(|lookup| {
-                builder.add_lookup(
-                    lookup.flags,
-                    lookup.mark_filter_set.clone(),
-                    lookup.subtables.clone(),
-                )
+            .enumerate()
+            .map(|(idx, lookup)| {
+                used.contains(&idx).then(|| {
+                    builder.add_lookup(
+                        lookup.flags,
+                        lookup.mark_filter_set.clone(),
+                        lookup.subtables.clone(),
+                    )
+                })
             })
             .collect::<Vec<_>>();
 
-        for (feature, ids) in &self.kerning.features {
+        for (feature, ids) in features {
             // get the generated lookup ids based on the stored lookup indices
-            let ids = ids.iter().map(|idx| lookup_ids[*idx]).collect();
+            let ids = ids.iter().filter_map(|idx| lookup_ids[*idx]).collect();
             builder.add_feature(*feature, ids);
         }
 
@@ -263,13 +338,27 @@ impl<'a> FeatureWriter<'a> {
                 .push(("Start add marks", Instant::now()));
         }
         let marks = self.marks;
+        let mode = self.misc.mark_writer_mode;
+        let mark_tag = Tag::new(b"mark");
+        let mkmk_tag = Tag::new(b"mkmk");
 
         // Build the actual mark base and mark mark constructs using fea-rs builders
 
         let mut mark_base_lookups = Vec::new();
         let mut mark_mark_lookups = Vec::new();
 
-        for mark_base in marks.mark_base.iter() {
+        let mark_base = if should_generate(builder, mode, mark_tag) {
+            marks.mark_base.as_slice()
+        } else {
+            &[]
+        };
+        let mark_mark = if should_generate(builder, mode, mkmk_tag) {
+            marks.mark_mark.as_slice()
+        } else {
+            &[]
+        };
+
+        for mark_base in mark_base.iter() {
             // each mark to base it's own lookup, whch differs from fontmake
             mark_base_lookups.push(builder.add_lookup(
                 LookupFlag::default(),
@@ -279,7 +368,7 @@ impl<'a> FeatureWriter<'a> {
         }
 
         // If a mark has anchors that are themselves marks what we got here is a mark to mark
-        for mark_mark in marks.mark_mark.iter() {
+        for mark_mark in mark_mark.iter() {
             mark_mark_lookups.push(builder.add_lookup(
                 LookupFlag::default(),
                 None,
@@ -288,10 +377,10 @@ impl<'a> FeatureWriter<'a> {
         }
 
         if !mark_base_lookups.is_empty() {
-            builder.add_to_default_language_systems(Tag::new(b"mark"), &mark_base_lookups);
+            builder.add_to_default_language_systems(mark_tag, &mark_base_lookups);
         }
         if !mark_mark_lookups.is_empty() {
-            builder.add_to_default_language_systems(Tag::new(b"mkmk"), &mark_mark_lookups);
+            builder.add_to_default_language_systems(mkmk_tag, &mark_mark_lookups);
         }
 
         {
@@ -321,56 +410,13 @@ impl<'a> VariationInfo for FeaVariationInfo<'a> {
         &self,
         values: &HashMap<NormalizedLocation, i16>,
     ) -> Result<(i16, Vec<(VariationRegion, i16)>), Error> {
-        let var_model = &self.static_metadata.variation_model;
-
-        // Compute deltas using f64 as 1d point and delta, then ship them home as i16
-        let point_seqs: HashMap<_, _> = values
+        let values: Vec<_> = values
             .iter()
-            .map(|(pos, value)| (pos.clone(), vec![*value as f64]))
+            .map(|(pos, value)| (pos, OrderedFloat(*value as f32)))
             .collect();
-
-        // We only support use when the point seq is at a location our variation model supports
-        // TODO: get a model for the location we are asked for so we can support sparseness
-        for loc in point_seqs.keys() {
-            if !var_model.supports(loc) {
-                return Err(Error::NoVariationModel(loc.clone()));
-            }
-        }
-
-        // Only 1 value per region for our input
-        let deltas: Vec<_> = var_model
-            .deltas(&point_seqs)
-            .map_err(Error::DeltaError)?
-            .into_iter()
-            .map(|(region, values)| {
-                assert!(values.len() == 1, "{} values?!", values.len());
-                (region, values[0])
-            })
-            .collect();
-
-        // Compute the default on the unrounded deltas
-        let default_value = deltas
-            .iter()
-            .filter_map(|(region, value)| {
-                let scaler = region.scalar_at(&var_model.default).into_inner();
-                match scaler {
-                    scaler if scaler == 0.0 => None,
-                    scaler => Some(scaler * *value as f32),
-                }
-            })
-            .sum::<f32>()
-            .ot_round();
-
-        // Produce the desired delta type
-        let mut fears_deltas = Vec::with_capacity(deltas.len());
-        for (region, value) in deltas.iter().filter(|(r, _)| !r.is_default()) {
-            fears_deltas.push((
-                region.to_write_fonts_variation_region(&self.static_metadata.axes),
-                value.ot_round(),
-            ));
-        }
-
-        Ok((default_value, fears_deltas))
+        self.models
+            .resolve_variable_metric(values.iter().map(|(pos, value)| (*pos, value)))
+            .map_err(Error::DeltaError)
     }
 
     fn axis_count(&self) -> u16 {
@@ -391,7 +437,7 @@ impl FeatureCompilationWork {
         marks: &FeaRsMarks,
     ) -> Result<Compilation, Error> {
         let var_info = FeaVariationInfo::new(static_metadata);
-        let feature_writer = FeatureWriter::new(kerns, marks);
+        let feature_writer = FeatureWriter::new(kerns, marks, &static_metadata.misc);
         // we've already validated the AST, so we only need to compile
         match fea_rs::compile::compile(
             &ast.ast,
@@ -694,4 +740,126 @@ mod tests {
         let region_values: Vec<_> = regions.into_iter().map(|(_, v)| v + default).collect();
         assert_eq!((15, vec![10, 20]), (default, region_values));
     }
+
+    #[test]
+    fn resolve_sparse_kern() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let wght = Tag::new(b"wght");
+        let mut static_metadata = weight_variable_static_metadata(300.0, 400.0, 700.0);
+        // An intermediate master, such as a brace layer, our kern has no value for
+        static_metadata.variation_model = VariationModel::new(
+            [0.0, 0.5, 1.0]
+                .into_iter()
+                .map(|coord| vec![(wght, NormalizedCoord::new(coord))].into())
+                .collect(),
+            static_metadata.axes.clone(),
+        )
+        .unwrap();
+        let var_info = FeaVariationInfo::new(&static_metadata);
+
+        let (default, regions) = var_info
+            .resolve_variable_metric(&HashMap::from([
+                (vec![(wght, NormalizedCoord::new(0.0))].into(), 15),
+                (vec![(wght, NormalizedCoord::new(1.0))].into(), 25),
+            ]))
+            .unwrap();
+
+        // The max should vary from the default, not from the intermediate we don't have
+        assert_eq!(1, regions.len());
+        let (region, delta) = &regions[0];
+        assert_eq!((15, 10), (default, *delta));
+        assert_eq!(
+            (0.0, 1.0, 1.0),
+            (
+                region.region_axes[0].start_coord.to_f32(),
+                region.region_axes[0].peak_coord.to_f32(),
+                region.region_axes[0].end_coord.to_f32()
+            )
+        );
+    }
+
+    #[test]
+    fn resolve_kern_off_master() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let wght = Tag::new(b"wght");
+        let static_metadata = weight_variable_static_metadata(300.0, 400.0, 700.0);
+        let var_info = FeaVariationInfo::new(&static_metadata);
+
+        // 0.5 isn't a master location but we can model it all the same
+        let (default, regions) = var_info
+            .resolve_variable_metric(&HashMap::from([
+                (vec![(wght, NormalizedCoord::new(0.0))].into(), 15),
+                (vec![(wght, NormalizedCoord::new(0.5))].into(), 30),
+                (vec![(wght, NormalizedCoord::new(1.0))].into(), 25),
+            ]))
+            .unwrap();
+        assert_eq!(15, default);
+        assert_eq!(2, regions.len());
+    }
+
+    #[test]
+    fn sparse_models_are_reused() {
+        let wght = Tag::new(b"wght");
+        let static_metadata = weight_variable_static_metadata(300.0, 400.0, 700.0);
+        let models = VariationModels::new(&static_metadata);
+
+        for value in [25.0, 35.0] {
+            let values: [(NormalizedLocation, OrderedFloat<f32>); 2] = [
+                (
+                    vec![(wght, NormalizedCoord::new(0.0))].into(),
+                    OrderedFloat(15.0),
+                ),
+                (
+                    vec![(wght, NormalizedCoord::new(1.0))].into(),
+                    OrderedFloat(value),
+                ),
+            ];
+            models
+                .resolve_variable_metric(values.iter().map(|item| (&item.0, &item.1)))
+                .unwrap();
+        }
+        assert_eq!(1, models.sparse.borrow().len());
+    }
+
+    fn static_metadata_without_axes() -> StaticMetadata {
+        StaticMetadata::new(
+            1024,
+            Default::default(),
+            Vec::new(),
+            Default::default(),
+            HashSet::from([NormalizedLocation::new()]),
+            Default::default(),
+            Default::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn static_font_resolves_to_default() {
+        let wght = Tag::new(b"wght");
+        let static_metadata = static_metadata_without_axes();
+        let var_info = FeaVariationInfo::new(&static_metadata);
+
+        let (default, regions) = var_info
+            .resolve_variable_metric(&HashMap::from([
+                (NormalizedLocation::new(), 15),
+                (vec![(wght, NormalizedCoord::new(1.0))].into(), 25),
+            ]))
+            .unwrap();
+        assert_eq!((15, 0), (default, regions.len()));
+    }
+
+    #[test]
+    fn static_font_needs_default() {
+        let wght = Tag::new(b"wght");
+        let static_metadata = static_metadata_without_axes();
+        let var_info = FeaVariationInfo::new(&static_metadata);
+
+        assert!(var_info
+            .resolve_variable_metric(&HashMap::from([(
+                vec![(wght, NormalizedCoord::new(1.0))].into(),
+                25
+            )]))
+            .is_err());
+    }
 }
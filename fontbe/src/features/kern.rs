@@ -20,7 +20,7 @@ use fontdrasil::{
     types::GlyphName,
 };
 use fontir::{
-    ir::{self, Glyph, KernGroup, KerningGroups, KerningInstance},
+    ir::{self, align_kerning, Glyph, GlyphOrder, KernGroup, KerningInstance},
     orchestration::WorkId as FeWorkId,
 };
 use icu_properties::BidiClass;
@@ -36,7 +36,7 @@ use crate::{
     error::Error,
     features::{
         properties::{ScriptDirection, UnicodeShortName, COMMON_SCRIPT, INHERITED_SCRIPT},
-        resolve_variable_metric,
+        VariationModels,
     },
     orchestration::{
         AllKerningPairs, AnyWorkId, BeWork, Context, FeaRsKerns, KernAdjustments, KernFragment,
@@ -53,6 +53,7 @@ const KERN: Tag = Tag::new(b"kern");
 // we don't currently compile this feature, but we will, and it is referenced
 // in places because our impl is based on fonttools.
 const DIST: Tag = Tag::new(b"dist");
+const VKRN: Tag = Tag::new(b"vkrn");
 const DFLT_SCRIPT: Tag = Tag::new(b"DFLT");
 const DFLT_LANG: Tag = Tag::new(b"dflt");
 
@@ -82,11 +83,11 @@ pub fn create_gather_ir_kerning_work() -> Box<BeWork> {
 }
 
 pub fn create_kern_segment_work(kern_pairs: &AllKerningPairs) -> Vec<Box<BeWork>> {
-    let segments = kern_pairs.adjustments.len().div_ceil(KERNS_PER_BLOCK);
+    let segments = kern_pairs.len().div_ceil(KERNS_PER_BLOCK);
     let mut work: Vec<Box<BeWork>> = Vec::with_capacity(segments);
     debug!(
         "Process {} kerning adjustments in {} chunks",
-        kern_pairs.adjustments.len(),
+        kern_pairs.len(),
         segments
     );
     for segment in 0..segments {
@@ -122,31 +123,8 @@ impl Work<Context, AnyWorkId, Error> for GatherIrKerningWork {
 
         // convert the groups stored in the Kerning object into the glyph classes
         // expected by fea-rs:
-        let groups = ir_groups
-            .groups
-            .iter()
-            .filter_map(|(class_name, glyph_set)| {
-                let glyph_class: GlyphSet = glyph_set
-                    .iter()
-                    // drop any unknown glyphs
-                    .filter_map(|name| {
-                        let r = glyph_order.glyph_id(name);
-                        if r.is_none() {
-                            log::warn!(
-                                "Skipping unknown glyph '{name}' in kern group '{class_name}'"
-                            );
-                        }
-                        r
-                    })
-                    .collect();
-                if glyph_class.is_empty() {
-                    log::warn!("Dropping empty kern group '{class_name}");
-                    None
-                } else {
-                    Some((class_name.clone(), glyph_class))
-                }
-            })
-            .collect::<BTreeMap<_, _>>();
+        let groups = glyph_classes(&ir_groups.groups, &glyph_order);
+        let vertical_groups = glyph_classes(&ir_groups.vertical_groups, &glyph_order);
 
         // Add IR kerns to builder. IR kerns are split by location so put them back together again.
         let mut kern_by_pos: HashMap<_, _> = ir_kerns
@@ -154,150 +132,128 @@ impl Work<Context, AnyWorkId, Error> for GatherIrKerningWork {
             .map(|(_, ki)| (ki.location.clone(), ki.as_ref().to_owned()))
             .collect();
 
-        align_kerning(&ir_groups, &mut kern_by_pos);
-        let mut adjustments: HashMap<ir::KernPair, KernAdjustments> = Default::default();
-
-        // We want to add items to locations in the same order as the group locations
-        // so start with group locations and then find the matching kerning.
-        ir_groups
-            .locations
-            .iter()
-            .filter_map(|pos| kern_by_pos.get(pos))
-            .flat_map(|instance| {
-                instance
-                    .kerns
-                    .iter()
-                    .map(|(pair, adjustment)| (pair, (instance.location.clone(), *adjustment)))
-            })
-            .for_each(|(pair, (location, adjustment))| {
-                adjustments
-                    .entry(pair.clone())
-                    .or_default()
-                    .insert(location, adjustment);
-            });
+        // Pairs missing from sparse locations are left out, to be resolved with a local model
+        align_kerning(
+            &ir_groups.groups,
+            &ir_groups.sparse_locations,
+            &mut kern_by_pos,
+            |ki| &mut ki.kerns,
+        );
+        align_kerning(
+            &ir_groups.vertical_groups,
+            &ir_groups.sparse_locations,
+            &mut kern_by_pos,
+            |ki| &mut ki.vertical_kerns,
+        );
 
-        let adjustments: Vec<_> = adjustments
-            .into_iter()
-            // drop any rule that references a non-existent group or glyph:
-            .filter(|((left, right), _)| {
-                for side in [left, right] {
-                    match side {
-                        ir::KernSide::Group(name) if !groups.contains_key(name) => {
-                            log::warn!("Unknown kern class '{name}' will be skipped");
-                            return false;
-                        }
-                        ir::KernSide::Glyph(name) if glyph_order.glyph_id(name).is_none() => {
-                            log::warn!("Unknown kern glyph '{name}' will be skipped");
-                            return false;
-                        }
-                        _ => (),
-                    }
-                }
-                true
-            })
-            .collect();
+        let adjustments = gather_adjustments(
+            &ir_groups.locations,
+            &kern_by_pos,
+            |ki| &ki.kerns,
+            &groups,
+            &glyph_order,
+        );
+        let vertical_adjustments = gather_adjustments(
+            &ir_groups.locations,
+            &kern_by_pos,
+            |ki| &ki.vertical_kerns,
+            &vertical_groups,
+            &glyph_order,
+        );
         debug!(
-            "{} ir kerns became {} classes and {} adjustments",
+            "{} ir kerns became {} classes and {} adjustments, {} vertical classes and {} vertical adjustments",
             ir_kerns.len(),
             groups.len(),
-            adjustments.len()
+            adjustments.len(),
+            vertical_groups.len(),
+            vertical_adjustments.len(),
         );
         context.all_kerning_pairs.set(AllKerningPairs {
             groups,
             adjustments,
+            vertical_groups,
+            vertical_adjustments,
         });
         Ok(())
     }
 }
 
-/// 'align' the kerning, ensuring each pair is defined for each location.
-///
-/// missing pairs are filled in via the UFO kerning value lookup algorithm:
-///
-/// <https://unifiedfontobject.org/versions/ufo3/kerning.plist/#kerning-value-lookup-algorithm>
-fn align_kerning(
-    groups: &KerningGroups,
-    instances: &mut HashMap<NormalizedLocation, KerningInstance>,
-) {
-    let union_kerning = instances
-        .values()
-        .flat_map(|instance| instance.kerns.keys())
-        .cloned()
-        .collect::<HashSet<_>>();
-
-    let side1_glyph_to_group_map = groups
-        .groups
-        .iter()
-        .filter(|(group, _)| matches!(group, KernGroup::Side1(_)))
-        .flat_map(|(group, glyphs)| glyphs.iter().map(move |glyph| (glyph, group)))
-        .collect::<HashMap<_, _>>();
-    let side2_glyph_to_group_map = groups
-        .groups
+/// Resolve IR kern groups to glyph classes, dropping unknown glyphs and empty groups
+fn glyph_classes(
+    ir_groups: &BTreeMap<KernGroup, BTreeSet<GlyphName>>,
+    glyph_order: &GlyphOrder,
+) -> BTreeMap<KernGroup, GlyphSet> {
+    ir_groups
         .iter()
-        .filter(|(group, _)| matches!(group, KernGroup::Side2(_)))
-        .flat_map(|(group, glyphs)| glyphs.iter().map(move |glyph| (glyph, group)))
-        .collect::<HashMap<_, _>>();
-
-    for instance in instances.values_mut() {
-        let missing_pairs = union_kerning
-            .iter()
-            .filter(|pair| !instance.kerns.contains_key(pair))
-            .collect::<Vec<_>>();
-
-        for pair in missing_pairs {
-            let value = lookup_kerning_value(
-                pair,
-                instance,
-                &side1_glyph_to_group_map,
-                &side2_glyph_to_group_map,
-            );
-            instance.kerns.insert(pair.to_owned(), value);
-        }
-    }
+        .filter_map(|(class_name, glyph_set)| {
+            let glyph_class: GlyphSet = glyph_set
+                .iter()
+                // drop any unknown glyphs
+                .filter_map(|name| {
+                    let r = glyph_order.glyph_id(name);
+                    if r.is_none() {
+                        log::warn!("Skipping unknown glyph '{name}' in kern group '{class_name}'");
+                    }
+                    r
+                })
+                .collect();
+            if glyph_class.is_empty() {
+                log::warn!("Dropping empty kern group '{class_name}");
+                None
+            } else {
+                Some((class_name.clone(), glyph_class))
+            }
+        })
+        .collect()
 }
 
-// <https://github.com/fonttools/fonttools/blob/a3b9eddcafca/Lib/fontTools/ufoLib/kerning.py#L1>
-fn lookup_kerning_value(
-    pair: &ir::KernPair,
-    kerning: &KerningInstance,
-    side1_glyphs: &HashMap<&GlyphName, &KernGroup>,
-    side2_glyphs: &HashMap<&GlyphName, &KernGroup>,
-) -> OrderedFloat<f32> {
-    // if already a group, return it, else look for group for glyph
-    fn get_group_if_glyph(
-        side: &ir::KernSide,
-        map: &HashMap<&GlyphName, &KernGroup>,
-    ) -> Option<ir::KernSide> {
-        match side {
-            ir::KernSide::Glyph(glyph) => map
-                .get(&glyph)
-                .map(|group| ir::KernSide::Group((*group).clone())),
-            ir::KernSide::Group(_) => Some(side.to_owned()),
-        }
-    }
+/// Collect the adjustments for each pair across all locations
+fn gather_adjustments(
+    locations: &BTreeSet<NormalizedLocation>,
+    kern_by_pos: &HashMap<NormalizedLocation, KerningInstance>,
+    kerns_of: impl Fn(&KerningInstance) -> &BTreeMap<ir::KernPair, OrderedFloat<f32>>,
+    groups: &BTreeMap<KernGroup, GlyphSet>,
+    glyph_order: &GlyphOrder,
+) -> Vec<(ir::KernPair, KernAdjustments)> {
+    let mut adjustments: HashMap<ir::KernPair, KernAdjustments> = Default::default();
+
+    // We want to add items to locations in the same order as the group locations
+    // so start with group locations and then find the matching kerning.
+    locations
+        .iter()
+        .filter_map(|pos| kern_by_pos.get(pos))
+        .flat_map(|instance| {
+            kerns_of(instance)
+                .iter()
+                .map(|(pair, adjustment)| (pair, (instance.location.clone(), *adjustment)))
+        })
+        .for_each(|(pair, (location, adjustment))| {
+            adjustments
+                .entry(pair.clone())
+                .or_default()
+                .insert(location, adjustment);
+        });
 
-    let (first, second) = pair;
-    // for each side: if it's a group, we only check the group.
-    // if it's a glyph, we check both the glyph as well as the group containing that glyph.
-    let first_group = get_group_if_glyph(first, side1_glyphs);
-    let second_group = get_group_if_glyph(second, side2_glyphs);
-    let first = Some(first).filter(|side| side.is_glyph());
-    let second = Some(second).filter(|side| side.is_glyph());
-
-    for (first, second) in [
-        (first.cloned(), second_group.clone()),
-        (first_group.clone(), second.cloned()),
-        (first_group.clone(), second_group.clone()),
-    ] {
-        if let Some(pair) = first.zip(second) {
-            if let Some(value) = kerning.kerns.get(&pair) {
-                return *value;
+    adjustments
+        .into_iter()
+        // drop any rule that references a non-existent group or glyph:
+        .filter(|((left, right), _)| {
+            for side in [left, right] {
+                match side {
+                    ir::KernSide::Group(name) if !groups.contains_key(name) => {
+                        log::warn!("Unknown kern class '{name}' will be skipped");
+                        return false;
+                    }
+                    ir::KernSide::Glyph(name) if glyph_order.glyph_id(name).is_none() => {
+                        log::warn!("Unknown kern glyph '{name}' will be skipped");
+                        return false;
+                    }
+                    _ => (),
+                }
             }
-        }
-    }
-
-    // then fallback to zero
-    0.0.into()
+            true
+        })
+        .collect()
 }
 
 impl Work<Context, AnyWorkId, Error> for KerningFragmentWork {
@@ -317,19 +273,26 @@ impl Work<Context, AnyWorkId, Error> for KerningFragmentWork {
         let glyph_order = context.ir.glyph_order.get();
         let kerning = context.all_kerning_pairs.get();
         let start = self.segment * KERNS_PER_BLOCK;
-        let end = (start + KERNS_PER_BLOCK).min(kerning.adjustments.len());
+        let end = (start + KERNS_PER_BLOCK).min(kerning.len());
         assert!(start <= end, "bad range {start}..{end}");
 
-        // now for each kerning entry, directly add a rule to a builder:
-        let our_kerns = &kerning.adjustments[start..end];
+        // our segment covers horizontal adjustments then vertical ones
+        let num_horizontal = kerning.adjustments.len();
+        let our_kerns = &kerning.adjustments[start.min(num_horizontal)..end.min(num_horizontal)];
+        let our_vertical_kerns = &kerning.vertical_adjustments
+            [start.max(num_horizontal) - num_horizontal..end.max(num_horizontal) - num_horizontal];
 
+        // now for each kerning entry, directly add a rule to a builder:
+        let models = VariationModels::new(&static_metadata);
         let mut kerns = Vec::with_capacity(our_kerns.len());
         for ((side1, side2), values) in our_kerns {
-            let (default_value, deltas) = resolve_variable_metric(&static_metadata, values.iter())
-                .map_err(|error| Error::KernDeltaError {
-                    pair: (side1.clone(), side2.clone()),
-                    error,
-                })?;
+            let (default_value, deltas) =
+                models
+                    .resolve_variable_metric(values.iter())
+                    .map_err(|error| Error::KernDeltaError {
+                        pair: (side1.clone(), side2.clone()),
+                        error,
+                    })?;
 
             let mut value = ValueRecordBuilder::new().with_x_advance(default_value);
             // only encode deltas if they aren't all zeros
@@ -346,9 +309,35 @@ impl Work<Context, AnyWorkId, Error> for KerningFragmentWork {
             })
         }
 
+        let mut vertical_kerns = Vec::with_capacity(our_vertical_kerns.len());
+        for ((side1, side2), values) in our_vertical_kerns {
+            let (default_value, deltas) =
+                models
+                    .resolve_variable_metric(values.iter())
+                    .map_err(|error| Error::KernDeltaError {
+                        pair: (side1.clone(), side2.clone()),
+                        error,
+                    })?;
+
+            let mut value = ValueRecordBuilder::new().with_y_advance(default_value);
+            if deltas.iter().any(|v| v.1 != 0) {
+                value = value.with_y_advance_device(deltas);
+            }
+            let side1 =
+                KernSide::from_ir_side(side1, &glyph_order, &kerning.vertical_groups).unwrap();
+            let side2 =
+                KernSide::from_ir_side(side2, &glyph_order, &kerning.vertical_groups).unwrap();
+            vertical_kerns.push(KernPair {
+                side1,
+                side2,
+                value,
+            })
+        }
+
         context.kern_fragments.set(KernFragment {
             segment: self.segment,
             kerns,
+            vertical_kerns,
         });
 
         Ok(())
@@ -459,10 +448,48 @@ impl KerningGatherWork {
         let split_ctx = KernSplitContext::new(&glyphs, &known_scripts, gsub, mark_glyphs)?;
 
         let lookups = split_ctx.make_lookups(&pairs);
-        let (lookups, features) = self.assign_lookups_to_scripts(lookups, ast, KERN);
+        let (mut lookups, mut features) = self.assign_lookups_to_scripts(lookups, ast, KERN);
+
+        let mut vertical_pairs = fragments
+            .iter()
+            .flat_map(|frag| frag.vertical_kerns.iter())
+            .collect::<Vec<_>>();
+        if !vertical_pairs.is_empty() {
+            vertical_pairs.sort();
+            let mut builder = PairPosBuilder::default();
+            for pair in vertical_pairs {
+                pair.clone().add_to(&mut builder);
+            }
+            lookups.push(split_ctx.make_lookup(builder, true));
+            features.extend(self.assign_vertical_lookup(lookups.len() - 1, ast));
+        }
+
         Ok(FeaRsKerns { lookups, features })
     }
 
+    /// Vertical kerning isn't split by script, register one lookup for every language system
+    fn assign_vertical_lookup(
+        &self,
+        lookup_idx: usize,
+        ast: &ParseTree,
+    ) -> BTreeMap<FeatureKey, Vec<usize>> {
+        let dflt_langs = vec![DFLT_LANG];
+        let mut language_systems: BTreeMap<Tag, Vec<Tag>> = get_script_language_systems(ast)
+            .into_values()
+            .flat_map(|x| x.into_iter())
+            .collect();
+        language_systems.entry(DFLT_SCRIPT).or_insert(dflt_langs);
+
+        language_systems
+            .into_iter()
+            .flat_map(|(script, languages)| {
+                languages
+                    .into_iter()
+                    .map(move |lang| (FeatureKey::new(VKRN, lang, script), vec![lookup_idx]))
+            })
+            .collect()
+    }
+
     /// returns a vec of lookups (as a vec of subtables), along with a map of features -> lookups
     /// (by order in the first vec)
     ///
@@ -17,6 +17,7 @@ use write_fonts::{
 
 use crate::{
     error::Error,
+    features::VariationModels,
     orchestration::{AnyWorkId, BeWork, Context, FeaRsMarks, WorkId},
 };
 use fontir::{
@@ -41,6 +42,7 @@ struct MarkLookupBuilder<'a> {
     anchors: BTreeMap<GlyphName, Vec<&'a ir::Anchor>>,
     glyph_order: &'a GlyphOrder,
     static_metadata: &'a StaticMetadata,
+    models: VariationModels<'a>,
     fea_scripts: HashSet<Tag>,
 }
 
@@ -132,6 +134,7 @@ impl<'a> MarkLookupBuilder<'a> {
             glyph_order,
             fea_scripts: Default::default(),
             static_metadata,
+            models: VariationModels::new(static_metadata),
         }
     }
 
@@ -164,13 +167,13 @@ impl<'a> MarkLookupBuilder<'a> {
                 for (mark_name, anchor) in group.marks {
                     // we already filtered to only things in glyph order
                     let gid = self.glyph_order.glyph_id(&mark_name).unwrap();
-                    let anchor = resolve_anchor(anchor, self.static_metadata, &mark_name)?;
+                    let anchor = resolve_anchor(anchor, &self.models, &mark_name)?;
                     builder.add_mark(gid, &group_name, anchor);
                 }
 
                 for (base_name, anchor) in group.bases {
                     let gid = self.glyph_order.glyph_id(&base_name).unwrap();
-                    let anchor = resolve_anchor(anchor, self.static_metadata, &base_name)?;
+                    let anchor = resolve_anchor(anchor, &self.models, &base_name)?;
                     builder.add_base(gid, &group_name, anchor);
                 }
 
@@ -290,7 +293,7 @@ impl Work<Context, AnyWorkId, Error> for MarkWork {
 
 fn resolve_anchor(
     anchor: &fontir::ir::Anchor,
-    static_metadata: &StaticMetadata,
+    models: &VariationModels,
     glyph_name: &GlyphName, // just used for error reporting
 ) -> Result<fea_rs::compile::Anchor, Error> {
     let (x_values, y_values): (Vec<_>, Vec<_>) = anchor
@@ -304,20 +307,18 @@ fn resolve_anchor(
         })
         .unzip();
 
-    let (x_default, x_deltas) = crate::features::resolve_variable_metric(
-        static_metadata,
-        // If I do map(|(pos, value)| (pos, value)) to destructure the tuple and
-        // convert &(T, V) => (&T, &V) as the values parameter expects, then
-        // clippy complains about the seemingly no-op identity map:
-        // https://rust-lang.github.io/rust-clippy/master/index.html#/map_identity
-        x_values.iter().map(|item| (&item.0, &item.1)),
-    )
-    .map_err(|err| Error::AnchorDeltaError(glyph_name.to_owned(), err))?;
-    let (y_default, y_deltas) = crate::features::resolve_variable_metric(
-        static_metadata,
-        y_values.iter().map(|item| (&item.0, &item.1)),
-    )
-    .map_err(|err| Error::AnchorDeltaError(glyph_name.to_owned(), err))?;
+    let (x_default, x_deltas) = models
+        .resolve_variable_metric(
+            // If I do map(|(pos, value)| (pos, value)) to destructure the tuple and
+            // convert &(T, V) => (&T, &V) as the values parameter expects, then
+            // clippy complains about the seemingly no-op identity map:
+            // https://rust-lang.github.io/rust-clippy/master/index.html#/map_identity
+            x_values.iter().map(|item| (&item.0, &item.1)),
+        )
+        .map_err(|err| Error::AnchorDeltaError(glyph_name.to_owned(), err))?;
+    let (y_default, y_deltas) = models
+        .resolve_variable_metric(y_values.iter().map(|item| (&item.0, &item.1)))
+        .map_err(|err| Error::AnchorDeltaError(glyph_name.to_owned(), err))?;
 
     let mut anchor = fea_rs::compile::Anchor::new(x_default, y_default);
     if x_deltas.iter().any(|v| v.1 != 0) {
@@ -1,10 +1,12 @@
 //! Merge tables into a font
 
+use std::collections::HashMap;
+
 use fontdrasil::orchestration::{Access, AccessBuilder, Work};
 use fontir::orchestration::WorkId as FeWorkId;
 use log::debug;
 use write_fonts::{
-    read::TopLevelTable,
+    read::{FontRef, TopLevelTable},
     tables::{
         avar::Avar, cmap::Cmap, fvar::Fvar, gdef::Gdef, glyf::Glyf, gpos::Gpos, gsub::Gsub,
         gvar::Gvar, head::Head, hhea::Hhea, hmtx::Hmtx, hvar::Hvar, loca::Loca, maxp::Maxp,
@@ -17,8 +19,14 @@ use write_fonts::{
 use crate::{
     error::Error,
     orchestration::{to_bytes, AnyWorkId, BeWork, Context, WorkId},
+    woff::padded_len,
 };
 
+const TTC_TAG: Tag = Tag::new(b"ttcf");
+const TTC_HEADER_SIZE: usize = 12;
+const TABLE_DIRECTORY_HEADER_SIZE: usize = 12;
+const TABLE_RECORD_SIZE: usize = 16;
+
 #[derive(Debug)]
 struct FontWork {}
 
@@ -51,6 +59,7 @@ const TABLES_TO_MERGE: &[(WorkId, Tag, TableType)] = &[
     (WorkId::Stat, Stat::TAG, TableType::Variable),
     (WorkId::Hvar, Hvar::TAG, TableType::Variable),
     (WorkId::Mvar, Mvar::TAG, TableType::Variable),
+    (WorkId::Varc, Tag::new(b"VARC"), TableType::Variable),
 ];
 
 fn has(context: &Context, id: WorkId) -> bool {
@@ -74,6 +83,10 @@ fn has(context: &Context, id: WorkId) -> bool {
         WorkId::Stat => context.stat.try_get().is_some(),
         WorkId::Hvar => context.hvar.try_get().is_some(),
         WorkId::Mvar => context.mvar.try_get().is_some(),
+        WorkId::Varc => context
+            .varc
+            .try_get()
+            .is_some_and(|varc| !varc.get().is_empty()),
         _ => false,
     }
 }
@@ -100,6 +113,7 @@ fn bytes_for(context: &Context, id: WorkId) -> Result<Option<Vec<u8>>, Error> {
         WorkId::Stat => to_bytes(context.stat.get().as_ref()),
         WorkId::Hvar => to_bytes(context.hvar.get().as_ref()),
         WorkId::Mvar => to_bytes(context.mvar.get().as_ref()),
+        WorkId::Varc => Some(context.varc.get().as_ref().get().to_vec()),
         _ => panic!("Missing a match for {id:?}"),
     };
     Ok(bytes)
@@ -131,6 +145,7 @@ impl Work<Context, AnyWorkId, Error> for FontWork {
             .variant(WorkId::Stat)
             .variant(WorkId::Hvar)
             .variant(WorkId::Mvar)
+            .variant(WorkId::Varc)
             .variant(WorkId::LocaFormat)
             .variant(FeWorkId::StaticMetadata)
             .build()
@@ -167,3 +182,126 @@ impl Work<Context, AnyWorkId, Error> for FontWork {
         Ok(())
     }
 }
+
+/// Glue fonts into a [TrueType Collection](https://learn.microsoft.com/en-us/typography/opentype/spec/otff#font-collections)
+///
+/// Tables that are byte-for-byte identical between fonts, such as a shared glyf, are written once.
+pub fn build_collection(fonts: &[&[u8]]) -> Result<Vec<u8>, Error> {
+    let fonts = fonts
+        .iter()
+        .map(|data| FontRef::new(data))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // The version 1.0 header is followed by an offset per font, then the table directories
+    let mut offset = TTC_HEADER_SIZE + 4 * fonts.len();
+    let mut font_offsets = Vec::with_capacity(fonts.len());
+    for font in fonts.iter() {
+        font_offsets.push(offset);
+        offset += TABLE_DIRECTORY_HEADER_SIZE
+            + TABLE_RECORD_SIZE * font.table_directory.table_records().len();
+    }
+
+    let mut directories = Vec::with_capacity(offset);
+    let mut table_data = Vec::new();
+    let mut table_offsets: HashMap<&[u8], usize> = HashMap::new();
+    for font in fonts.iter() {
+        let table_directory = &font.table_directory;
+        directories.extend(table_directory.sfnt_version().to_be_bytes());
+        directories.extend(table_directory.num_tables().to_be_bytes());
+        directories.extend(table_directory.search_range().to_be_bytes());
+        directories.extend(table_directory.entry_selector().to_be_bytes());
+        directories.extend(table_directory.range_shift().to_be_bytes());
+        for record in table_directory.table_records() {
+            let tag = record.tag();
+            let data = font
+                .table_data(tag)
+                .ok_or(Error::MissingTable(tag))?
+                .as_bytes();
+            let table_offset = *table_offsets.entry(data).or_insert_with(|| {
+                let table_offset = offset + table_data.len();
+                table_data.extend(data);
+                table_data.resize(padded_len(table_data.len()), 0);
+                table_offset
+            });
+            directories.extend(tag.into_bytes());
+            directories.extend(record.checksum().to_be_bytes());
+            directories.extend((table_offset as u32).to_be_bytes());
+            directories.extend(record.length().to_be_bytes());
+        }
+    }
+    debug!(
+        "Assembled a {} font collection with {} distinct tables",
+        fonts.len(),
+        table_offsets.len()
+    );
+
+    let mut collection = Vec::with_capacity(offset + table_data.len());
+    collection.extend(TTC_TAG.into_bytes());
+    collection.extend(1u16.to_be_bytes()); // majorVersion
+    collection.extend(0u16.to_be_bytes()); // minorVersion
+    collection.extend((fonts.len() as u32).to_be_bytes());
+    for font_offset in font_offsets {
+        collection.extend((font_offset as u32).to_be_bytes());
+    }
+    collection.extend(directories);
+    collection.extend(table_data);
+    Ok(collection)
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{
+        read::{CollectionRef, TableProvider},
+        tables::maxp::Maxp,
+        types::Fixed,
+    };
+
+    use super::*;
+
+    fn test_font(font_revision: f64) -> Vec<u8> {
+        let head = Head {
+            font_revision: Fixed::from_f64(font_revision),
+            ..Default::default()
+        };
+        let maxp = Maxp::new(7);
+        let mut builder = FontBuilder::new();
+        builder.add_table(&head).unwrap();
+        builder.add_table(&maxp).unwrap();
+        builder.build()
+    }
+
+    fn table_offset(collection: &CollectionRef, index: u32, tag: Tag) -> u32 {
+        collection
+            .get(index)
+            .unwrap()
+            .table_directory
+            .table_records()
+            .iter()
+            .find(|record| record.tag() == tag)
+            .unwrap()
+            .offset()
+    }
+
+    #[test]
+    fn collection_shares_identical_tables() {
+        let regular = test_font(1.0);
+        let bold = test_font(2.0);
+        let ttc = build_collection(&[&regular, &bold]).unwrap();
+        let collection = CollectionRef::new(&ttc).unwrap();
+
+        assert_eq!(2, collection.len());
+        assert_eq!(
+            table_offset(&collection, 0, Maxp::TAG),
+            table_offset(&collection, 1, Maxp::TAG)
+        );
+        assert_ne!(
+            table_offset(&collection, 0, Head::TAG),
+            table_offset(&collection, 1, Head::TAG)
+        );
+        assert_eq!(
+            Fixed::from_f64(2.0),
+            collection.get(1).unwrap().head().unwrap().font_revision()
+        );
+        assert_eq!(7, collection.get(1).unwrap().maxp().unwrap().num_glyphs());
+    }
+}
@@ -3,7 +3,10 @@
 //! Each glyph is built in isolation and then the fragments are collected
 //! and glued together to form a final table.
 
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::Arc,
+};
 
 use fontdrasil::{
     coords::NormalizedLocation,
@@ -11,17 +14,22 @@ use fontdrasil::{
     types::GlyphName,
 };
 use fontir::{
+    instancer::instantiate_contours,
     ir,
     orchestration::{Flags, WorkId as FeWorkId},
+    overlaps::remove_overlaps,
     variations::{VariationModel, VariationRegion},
 };
-use kurbo::{cubics_to_quadratic_splines, Affine, BezPath, CubicBez, PathEl, Point, Rect, Vec2};
+use kurbo::{
+    cubics_to_quadratic_splines, Affine, BezPath, CubicBez, PathEl, Point, QuadBez, Rect, Vec2,
+};
 use log::{log_enabled, trace, warn};
 
 use write_fonts::{
     read::{
         tables::glyf::{self, Anchor, Transform},
         types::F2Dot14,
+        FontData, ReadError,
     },
     tables::{
         glyf::{
@@ -29,6 +37,7 @@ use write_fonts::{
             SimpleGlyph,
         },
         gvar::{iup::iup_delta_optimize, GlyphDelta},
+        loca::LocaFormat,
     },
     OtRound,
 };
@@ -40,13 +49,53 @@ use crate::{
 
 type Deltas = Vec<(VariationRegion, Vec<GlyphDelta>)>;
 
+/// <https://learn.microsoft.com/en-us/typography/opentype/spec/glyf#simple-glyph-description>
+const OVERLAP_SIMPLE: u8 = 0x40;
+/// <https://learn.microsoft.com/en-us/typography/opentype/spec/glyf#composite-glyph-description>
+const OVERLAP_COMPOUND: u16 = 0x0400;
+const ON_CURVE_POINT: u8 = 0x01;
+const REPEAT_FLAG: u8 = 0x08;
+/// <https://github.com/harfbuzz/boring-expansion-spec/blob/main/glyf1-cubicOutlines.md>
+const CUBIC: u8 = 0x80;
+
+/// The furthest a quadratic approximation may stray from the cubic curve it replaces
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cu2QuMaxError {
+    /// In font units
+    Absolute(f64),
+    /// As a fraction of units per em
+    RelativeToUpem(f64),
+}
+
+impl Default for Cu2QuMaxError {
+    /// Match the fontTools.cu2qu default tolerance, 1/1000th of UPEM:
+    /// <https://github.com/fonttools/fonttools/blob/f99774a/Lib/fontTools/cu2qu/ufo.py#L43-L46>
+    fn default() -> Self {
+        Cu2QuMaxError::RelativeToUpem(0.001)
+    }
+}
+
+impl Cu2QuMaxError {
+    /// The max error in font units
+    pub fn in_units(&self, units_per_em: u16) -> f64 {
+        match self {
+            Cu2QuMaxError::Absolute(max_error) => *max_error,
+            Cu2QuMaxError::RelativeToUpem(max_error) => *max_error * units_per_em as f64,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct GlyphWork {
     glyph_name: GlyphName,
+    max_error: Cu2QuMaxError,
 }
 
-pub fn create_glyf_work(glyph_name: GlyphName) -> Box<BeWork> {
-    Box::new(GlyphWork { glyph_name })
+pub fn create_glyf_work(glyph_name: GlyphName, max_error: Cu2QuMaxError) -> Box<BeWork> {
+    Box::new(GlyphWork {
+        glyph_name,
+        max_error,
+    })
 }
 
 /// Can glyph instance reuse the metrics of other?
@@ -335,14 +384,28 @@ impl Work<Context, AnyWorkId, Error> for GlyphWork {
 
         let static_metadata = context.ir.static_metadata.get();
         let default_location = static_metadata.default_location();
-        let ir_glyph = &*context
+        let mut ir_glyph = context
             .ir
             .glyphs
             .get(&FeWorkId::Glyph(self.glyph_name.clone()));
+        // glyf has no variable components, VARC may add them back
+        if ir_glyph.has_variable_components() {
+            ir_glyph = Arc::new(with_components_drawn(context, &ir_glyph)?);
+        }
+        // A variable font keeps its overlaps, see set_overlap_flags
+        if context.flags.contains(Flags::REMOVE_OVERLAPS) && static_metadata.axes.is_empty() {
+            ir_glyph = Arc::new(without_overlaps(&ir_glyph));
+        }
+        let ir_glyph = &*ir_glyph;
         let glyph = CheckedGlyph::new(ir_glyph)?;
 
         // Hopefully in time https://github.com/harfbuzz/boring-expansion-spec means we can drop this
-        let mut glyph = cubics_to_quadratics(glyph, static_metadata.units_per_em);
+        let cubic = context.flags.contains(Flags::CUBIC_GLYF);
+        let mut glyph = if cubic {
+            cubics_to_quadratic_pairs(glyph)
+        } else {
+            cubics_to_quadratics(glyph, self.max_error.in_units(static_metadata.units_per_em))?
+        };
 
         if !context.flags.contains(Flags::KEEP_DIRECTION) {
             glyph.reverse_contour_direction();
@@ -372,6 +435,12 @@ impl Work<Context, AnyWorkId, Error> for GlyphWork {
                             .collect::<Vec<_>>()
                             .join("\n"),
                     })?;
+                if cubic && !simple_glyphs.iter().all(has_paired_off_curves) {
+                    return Err(Error::GlyphError(
+                        ir_glyph.name.clone(),
+                        GlyphProblem::UnpairedCubicOffCurve,
+                    ));
+                }
                 let mut instances = HashMap::new();
                 for (loc, glyph) in locations.into_iter().zip(simple_glyphs.into_iter()) {
                     instances.insert(loc, glyph);
@@ -447,21 +516,47 @@ impl Work<Context, AnyWorkId, Error> for GlyphWork {
     }
 }
 
-fn cubics_to_quadratics(glyph: CheckedGlyph, units_per_em: u16) -> CheckedGlyph {
+/// The glyph with every component, variable or not, drawn into contours at each source
+fn with_components_drawn(context: &Context, glyph: &ir::Glyph) -> Result<ir::Glyph, Error> {
+    let static_metadata = context.ir.static_metadata.get();
+    let glyphs = |name: &GlyphName| context.ir.glyphs.try_get(&FeWorkId::Glyph(name.clone()));
+    let mut drawn = glyph.clone();
+    for (loc, instance) in drawn.sources_mut() {
+        instance.contours = instantiate_contours(&glyphs, &static_metadata.axes, glyph, loc)
+            .map_err(|e| Error::VariableComponentError(glyph.name.clone(), e))?;
+        instance.components.clear();
+    }
+    Ok(drawn)
+}
+
+/// The glyph with the contours of each source merged where they overlap
+fn without_overlaps(glyph: &ir::Glyph) -> ir::Glyph {
+    let mut glyph = glyph.clone();
+    let name = glyph.name.clone();
+    for (loc, instance) in glyph.sources_mut() {
+        match remove_overlaps(&instance.contours) {
+            Some(contours) => instance.contours = contours,
+            None => warn!("Unable to remove overlaps from '{name}' at {loc:?}"),
+        }
+    }
+    glyph
+}
+
+/// Convert every cubic to quadratic splines no more than `tolerance` font units away.
+///
+/// Corresponding cubics of all the masters are converted together, into the same number
+/// of quadratics, so the result is as compatible as the input.
+fn cubics_to_quadratics(glyph: CheckedGlyph, tolerance: f64) -> Result<CheckedGlyph, Error> {
     let CheckedGlyph::Contour {
         name,
         paths: contours,
     } = glyph
     else {
-        return glyph; // nop for composite
+        return Ok(glyph); // nop for composite
     };
 
     trace!("Convert '{name}' to quadratic");
 
-    // match fontTools.cu2qu default tolerance (i.e 1/1000th of UPEM):
-    // https://github.com/fonttools/fonttools/blob/f99774a/Lib/fontTools/cu2qu/ufo.py#L43-L46
-    let tolerance = units_per_em as f64 / 1000.0;
-
     // put all the loc + path iters into a vec
     let mut loc_iters: Vec<_> = contours
         .iter()
@@ -515,7 +610,7 @@ fn cubics_to_quadratics(glyph: CheckedGlyph, units_per_em: u16) -> CheckedGlyph
 
             // At long last, actually convert something to quadratic
             let Some(quad_splines) = cubics_to_quadratic_splines(&cubics, tolerance) else {
-                panic!("'{name}': unable to convert to quadratic {cubics:?}");
+                return Err(Error::Cu2QuError(name, tolerance));
             };
             if quad_splines.len() != loc_iters.len() {
                 panic!(
@@ -560,12 +655,104 @@ fn cubics_to_quadratics(glyph: CheckedGlyph, units_per_em: u16) -> CheckedGlyph
         }
     }
 
-    CheckedGlyph::Contour {
+    Ok(CheckedGlyph::Contour {
         name,
         paths: new_contours,
+    })
+}
+
+/// Prepare a glyph to be written with cubic curves, see [`set_cubic_flags`].
+///
+/// [`SimpleGlyph`] only understands quadratics so each cubic becomes two quadratics that
+/// meet at the midpoint of its control points. That on-curve point is implied, so it's
+/// dropped when the glyph is built, leaving exactly the points of the cubic. Quadratics are
+/// raised to cubics first; points are rounded such that the control points of each cubic
+/// sum to even coordinates, so the implied points are exact.
+fn cubics_to_quadratic_pairs(glyph: CheckedGlyph) -> CheckedGlyph {
+    let CheckedGlyph::Contour { name, paths } = glyph else {
+        return glyph; // nop for composite
+    };
+
+    trace!("Prepare '{name}' for cubic glyf");
+
+    let round = |p: Point| Point::new(p.x.round(), p.y.round());
+    let paths = paths
+        .into_iter()
+        .map(|(loc, path)| {
+            let mut pairs = BezPath::new();
+            let mut current = Point::ZERO;
+            for el in path.elements() {
+                let cubic = match *el {
+                    PathEl::MoveTo(p) => {
+                        current = round(p);
+                        pairs.move_to(current);
+                        continue;
+                    }
+                    PathEl::LineTo(p) => {
+                        current = round(p);
+                        pairs.line_to(current);
+                        continue;
+                    }
+                    PathEl::ClosePath => {
+                        pairs.close_path();
+                        continue;
+                    }
+                    PathEl::QuadTo(p1, p2) => QuadBez::new(current, p1, p2).raise(),
+                    PathEl::CurveTo(p1, p2, p3) => CubicBez::new(current, p1, p2, p3),
+                };
+                let p1 = round(cubic.p1);
+                let p2 = Point::new(
+                    round_to_even_sum(p1.x, cubic.p2.x),
+                    round_to_even_sum(p1.y, cubic.p2.y),
+                );
+                current = round(cubic.p3);
+                pairs.quad_to(p1, p1.midpoint(p2));
+                pairs.quad_to(p2, current);
+            }
+            (loc, pairs)
+        })
+        .collect();
+    CheckedGlyph::Contour { name, paths }
+}
+
+/// Round `value` to the nearest integer that makes `value + rounded_other` even.
+///
+/// Anything else puts the midpoint of the two on a half unit, which can't be implied.
+fn round_to_even_sum(rounded_other: f64, value: f64) -> f64 {
+    let rounded = value.round();
+    if (rounded_other + rounded) % 2.0 == 0.0 {
+        rounded
+    } else if value >= rounded {
+        rounded + 1.0
+    } else {
+        rounded - 1.0
     }
 }
 
+/// Whether the off-curve points of every contour come in pairs, as cubic curves need.
+///
+/// A run of more than two is fine, it has implied on-curve points between each pair.
+fn has_paired_off_curves(glyph: &SimpleGlyph) -> bool {
+    glyph.contours().iter().all(|contour| {
+        let points: Vec<_> = contour.iter().collect();
+        let Some(first_on) = points.iter().position(|p| p.on_curve) else {
+            return points.len() % 2 == 0;
+        };
+        let mut run = 0;
+        for point in points[first_on..].iter().chain(&points[..first_on]) {
+            if point.on_curve {
+                if run % 2 != 0 {
+                    return false;
+                }
+                run = 0;
+            } else {
+                run += 1;
+            }
+        }
+        run % 2 == 0
+    })
+}
+
 /// An [ir::Glyph] that has been confirmed to maintain invariants:
 ///
 /// <ul>
@@ -844,6 +1031,99 @@ fn compute_composite_bboxes(context: &Context) -> Result<(), Error> {
     Ok(())
 }
 
+/// Flag every glyph as possibly having overlaps, as fontmake does for variable fonts.
+///
+/// Overlaps can't be removed from a variable font without breaking compatibility, so we tell
+/// rasterizers to expect them instead. The flags are only meaningful on the first point of
+/// a simple glyph and the first component of a composite.
+fn set_overlap_flags(
+    glyf: &mut [u8],
+    loca: &[u8],
+    loca_format: LocaFormat,
+) -> Result<(), ReadError> {
+    let offsets = glyph_offsets(loca, loca_format);
+    for range in offsets.windows(2) {
+        let glyph = glyf
+            .get_mut(range[0]..range[1])
+            .ok_or(ReadError::OutOfBounds)?;
+        if glyph.is_empty() {
+            continue; // no outline
+        }
+        let data = FontData::new(glyph);
+        let number_of_contours: i16 = data.read_at(0)?;
+        // numberOfContours and the bbox are followed by endPtsOfContours or the first component
+        let (pos, flag) = match number_of_contours {
+            0 => continue, // no points to flag
+            1.. => {
+                let instruction_length = 10 + 2 * number_of_contours as usize;
+                let instructions: u16 = data.read_at(instruction_length)?;
+                (
+                    instruction_length + 2 + instructions as usize,
+                    OVERLAP_SIMPLE,
+                )
+            }
+            _ => (10, (OVERLAP_COMPOUND >> 8) as u8),
+        };
+        *glyph.get_mut(pos).ok_or(ReadError::OutOfBounds)? |= flag;
+    }
+    Ok(())
+}
+
+/// Mark every off-curve point of every simple glyph as a cubic control point.
+///
+/// See [`cubics_to_quadratic_pairs`] for how we got only cubic off-curve points.
+fn set_cubic_flags(glyf: &mut [u8], loca: &[u8], loca_format: LocaFormat) -> Result<(), ReadError> {
+    let offsets = glyph_offsets(loca, loca_format);
+    for range in offsets.windows(2) {
+        let glyph = glyf
+            .get_mut(range[0]..range[1])
+            .ok_or(ReadError::OutOfBounds)?;
+        if glyph.is_empty() {
+            continue; // no outline
+        }
+        let data = FontData::new(glyph);
+        let number_of_contours: i16 = data.read_at(0)?;
+        if number_of_contours <= 0 {
+            continue; // composite, or no points
+        }
+        // numberOfContours and the bbox are followed by endPtsOfContours
+        let last_end = 10 + 2 * (number_of_contours as usize - 1);
+        let num_points = data.read_at::<u16>(last_end)? as usize + 1;
+        let instruction_length = last_end + 2;
+        let instructions: u16 = data.read_at(instruction_length)?;
+
+        let mut pos = instruction_length + 2 + instructions as usize;
+        let mut seen = 0;
+        while seen < num_points {
+            let flag = glyph.get_mut(pos).ok_or(ReadError::OutOfBounds)?;
+            if *flag & ON_CURVE_POINT == 0 {
+                *flag |= CUBIC;
+            }
+            if *flag & REPEAT_FLAG != 0 {
+                pos += 1;
+                seen += *glyph.get(pos).ok_or(ReadError::OutOfBounds)? as usize;
+            }
+            pos += 1;
+            seen += 1;
+        }
+    }
+    Ok(())
+}
+
+/// The offset of each glyph in glyf, and the end of the last one
+fn glyph_offsets(loca: &[u8], loca_format: LocaFormat) -> Vec<usize> {
+    match loca_format {
+        LocaFormat::Short => loca
+            .chunks_exact(2)
+            .map(|offset| u16::from_be_bytes([offset[0], offset[1]]) as usize * 2)
+            .collect(),
+        LocaFormat::Long => loca
+            .chunks_exact(4)
+            .map(|offset| u32::from_be_bytes(offset.try_into().unwrap()) as usize)
+            .collect(),
+    }
+}
+
 impl Work<Context, AnyWorkId, Error> for GlyfLocaWork {
     fn id(&self) -> AnyWorkId {
         WorkId::Glyf.into()
@@ -889,7 +1169,15 @@ impl Work<Context, AnyWorkId, Error> for GlyfLocaWork {
 
         let (glyf, loca, loca_format) = builder.build();
         let raw_loca = write_fonts::dump_table(&loca).unwrap();
-        let raw_glyf = write_fonts::dump_table(&glyf).unwrap();
+        let mut raw_glyf = write_fonts::dump_table(&glyf).unwrap();
+        if context.flags.contains(Flags::REMOVE_OVERLAPS)
+            && !context.ir.static_metadata.get().axes.is_empty()
+        {
+            set_overlap_flags(&mut raw_glyf, &raw_loca, loca_format)?;
+        }
+        if context.flags.contains(Flags::CUBIC_GLYF) {
+            set_cubic_flags(&mut raw_glyf, &raw_loca, loca_format)?;
+        }
         context.loca_format.set(loca_format.into());
         context.glyf.set(raw_glyf.into());
         context.loca.set(raw_loca.into());
@@ -1014,9 +1302,11 @@ mod tests {
         // The default conversion accuracy/tolerance is set to 1/1000th of the UPEM.
         // Therefore, the number of converted quadratic segments increases as the UPEM
         // decreases, or decreases as the UPEM increases.
-        let CheckedGlyph::Contour { paths, .. } =
-            cubics_to_quadratics(simple_static_contour_glyph(), upem)
-        else {
+        let CheckedGlyph::Contour { paths, .. } = cubics_to_quadratics(
+            simple_static_contour_glyph(),
+            Cu2QuMaxError::default().in_units(upem),
+        )
+        .unwrap() else {
             panic!("Expected a contour glyph");
         };
 
@@ -1025,4 +1315,159 @@ mod tests {
             expected_segments
         );
     }
+
+    #[test]
+    fn absolute_max_error_ignores_upem() {
+        let max_error = Cu2QuMaxError::Absolute(1.0);
+        assert_eq!(1.0, max_error.in_units(2000));
+        let CheckedGlyph::Contour { paths, .. } =
+            cubics_to_quadratics(simple_static_contour_glyph(), max_error.in_units(2000)).unwrap()
+        else {
+            panic!("Expected a contour glyph");
+        };
+        // The same as the default at 1000 upem
+        assert_eq!(7, paths.values().next().unwrap().segments().count());
+    }
+
+    #[test]
+    fn unreachable_max_error_is_an_error() {
+        assert!(cubics_to_quadratics(simple_static_contour_glyph(), 0.0).is_err());
+    }
+
+    fn cubic_simple_glyph() -> SimpleGlyph {
+        let CheckedGlyph::Contour { paths, .. } =
+            cubics_to_quadratic_pairs(simple_static_contour_glyph())
+        else {
+            panic!("Expected a contour glyph");
+        };
+        let paths: Vec<_> = paths.into_values().collect();
+        SimpleGlyph::interpolatable_glyphs_from_bezpaths(&paths)
+            .unwrap()
+            .pop()
+            .unwrap()
+    }
+
+    #[test]
+    fn quadratic_pairs_keep_implied_points_exact() {
+        let mut paths = HashMap::new();
+        paths.insert(
+            NormalizedLocation::from(vec![(Tag::new(b"wght"), NormalizedCoord::new(0.0))]),
+            BezPath::from_vec(vec![
+                PathEl::MoveTo((0.0, 0.0).into()),
+                // Rounded naively the control points sum to (31, 705)
+                PathEl::CurveTo(
+                    (10.4, 400.0).into(),
+                    (21.3, 304.6).into(),
+                    (500.0, 0.0).into(),
+                ),
+                PathEl::ClosePath,
+            ]),
+        );
+        let CheckedGlyph::Contour { paths, .. } =
+            cubics_to_quadratic_pairs(CheckedGlyph::Contour {
+                name: GlyphName::from("test"),
+                paths,
+            })
+        else {
+            panic!("Expected a contour glyph");
+        };
+        let paths: Vec<_> = paths.into_values().collect();
+        let glyph = SimpleGlyph::interpolatable_glyphs_from_bezpaths(&paths)
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert!(has_paired_off_curves(&glyph));
+        assert_eq!(
+            vec![
+                ((0, 0), true),
+                ((10, 400), false),
+                ((22, 304), false),
+                ((500, 0), true),
+            ],
+            glyph
+                .contours()
+                .iter()
+                .flat_map(|c| c.iter())
+                .map(|p| ((p.x, p.y), p.on_curve))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn quadratic_pairs_leave_only_the_cubic_points() {
+        let glyph = cubic_simple_glyph();
+        assert!(has_paired_off_curves(&glyph));
+        assert_eq!(
+            vec![
+                ((0, 500), true),
+                ((200, 500), false),
+                ((500, 200), false),
+                ((500, 0), true),
+            ],
+            glyph
+                .contours()
+                .iter()
+                .flat_map(|c| c.iter())
+                .map(|p| ((p.x, p.y), p.on_curve))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn set_cubic_flags_on_off_curve_points() {
+        let mut builder = GlyfLocaBuilder::new();
+        builder.add_glyph(&cubic_simple_glyph()).unwrap();
+        let (glyf, loca, loca_format) = builder.build();
+        let raw_loca = write_fonts::dump_table(&loca).unwrap();
+        let mut raw_glyf = write_fonts::dump_table(&glyf).unwrap();
+        set_cubic_flags(&mut raw_glyf, &raw_loca, loca_format).unwrap();
+
+        // one contour, no instructions, so the flags start after the header, endPts and instructionLength
+        let mut flags = Vec::new();
+        let mut pos = 14;
+        while flags.len() < 4 {
+            let flag = raw_glyf[pos];
+            let repeat = if flag & REPEAT_FLAG != 0 {
+                pos += 1;
+                raw_glyf[pos] as usize
+            } else {
+                0
+            };
+            flags.extend(std::iter::repeat(flag).take(repeat + 1));
+            pos += 1;
+        }
+        assert_eq!(
+            vec![false, true, true, false],
+            flags
+                .iter()
+                .map(|flag| flag & CUBIC != 0)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn set_overlap_flag_on_first_point() {
+        let mut builder = GlyfLocaBuilder::new();
+        builder.add_glyph(&cubic_simple_glyph()).unwrap();
+        let (glyf, loca, loca_format) = builder.build();
+        let raw_loca = write_fonts::dump_table(&loca).unwrap();
+        let mut raw_glyf = write_fonts::dump_table(&glyf).unwrap();
+        set_overlap_flags(&mut raw_glyf, &raw_loca, loca_format).unwrap();
+
+        // one contour, no instructions, so the first flag is after the header, endPts and instructionLength
+        assert_ne!(0, raw_glyf[14] & OVERLAP_SIMPLE);
+    }
+
+    #[test]
+    fn set_flags_in_truncated_glyf() {
+        let mut builder = GlyfLocaBuilder::new();
+        builder.add_glyph(&cubic_simple_glyph()).unwrap();
+        let (glyf, loca, loca_format) = builder.build();
+        let raw_loca = write_fonts::dump_table(&loca).unwrap();
+        let mut raw_glyf = write_fonts::dump_table(&glyf).unwrap();
+        raw_glyf.truncate(12);
+
+        assert!(set_overlap_flags(&mut raw_glyf, &raw_loca, loca_format).is_err());
+        assert!(set_cubic_flags(&mut raw_glyf, &raw_loca, loca_format).is_err());
+    }
 }
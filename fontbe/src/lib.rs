@@ -18,3 +18,6 @@ pub mod post;
 pub mod stat;
 #[cfg(test)]
 mod test_util;
+pub mod varc;
+pub mod woff;
+pub mod woff2;
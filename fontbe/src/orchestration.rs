@@ -102,6 +102,7 @@ pub enum WorkId {
     Os2,
     Post,
     Stat,
+    Varc,
 }
 
 impl WorkId {
@@ -144,6 +145,7 @@ impl Identifier for WorkId {
             WorkId::Os2 => "BeOs2",
             WorkId::Post => "BePost",
             WorkId::Stat => "BeStat",
+            WorkId::Varc => "BeVarc",
         }
     }
 }
@@ -404,12 +406,22 @@ pub type KernAdjustments = BTreeMap<NormalizedLocation, OrderedFloat<f32>>;
 ///
 /// It is an invariant that every group referenced in an adjustment exists in the
 /// groups mapping, and every glyph in a rule (including in all groups) is defined
-/// in the glyph order.
+/// in the glyph order. Vertical adjustments likewise reference vertical groups.
 #[derive(Default, Clone, Serialize, Deserialize, PartialEq)]
 pub struct AllKerningPairs {
     /// A mapping from named kern groups to the appropriate set of glyphs
     pub groups: BTreeMap<KernGroup, GlyphSet>,
     pub adjustments: Vec<(ir::KernPair, KernAdjustments)>,
+    /// As groups, for vertical kerning
+    pub vertical_groups: BTreeMap<KernGroup, GlyphSet>,
+    pub vertical_adjustments: Vec<(ir::KernPair, KernAdjustments)>,
+}
+
+impl AllKerningPairs {
+    /// The number of horizontal and vertical adjustments
+    pub(crate) fn len(&self) -> usize {
+        self.adjustments.len() + self.vertical_adjustments.len()
+    }
 }
 
 impl Persistable for AllKerningPairs {
@@ -574,11 +586,13 @@ impl KernPair {
 
 /// A chunk of kerning that needs to be fed into a [PairPosBuilder]
 ///
-/// Points to a slice of [AllKerningPairs].
+/// Points to a slice of [AllKerningPairs], horizontal adjustments first.
 #[derive(Default, Clone, Serialize, Deserialize, PartialEq)]
 pub struct KernFragment {
     pub(crate) segment: usize,
     pub(crate) kerns: Vec<KernPair>,
+    /// Pairs for vertical kerning, the value is a y-advance
+    pub(crate) vertical_kerns: Vec<KernPair>,
 }
 
 impl IdAware<AnyWorkId> for KernFragment {
@@ -756,6 +770,8 @@ pub struct Context {
     pub fea_rs_kerns: BeContextItem<FeaRsKerns>,
     pub fea_rs_marks: BeContextItem<FeaRsMarks>,
     pub stat: BeContextItem<BeValue<Stat>>,
+    // Empty if there is no VARC
+    pub varc: BeContextItem<Bytes>,
     pub font: BeContextItem<Bytes>,
 }
 
@@ -792,6 +808,7 @@ impl Context {
             fea_rs_kerns: self.fea_rs_kerns.clone_with_acl(acl.clone()),
             fea_rs_marks: self.fea_rs_marks.clone_with_acl(acl.clone()),
             stat: self.stat.clone_with_acl(acl.clone()),
+            varc: self.varc.clone_with_acl(acl.clone()),
             fea_ast: self.fea_ast.clone_with_acl(acl.clone()),
             font: self.font.clone_with_acl(acl),
         }
@@ -854,6 +871,7 @@ impl Context {
                 persistent_storage.clone(),
             ),
             stat: ContextItem::new(WorkId::Stat.into(), acl.clone(), persistent_storage.clone()),
+            varc: ContextItem::new(WorkId::Varc.into(), acl.clone(), persistent_storage.clone()),
             font: ContextItem::new(WorkId::Font.into(), acl, persistent_storage),
         }
     }
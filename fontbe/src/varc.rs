@@ -0,0 +1,829 @@
+//! Generates a [VARC](https://github.com/harfbuzz/boring-expansion-spec/blob/main/VARC.md) table.
+//!
+//! write-fonts doesn't know VARC yet so the table is assembled by hand. We only write
+//! what we need: a format 1 coverage, no conditions and one MultiItemVariationData per glyph.
+
+use std::{collections::HashMap, f64::consts::FRAC_PI_2};
+
+use fontdrasil::{
+    coords::NormalizedLocation,
+    orchestration::{Access, AccessBuilder, Work},
+    types::{Axis, GlyphName},
+};
+use fontir::{
+    ir::{self, GlyphOrder},
+    orchestration::{Flags, WorkId as FeWorkId},
+    variations::{VariationModel, VariationRegion},
+};
+use indexmap::IndexSet;
+use kurbo::Affine;
+use log::warn;
+use write_fonts::{
+    types::{F2Dot14, Tag},
+    OtRound,
+};
+
+use crate::{
+    error::{Error, GlyphProblem},
+    orchestration::{AnyWorkId, BeWork, Context, WorkId},
+};
+
+const HAVE_AXES: u32 = 1 << 1;
+const AXIS_VALUES_HAVE_VARIATION: u32 = 1 << 2;
+const TRANSFORM_HAS_VARIATION: u32 = 1 << 3;
+const HAVE_TRANSLATE_X: u32 = 1 << 4;
+const HAVE_TRANSLATE_Y: u32 = 1 << 5;
+const HAVE_ROTATION: u32 = 1 << 6;
+const HAVE_SCALE_X: u32 = 1 << 8;
+const HAVE_SCALE_Y: u32 = 1 << 9;
+const HAVE_SKEW_X: u32 = 1 << 13;
+const HAVE_SKEW_Y: u32 = 1 << 14;
+
+const SCALE_X: usize = 3;
+const SCALE_Y: usize = 4;
+
+/// The transform fields of a VarComponent, in the order they are written.
+///
+/// Each is (flag, fractional bits, the value stored as 1.0, default).
+const TRANSFORM_FIELDS: [(u32, u32, f64, f64); 7] = [
+    (HAVE_TRANSLATE_X, 0, 1.0, 0.0),
+    (HAVE_TRANSLATE_Y, 0, 1.0, 0.0),
+    (HAVE_ROTATION, 12, 180.0, 0.0),
+    (HAVE_SCALE_X, 10, 1.0, 1.0),
+    (HAVE_SCALE_Y, 10, 1.0, 1.0),
+    (HAVE_SKEW_X, 12, -180.0, 0.0),
+    (HAVE_SKEW_Y, 12, 180.0, 0.0),
+];
+
+#[derive(Debug)]
+struct VarcWork {}
+
+pub fn create_varc_work() -> Box<BeWork> {
+    Box::new(VarcWork {})
+}
+
+/// An affine transform as the parameters VARC stores, angles in degrees.
+///
+/// Rust version of fontTools [DecomposedTransform.fromTransform](https://github.com/fonttools/fonttools/blob/main/Lib/fontTools/misc/transform.py)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct DecomposedTransform {
+    translate_x: f64,
+    translate_y: f64,
+    rotation: f64,
+    scale_x: f64,
+    scale_y: f64,
+    skew_x: f64,
+    skew_y: f64,
+}
+
+impl DecomposedTransform {
+    fn from_affine(affine: Affine) -> Self {
+        let [mut a, mut b, c, d, translate_x, translate_y] = affine.as_coeffs();
+        let sx = 1.0f64.copysign(a);
+        if sx < 0.0 {
+            a *= sx;
+            b *= sx;
+        }
+        let det = a * d - b * c;
+
+        let mut rotation = 0.0;
+        let mut scale_x = 0.0;
+        let mut scale_y = 0.0;
+        let mut skew_x = 0.0;
+        let mut skew_y = 0.0;
+        if a != 0.0 || b != 0.0 {
+            let r = a.hypot(b);
+            rotation = if b >= 0.0 {
+                (a / r).acos()
+            } else {
+                -(a / r).acos()
+            };
+            scale_x = r;
+            scale_y = det / r;
+            skew_x = ((a * c + b * d) / (r * r)).atan();
+        } else if c != 0.0 || d != 0.0 {
+            let s = c.hypot(d);
+            rotation = FRAC_PI_2
+                - if d >= 0.0 {
+                    (-c / s).acos()
+                } else {
+                    -(c / s).acos()
+                };
+            scale_x = det / s;
+            scale_y = s;
+            skew_y = ((a * c + b * d) / (s * s)).atan();
+        }
+
+        DecomposedTransform {
+            translate_x,
+            translate_y,
+            rotation: rotation.to_degrees(),
+            scale_x: scale_x * sx,
+            scale_y,
+            skew_x: skew_x.to_degrees() * sx,
+            skew_y: skew_y.to_degrees(),
+        }
+    }
+
+    fn values(&self) -> [f64; 7] {
+        [
+            self.translate_x,
+            self.translate_y,
+            self.rotation,
+            self.scale_x,
+            self.scale_y,
+            self.skew_x,
+            self.skew_y,
+        ]
+    }
+}
+
+/// The fixed point value of a transform field as stored in VARC
+fn transform_field(value: f64, frac_bits: u32, unit: f64) -> Result<i16, Error> {
+    let raw: i32 = (value / unit * (1 << frac_bits) as f64).ot_round();
+    i16::try_from(raw).map_err(|_| Error::OutOfBounds {
+        what: "VARC transform field".to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn stored_transform(transform: Affine) -> Result<[i16; 7], Error> {
+    let values = DecomposedTransform::from_affine(transform).values();
+    let mut stored = [0; 7];
+    for (i, (_, frac_bits, unit, _)) in TRANSFORM_FIELDS.iter().enumerate() {
+        stored[i] = transform_field(values[i], *frac_bits, *unit)?;
+    }
+    Ok(stored)
+}
+
+/// Start, peak and end of a sparse region on an fvar axis, by axis index
+type RegionAxis = (u16, i16, i16, i16);
+
+/// Deltas for a single set of regions, a MultiItemVariationData
+#[derive(Debug, Default)]
+struct VarData {
+    region_indices: Vec<u16>,
+    items: Vec<Vec<i32>>,
+}
+
+/// Helper to build VARC from glyphs with variable components.
+struct VarcBuilder<'a> {
+    /// Variation axes
+    axes: &'a [Axis],
+    /// Glyph ids of the variable composites, ascending, and their VarComponent records
+    glyphs: Vec<(u16, Vec<u8>)>,
+    axis_indices: IndexSet<Vec<u16>>,
+    regions: IndexSet<Vec<RegionAxis>>,
+    var_data: Vec<VarData>,
+}
+
+impl<'a> VarcBuilder<'a> {
+    fn new(axes: &'a [Axis]) -> Self {
+        VarcBuilder {
+            axes,
+            glyphs: Vec::new(),
+            axis_indices: IndexSet::new(),
+            regions: IndexSet::new(),
+            var_data: Vec::new(),
+        }
+    }
+
+    fn axis_index(&self, tag: &Tag) -> Option<u16> {
+        self.axes
+            .iter()
+            .position(|axis| axis.tag == *tag)
+            .map(|i| i as u16)
+    }
+
+    fn region_index(&mut self, region: &VariationRegion) -> u16 {
+        let region = region
+            .iter()
+            .filter(|(_, tent)| tent.has_non_zero())
+            .filter_map(|(tag, tent)| {
+                self.axis_index(tag).map(|i| {
+                    (
+                        i,
+                        F2Dot14::from_f32(tent.min.to_f32()).to_bits(),
+                        F2Dot14::from_f32(tent.peak.to_f32()).to_bits(),
+                        F2Dot14::from_f32(tent.max.to_f32()).to_bits(),
+                    )
+                })
+            })
+            .collect();
+        self.regions.insert_full(region).0 as u16
+    }
+
+    /// Add deltas for values that vary across the glyph's sources, returns the VarIndex
+    fn add_deltas(
+        &mut self,
+        glyph_name: &GlyphName,
+        model: &VariationModel,
+        var_data: &mut VarData,
+        values: &HashMap<NormalizedLocation, Vec<i32>>,
+    ) -> Result<u32, Error> {
+        let values: HashMap<_, _> = values
+            .iter()
+            .map(|(loc, values)| {
+                (
+                    loc.clone(),
+                    values.iter().map(|v| *v as f64).collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        let deltas: Vec<_> = model
+            .deltas::<f64, f64>(&values)
+            .map_err(|e| Error::VarcDeltaError(glyph_name.clone(), e))?
+            .into_iter()
+            .filter(|(region, _)| !region.is_default())
+            .collect();
+
+        // The model is the same for every value of the glyph so the regions are too
+        if var_data.items.is_empty() {
+            var_data.region_indices = deltas
+                .iter()
+                .map(|(region, _)| self.region_index(region))
+                .collect();
+        }
+        let item = deltas
+            .into_iter()
+            .flat_map(|(_, deltas)| deltas.into_iter().map(|d| d.ot_round()))
+            .collect();
+        var_data.items.push(item);
+
+        let outer = self.var_data.len() as u32;
+        let inner = (var_data.items.len() - 1) as u32;
+        Ok((outer << 16) | inner)
+    }
+
+    fn add_glyph(
+        &mut self,
+        gid: u16,
+        glyph: &ir::Glyph,
+        glyph_order: &GlyphOrder,
+    ) -> Result<(), Error> {
+        let default = glyph.default_instance();
+        for instance in glyph.sources().values() {
+            if instance.components.len() != default.components.len()
+                || instance
+                    .components
+                    .iter()
+                    .zip(default.components.iter())
+                    .any(|(c1, c2)| c1.base != c2.base)
+            {
+                return Err(Error::GlyphError(
+                    glyph.name.clone(),
+                    GlyphProblem::InconsistentComponents,
+                ));
+            }
+        }
+        let model = VariationModel::new(
+            glyph.sources().keys().cloned().collect(),
+            self.axes.to_vec(),
+        )
+        .map_err(|e| Error::VariationModelError(glyph.name.clone(), e))?;
+        let mut var_data = VarData::default();
+
+        let mut record = Vec::new();
+        for (i, component) in default.components.iter().enumerate() {
+            let Some(base_gid) = glyph_order.glyph_id(&component.base) else {
+                return Err(Error::ComponentError {
+                    glyph: glyph.name.clone(),
+                    referenced_glyph: component.base.clone(),
+                    problem: GlyphProblem::NotInGlyphOrder,
+                });
+            };
+            let mut flags = 0;
+
+            // Axes not in fvar, if any, can't be expressed
+            let axes: Vec<_> = component
+                .location
+                .iter()
+                .filter_map(|(tag, _)| self.axis_index(tag).map(|i| (i, *tag)))
+                .collect();
+            let values_of = |component: &ir::Component| -> Vec<i32> {
+                axes.iter()
+                    .map(|(_, tag)| {
+                        let value = component
+                            .location
+                            .get(*tag)
+                            .map(|c| c.to_f32())
+                            .unwrap_or_default();
+                        F2Dot14::from_f32(value).to_bits() as i32
+                    })
+                    .collect()
+            };
+            let mut axis_values = HashMap::new();
+            let mut transforms = HashMap::new();
+            for (loc, instance) in glyph.sources() {
+                let component = &instance.components[i];
+                axis_values.insert(loc.clone(), values_of(component));
+                transforms.insert(loc.clone(), stored_transform(component.transform)?);
+            }
+            let default_axis_values = &values_of(component);
+            let default_transform = stored_transform(component.transform)?;
+
+            // Write the fields that aren't their default anywhere. Absent scaleY means scaleX.
+            let present: Vec<_> = (0..TRANSFORM_FIELDS.len())
+                .filter(|f| {
+                    transforms.values().any(|stored| {
+                        if *f == SCALE_Y {
+                            stored[SCALE_Y] != stored[SCALE_X]
+                        } else {
+                            let (_, frac_bits, unit, default) = TRANSFORM_FIELDS[*f];
+                            // Defaults are small, they always fit
+                            stored[*f] != transform_field(default, frac_bits, unit).unwrap()
+                        }
+                    })
+                })
+                .collect();
+            for f in present.iter() {
+                flags |= TRANSFORM_FIELDS[*f].0;
+            }
+
+            let mut axis_indices_index = None;
+            let mut axis_values_var_index = None;
+            if !axes.is_empty() {
+                flags |= HAVE_AXES;
+                let indices = axes.iter().map(|(i, _)| *i).collect();
+                axis_indices_index = Some(self.axis_indices.insert_full(indices).0 as u32);
+                if axis_values.values().any(|v| v != default_axis_values) {
+                    flags |= AXIS_VALUES_HAVE_VARIATION;
+                    axis_values_var_index =
+                        Some(self.add_deltas(&glyph.name, &model, &mut var_data, &axis_values)?);
+                }
+            }
+
+            let mut transform_var_index = None;
+            if transforms
+                .values()
+                .any(|stored| present.iter().any(|f| stored[*f] != default_transform[*f]))
+            {
+                flags |= TRANSFORM_HAS_VARIATION;
+                let transforms = transforms
+                    .iter()
+                    .map(|(loc, stored)| {
+                        (
+                            loc.clone(),
+                            present.iter().map(|f| stored[*f] as i32).collect(),
+                        )
+                    })
+                    .collect();
+                transform_var_index =
+                    Some(self.add_deltas(&glyph.name, &model, &mut var_data, &transforms)?);
+            }
+
+            write_uint32var(&mut record, flags);
+            record.extend(base_gid.to_u16().to_be_bytes());
+            if let Some(axis_indices_index) = axis_indices_index {
+                write_uint32var(&mut record, axis_indices_index);
+                write_tuple_values(&mut record, default_axis_values);
+            }
+            if let Some(var_index) = axis_values_var_index {
+                write_uint32var(&mut record, var_index);
+            }
+            if let Some(var_index) = transform_var_index {
+                write_uint32var(&mut record, var_index);
+            }
+            for f in present {
+                record.extend(default_transform[f].to_be_bytes());
+            }
+        }
+
+        if !var_data.items.is_empty() {
+            self.var_data.push(var_data);
+        }
+        self.glyphs.push((gid, record));
+        Ok(())
+    }
+
+    /// The VARC table, empty if there are no variable composites
+    fn build(self) -> Vec<u8> {
+        if self.glyphs.is_empty() {
+            return Vec::new();
+        }
+
+        let mut coverage = Vec::new();
+        coverage.extend(1u16.to_be_bytes());
+        coverage.extend((self.glyphs.len() as u16).to_be_bytes());
+        for (gid, _) in self.glyphs.iter() {
+            coverage.extend(gid.to_be_bytes());
+        }
+
+        let var_store = (!self.var_data.is_empty())
+            .then(|| multi_item_variation_store(&self.regions, &self.var_data));
+
+        let axis_indices: Vec<_> = self
+            .axis_indices
+            .iter()
+            .map(|indices| {
+                let mut item = Vec::new();
+                let indices: Vec<_> = indices.iter().map(|i| *i as i32).collect();
+                write_tuple_values(&mut item, &indices);
+                item
+            })
+            .collect();
+        let axis_indices = index(&axis_indices);
+
+        let records: Vec<_> = self.glyphs.into_iter().map(|(_, record)| record).collect();
+        let composites = index(&records);
+
+        // majorVersion, minorVersion and five Offset32
+        let mut offset = 24u32;
+        let mut varc = Vec::new();
+        varc.extend(1u16.to_be_bytes());
+        varc.extend(0u16.to_be_bytes());
+        let mut subtables = Vec::new();
+        for subtable in [
+            Some(coverage),
+            var_store,
+            None,
+            Some(axis_indices),
+            Some(composites),
+        ] {
+            match subtable {
+                Some(subtable) => {
+                    varc.extend(offset.to_be_bytes());
+                    offset += subtable.len() as u32;
+                    subtables.push(subtable);
+                }
+                None => varc.extend(0u32.to_be_bytes()),
+            }
+        }
+        for subtable in subtables {
+            varc.extend(subtable);
+        }
+        varc
+    }
+}
+
+fn multi_item_variation_store(
+    regions: &IndexSet<Vec<RegionAxis>>,
+    var_data: &[VarData],
+) -> Vec<u8> {
+    let mut region_list = Vec::new();
+    region_list.extend((regions.len() as u16).to_be_bytes());
+    let mut offset = 2 + 4 * regions.len() as u32;
+    for region in regions.iter() {
+        region_list.extend(offset.to_be_bytes());
+        offset += 2 + 8 * region.len() as u32;
+    }
+    for region in regions.iter() {
+        region_list.extend((region.len() as u16).to_be_bytes());
+        for (axis_index, start, peak, end) in region.iter() {
+            region_list.extend(axis_index.to_be_bytes());
+            region_list.extend(start.to_be_bytes());
+            region_list.extend(peak.to_be_bytes());
+            region_list.extend(end.to_be_bytes());
+        }
+    }
+
+    let var_data: Vec<_> = var_data
+        .iter()
+        .map(|var_data| {
+            let mut data = vec![1u8];
+            data.extend((var_data.region_indices.len() as u16).to_be_bytes());
+            for region_index in var_data.region_indices.iter() {
+                data.extend(region_index.to_be_bytes());
+            }
+            let items: Vec<_> = var_data
+                .items
+                .iter()
+                .map(|deltas| {
+                    let mut item = Vec::new();
+                    write_tuple_values(&mut item, deltas);
+                    item
+                })
+                .collect();
+            data.extend(index(&items));
+            data
+        })
+        .collect();
+
+    // format, regionListOffset, itemVariationDataCount, itemVariationDataOffsets
+    let mut offset = 2 + 4 + 2 + 4 * var_data.len() as u32;
+    let mut store = Vec::new();
+    store.extend(1u16.to_be_bytes());
+    store.extend(offset.to_be_bytes());
+    offset += region_list.len() as u32;
+    store.extend((var_data.len() as u16).to_be_bytes());
+    for data in var_data.iter() {
+        store.extend(offset.to_be_bytes());
+        offset += data.len() as u32;
+    }
+    store.extend(region_list);
+    for data in var_data {
+        store.extend(data);
+    }
+    store
+}
+
+/// Write a variable length uint32, big-endian with the length in the leading bits
+fn write_uint32var(out: &mut Vec<u8>, value: u32) {
+    if value < 0x80 {
+        out.push(value as u8);
+    } else if value < 0x4000 {
+        out.extend((value as u16 | 0x8000).to_be_bytes());
+    } else if value < 0x20_0000 {
+        out.extend(&(value | 0xC0_0000).to_be_bytes()[1..]);
+    } else if value < 0x1000_0000 {
+        out.extend((value | 0xE000_0000).to_be_bytes());
+    } else {
+        out.push(0xF0);
+        out.extend(value.to_be_bytes());
+    }
+}
+
+/// Write values using the packed deltas encoding of gvar, extended with 32-bit runs
+fn write_tuple_values(out: &mut Vec<u8>, values: &[i32]) {
+    const ZEROS: u8 = 0x80;
+    const BYTES: u8 = 0x00;
+    const WORDS: u8 = 0x40;
+    const LONGS: u8 = 0xC0;
+    const MAX_RUN: usize = 64;
+
+    let run_type_of = |value: i32| {
+        if value == 0 {
+            ZEROS
+        } else if i8::try_from(value).is_ok() {
+            BYTES
+        } else if i16::try_from(value).is_ok() {
+            WORDS
+        } else {
+            LONGS
+        }
+    };
+
+    let mut values = values;
+    while let Some(first) = values.first() {
+        let run_type = run_type_of(*first);
+        let run_len = values
+            .iter()
+            .take(MAX_RUN)
+            .take_while(|v| run_type_of(**v) == run_type)
+            .count();
+        out.push(run_type | (run_len - 1) as u8);
+        for value in &values[..run_len] {
+            match run_type {
+                BYTES => out.push(*value as i8 as u8),
+                WORDS => out.extend((*value as i16).to_be_bytes()),
+                LONGS => out.extend(value.to_be_bytes()),
+                _ => (),
+            }
+        }
+        values = &values[run_len..];
+    }
+}
+
+/// Write a CFF2 INDEX of the given items
+fn index(items: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend((items.len() as u32).to_be_bytes());
+    if items.is_empty() {
+        return out;
+    }
+    let data_len: usize = items.iter().map(|item| item.len()).sum();
+    let max_offset = data_len as u32 + 1;
+    let off_size = match max_offset {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x1_0000..=0xFF_FFFF => 3,
+        _ => 4,
+    };
+    out.push(off_size as u8);
+    let mut offset = 1u32;
+    out.extend(&offset.to_be_bytes()[4 - off_size..]);
+    for item in items {
+        offset += item.len() as u32;
+        out.extend(&offset.to_be_bytes()[4 - off_size..]);
+    }
+    for item in items {
+        out.extend(item);
+    }
+    out
+}
+
+impl Work<Context, AnyWorkId, Error> for VarcWork {
+    fn id(&self) -> AnyWorkId {
+        WorkId::Varc.into()
+    }
+
+    fn read_access(&self) -> Access<AnyWorkId> {
+        AccessBuilder::new()
+            .variant(FeWorkId::StaticMetadata)
+            .variant(FeWorkId::GlyphOrder)
+            .variant(FeWorkId::Glyph(GlyphName::NOTDEF))
+            .build()
+    }
+
+    /// Generate [VARC](https://github.com/harfbuzz/boring-expansion-spec/blob/main/VARC.md)
+    ///
+    /// Always produces bytes, empty if there is no VARC.
+    fn exec(&self, context: &Context) -> Result<(), Error> {
+        let static_metadata = context.ir.static_metadata.get();
+        let glyph_order = context.ir.glyph_order.get();
+
+        let mut builder = VarcBuilder::new(&static_metadata.axes);
+        if context.flags.contains(Flags::VARC) && !static_metadata.axes.is_empty() {
+            for (gid, glyph_name) in glyph_order.iter().enumerate() {
+                let glyph = context.ir.glyphs.get(&FeWorkId::Glyph(glyph_name.clone()));
+                if !glyph.has_variable_components() {
+                    continue;
+                }
+                // A VARC glyph is only its components, contours would need a glyph of their own
+                if glyph
+                    .sources()
+                    .values()
+                    .any(|instance| !instance.contours.is_empty())
+                {
+                    warn!("'{glyph_name}' has contours and variable components, it's only in glyf");
+                    continue;
+                }
+                builder.add_glyph(gid as u16, &glyph, &glyph_order)?;
+            }
+        }
+        context.varc.set(builder.build().into());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use fontdrasil::{
+        coords::{CoordConverter, NormalizedCoord, UserCoord},
+        types::Axis,
+    };
+    use fontir::ir::{Component, GlyphBuilder, GlyphInstance};
+
+    use super::*;
+
+    fn axis(tag: &str, hidden: bool) -> Axis {
+        let min = UserCoord::new(-1.0);
+        let default = UserCoord::new(0.0);
+        let max = UserCoord::new(1.0);
+        Axis {
+            name: tag.to_string(),
+            tag: Tag::from_str(tag).unwrap(),
+            min,
+            default,
+            max,
+            hidden,
+            converter: CoordConverter::unmapped(min, default, max),
+        }
+    }
+
+    fn location(tag: &str, value: f32) -> NormalizedLocation {
+        let mut loc = NormalizedLocation::new();
+        loc.insert(Tag::from_str(tag).unwrap(), NormalizedCoord::new(value));
+        loc
+    }
+
+    fn to_affine(transform: DecomposedTransform) -> Affine {
+        Affine::translate((transform.translate_x, transform.translate_y))
+            * Affine::rotate(transform.rotation.to_radians())
+            * Affine::scale_non_uniform(transform.scale_x, transform.scale_y)
+            * Affine::skew(
+                transform.skew_x.to_radians().tan(),
+                transform.skew_y.to_radians().tan(),
+            )
+    }
+
+    fn assert_affine_eq(expected: Affine, actual: Affine) {
+        let expected = expected.as_coeffs();
+        let actual = actual.as_coeffs();
+        assert!(
+            expected
+                .iter()
+                .zip(actual)
+                .all(|(e, a)| (e - a).abs() < 1e-9),
+            "{expected:?} != {actual:?}"
+        );
+    }
+
+    #[test]
+    fn decompose_round_trips() {
+        for affine in [
+            Affine::IDENTITY,
+            Affine::translate((10.0, -20.0)),
+            Affine::rotate(0.5) * Affine::scale_non_uniform(2.0, 0.5),
+            Affine::scale_non_uniform(-1.0, 1.0) * Affine::skew(0.5, 0.0),
+            Affine::new([0.0, 1.0, -1.0, 0.0, 5.0, 5.0]),
+            Affine::new([0.0, 0.0, 2.0, 3.0, 0.0, 0.0]),
+        ] {
+            assert_affine_eq(affine, to_affine(DecomposedTransform::from_affine(affine)));
+        }
+    }
+
+    #[test]
+    fn uint32var() {
+        for (value, expected) in [
+            (0x7F, vec![0x7F]),
+            (0x80, vec![0x80, 0x80]),
+            (0x3FFF, vec![0xBF, 0xFF]),
+            (0x4000, vec![0xC0, 0x40, 0x00]),
+            (0x20_0000, vec![0xE0, 0x20, 0x00, 0x00]),
+            (0x1000_0000, vec![0xF0, 0x10, 0x00, 0x00, 0x00]),
+        ] {
+            let mut out = Vec::new();
+            write_uint32var(&mut out, value);
+            assert_eq!(expected, out, "{value:#x}");
+        }
+    }
+
+    #[test]
+    fn tuple_values() {
+        let mut out = Vec::new();
+        write_tuple_values(&mut out, &[0, 0, 1, -1, 300, 70000]);
+        assert_eq!(
+            vec![
+                0x81, // two zeros
+                0x01, 0x01, 0xFF, // two bytes
+                0x40, 0x01, 0x2C, // one word
+                0xC0, 0x00, 0x01, 0x11, 0x70, // one long
+            ],
+            out
+        );
+    }
+
+    #[test]
+    fn long_runs_are_split() {
+        let mut out = Vec::new();
+        write_tuple_values(&mut out, &[0; 65]);
+        assert_eq!(vec![0xBF, 0x80], out);
+    }
+
+    #[test]
+    fn cff2_index() {
+        assert_eq!(vec![0, 0, 0, 0], index(&[]));
+        assert_eq!(
+            vec![0, 0, 0, 2, 1, 1, 2, 4, 0xA, 0xB, 0xC],
+            index(&[vec![0xA], vec![0xB, 0xC]])
+        );
+    }
+
+    #[test]
+    fn no_glyphs_no_table() {
+        let axes = vec![axis("V000", true)];
+        assert!(VarcBuilder::new(&axes).build().is_empty());
+    }
+
+    #[test]
+    fn variable_component_record() {
+        let axes = vec![axis("V000", true)];
+        let glyph_order: GlyphOrder = ["base", "composite"]
+            .into_iter()
+            .map(GlyphName::from)
+            .collect();
+
+        let component = |value: f32, dx: f64| Component {
+            base: "base".into(),
+            transform: Affine::translate((dx, 0.0)),
+            location: location("V000", value),
+        };
+        let mut builder = GlyphBuilder::new("composite".into());
+        for (loc, value, dx) in [(0.0, 0.5, 10.0), (1.0, 1.0, 20.0)] {
+            builder
+                .try_add_source(
+                    &location("V000", loc),
+                    GlyphInstance {
+                        components: vec![component(value, dx)],
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+        let glyph = builder.build().unwrap();
+
+        let mut varc = VarcBuilder::new(&axes);
+        varc.add_glyph(1, &glyph, &glyph_order).unwrap();
+
+        let (gid, record) = &varc.glyphs[0];
+        assert_eq!(1, *gid);
+        let flags =
+            HAVE_AXES | AXIS_VALUES_HAVE_VARIATION | TRANSFORM_HAS_VARIATION | HAVE_TRANSLATE_X;
+        assert_eq!(
+            vec![
+                flags as u8,
+                0x00,
+                0x00, // base gid
+                0x00, // axis indices index
+                0x40,
+                0x20,
+                0x00, // 0.5 as F2Dot14, as a word
+                0x00, // axis values VarIndex
+                0x01, // transform VarIndex
+                0x00,
+                0x0A, // translateX
+            ],
+            *record
+        );
+        assert_eq!(
+            vec![vec![0u16]],
+            varc.axis_indices.into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(1, varc.var_data.len());
+        assert_eq!(vec![vec![0x2000], vec![10]], varc.var_data[0].items);
+    }
+}
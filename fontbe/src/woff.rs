@@ -0,0 +1,197 @@
+//! Encodes a compiled font as [WOFF](https://www.w3.org/TR/WOFF/).
+//!
+//! The extended metadata block is written if the source has any. No source
+//! format has a place for private data, so that block never is.
+
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+use fontir::ir::WoffMetadata;
+use write_fonts::read::{FontRef, TableProvider};
+
+use crate::error::Error;
+
+const WOFF_SIGNATURE: u32 = 0x774F4646; // 'wOFF'
+const HEADER_SIZE: usize = 44;
+const TABLE_DIRECTORY_ENTRY_SIZE: usize = 20;
+
+/// Encode a TrueType font as WOFF
+///
+/// Each table is zlib compressed, unless that would make it bigger.
+pub fn compress(font_data: &[u8], metadata: &WoffMetadata) -> Result<Vec<u8>, Error> {
+    let font = FontRef::new(font_data)?;
+    let mut records = font.table_directory.table_records().to_vec();
+    records.sort_by_key(|record| record.tag());
+
+    let mut offset = HEADER_SIZE + TABLE_DIRECTORY_ENTRY_SIZE * records.len();
+    let mut directory = Vec::with_capacity(TABLE_DIRECTORY_ENTRY_SIZE * records.len());
+    let mut table_data = Vec::new();
+    for record in records.iter() {
+        let tag = record.tag();
+        let data = font
+            .table_data(tag)
+            .ok_or(Error::MissingTable(tag))?
+            .as_bytes();
+        let compressed = zlib_compress(data)?;
+        let data = if compressed.len() < data.len() {
+            compressed.as_slice()
+        } else {
+            data
+        };
+        directory.extend(tag.into_bytes());
+        directory.extend((offset as u32).to_be_bytes());
+        directory.extend((data.len() as u32).to_be_bytes());
+        directory.extend(record.length().to_be_bytes());
+        directory.extend(record.checksum().to_be_bytes());
+
+        table_data.extend(data);
+        table_data.resize(padded_len(table_data.len()), 0);
+        offset += padded_len(data.len());
+    }
+
+    let total_sfnt_size = 12
+        + 16 * records.len()
+        + records
+            .iter()
+            .map(|record| padded_len(record.length() as usize))
+            .sum::<usize>();
+
+    let mut woff = Vec::new();
+    woff.extend(WOFF_SIGNATURE.to_be_bytes());
+    woff.extend(font.table_directory.sfnt_version().to_be_bytes());
+    woff.extend(0u32.to_be_bytes()); // length, filled in below
+    woff.extend((records.len() as u16).to_be_bytes());
+    woff.extend(0u16.to_be_bytes()); // reserved
+    woff.extend((total_sfnt_size as u32).to_be_bytes());
+    let (major, minor) = version(&font, metadata)?;
+    woff.extend(major.to_be_bytes());
+    woff.extend(minor.to_be_bytes());
+    let metadata = metadata
+        .xml
+        .as_ref()
+        .map(|xml| Ok::<_, Error>((zlib_compress(xml.as_bytes())?, xml.len())))
+        .transpose()?;
+    write_metadata_and_private_fields(&mut woff, offset, metadata.as_ref());
+    woff.extend(directory);
+    woff.extend(table_data);
+    if let Some((compressed, _)) = metadata {
+        woff.extend(compressed);
+    }
+
+    let length = woff.len() as u32;
+    woff[8..12].copy_from_slice(&length.to_be_bytes());
+    Ok(woff)
+}
+
+fn zlib_compress(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// The major and minor version for a WOFF or WOFF2 header
+///
+/// Unless the source says otherwise this is the head table fontRevision, as it is in fontTools.
+pub(crate) fn version(font: &FontRef, metadata: &WoffMetadata) -> Result<(u16, u16), Error> {
+    let font_revision = font.head()?.font_revision().to_bits() as u32;
+    Ok((
+        metadata
+            .major_version
+            .unwrap_or((font_revision >> 16) as u16),
+        metadata
+            .minor_version
+            .unwrap_or((font_revision & 0xffff) as u16),
+    ))
+}
+
+/// Write the metaOffset, metaLength, metaOrigLength, privOffset and privLength header fields
+///
+/// `metadata` is the compressed metadata and its original length, to be written at `offset`.
+/// We never write a private data block.
+pub(crate) fn write_metadata_and_private_fields(
+    header: &mut Vec<u8>,
+    offset: usize,
+    metadata: Option<&(Vec<u8>, usize)>,
+) {
+    let (meta_offset, meta_length, meta_orig_length) = match metadata {
+        Some((compressed, orig_length)) => (offset, compressed.len(), *orig_length),
+        None => (0, 0, 0),
+    };
+    for value in [meta_offset, meta_length, meta_orig_length, 0, 0] {
+        header.extend((value as u32).to_be_bytes());
+    }
+}
+
+pub(crate) fn table_checksum(data: &[u8]) -> u32 {
+    data.chunks(4)
+        .map(|chunk| {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_be_bytes(word)
+        })
+        .fold(0u32, |sum, word| sum.wrapping_add(word))
+}
+
+pub(crate) fn padded_len(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{
+        read::FontData,
+        tables::head::Head,
+        types::{Fixed, Tag},
+        FontBuilder,
+    };
+
+    use super::*;
+
+    fn test_font() -> Vec<u8> {
+        let head = Head {
+            font_revision: Fixed::from_f64(1.5),
+            ..Default::default()
+        };
+        let mut builder = FontBuilder::new();
+        builder.add_table(&head).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn header() {
+        let font = test_font();
+        let metadata = WoffMetadata {
+            xml: Some("<metadata version=\"1.0\"/>".to_string()),
+            ..Default::default()
+        };
+        let woff = compress(&font, &metadata).unwrap();
+        let header = FontData::new(&woff);
+
+        assert_eq!(Tag::new(b"wOFF"), header.read_at::<Tag>(0).unwrap());
+        assert_eq!(woff.len() as u32, header.read_at::<u32>(8).unwrap());
+        assert_eq!(1, header.read_at::<u16>(12).unwrap());
+        // fontRevision 1.5
+        assert_eq!(1, header.read_at::<u16>(20).unwrap());
+        assert_eq!(0x8000, header.read_at::<u16>(22).unwrap());
+        // metadata
+        let meta_offset = header.read_at::<u32>(24).unwrap();
+        assert_eq!(0, meta_offset % 4);
+        assert_ne!(0, header.read_at::<u32>(28).unwrap());
+        assert_eq!(25, header.read_at::<u32>(32).unwrap());
+        // no private data
+        assert_eq!(0, header.read_at::<u32>(36).unwrap());
+    }
+
+    #[test]
+    fn version_from_metadata() {
+        let font = test_font();
+        let metadata = WoffMetadata {
+            major_version: Some(3),
+            minor_version: Some(7),
+            xml: None,
+        };
+        let font = FontRef::new(&font).unwrap();
+        assert_eq!((3, 7), version(&font, &metadata).unwrap());
+        assert_eq!((1, 0x8000), version(&font, &Default::default()).unwrap());
+    }
+}
@@ -0,0 +1,944 @@
+//! Encodes a compiled font as [WOFF2](https://www.w3.org/TR/WOFF2/).
+//!
+//! The glyf and loca tables are transformed unless they have cubic curves, which
+//! the transform can't represent. The hmtx table is
+//! transformed when the left side bearings can be recovered from the glyph
+//! bounding boxes, as they usually can.
+
+use std::io::Cursor;
+
+use brotli::enc::{backward_references::BrotliEncoderMode, BrotliEncoderParams};
+use fontir::ir::WoffMetadata;
+use write_fonts::{
+    read::{tables::glyf::SimpleGlyph, FontData, FontRead, FontRef, ReadError, TableProvider},
+    types::Tag,
+};
+
+use crate::{
+    error::Error,
+    woff::{padded_len, table_checksum, version, write_metadata_and_private_fields},
+};
+
+const WOFF2_SIGNATURE: u32 = 0x774F4632; // 'wOF2'
+const HEADER_SIZE: usize = 48;
+
+const GLYF: Tag = Tag::new(b"glyf");
+const LOCA: Tag = Tag::new(b"loca");
+const HMTX: Tag = Tag::new(b"hmtx");
+const HEAD: Tag = Tag::new(b"head");
+
+/// Tags that can be encoded in the table directory flags rather than spelled out
+///
+/// See <https://www.w3.org/TR/WOFF2/#table_dir_format>
+const KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+    b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+    b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+    b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+/// In the table directory, marks a tag that isn't one of [`KNOWN_TAGS`]
+const ARBITRARY_TAG: u8 = 63;
+/// The transform version that means "not transformed" for glyf and loca
+const GLYF_NULL_TRANSFORM: u8 = 3;
+
+// head flags bit 11: the font has been subjected to a lossless transform
+const HEAD_FLAG_TRANSFORMED: u16 = 1 << 11;
+const CHECKSUM_MAGIC: u32 = 0xB1B0AFBA;
+
+// glyph flags we need to look at
+const OVERLAP_SIMPLE: u8 = 0x40;
+const REPEAT_FLAG: u8 = 0x08;
+const CUBIC: u8 = 0x80;
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+/// A table as it will be written to the WOFF2 file
+struct Woff2Table {
+    tag: Tag,
+    orig_length: u32,
+    /// The transform version, see <https://www.w3.org/TR/WOFF2/#table_dir_format>
+    transform_version: u8,
+    data: Vec<u8>,
+}
+
+impl Woff2Table {
+    fn is_transformed(&self) -> bool {
+        if self.tag == GLYF || self.tag == LOCA {
+            self.transform_version != GLYF_NULL_TRANSFORM
+        } else {
+            self.transform_version != 0
+        }
+    }
+}
+
+/// Encode a TrueType font as WOFF2
+pub fn compress(font_data: &[u8], metadata: &WoffMetadata) -> Result<Vec<u8>, Error> {
+    let font = FontRef::new(font_data)?;
+    let mut tables = font
+        .table_directory
+        .table_records()
+        .iter()
+        .map(|record| {
+            let tag = record.tag();
+            let data = font
+                .table_data(tag)
+                .ok_or(Error::MissingTable(tag))?
+                .as_bytes()
+                .to_vec();
+            Ok((tag, data))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    tables.sort_by_key(|(tag, _)| *tag);
+
+    let glyf = tables.iter().find(|(tag, _)| *tag == GLYF);
+    let loca = tables.iter().find(|(tag, _)| *tag == LOCA);
+    let transformed_glyf = match (glyf, loca) {
+        (Some((_, glyf)), Some((_, loca))) => transform_glyf(
+            glyf,
+            loca,
+            font.head()?.index_to_loc_format(),
+            font.maxp()?.num_glyphs(),
+        )?,
+        _ => None,
+    };
+    let transformed_hmtx = match (&transformed_glyf, tables.iter().find(|(t, _)| *t == HMTX)) {
+        (Some(glyf), Some((_, hmtx))) => transform_hmtx(&font, hmtx, &glyf.x_mins)?,
+        _ => None,
+    };
+
+    // The decoder recomputes glyf and loca so we must say we changed the font
+    let sfnt_version = font.table_directory.sfnt_version();
+    if transformed_glyf.is_some() {
+        set_head_transform_flag(sfnt_version, &mut tables)?;
+    }
+    let total_sfnt_size = 12
+        + 16 * tables.len()
+        + tables
+            .iter()
+            .map(|(_, data)| padded_len(data.len()))
+            .sum::<usize>();
+
+    let mut woff2_tables = Vec::with_capacity(tables.len());
+    for (tag, data) in tables {
+        let orig_length = data.len() as u32;
+        let (transform_version, data) = match tag {
+            GLYF | LOCA if transformed_glyf.is_none() => (GLYF_NULL_TRANSFORM, data),
+            // the decoder rebuilds loca from glyf; the transformed loca is empty
+            GLYF => (0, transformed_glyf.as_ref().unwrap().data.clone()),
+            LOCA => (0, Vec::new()),
+            HMTX if transformed_hmtx.is_some() => (1, transformed_hmtx.clone().unwrap()),
+            _ => (0, data),
+        };
+        woff2_tables.push(Woff2Table {
+            tag,
+            orig_length,
+            transform_version,
+            data,
+        });
+    }
+    // loca must immediately follow glyf
+    if let Some(loca_idx) = woff2_tables.iter().position(|t| t.tag == LOCA) {
+        let loca = woff2_tables.remove(loca_idx);
+        let glyf_idx = woff2_tables
+            .iter()
+            .position(|t| t.tag == GLYF)
+            .unwrap_or(woff2_tables.len() - 1);
+        woff2_tables.insert(glyf_idx + 1, loca);
+    }
+
+    let mut directory = Vec::new();
+    let mut uncompressed = Vec::new();
+    for table in woff2_tables.iter() {
+        let known_idx = KNOWN_TAGS
+            .iter()
+            .position(|known| **known == table.tag.into_bytes());
+        directory.push(
+            known_idx.unwrap_or(ARBITRARY_TAG as usize) as u8 | (table.transform_version << 6),
+        );
+        if known_idx.is_none() {
+            directory.extend(table.tag.into_bytes());
+        }
+        write_uint_base128(&mut directory, table.orig_length);
+        if table.is_transformed() {
+            write_uint_base128(&mut directory, table.data.len() as u32);
+        }
+        uncompressed.extend(&table.data);
+    }
+
+    let compressed = brotli_compress(&uncompressed)?;
+    let (major, minor) = version(&font, metadata)?;
+    let metadata = metadata
+        .xml
+        .as_ref()
+        .map(|xml| Ok::<_, Error>((brotli_compress(xml.as_bytes())?, xml.len())))
+        .transpose()?;
+
+    // the metadata block must start on a 4-byte boundary
+    let font_data_end = HEADER_SIZE + directory.len() + compressed.len();
+    let length = match &metadata {
+        Some((compressed, _)) => padded_len(font_data_end) + compressed.len(),
+        None => padded_len(font_data_end),
+    };
+    let mut woff2 = Vec::with_capacity(length);
+    woff2.extend(WOFF2_SIGNATURE.to_be_bytes());
+    woff2.extend(sfnt_version.to_be_bytes());
+    woff2.extend((length as u32).to_be_bytes());
+    woff2.extend((woff2_tables.len() as u16).to_be_bytes());
+    woff2.extend(0u16.to_be_bytes()); // reserved
+    woff2.extend((total_sfnt_size as u32).to_be_bytes());
+    woff2.extend((compressed.len() as u32).to_be_bytes());
+    woff2.extend(major.to_be_bytes());
+    woff2.extend(minor.to_be_bytes());
+    write_metadata_and_private_fields(&mut woff2, padded_len(font_data_end), metadata.as_ref());
+    woff2.extend(directory);
+    woff2.extend(compressed);
+    woff2.resize(padded_len(woff2.len()), 0);
+    if let Some((compressed, _)) = metadata {
+        woff2.extend(compressed);
+    }
+    Ok(woff2)
+}
+
+fn brotli_compress(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let params = BrotliEncoderParams {
+        quality: 11,
+        mode: BrotliEncoderMode::BROTLI_MODE_FONT,
+        size_hint: data.len(),
+        ..Default::default()
+    };
+    let mut compressed = Vec::new();
+    brotli::BrotliCompress(&mut Cursor::new(data), &mut compressed, &params)?;
+    Ok(compressed)
+}
+
+/// The result of <https://www.w3.org/TR/WOFF2/#glyf_table_format>
+struct TransformedGlyf {
+    data: Vec<u8>,
+    /// The xMin of each glyph, needed to transform hmtx
+    x_mins: Vec<i16>,
+}
+
+/// The separate streams the transformed glyf table is made of
+#[derive(Default)]
+struct GlyfStreams {
+    n_contours: Vec<u8>,
+    n_points: Vec<u8>,
+    flags: Vec<u8>,
+    glyphs: Vec<u8>,
+    composites: Vec<u8>,
+    bbox_bitmap: Vec<u8>,
+    bboxes: Vec<u8>,
+    instructions: Vec<u8>,
+    overlap_bitmap: Vec<u8>,
+}
+
+/// The transformed glyf, or None if it has cubic curves
+fn transform_glyf(
+    glyf: &[u8],
+    loca: &[u8],
+    index_format: i16,
+    num_glyphs: u16,
+) -> Result<Option<TransformedGlyf>, Error> {
+    let offsets = loca_offsets(loca, index_format, num_glyphs as usize)?;
+
+    let bitmap_len = ((num_glyphs as usize + 31) >> 5) << 2;
+    let mut streams = GlyfStreams {
+        bbox_bitmap: vec![0; bitmap_len],
+        overlap_bitmap: vec![0; bitmap_len],
+        ..Default::default()
+    };
+    let mut has_overlap = false;
+    let mut x_mins = Vec::with_capacity(num_glyphs as usize);
+
+    for (gid, range) in offsets.windows(2).enumerate() {
+        let data = glyf.get(range[0]..range[1]).ok_or(ReadError::OutOfBounds)?;
+        if data.is_empty() {
+            streams.n_contours.extend(0i16.to_be_bytes());
+            x_mins.push(0);
+            continue;
+        }
+        let header = FontData::new(data);
+        let num_contours: i16 = header.read_at(0)?;
+        let bbox: [i16; 4] = [
+            header.read_at(2)?,
+            header.read_at(4)?,
+            header.read_at(6)?,
+            header.read_at(8)?,
+        ];
+        streams.n_contours.extend(num_contours.to_be_bytes());
+        if num_contours == 0 {
+            // decoded as an empty glyph, which must not have an explicit bbox
+            x_mins.push(0);
+            continue;
+        }
+        x_mins.push(bbox[0]);
+
+        let explicit_bbox = if num_contours < 0 {
+            encode_composite(&mut streams, &data[10..])?;
+            true
+        } else {
+            let glyph = SimpleGlyph::read(header)?;
+            if has_cubic_points(&glyph) {
+                return Ok(None);
+            }
+            if glyph.glyph_data().first().copied().unwrap_or_default() & OVERLAP_SIMPLE != 0 {
+                streams.overlap_bitmap[gid >> 3] |= 0x80 >> (gid & 7);
+                has_overlap = true;
+            }
+            encode_simple(&mut streams, &glyph)? != bbox
+        };
+        if explicit_bbox {
+            streams.bbox_bitmap[gid >> 3] |= 0x80 >> (gid & 7);
+            streams
+                .bboxes
+                .extend(bbox.iter().flat_map(|v| v.to_be_bytes()));
+        }
+    }
+
+    let option_flags: u16 = if has_overlap { 1 } else { 0 };
+    let bbox_stream_len = streams.bbox_bitmap.len() + streams.bboxes.len();
+    let mut data = Vec::new();
+    data.extend(0u16.to_be_bytes()); // reserved
+    data.extend(option_flags.to_be_bytes());
+    data.extend(num_glyphs.to_be_bytes());
+    data.extend((index_format as u16).to_be_bytes());
+    for len in [
+        streams.n_contours.len(),
+        streams.n_points.len(),
+        streams.flags.len(),
+        streams.glyphs.len(),
+        streams.composites.len(),
+        bbox_stream_len,
+        streams.instructions.len(),
+    ] {
+        data.extend((len as u32).to_be_bytes());
+    }
+    data.extend(streams.n_contours);
+    data.extend(streams.n_points);
+    data.extend(streams.flags);
+    data.extend(streams.glyphs);
+    data.extend(streams.composites);
+    data.extend(streams.bbox_bitmap);
+    data.extend(streams.bboxes);
+    data.extend(streams.instructions);
+    if has_overlap {
+        data.extend(streams.overlap_bitmap);
+    }
+    Ok(Some(TransformedGlyf { data, x_mins }))
+}
+
+/// Whether any point of the glyph has the cubic flag, which the transform would drop
+fn has_cubic_points(glyph: &SimpleGlyph) -> bool {
+    let num_points = glyph
+        .end_pts_of_contours()
+        .last()
+        .map(|end| end.get() as usize + 1)
+        .unwrap_or_default();
+    let mut flags = glyph.glyph_data().iter();
+    let mut seen = 0;
+    while seen < num_points {
+        let Some(flag) = flags.next() else {
+            return false;
+        };
+        if flag & CUBIC != 0 {
+            return true;
+        }
+        if flag & REPEAT_FLAG != 0 {
+            seen += flags.next().copied().unwrap_or_default() as usize;
+        }
+        seen += 1;
+    }
+    false
+}
+
+/// The glyf offsets from a loca table, num_glyphs + 1 of them
+fn loca_offsets(loca: &[u8], index_format: i16, num_glyphs: usize) -> Result<Vec<usize>, Error> {
+    let loca = FontData::new(loca);
+    (0..=num_glyphs)
+        .map(|i| {
+            Ok(if index_format == 0 {
+                loca.read_at::<u16>(i * 2)? as usize * 2
+            } else {
+                loca.read_at::<u32>(i * 4)? as usize
+            })
+        })
+        .collect()
+}
+
+/// Add a simple glyph to the streams, returning the bbox computed from its points
+fn encode_simple(streams: &mut GlyfStreams, glyph: &SimpleGlyph) -> Result<[i16; 4], Error> {
+    let mut prev_end = -1i32;
+    for end in glyph.end_pts_of_contours() {
+        let end = end.get() as i32;
+        write_255_u16(&mut streams.n_points, (end - prev_end) as u16);
+        prev_end = end;
+    }
+
+    let mut bbox = [i16::MAX, i16::MAX, i16::MIN, i16::MIN];
+    let (mut prev_x, mut prev_y) = (0i32, 0i32);
+    for point in glyph.points() {
+        bbox = [
+            bbox[0].min(point.x),
+            bbox[1].min(point.y),
+            bbox[2].max(point.x),
+            bbox[3].max(point.y),
+        ];
+        let (x, y) = (point.x as i32, point.y as i32);
+        write_triplet(streams, x - prev_x, y - prev_y, point.on_curve);
+        (prev_x, prev_y) = (x, y);
+    }
+
+    let instructions = glyph.instructions();
+    write_255_u16(&mut streams.glyphs, instructions.len() as u16);
+    streams.instructions.extend(instructions);
+    Ok(bbox)
+}
+
+/// Add the components of a composite glyph, everything after the header, to the streams
+fn encode_composite(streams: &mut GlyfStreams, data: &[u8]) -> Result<(), Error> {
+    let components = FontData::new(data);
+    let mut pos = 0;
+    let mut has_instructions = false;
+    loop {
+        let flags: u16 = components.read_at(pos)?;
+        has_instructions |= flags & WE_HAVE_INSTRUCTIONS != 0;
+        // flags, glyph index, arguments
+        pos += 4;
+        pos += if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+            4
+        } else {
+            2
+        };
+        pos += if flags & WE_HAVE_A_SCALE != 0 {
+            2
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            4
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            8
+        } else {
+            0
+        };
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+    }
+    streams
+        .composites
+        .extend(data.get(..pos).ok_or(ReadError::OutOfBounds)?);
+    if has_instructions {
+        let len: u16 = components.read_at(pos)?;
+        let instructions = data
+            .get(pos + 2..pos + 2 + len as usize)
+            .ok_or(ReadError::OutOfBounds)?;
+        write_255_u16(&mut streams.glyphs, len);
+        streams.instructions.extend(instructions);
+    }
+    Ok(())
+}
+
+/// Write a point delta, see <https://www.w3.org/TR/WOFF2/#triplet_decoding>
+fn write_triplet(streams: &mut GlyfStreams, dx: i32, dy: i32, on_curve: bool) {
+    let (abs_x, abs_y) = (dx.unsigned_abs(), dy.unsigned_abs());
+    let on_curve_bit = if on_curve { 0 } else { 128 };
+    let x_sign_bit = if dx < 0 { 0 } else { 1 };
+    let y_sign_bit = if dy < 0 { 0 } else { 1 };
+    let xy_sign_bits = x_sign_bit + 2 * y_sign_bit;
+
+    let glyphs = &mut streams.glyphs;
+    let flag = if dx == 0 && abs_y < 1280 {
+        glyphs.push(abs_y as u8);
+        on_curve_bit + ((abs_y & 0xf00) >> 7) + y_sign_bit
+    } else if dy == 0 && abs_x < 1280 {
+        glyphs.push(abs_x as u8);
+        on_curve_bit + 10 + ((abs_x & 0xf00) >> 7) + x_sign_bit
+    } else if abs_x < 65 && abs_y < 65 {
+        glyphs.push(((((abs_x - 1) & 0xf) << 4) | ((abs_y - 1) & 0xf)) as u8);
+        on_curve_bit + 20 + ((abs_x - 1) & 0x30) + (((abs_y - 1) & 0x30) >> 2) + xy_sign_bits
+    } else if abs_x < 769 && abs_y < 769 {
+        glyphs.push((abs_x - 1) as u8);
+        glyphs.push((abs_y - 1) as u8);
+        on_curve_bit
+            + 84
+            + 12 * (((abs_x - 1) & 0x300) >> 8)
+            + (((abs_y - 1) & 0x300) >> 6)
+            + xy_sign_bits
+    } else if abs_x < 4096 && abs_y < 4096 {
+        glyphs.push((abs_x >> 4) as u8);
+        glyphs.push((((abs_x & 0xf) << 4) | (abs_y >> 8)) as u8);
+        glyphs.push(abs_y as u8);
+        on_curve_bit + 120 + xy_sign_bits
+    } else {
+        glyphs.extend((abs_x as u16).to_be_bytes());
+        glyphs.extend((abs_y as u16).to_be_bytes());
+        on_curve_bit + 124 + xy_sign_bits
+    };
+    streams.flags.push(flag as u8);
+}
+
+/// Drop left side bearings that match the glyph xMin, if any do.
+///
+/// See <https://www.w3.org/TR/WOFF2/#hmtx_table_format>
+fn transform_hmtx(font: &FontRef, hmtx: &[u8], x_mins: &[i16]) -> Result<Option<Vec<u8>>, Error> {
+    let num_h_metrics = font.hhea()?.number_of_h_metrics() as usize;
+    let hmtx = FontData::new(hmtx);
+    let mut advances = Vec::with_capacity(num_h_metrics);
+    let mut lsbs = Vec::with_capacity(x_mins.len());
+    for gid in 0..x_mins.len() {
+        if gid < num_h_metrics {
+            advances.push(hmtx.read_at::<u16>(gid * 4)?);
+            lsbs.push(hmtx.read_at::<i16>(gid * 4 + 2)?);
+        } else {
+            lsbs.push(hmtx.read_at::<i16>(num_h_metrics * 4 + (gid - num_h_metrics) * 2)?);
+        }
+    }
+    let num_h_metrics = num_h_metrics.min(x_mins.len());
+    let matches_x_min = |range: std::ops::Range<usize>| lsbs[range.clone()] == x_mins[range];
+    let omit_proportional = matches_x_min(0..num_h_metrics);
+    let omit_monospaced = matches_x_min(num_h_metrics..x_mins.len());
+    if !omit_proportional && !omit_monospaced {
+        return Ok(None);
+    }
+
+    let flags = omit_proportional as u8 | (omit_monospaced as u8) << 1;
+    let mut data = vec![flags];
+    data.extend(advances.iter().flat_map(|a| a.to_be_bytes()));
+    if !omit_proportional {
+        data.extend(lsbs[..num_h_metrics].iter().flat_map(|l| l.to_be_bytes()));
+    }
+    if !omit_monospaced {
+        data.extend(lsbs[num_h_metrics..].iter().flat_map(|l| l.to_be_bytes()));
+    }
+    Ok(Some(data))
+}
+
+/// Set the head flag for a transformed font and update the checksum adjustment to match
+fn set_head_transform_flag(sfnt_version: u32, tables: &mut [(Tag, Vec<u8>)]) -> Result<(), Error> {
+    let head = tables
+        .iter_mut()
+        .find(|(tag, _)| *tag == HEAD)
+        .map(|(_, data)| data)
+        .ok_or(Error::MissingTable(HEAD))?;
+    if head.len() < 54 {
+        return Err(Error::InvalidTableBytes(HEAD));
+    }
+    let flags = u16::from_be_bytes([head[16], head[17]]) | HEAD_FLAG_TRANSFORMED;
+    head[16..18].copy_from_slice(&flags.to_be_bytes());
+    head[8..12].copy_from_slice(&[0; 4]);
+
+    let adjustment = CHECKSUM_MAGIC.wrapping_sub(sfnt_checksum(sfnt_version, tables));
+    let head = tables
+        .iter_mut()
+        .find(|(tag, _)| *tag == HEAD)
+        .map(|(_, data)| data)
+        .unwrap();
+    head[8..12].copy_from_slice(&adjustment.to_be_bytes());
+    Ok(())
+}
+
+/// The checksum of the sfnt we would write for these tables, which must be sorted by tag
+fn sfnt_checksum(sfnt_version: u32, tables: &[(Tag, Vec<u8>)]) -> u32 {
+    let num_tables = tables.len() as u16;
+    let entry_selector = num_tables.checked_ilog2().unwrap_or_default() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+    let range_shift = num_tables * 16 - search_range;
+
+    let mut directory = Vec::with_capacity(12 + 16 * tables.len());
+    directory.extend(sfnt_version.to_be_bytes());
+    directory.extend(num_tables.to_be_bytes());
+    directory.extend(search_range.to_be_bytes());
+    directory.extend(entry_selector.to_be_bytes());
+    directory.extend(range_shift.to_be_bytes());
+
+    let mut offset = 12 + 16 * tables.len();
+    let mut checksum = 0u32;
+    for (tag, data) in tables {
+        let table_checksum = table_checksum(data);
+        directory.extend(tag.into_bytes());
+        directory.extend(table_checksum.to_be_bytes());
+        directory.extend((offset as u32).to_be_bytes());
+        directory.extend((data.len() as u32).to_be_bytes());
+        checksum = checksum.wrapping_add(table_checksum);
+        offset += padded_len(data.len());
+    }
+    checksum.wrapping_add(table_checksum(&directory))
+}
+
+/// See <https://www.w3.org/TR/WOFF2/#DataTypes>
+fn write_uint_base128(buf: &mut Vec<u8>, value: u32) {
+    let num_bytes = (1..5).find(|n| value >> (7 * n) == 0).unwrap_or(5);
+    for i in (0..num_bytes).rev() {
+        let mut byte = ((value >> (7 * i)) & 0x7f) as u8;
+        if i > 0 {
+            byte |= 0x80;
+        }
+        buf.push(byte);
+    }
+}
+
+/// See <https://www.w3.org/TR/WOFF2/#DataTypes>
+fn write_255_u16(buf: &mut Vec<u8>, value: u16) {
+    const WORD_CODE: u8 = 253;
+    const ONE_MORE_BYTE_CODE2: u8 = 254;
+    const ONE_MORE_BYTE_CODE1: u8 = 255;
+    const LOWEST_U_CODE: u16 = 253;
+    if value < LOWEST_U_CODE {
+        buf.push(value as u8);
+    } else if value < LOWEST_U_CODE * 2 {
+        buf.extend([ONE_MORE_BYTE_CODE1, (value - LOWEST_U_CODE) as u8]);
+    } else if value < LOWEST_U_CODE * 3 {
+        buf.extend([ONE_MORE_BYTE_CODE2, (value - LOWEST_U_CODE * 2) as u8]);
+    } else {
+        buf.push(WORD_CODE);
+        buf.extend(value.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::BezPath;
+    use write_fonts::{
+        dump_table,
+        from_obj::FromObjRef,
+        read::tables::glyf::{self, Anchor, Transform},
+        tables::{
+            glyf::{Bbox, Component, ComponentFlags, CompositeGlyph, GlyfLocaBuilder, Glyph},
+            loca::LocaFormat,
+        },
+        types::{F2Dot14, GlyphId},
+    };
+
+    use super::*;
+
+    /// Reads consecutive values from one of the streams of a transformed glyf
+    struct Stream<'a> {
+        data: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Stream<'a> {
+        fn new(data: &'a [u8]) -> Self {
+            Stream { data, pos: 0 }
+        }
+
+        fn bytes(&mut self, len: usize) -> &'a [u8] {
+            self.pos += len;
+            &self.data[self.pos - len..self.pos]
+        }
+
+        fn u8(&mut self) -> u8 {
+            self.bytes(1)[0]
+        }
+
+        fn u16(&mut self) -> u16 {
+            let bytes = self.bytes(2);
+            u16::from_be_bytes([bytes[0], bytes[1]])
+        }
+
+        fn i16(&mut self) -> i16 {
+            self.u16() as i16
+        }
+
+        fn u32(&mut self) -> u32 {
+            let bytes = self.bytes(4);
+            u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+        }
+
+        fn u16_255(&mut self) -> u16 {
+            match self.u8() {
+                253 => self.u16(),
+                254 => 506 + self.u8() as u16,
+                255 => 253 + self.u8() as u16,
+                value => value as u16,
+            }
+        }
+
+        /// See <https://www.w3.org/TR/WOFF2/#triplet_decoding>
+        fn triplet(&mut self, flag: u8) -> (i32, i32) {
+            let flag = (flag & 0x7f) as i32;
+            let with_sign = |flag: i32, value: i32| if flag & 1 != 0 { value } else { -value };
+            if flag < 10 {
+                let dy = ((flag & 14) << 7) + self.u8() as i32;
+                (0, with_sign(flag, dy))
+            } else if flag < 20 {
+                let dx = (((flag - 10) & 14) << 7) + self.u8() as i32;
+                (with_sign(flag, dx), 0)
+            } else if flag < 84 {
+                let (b0, b1) = (flag - 20, self.u8() as i32);
+                (
+                    with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+                    with_sign(flag >> 1, 1 + ((b0 & 0x0c) << 2) + (b1 & 0x0f)),
+                )
+            } else if flag < 120 {
+                let b0 = flag - 84;
+                let (b1, b2) = (self.u8() as i32, self.u8() as i32);
+                (
+                    with_sign(flag, 1 + ((b0 / 12) << 8) + b1),
+                    with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b2),
+                )
+            } else if flag < 124 {
+                let (b1, b2, b3) = (self.u8() as i32, self.u8() as i32, self.u8() as i32);
+                (
+                    with_sign(flag, (b1 << 4) + (b2 >> 4)),
+                    with_sign(flag >> 1, ((b2 & 0x0f) << 8) + b3),
+                )
+            } else {
+                let (dx, dy) = (self.u16() as i32, self.u16() as i32);
+                (with_sign(flag, dx), with_sign(flag >> 1, dy))
+            }
+        }
+    }
+
+    /// Decode a transformed glyf back to glyf and loca, as write-fonts would write them.
+    ///
+    /// See <https://www.w3.org/TR/WOFF2/#glyf_table_format>
+    fn decode_glyf(data: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut header = Stream::new(data);
+        header.u16(); // reserved
+        assert_eq!(0, header.u16(), "overlap bitmap isn't decoded");
+        let num_glyphs = header.u16() as usize;
+        header.u16(); // index format
+        let lens: Vec<_> = (0..7).map(|_| header.u32() as usize).collect();
+        let mut streams = lens.into_iter().map(|len| Stream::new(header.bytes(len)));
+        let mut n_contours = streams.next().unwrap();
+        let mut n_points = streams.next().unwrap();
+        let mut flags = streams.next().unwrap();
+        let mut glyphs = streams.next().unwrap();
+        let mut composites = streams.next().unwrap();
+        let mut bboxes = streams.next().unwrap();
+        let mut instructions = streams.next().unwrap();
+        let bbox_bitmap = bboxes.bytes(((num_glyphs + 31) >> 5) << 2);
+
+        let mut builder = GlyfLocaBuilder::new();
+        for gid in 0..num_glyphs {
+            let has_bbox = bbox_bitmap[gid >> 3] & (0x80 >> (gid & 7)) != 0;
+            let num_contours = n_contours.i16();
+            // the header of the glyph in glyf, without the bbox
+            let mut raw = num_contours.to_be_bytes().to_vec();
+            let glyph = if num_contours == 0 {
+                assert!(!has_bbox, "empty glyph {gid} has an explicit bbox");
+                Glyph::Empty
+            } else if num_contours > 0 {
+                let mut end_pts = Vec::new();
+                for _ in 0..num_contours {
+                    let prev_end = end_pts.last().copied().unwrap_or(-1i32);
+                    end_pts.push(prev_end + n_points.u16_255() as i32);
+                }
+                let mut points = Vec::new();
+                let (mut x, mut y) = (0, 0);
+                for _ in 0..=*end_pts.last().unwrap() {
+                    let flag = flags.u8();
+                    let (dx, dy) = glyphs.triplet(flag);
+                    (x, y) = (x + dx, y + dy);
+                    points.push((x as i16, y as i16, flag & 0x80 == 0));
+                }
+                let bbox = if has_bbox {
+                    [bboxes.i16(), bboxes.i16(), bboxes.i16(), bboxes.i16()]
+                } else {
+                    points.iter().fold(
+                        [i16::MAX, i16::MAX, i16::MIN, i16::MIN],
+                        |[x_min, y_min, x_max, y_max], (x, y, _)| {
+                            [x_min.min(*x), y_min.min(*y), x_max.max(*x), y_max.max(*y)]
+                        },
+                    )
+                };
+                let num_instructions = glyphs.u16_255();
+
+                // the simplest valid encoding: one flag per point and word deltas
+                raw.extend(bbox.iter().flat_map(|v| v.to_be_bytes()));
+                raw.extend(end_pts.iter().flat_map(|end| (*end as u16).to_be_bytes()));
+                raw.extend(num_instructions.to_be_bytes());
+                raw.extend(instructions.bytes(num_instructions as usize));
+                raw.extend(points.iter().map(|(_, _, on_curve)| *on_curve as u8));
+                let (mut prev_x, mut prev_y) = (0i16, 0i16);
+                let mut ys = Vec::new();
+                for (x, y, _) in points.iter() {
+                    raw.extend(x.wrapping_sub(prev_x).to_be_bytes());
+                    ys.extend(y.wrapping_sub(prev_y).to_be_bytes());
+                    (prev_x, prev_y) = (*x, *y);
+                }
+                raw.extend(ys);
+                let glyph = glyf::SimpleGlyph::read(FontData::new(&raw)).unwrap();
+                Glyph::Simple(FromObjRef::from_obj_ref(&glyph, FontData::new(&raw)))
+            } else {
+                assert!(has_bbox, "composite {gid} has no explicit bbox");
+                for _ in 0..4 {
+                    raw.extend(bboxes.i16().to_be_bytes());
+                }
+                let mut has_instructions = false;
+                loop {
+                    let flags = composites.u16();
+                    has_instructions |= flags & WE_HAVE_INSTRUCTIONS != 0;
+                    let args_len = if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                        4
+                    } else {
+                        2
+                    };
+                    let transform_len = if flags & WE_HAVE_A_SCALE != 0 {
+                        2
+                    } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                        4
+                    } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                        8
+                    } else {
+                        0
+                    };
+                    raw.extend(flags.to_be_bytes());
+                    raw.extend(composites.bytes(2 + args_len + transform_len));
+                    if flags & MORE_COMPONENTS == 0 {
+                        break;
+                    }
+                }
+                if has_instructions {
+                    let num_instructions = glyphs.u16_255();
+                    raw.extend(num_instructions.to_be_bytes());
+                    raw.extend(instructions.bytes(num_instructions as usize));
+                }
+                let glyph = glyf::CompositeGlyph::read(FontData::new(&raw)).unwrap();
+                Glyph::Composite(FromObjRef::from_obj_ref(&glyph, FontData::new(&raw)))
+            };
+            builder.add_glyph(&glyph).unwrap();
+        }
+        let (glyf, loca, _) = builder.build();
+        (dump_table(&glyf).unwrap(), dump_table(&loca).unwrap())
+    }
+
+    fn simple_glyph(svg: &str) -> Glyph {
+        Glyph::Simple(SimpleGlyph::from_bezpath(&BezPath::from_svg(svg).unwrap()).unwrap())
+    }
+
+    fn transformed_glyf(
+        glyf: &[u8],
+        loca: &[u8],
+        loca_format: LocaFormat,
+        num_glyphs: u16,
+    ) -> Vec<u8> {
+        let index_format = match loca_format {
+            LocaFormat::Short => 0,
+            LocaFormat::Long => 1,
+        };
+        transform_glyf(glyf, loca, index_format, num_glyphs)
+            .unwrap()
+            .unwrap()
+            .data
+    }
+
+    #[test]
+    fn glyf_round_trip() {
+        let half = F2Dot14::from_f32(0.5);
+        let zero = F2Dot14::from_f32(0.0);
+        let component = Component::new(
+            GlyphId::new(1),
+            Anchor::Offset { x: 50, y: -300 },
+            Transform {
+                xx: half,
+                yx: zero,
+                xy: zero,
+                yy: half,
+            },
+            ComponentFlags::default(),
+        );
+        let component_bbox = Bbox {
+            x_min: 50,
+            y_min: -300,
+            x_max: 250,
+            y_max: -50,
+        };
+        let composite = CompositeGlyph::try_from_iter([(component, component_bbox)]).unwrap();
+        let glyphs = [
+            Glyph::Empty,
+            simple_glyph("M0,0 L0,500 L400,500 L400,0 Z"),
+            // point deltas of every size the triplet encoding has
+            simple_glyph(concat!(
+                "M-20,10 Q30,1500 900,-700 L4000,-4010 Q5100,0 900,20 L0,20 L600,520 Z ",
+                "M600,0 L601,-1 L6000,6000 Z",
+            )),
+            Glyph::Composite(composite),
+        ];
+        let mut builder = GlyfLocaBuilder::new();
+        for glyph in glyphs.iter() {
+            builder.add_glyph(glyph).unwrap();
+        }
+        let (glyf, loca, loca_format) = builder.build();
+        let (glyf, loca) = (dump_table(&glyf).unwrap(), dump_table(&loca).unwrap());
+
+        let transformed = transformed_glyf(&glyf, &loca, loca_format, glyphs.len() as u16);
+        assert_eq!((glyf, loca), decode_glyf(&transformed));
+    }
+
+    #[test]
+    fn no_explicit_bbox_without_contours() {
+        // a glyph with no contours, but a bbox and an instruction length
+        let mut glyf = 0i16.to_be_bytes().to_vec();
+        glyf.extend([10i16, 20, 30, 40].iter().flat_map(|v| v.to_be_bytes()));
+        glyf.extend(0u16.to_be_bytes());
+        let loca: Vec<_> = [0u16, 6].iter().flat_map(|v| v.to_be_bytes()).collect();
+
+        let transformed = transformed_glyf(&glyf, &loca, LocaFormat::Short, 1);
+        let data = FontData::new(&transformed);
+        let bbox_stream_len: u32 = data.read_at(28).unwrap();
+        // the bbox stream, last as there are no instructions, is just an empty bitmap
+        assert_eq!(
+            (4, &[0u8; 4][..]),
+            (bbox_stream_len, &transformed[transformed.len() - 4..])
+        );
+    }
+
+    #[test]
+    fn uint_base128() {
+        for (value, expected) in [
+            (0, vec![0x00]),
+            (63, vec![0x3f]),
+            (128, vec![0x81, 0x00]),
+            (16384, vec![0x81, 0x80, 0x00]),
+            (u32::MAX, vec![0x8f, 0xff, 0xff, 0xff, 0x7f]),
+        ] {
+            let mut buf = Vec::new();
+            write_uint_base128(&mut buf, value);
+            assert_eq!(expected, buf, "{value}");
+        }
+    }
+
+    #[test]
+    fn u16_255() {
+        for (value, expected) in [
+            (252, vec![252]),
+            (253, vec![255, 0]),
+            (505, vec![255, 252]),
+            (506, vec![254, 0]),
+            (762, vec![253, 2, 250]),
+        ] {
+            let mut buf = Vec::new();
+            write_255_u16(&mut buf, value);
+            assert_eq!(expected, buf, "{value}");
+        }
+    }
+
+    #[test]
+    fn triplets() {
+        for ((dx, dy, on_curve), (flag, bytes)) in [
+            ((0, 5, true), (1, vec![5])),
+            ((0, -300, false), (130, vec![44])),
+            ((-7, 0, true), (10, vec![7])),
+            ((3, -4, true), (21, vec![0x23])),
+            ((100, 200, true), (87, vec![99, 199])),
+            ((1000, -2000, true), (121, vec![62, 0x87, 0xd0])),
+            ((5000, 5000, true), (127, vec![0x13, 0x88, 0x13, 0x88])),
+        ] {
+            let mut streams = GlyfStreams::default();
+            write_triplet(&mut streams, dx, dy, on_curve);
+            assert_eq!(
+                (vec![flag], bytes),
+                (streams.flags, streams.glyphs),
+                "{dx},{dy}"
+            );
+        }
+    }
+}
@@ -2,15 +2,24 @@
 
 use std::path::{Path, PathBuf};
 
-use clap::{ArgAction, Parser};
-use fontir::orchestration::Flags;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use fontbe::glyphs::Cu2QuMaxError;
+use fontir::{ir::Transformation, orchestration::Flags};
 use regex::Regex;
 use serde::{Deserialize, Deserializer, Serialize};
 
 /// What font can we build for you today?
 #[derive(Serialize, Deserialize, Parser, Debug, Clone, PartialEq)]
-#[command(version)]
+#[command(
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 pub struct Args {
+    /// Do something other than compile a font
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// A designspace, ufo, or glyphs file
     #[arg(
         conflicts_with = "source",
@@ -28,9 +37,30 @@ pub struct Args {
     pub incremental: bool,
 
     /// Output file name (default: build/font.ttf)
+    ///
+    /// A `.ttc` file gets a TrueType Collection, see `--collection-source`.
     #[arg(short, long)]
     pub output_file: Option<PathBuf>,
 
+    /// Another source to compile into the same TrueType Collection, may be repeated.
+    ///
+    /// The collection holds the font for the input source followed by one for each
+    /// collection source, or their static instances with `--static-instances`. Tables that
+    /// are byte-for-byte identical between fonts are only stored once.
+    #[arg(long = "collection-source")]
+    pub collection_sources: Vec<PathBuf>,
+
+    /// Compressed format to write the font in. If not set, inferred from the
+    /// output file extension.
+    #[arg(long, value_enum)]
+    pub flavor: Option<Flavor>,
+
+    /// Also write a static font for each named instance, next to the output file if
+    /// there is one, otherwise to `instance_ttf` in the build directory.
+    // Analogous to fontmake's `-o ttf -i`.
+    #[arg(long, default_value = "false")]
+    pub static_instances: bool,
+
     /// Whether to write additional debug files to disk.
     #[arg(long, default_value = "false")]
     pub emit_debug: bool,
@@ -50,6 +80,92 @@ pub struct Args {
     #[arg(long, default_value = "false")]
     pub decompose_transformed_components: bool,
 
+    /// Reorder contours and move their start points to match the default master, where
+    /// that makes an incompatible glyph compatible.
+    #[arg(long, default_value = "false")]
+    pub repair_contours: bool,
+
+    /// Merge overlapping contours of static fonts, including static instances. Variable
+    /// fonts keep their overlaps but have the glyf OVERLAP_SIMPLE and OVERLAP_COMPOUND flags set.
+    // Analogous to fontmake's --overlaps-backend, which fontmake uses by default for static fonts.
+    #[arg(long, default_value = "false")]
+    pub remove_overlaps: bool,
+
+    /// Replace the contours of a glyph with components where every one of them, in every
+    /// master, is a translated copy of the contours of another glyph.
+    ///
+    /// Shrinks glyf for sources that have been decomposed.
+    #[arg(long, default_value = "false")]
+    pub auto_components: bool,
+
+    /// As `--auto-components`, also finding copies that are flipped or rotated by 90 degrees.
+    #[arg(long, default_value = "false")]
+    pub auto_components_transformed: bool,
+
+    /// Copy anchors from the components of composite glyphs into composites that don't
+    /// draw them, so composites such as accented letters can take marks.
+    ///
+    /// Glyphs sources always have their anchors propagated, as Glyphs.app does.
+    // Analogous to ufo2ft's propagateAnchors filter
+    #[arg(long, default_value = "false")]
+    pub propagate_anchors: bool,
+
+    /// Slant glyphs clockwise by this many degrees, e.g. to derive an oblique from an upright.
+    ///
+    /// The italic angle and caret slope are updated to match.
+    #[arg(long)]
+    pub slant: Option<f64>,
+
+    /// Scale glyphs horizontally, as a percentage.
+    #[arg(long)]
+    pub scale_x: Option<f64>,
+
+    /// Scale glyphs vertically, as a percentage.
+    #[arg(long)]
+    pub scale_y: Option<f64>,
+
+    /// Move glyphs right by this many units, after any slant or scale.
+    #[arg(long)]
+    pub offset_x: Option<f64>,
+
+    /// Move glyphs up by this many units, after any slant or scale.
+    #[arg(long)]
+    pub offset_y: Option<f64>,
+
+    /// The height glyphs are slanted and scaled around, which doesn't move.
+    #[arg(long, value_enum, default_value = "baseline")]
+    pub transform_origin: TransformOrigin,
+
+    /// Maximum distance between a cubic curve and the quadratic curves that replace it,
+    /// as a fraction of units per em (default: 0.001).
+    ///
+    /// Corresponding curves of all masters are converted together so they remain
+    /// compatible. Contour direction is reversed as for any TrueType outline, see
+    /// `--keep-direction`.
+    // Named to match fontmake's homonymous flag
+    #[arg(long, conflicts_with = "conversion_error_units")]
+    pub conversion_error: Option<f64>,
+
+    /// Maximum distance between a cubic curve and the quadratic curves that replace it,
+    /// in font units.
+    #[arg(long)]
+    pub conversion_error_units: Option<f64>,
+
+    /// EXPERIMENTAL: write cubic curves to glyf as they are, rather than converting them
+    /// to quadratic.
+    ///
+    /// Only engines that implement cubic glyf outlines can render the result, see
+    /// <https://github.com/harfbuzz/boring-expansion-spec/blob/main/glyf1-cubicOutlines.md>.
+    #[arg(long, default_value = "false")]
+    pub experimental_cubic_glyf: bool,
+
+    /// EXPERIMENTAL: write glyphs with variable components to VARC.
+    ///
+    /// Such glyphs are always drawn into contours in glyf, the fallback for engines
+    /// that don't implement <https://github.com/harfbuzz/boring-expansion-spec/blob/main/VARC.md>.
+    #[arg(long, default_value = "false")]
+    pub experimental_varc: bool,
+
     /// Whether to out timing data, notably a visualization of threadpool execution of tasks.
     ///
     /// See <https://github.com/googlefonts/fontc/pull/443>
@@ -91,6 +207,66 @@ pub struct Args {
     pub verbose_version: bool,
 }
 
+/// Things we can do with a source besides compile it
+#[derive(Serialize, Deserialize, Subcommand, Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Report everything that stops the masters of a source interpolating
+    ///
+    /// Checks contour and point counts, start points, contour order and direction,
+    /// components and anchors, much like fontTools' varLib.interpolatable. Exits with
+    /// an error if there are any problems.
+    Interpolatable {
+        /// A designspace, ufo, or glyphs file
+        source: PathBuf,
+
+        /// Also write the report as JSON to this file
+        #[arg(long)]
+        json: Option<PathBuf>,
+    },
+}
+
+/// A compressed format for the output font
+#[derive(Serialize, Deserialize, ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    /// [WOFF](https://www.w3.org/TR/WOFF/)
+    Woff,
+    /// [WOFF2](https://www.w3.org/TR/WOFF2/)
+    Woff2,
+}
+
+impl Flavor {
+    /// The file extension for fonts in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Flavor::Woff => "woff",
+            Flavor::Woff2 => "woff2",
+        }
+    }
+}
+
+/// The height to slant and scale glyphs around
+#[derive(Serialize, Deserialize, ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TransformOrigin {
+    CapHeight,
+    HalfCapHeight,
+    XHeight,
+    HalfXHeight,
+    #[default]
+    Baseline,
+}
+
+impl From<TransformOrigin> for fontir::ir::TransformOrigin {
+    fn from(value: TransformOrigin) -> Self {
+        match value {
+            TransformOrigin::CapHeight => fontir::ir::TransformOrigin::CapHeight,
+            TransformOrigin::HalfCapHeight => fontir::ir::TransformOrigin::HalfCapHeight,
+            TransformOrigin::XHeight => fontir::ir::TransformOrigin::XHeight,
+            TransformOrigin::HalfXHeight => fontir::ir::TransformOrigin::HalfXHeight,
+            TransformOrigin::Baseline => fontir::ir::TransformOrigin::Baseline,
+        }
+    }
+}
+
 /// A wrapper around a validated regex string
 ///
 /// This is a wrapper because the Regex type itself does not implement PartialEq or
@@ -111,6 +287,19 @@ impl Args {
             Flags::DECOMPOSE_TRANSFORMED_COMPONENTS,
             self.decompose_transformed_components,
         );
+        flags.set(Flags::REPAIR_CONTOURS, self.repair_contours);
+        flags.set(Flags::REMOVE_OVERLAPS, self.remove_overlaps);
+        flags.set(
+            Flags::AUTO_COMPONENTS,
+            self.auto_components || self.auto_components_transformed,
+        );
+        flags.set(
+            Flags::AUTO_COMPONENTS_TRANSFORMED,
+            self.auto_components_transformed,
+        );
+        flags.set(Flags::PROPAGATE_ANCHORS, self.propagate_anchors);
+        flags.set(Flags::CUBIC_GLYF, self.experimental_cubic_glyf);
+        flags.set(Flags::VARC, self.experimental_varc);
         flags.set(Flags::EMIT_TIMING, self.emit_timing);
         flags.set(Flags::KEEP_DIRECTION, self.keep_direction);
         flags.set(Flags::PRODUCTION_NAMES, !self.no_production_names);
@@ -126,11 +315,15 @@ impl Args {
         let input_source = testdata_dir().join(source).canonicalize().unwrap();
 
         Args {
+            command: None,
             glyph_name_filter: None,
             input_source: Some(input_source),
             source: None,
             incremental: true,
             output_file: None,
+            collection_sources: Vec::new(),
+            flavor: None,
+            static_instances: false,
             emit_debug: false, // they get destroyed by test cleanup
             emit_timing: false,
             build_dir: build_dir.to_path_buf(),
@@ -138,6 +331,22 @@ impl Args {
             flatten_components: Flags::default().contains(Flags::FLATTEN_COMPONENTS),
             decompose_transformed_components: Flags::default()
                 .contains(Flags::DECOMPOSE_TRANSFORMED_COMPONENTS),
+            repair_contours: Flags::default().contains(Flags::REPAIR_CONTOURS),
+            remove_overlaps: Flags::default().contains(Flags::REMOVE_OVERLAPS),
+            auto_components: Flags::default().contains(Flags::AUTO_COMPONENTS),
+            auto_components_transformed: Flags::default()
+                .contains(Flags::AUTO_COMPONENTS_TRANSFORMED),
+            propagate_anchors: Flags::default().contains(Flags::PROPAGATE_ANCHORS),
+            slant: None,
+            scale_x: None,
+            scale_y: None,
+            offset_x: None,
+            offset_y: None,
+            transform_origin: Default::default(),
+            conversion_error: None,
+            conversion_error_units: None,
+            experimental_cubic_glyf: Flags::default().contains(Flags::CUBIC_GLYF),
+            experimental_varc: Flags::default().contains(Flags::VARC),
             skip_features: false,
             keep_direction: false,
             no_production_names: false,
@@ -145,6 +354,94 @@ impl Args {
         }
     }
 
+    /// The compressed format to write, if any.
+    ///
+    /// An explicit `--flavor` wins, otherwise it's inferred from the output file.
+    pub fn flavor(&self) -> Option<Flavor> {
+        self.flavor.or_else(|| {
+            let extension = self.output_file.as_ref()?.extension()?.to_str()?;
+            [Flavor::Woff, Flavor::Woff2]
+                .into_iter()
+                .find(|flavor| extension.eq_ignore_ascii_case(flavor.extension()))
+        })
+    }
+
+    /// Whether to write a TrueType Collection rather than a single font.
+    pub fn collection(&self) -> bool {
+        !self.collection_sources.is_empty()
+            || self
+                .output_file
+                .as_ref()
+                .and_then(|output_file| output_file.extension())
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("ttc"))
+    }
+
+    /// Where to write static instances.
+    ///
+    /// Next to the output file, unless that's a collection that holds them, otherwise
+    /// `instance_ttf` in the build directory.
+    pub fn instance_dir(&self) -> PathBuf {
+        match self.output_file.as_ref().and_then(|file| file.parent()) {
+            Some(dir) if !self.collection() => {
+                if dir.as_os_str().is_empty() {
+                    PathBuf::from(".")
+                } else {
+                    dir.to_path_buf()
+                }
+            }
+            _ => self.build_dir.join("instance_ttf"),
+        }
+    }
+
+    /// Args to compile one of our collection sources from scratch in `build_dir`
+    pub(crate) fn for_collection_source(&self, source: &Path, build_dir: &Path) -> Args {
+        Args {
+            input_source: Some(source.to_path_buf()),
+            source: None,
+            incremental: false,
+            output_file: None,
+            collection_sources: Vec::new(),
+            flavor: None,
+            emit_timing: false,
+            build_dir: build_dir.to_path_buf(),
+            ..self.clone()
+        }
+    }
+
+    /// How closely to approximate cubic curves with quadratics
+    pub fn cu2qu_max_error(&self) -> Cu2QuMaxError {
+        match (self.conversion_error_units, self.conversion_error) {
+            (Some(units), _) => Cu2QuMaxError::Absolute(units),
+            (None, Some(relative)) => Cu2QuMaxError::RelativeToUpem(relative),
+            (None, None) => Cu2QuMaxError::default(),
+        }
+    }
+
+    /// How to transform the glyphs of the source, if at all
+    pub fn transformation(&self) -> Option<Transformation> {
+        if [
+            self.slant,
+            self.scale_x,
+            self.scale_y,
+            self.offset_x,
+            self.offset_y,
+        ]
+        .iter()
+        .all(Option::is_none)
+        {
+            return None;
+        }
+        let default = Transformation::default();
+        Some(Transformation {
+            offset_x: self.offset_x.map(Into::into).unwrap_or(default.offset_x),
+            offset_y: self.offset_y.map(Into::into).unwrap_or(default.offset_y),
+            scale_x: self.scale_x.map(Into::into).unwrap_or(default.scale_x),
+            scale_y: self.scale_y.map(Into::into).unwrap_or(default.scale_y),
+            slant: self.slant.map(Into::into).unwrap_or(default.slant),
+            origin: self.transform_origin.into(),
+        })
+    }
+
     /// The input source to compile.
     pub fn source(&self) -> &Path {
         // safe to unwrap because clap ensures that the input_source is
@@ -198,10 +495,13 @@ impl Serialize for ValidatedRegex {
 }
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
     use clap::Parser;
-    use fontir::orchestration::Flags;
+    use fontbe::glyphs::Cu2QuMaxError;
+    use fontir::{ir::Transformation, orchestration::Flags};
 
-    use crate::Args;
+    use crate::{Args, Command, Flavor};
 
     // It's awkward to get the Flags::default values into #[arg] so test for consistency
     #[test]
@@ -216,4 +516,122 @@ mod tests {
             arg_default.bits(),
         );
     }
+
+    #[test]
+    fn flavor_from_output_file() {
+        let args = |extra: &[&str]| {
+            Args::parse_from(["program", "--source", "dont.care"].iter().chain(extra))
+        };
+        assert_eq!(None, args(&[]).flavor());
+        assert_eq!(None, args(&["-o", "font.ttf"]).flavor());
+        assert_eq!(Some(Flavor::Woff), args(&["-o", "font.woff"]).flavor());
+        assert_eq!(Some(Flavor::Woff2), args(&["-o", "font.WOFF2"]).flavor());
+        assert_eq!(
+            Some(Flavor::Woff2),
+            args(&["-o", "font.ttf", "--flavor", "woff2"]).flavor()
+        );
+    }
+
+    #[test]
+    fn transformation_from_args() {
+        let args = |extra: &[&str]| {
+            Args::parse_from(["program", "--source", "dont.care"].iter().chain(extra))
+        };
+        assert_eq!(None, args(&[]).transformation());
+        assert_eq!(
+            None,
+            args(&["--transform-origin", "x-height"]).transformation()
+        );
+        assert_eq!(
+            Some(Transformation {
+                slant: 12.0.into(),
+                scale_y: 90.0.into(),
+                origin: fontir::ir::TransformOrigin::HalfXHeight,
+                ..Default::default()
+            }),
+            args(&[
+                "--slant",
+                "12",
+                "--scale-y",
+                "90",
+                "--transform-origin",
+                "half-x-height"
+            ])
+            .transformation()
+        );
+    }
+
+    #[test]
+    fn instance_dir_from_args() {
+        let args = |extra: &[&str]| {
+            Args::parse_from(["program", "--source", "dont.care"].iter().chain(extra))
+        };
+        assert_eq!(
+            Path::new("build/instance_ttf"),
+            args(&[]).instance_dir().as_path()
+        );
+        assert_eq!(
+            Path::new("out/fonts"),
+            args(&["-o", "out/fonts/font.woff2"])
+                .instance_dir()
+                .as_path()
+        );
+        assert_eq!(
+            Path::new("."),
+            args(&["-o", "font.ttf"]).instance_dir().as_path()
+        );
+        assert_eq!(
+            Path::new("build/instance_ttf"),
+            args(&["-o", "out/family.ttc"]).instance_dir().as_path()
+        );
+    }
+
+    #[test]
+    fn collection_from_args() {
+        let args = |extra: &[&str]| {
+            Args::parse_from(["program", "--source", "dont.care"].iter().chain(extra))
+        };
+        assert!(!args(&[]).collection());
+        assert!(!args(&["-o", "font.ttf"]).collection());
+        assert!(args(&["-o", "family.TTC"]).collection());
+        assert!(args(&["--collection-source", "other.designspace"]).collection());
+    }
+
+    #[test]
+    fn cu2qu_max_error_from_args() {
+        let args = |extra: &[&str]| {
+            Args::parse_from(["program", "--source", "dont.care"].iter().chain(extra))
+        };
+        assert_eq!(Cu2QuMaxError::default(), args(&[]).cu2qu_max_error());
+        assert_eq!(
+            Cu2QuMaxError::RelativeToUpem(0.002),
+            args(&["--conversion-error", "0.002"]).cu2qu_max_error()
+        );
+        assert_eq!(
+            Cu2QuMaxError::Absolute(0.5),
+            args(&["--conversion-error-units", "0.5"]).cu2qu_max_error()
+        );
+        assert!(Args::try_parse_from([
+            "program",
+            "--source",
+            "dont.care",
+            "--conversion-error",
+            "0.002",
+            "--conversion-error-units",
+            "0.5"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn interpolatable_command() {
+        let args = Args::parse_from(["program", "interpolatable", "font.designspace"]);
+        assert_eq!(
+            Some(Command::Interpolatable {
+                source: "font.designspace".into(),
+                json: None
+            }),
+            args.command
+        );
+    }
 }
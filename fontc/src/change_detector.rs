@@ -1,9 +1,10 @@
 //! tracking changes during compilation
 
-use std::{ffi::OsStr, fmt::Debug, fs, path::Path};
+use std::{ffi::OsStr, fmt::Debug, fs, path::Path, rc::Rc};
 
 use bitflags::bitflags;
 use fontbe::{
+    glyphs::Cu2QuMaxError,
     orchestration::{AnyWorkId, WorkId as BeWorkIdentifier},
     paths::Paths as BePaths,
 };
@@ -12,9 +13,11 @@ use fontra2fontir::source::FontraIrSource;
 use crate::{create_timer, timing::JobTimer, work::AnyWork, workload::Workload, Config, Error};
 use fontdrasil::{coords::NormalizedLocation, types::GlyphName};
 use fontir::{
+    ir::GlyphOrder,
     orchestration::WorkId as FeWorkIdentifier,
     paths::Paths as IrPaths,
     source::{Input, Source},
+    transform::TransformedSource,
 };
 use glyphs2fontir::source::GlyphsIrSource;
 use ufo2fontir::source::DesignSpaceIrSource;
@@ -39,12 +42,13 @@ bitflags! {
 pub struct ChangeDetector {
     glyph_name_filter: Option<Regex>,
     ir_paths: IrPaths,
-    ir_source: Box<dyn Source>,
+    ir_source: Rc<dyn Source>,
     prev_inputs: Input,
     current_inputs: Input,
     be_paths: BePaths,
     emit_ir: bool,
     skip_features: bool,
+    cu2qu_max_error: Cu2QuMaxError,
     static_metadata_changed: bool,
     glyph_order_changed: bool,
     glyphs_changed: IndexSet<GlyphName>,
@@ -70,6 +74,9 @@ impl ChangeDetector {
             .run();
 
         let mut ir_source = ir_source(config.args.source())?;
+        if let Some(transformation) = config.args.transformation() {
+            ir_source = Box::new(TransformedSource::new(ir_source, transformation));
+        }
         let mut current_inputs = ir_source.inputs().map_err(Error::FontIrError)?;
 
         let glyph_name_filter = config
@@ -133,12 +140,13 @@ impl ChangeDetector {
         Ok(ChangeDetector {
             glyph_name_filter,
             ir_paths,
-            ir_source,
+            ir_source: ir_source.into(),
             prev_inputs,
             current_inputs,
             be_paths,
             emit_ir: config.args.incremental,
             skip_features: config.args.skip_features,
+            cu2qu_max_error: config.args.cu2qu_max_error(),
             static_metadata_changed,
             glyph_order_changed,
             glyphs_changed,
@@ -146,6 +154,31 @@ impl ChangeDetector {
         })
     }
 
+    /// A change detector for a static instance, whose IR is complete, in `build_dir`
+    ///
+    /// Nothing of the instance has been built yet so everything is deemed to have changed.
+    pub(crate) fn for_instance(
+        &self,
+        build_dir: &Path,
+        glyph_order: &GlyphOrder,
+    ) -> ChangeDetector {
+        ChangeDetector {
+            glyph_name_filter: self.glyph_name_filter.clone(),
+            ir_paths: IrPaths::new(build_dir),
+            ir_source: self.ir_source.clone(),
+            prev_inputs: Input::new(),
+            current_inputs: self.current_inputs.clone(),
+            be_paths: BePaths::new(build_dir),
+            emit_ir: false,
+            skip_features: self.skip_features,
+            cu2qu_max_error: self.cu2qu_max_error,
+            static_metadata_changed: true,
+            glyph_order_changed: true,
+            glyphs_changed: glyph_order.iter().cloned().collect(),
+            glyphs_deleted: IndexSet::new(),
+        }
+    }
+
     pub fn glyph_name_filter(&self) -> Option<&Regex> {
         self.glyph_name_filter.as_ref()
     }
@@ -206,6 +239,10 @@ impl ChangeDetector {
         self.skip_features
     }
 
+    pub fn cu2qu_max_error(&self) -> Cu2QuMaxError {
+        self.cu2qu_max_error
+    }
+
     /// Not all work ... works ... with this method; notably muts support input_changed.
     pub(crate) fn simple_should_run(&self, work: &AnyWork) -> bool {
         let work_id = work.id();
@@ -364,7 +401,7 @@ impl ChangeDetector {
     }
 }
 
-fn ir_source(source: &Path) -> Result<Box<dyn Source>, Error> {
+pub(crate) fn ir_source(source: &Path) -> Result<Box<dyn Source>, Error> {
     if !source.exists() {
         return Err(Error::FileExpected(source.to_path_buf()));
     }
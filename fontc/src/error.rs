@@ -1,6 +1,8 @@
 use std::{io, path::PathBuf};
 
 use fontbe::orchestration::AnyWorkId;
+
+use crate::Flavor;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -21,4 +23,16 @@ pub enum Error {
     TasksFailed(Vec<(AnyWorkId, String)>),
     #[error("Unable to proceed; {0} jobs stuck pending")]
     UnableToProceed(usize),
+    #[error("Unable to instantiate '{0}': {1}")]
+    InstantiationError(String, fontir::error::WorkError),
+    #[error("Unable to encode font as {0:?}: {1}")]
+    EncodingError(Flavor, fontbe::error::Error),
+    #[error("A font collection can't be written as {0:?}")]
+    CompressedCollection(Flavor),
+    #[error("Unable to build font collection: {0}")]
+    CollectionError(fontbe::error::Error),
+    #[error("{0} interpolation problem(s) found")]
+    IncompatibleMasters(usize),
+    #[error("json error: '{0}'")]
+    JsonError(#[from] serde_json::Error),
 }
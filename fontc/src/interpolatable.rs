@@ -0,0 +1,192 @@
+//! Reports what stops the sources of a font interpolating, see [`fontir::interpolatable`].
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use fontbe::orchestration::AnyWorkId;
+use fontdrasil::{
+    coords::UserLocation,
+    orchestration::{Access, Work},
+    types::GlyphName,
+};
+use fontir::{
+    interpolatable::{check_glyph, ProblemKind},
+    orchestration::{Context as FeContext, Flags, WorkId as FeWorkIdentifier},
+    paths::Paths as IrPaths,
+};
+use indexmap::IndexSet;
+use serde::Serialize;
+
+use crate::{change_detector::ir_source, Error};
+
+/// Every problem found with the sources of a font
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+    pub source: PathBuf,
+    pub problems: Vec<Problem>,
+    /// Glyphs we couldn't check because we couldn't produce their IR
+    pub errors: Vec<GlyphError>,
+}
+
+/// A [`fontir::interpolatable::Problem`], located in user space
+#[derive(Serialize, Debug)]
+pub struct Problem {
+    pub glyph: GlyphName,
+    pub location: UserLocation,
+    #[serde(flatten)]
+    pub kind: ProblemKind,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GlyphError {
+    pub glyph: GlyphName,
+    pub message: String,
+}
+
+impl Report {
+    /// The number of problems, counting a glyph we couldn't check as one
+    pub fn len(&self) -> usize {
+        self.problems.len() + self.errors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for error in self.errors.iter() {
+            writeln!(f, "'{}' {}", error.glyph, error.message)?;
+        }
+        for problem in self.problems.iter() {
+            writeln!(
+                f,
+                "'{}' at {:?} {}",
+                problem.glyph, problem.location, problem.kind
+            )?;
+        }
+        write!(
+            f,
+            "{}: {} problem(s) in {} glyph(s)",
+            self.source.display(),
+            self.len(),
+            self.problems
+                .iter()
+                .map(|p| &p.glyph)
+                .chain(self.errors.iter().map(|e| &e.glyph))
+                .collect::<IndexSet<_>>()
+                .len()
+        )
+    }
+}
+
+fn run(work: Box<fontir::orchestration::IrWork>, context: &FeContext) -> Result<(), Error> {
+    work.exec(context)
+        .map_err(|e| Error::TasksFailed(vec![(AnyWorkId::from(work.id()), format!("{e}"))]))
+}
+
+/// Check every glyph in `source` for compatibility between its sources.
+///
+/// Only the glyph IR is built, in memory, so a glyph that fails to build doesn't
+/// stop us checking the rest.
+pub fn check_interpolatable(source: &Path) -> Result<Report, Error> {
+    let mut ir_source = ir_source(source)?;
+    let input = ir_source.inputs()?;
+
+    let mut flags = Flags::default();
+    flags.remove(Flags::EMIT_IR);
+    let fe_root = FeContext::new_root(flags, IrPaths::new(Path::new("")), input.clone());
+    let context = fe_root.copy_for_work(Access::All, Access::All);
+    run(ir_source.create_static_metadata_work(&input)?, &context)?;
+
+    let static_metadata = context.static_metadata.get();
+    let axes = static_metadata
+        .axes
+        .iter()
+        .map(|axis| (axis.tag, axis))
+        .collect::<HashMap<_, _>>();
+
+    let mut glyph_names: Vec<_> = input.glyphs.keys().cloned().collect();
+    glyph_names.sort();
+    let glyph_names: IndexSet<_> = glyph_names.into_iter().collect();
+    let work = ir_source.create_glyph_ir_work(&glyph_names, &input)?;
+
+    let mut report = Report {
+        source: source.to_path_buf(),
+        ..Default::default()
+    };
+    for (glyph_name, work) in glyph_names.into_iter().zip(work) {
+        if let Err(e) = work.exec(&context) {
+            report.errors.push(GlyphError {
+                glyph: glyph_name,
+                message: format!("{e}"),
+            });
+            continue;
+        }
+        let glyph = context
+            .glyphs
+            .get(&FeWorkIdentifier::Glyph(glyph_name.clone()));
+        let anchors = context
+            .anchors
+            .try_get(&FeWorkIdentifier::Anchor(glyph_name));
+        report
+            .problems
+            .extend(
+                check_glyph(&glyph, anchors.as_deref())
+                    .into_iter()
+                    .map(|problem| Problem {
+                        glyph: problem.glyph,
+                        location: problem.location.to_user(&axes),
+                        kind: problem.kind,
+                    }),
+            );
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testdata_dir;
+
+    use super::*;
+
+    #[test]
+    fn compatible_sources() {
+        let report = check_interpolatable(&testdata_dir().join("glyphs3/WghtVar.glyphs")).unwrap();
+        assert!(report.is_empty(), "{report}");
+    }
+
+    #[test]
+    fn reports_incompatible_sources() {
+        let report =
+            check_interpolatable(&testdata_dir().join("IncompatibleMasters.designspace")).unwrap();
+        let kinds: Vec<_> = report
+            .problems
+            .iter()
+            .map(|p| (p.glyph.as_str(), &p.kind))
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "a",
+                    &ProblemKind::StartPoint {
+                        contour: 0,
+                        proposed_start: 2
+                    }
+                ),
+                (
+                    "b",
+                    &ProblemKind::ContourCount {
+                        expected: 1,
+                        actual: 2
+                    }
+                ),
+            ],
+            kinds
+        );
+    }
+}
@@ -4,19 +4,21 @@ mod args;
 mod change_detector;
 mod config;
 mod error;
+mod interpolatable;
 mod timing;
 pub mod work;
 mod workload;
 
-pub use args::Args;
+pub use args::{Args, Command, Flavor, TransformOrigin};
 pub use change_detector::ChangeDetector;
 pub use config::Config;
 pub use error::Error;
+pub use interpolatable::{check_interpolatable, GlyphError, Problem, Report};
 
 pub use timing::{create_timer, JobTimer};
 use workload::Workload;
 
-use std::{fs, io, path::Path};
+use std::{fs, io, path::Path, time::Instant};
 
 use fontbe::{
     avar::create_avar_work,
@@ -25,7 +27,7 @@ use fontbe::{
         create_gather_ir_kerning_work, create_kerns_work, create_mark_work, FeatureCompilationWork,
         FeatureParsingWork,
     },
-    font::create_font_work,
+    font::{build_collection, create_font_work},
     fvar::create_fvar_work,
     glyphs::{create_glyf_loca_work, create_glyf_work},
     gvar::create_gvar_work,
@@ -38,10 +40,18 @@ use fontbe::{
     os2::create_os2_work,
     post::create_post_work,
     stat::create_stat_work,
+    varc::create_varc_work,
+    woff, woff2,
 };
 
-use fontdrasil::{coords::NormalizedLocation, types::GlyphName};
-use fontir::{glyph::create_glyph_order_work, source::DeleteWork};
+use fontdrasil::{coords::NormalizedLocation, orchestration::Access, types::GlyphName};
+use fontir::{
+    glyph::create_glyph_order_work,
+    instancer,
+    orchestration::{Context as FeContext, Flags},
+    source::DeleteWork,
+};
+use write_fonts::types::NameId;
 
 use fontbe::orchestration::Context as BeContext;
 use fontbe::paths::Paths as BePaths;
@@ -66,10 +76,12 @@ pub fn require_dir(dir: &Path) -> Result<(), io::Error> {
 
 pub fn init_paths(args: &Args) -> Result<(IrPaths, BePaths), Error> {
     let ir_paths = IrPaths::new(&args.build_dir);
-    let be_paths = if let Some(output_file) = &args.output_file {
-        BePaths::with_output_file(&args.build_dir, output_file)
-    } else {
-        BePaths::new(&args.build_dir)
+    // a compressed font or collection is written after the build, so the sfnt stays in the build dir
+    let be_paths = match (&args.output_file, args.flavor()) {
+        (Some(output_file), None) if !args.collection() => {
+            BePaths::with_output_file(&args.build_dir, output_file)
+        }
+        _ => BePaths::new(&args.build_dir),
     };
     // create the output file's parent directory if it doesn't exist
     if let Some(output_file) = &args.output_file {
@@ -98,10 +110,28 @@ pub fn init_paths(args: &Args) -> Result<(IrPaths, BePaths), Error> {
     Ok((ir_paths, be_paths))
 }
 
+/// Encode the font in `be_context` in a compressed format
+fn encode(flavor: Flavor, be_context: &BeContext) -> Result<Vec<u8>, Error> {
+    let font = be_context.font.get();
+    let woff_metadata = &be_context.ir.static_metadata.get().misc.woff;
+    match flavor {
+        Flavor::Woff => woff::compress(font.get(), woff_metadata),
+        Flavor::Woff2 => woff2::compress(font.get(), woff_metadata),
+    }
+    .map_err(|e| Error::EncodingError(flavor, e))
+}
+
 pub fn write_font_file(args: &Args, be_context: &BeContext) -> Result<(), Error> {
-    // if IR is off the font didn't get written yet (nothing did), otherwise it's done already
     let font_file = be_context.font_file();
-    if !args.incremental {
+    if let Some(flavor) = args.flavor() {
+        let compressed = encode(flavor, be_context)?;
+        let output_file = args
+            .output_file
+            .clone()
+            .unwrap_or_else(|| font_file.with_extension(flavor.extension()));
+        fs::write(output_file, compressed).map_err(Error::IoError)?;
+    } else if !args.incremental {
+        // if IR is off the font didn't get written yet (nothing did), otherwise it's done already
         fs::write(font_file, be_context.font.get().get()).map_err(Error::IoError)?;
     } else if !font_file.exists() {
         return Err(Error::FileExpected(font_file));
@@ -109,6 +139,145 @@ pub fn write_font_file(args: &Args, be_context: &BeContext) -> Result<(), Error>
     Ok(())
 }
 
+/// Write a TrueType Collection of `fonts` followed by the fonts for our collection sources
+///
+/// The collection goes to the output file, or `font.ttc` in the build directory.
+pub fn write_collection(args: &Args, mut fonts: Vec<Vec<u8>>) -> Result<(), Error> {
+    if let Some(flavor) = args.flavor() {
+        return Err(Error::CompressedCollection(flavor));
+    }
+    if !args.collection_sources.is_empty() {
+        let collection_dir = args.build_dir.join("collection");
+        require_dir(&args.build_dir)?;
+        require_dir(&collection_dir)?;
+        for (i, source) in args.collection_sources.iter().enumerate() {
+            let source_args =
+                args.for_collection_source(source, &collection_dir.join(i.to_string()));
+            fonts.extend(compile_collection_source(source_args)?);
+        }
+    }
+
+    let fonts = fonts.iter().map(|font| font.as_slice()).collect::<Vec<_>>();
+    let collection = build_collection(&fonts).map_err(Error::CollectionError)?;
+    let output_file = args
+        .output_file
+        .clone()
+        .unwrap_or_else(|| args.build_dir.join("font.ttc"));
+    fs::write(output_file, collection).map_err(Error::IoError)
+}
+
+/// Compile a collection source from scratch, returning its font or its static instances
+fn compile_collection_source(args: Args) -> Result<Vec<Vec<u8>>, Error> {
+    debug!("Compile collection source {:?}", args.source());
+    let mut timer = JobTimer::new(Instant::now());
+    let (ir_paths, be_paths) = init_paths(&args)?;
+    let config = Config::new(args)?;
+    let prev_inputs = config.init()?;
+    let mut change_detector = ChangeDetector::new(
+        config.clone(),
+        ir_paths.clone(),
+        be_paths.clone(),
+        prev_inputs,
+        &mut timer,
+    )?;
+
+    let workload = create_workload(&mut change_detector, timer)?;
+    let fe_root = FeContext::new_root(
+        config.args.flags(),
+        ir_paths,
+        workload.current_inputs().clone(),
+    );
+    let be_root = BeContext::new_root(config.args.flags(), be_paths, &fe_root);
+    workload.exec(&fe_root, &be_root)?;
+
+    let fonts = if config.args.static_instances {
+        write_static_instances(&config.args, &change_detector, &fe_root)?
+    } else {
+        vec![be_root.font.get().get().to_vec()]
+    };
+    change_detector.finish_successfully()?;
+    Ok(fonts)
+}
+
+/// Write a static font for each named instance, interpolated from the IR of the variable font.
+///
+/// The fonts are written to [Args::instance_dir], named for their PostScript name.
+/// Returns the uncompressed fonts, in named instance order.
+pub fn write_static_instances(
+    args: &Args,
+    change_detector: &ChangeDetector,
+    fe_root: &FeContext,
+) -> Result<Vec<Vec<u8>>, Error> {
+    let instance_dir = args.build_dir.join("instance_ttf");
+    let output_dir = args.instance_dir();
+    require_dir(&args.build_dir)?;
+    require_dir(&instance_dir)?;
+    require_dir(&output_dir)?;
+
+    // Instances are cheap relative to the variable font so we build them in memory, from scratch
+    let mut flags = args.flags();
+    flags.remove(Flags::EMIT_IR | Flags::EMIT_DEBUG | Flags::EMIT_TIMING);
+
+    let static_metadata = fe_root.static_metadata.get();
+    let mut fonts = Vec::with_capacity(static_metadata.named_instances.len());
+    for instance in static_metadata.named_instances.iter() {
+        debug!("Instantiate {}", instance.name);
+        let instance_fe = FeContext::new_root(
+            flags,
+            IrPaths::new(&instance_dir),
+            change_detector.current_inputs().clone(),
+        );
+        instancer::instantiate(
+            fe_root,
+            instance,
+            &instance_fe.copy_for_work(Access::All, Access::All),
+        )
+        .map_err(|e| Error::InstantiationError(instance.name.clone(), e))?;
+
+        let instance_detector =
+            change_detector.for_instance(&instance_dir, &instance_fe.glyph_order.get());
+        let be_root = BeContext::new_root(flags, BePaths::new(&instance_dir), &instance_fe);
+        create_instance_workload(&instance_detector, &instance_fe)?.exec(&instance_fe, &be_root)?;
+
+        let postscript_name = instance_fe
+            .static_metadata
+            .get()
+            .names
+            .iter()
+            .find(|(key, _)| key.name_id == NameId::POSTSCRIPT_NAME)
+            .map(|(_, name)| name.clone())
+            .unwrap_or_else(|| instance.name.clone());
+        let font = be_root.font.get().get().to_vec();
+        let (extension, encoded) = match args.flavor() {
+            Some(flavor) => (flavor.extension(), encode(flavor, &be_root)?),
+            None => ("ttf", font.clone()),
+        };
+        fs::write(
+            output_dir.join(format!("{postscript_name}.{extension}")),
+            encoded,
+        )
+        .map_err(Error::IoError)?;
+        fonts.push(font);
+    }
+    Ok(fonts)
+}
+
+/// Create the BE work for a static instance, whose IR is complete before we start
+fn create_instance_workload<'a>(
+    change_detector: &'a ChangeDetector,
+    fe_root: &FeContext,
+) -> Result<Workload<'a>, Error> {
+    let mut workload = Workload::new(change_detector, JobTimer::new(Instant::now()));
+    add_be_jobs(&mut workload)?;
+
+    // Normally set when IR kerning groups are done, see Workload::handle_success, but ours already are
+    if let Some(groups) = fe_root.kerning_groups.try_load() {
+        workload.set_gather_ir_kerning_access(&groups);
+    }
+
+    Ok(workload)
+}
+
 fn add_glyph_order_ir_job(workload: &mut Workload) -> Result<(), Error> {
     let work = create_glyph_order_work().into();
     workload.add(work, workload.change_detector.glyph_order_ir_change());
@@ -399,6 +568,20 @@ fn add_mvar_be_job(workload: &mut Workload) -> Result<(), Error> {
     Ok(())
 }
 
+fn add_varc_be_job(workload: &mut Workload) -> Result<(), Error> {
+    let glyphs_changed = workload.change_detector.glyphs_changed();
+
+    let work = create_varc_work().into();
+    workload.add(
+        work,
+        // VARC refers to axes by index and glyphs by id
+        workload.change_detector.static_metadata_ir_change()
+            || workload.change_detector.glyph_order_ir_change()
+            || !glyphs_changed.is_empty(),
+    );
+    Ok(())
+}
+
 fn add_font_be_job(workload: &mut Workload) -> Result<(), Error> {
     let glyphs_changed = workload.change_detector.glyphs_changed();
 
@@ -412,11 +595,40 @@ fn add_font_be_job(workload: &mut Workload) -> Result<(), Error> {
 }
 
 fn add_glyph_be_job(workload: &mut Workload, glyph_name: GlyphName) {
-    let work = create_glyf_work(glyph_name).into();
+    let max_error = workload.change_detector.cu2qu_max_error();
+    let work = create_glyf_work(glyph_name, max_error).into();
     let should_run = workload.change_detector.simple_should_run(&work);
     workload.add(work, should_run);
 }
 
+/// Add the jobs that turn IR into binary, as far as the change detector says they should run
+fn add_be_jobs(workload: &mut Workload) -> Result<(), Error> {
+    add_feature_parse_be_job(workload)?;
+    add_feature_comp_be_job(workload)?;
+    add_glyph_be_jobs(workload)?;
+    add_glyf_loca_be_job(workload)?;
+    add_avar_be_job(workload)?;
+    add_stat_be_job(workload)?;
+    add_cmap_be_job(workload)?;
+    add_fvar_be_job(workload)?;
+    add_gvar_be_job(workload)?;
+    add_head_be_job(workload)?;
+    add_gather_ir_kerning_be_job(workload)?;
+    add_kerns_be_job(workload)?;
+    add_marks_be_job(workload)?;
+    add_metric_and_limits_job(workload)?;
+    add_hvar_be_job(workload)?;
+    add_mvar_be_job(workload)?;
+    add_name_be_job(workload)?;
+    add_os2_be_job(workload)?;
+    add_post_be_job(workload)?;
+    add_varc_be_job(workload)?;
+
+    // Make a damn font
+    add_font_be_job(workload)?;
+    Ok(())
+}
+
 //FIXME: I should be a method on ChangeDetector
 pub fn create_workload(
     change_detector: &mut ChangeDetector,
@@ -434,29 +646,7 @@ pub fn create_workload(
     add_glyph_order_ir_job(&mut workload)?;
 
     // BE: f(IR, maybe other BE work) => binary
-
-    add_feature_parse_be_job(&mut workload)?;
-    add_feature_comp_be_job(&mut workload)?;
-    add_glyph_be_jobs(&mut workload)?;
-    add_glyf_loca_be_job(&mut workload)?;
-    add_avar_be_job(&mut workload)?;
-    add_stat_be_job(&mut workload)?;
-    add_cmap_be_job(&mut workload)?;
-    add_fvar_be_job(&mut workload)?;
-    add_gvar_be_job(&mut workload)?;
-    add_head_be_job(&mut workload)?;
-    add_gather_ir_kerning_be_job(&mut workload)?;
-    add_kerns_be_job(&mut workload)?;
-    add_marks_be_job(&mut workload)?;
-    add_metric_and_limits_job(&mut workload)?;
-    add_hvar_be_job(&mut workload)?;
-    add_mvar_be_job(&mut workload)?;
-    add_name_be_job(&mut workload)?;
-    add_os2_be_job(&mut workload)?;
-    add_post_be_job(&mut workload)?;
-
-    // Make a damn font
-    add_font_be_job(&mut workload)?;
+    add_be_jobs(&mut workload)?;
 
     workload.timer.add(time.complete());
 
@@ -515,7 +705,7 @@ mod tests {
                 loca::Loca,
             },
             types::F2Dot14,
-            FontData, FontRead, FontReadWithArgs, FontRef, TableProvider,
+            CollectionRef, FontData, FontRead, FontReadWithArgs, FontRef, TableProvider,
         },
         GlyphId, MetadataProvider, Tag,
     };
@@ -530,7 +720,7 @@ mod tests {
     use write_fonts::{
         read::{
             tables::{
-                gpos::{AnchorTable, Gpos, MarkBasePosFormat1Marker, PositionLookup},
+                gpos::{AnchorTable, Gpos, MarkBasePosFormat1Marker, PairPos, PositionLookup},
                 name::Name,
                 os2::SelectionFlags,
                 variations::{DeltaSetIndexMap, ItemVariationData},
@@ -614,10 +804,24 @@ mod tests {
             let mut workload = create_workload(&mut change_detector, timer).unwrap();
             let completed = workload.run_for_test(&result.fe_context, &result.be_context);
 
+            let instances = if config.args.static_instances {
+                write_static_instances(&config.args, &change_detector, &result.fe_context).unwrap()
+            } else {
+                Vec::new()
+            };
             change_detector.finish_successfully().unwrap();
             result.work_executed = completed;
 
-            write_font_file(&config.args, &result.be_context).unwrap();
+            if config.args.collection() {
+                let fonts = if config.args.static_instances {
+                    instances
+                } else {
+                    vec![result.be_context.font.get().get().to_vec()]
+                };
+                write_collection(&config.args, fonts).unwrap();
+            } else {
+                write_font_file(&config.args, &result.be_context).unwrap();
+            }
 
             result.raw_font = fs::read(result.build_dir.join("font.ttf")).unwrap();
 
@@ -752,6 +956,7 @@ mod tests {
             BeWorkIdentifier::Os2.into(),
             BeWorkIdentifier::Post.into(),
             BeWorkIdentifier::Stat.into(),
+            BeWorkIdentifier::Varc.into(),
         ];
 
         expected.extend(
@@ -1007,6 +1212,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn compile_again_with_modified_smart_component() {
+        let temp_dir = tempdir().unwrap();
+        let source_dir = temp_dir.path().join("sources");
+        fs::create_dir(&source_dir).unwrap();
+        copy_testdata(["glyphs3/SmartComponent.glyphs"], &source_dir);
+
+        let source = source_dir
+            .join("glyphs3/SmartComponent.glyphs")
+            .canonicalize()
+            .unwrap();
+        let result = TestCompile::compile_source(source.to_str().unwrap());
+
+        // Widen the top layer of the smart component, bar and bar.default use it
+        fs::write(
+            &source,
+            fs::read_to_string(&source)
+                .unwrap()
+                .replace("(300,0,l),\n(300,100,l)", "(400,0,l),\n(400,100,l)"),
+        )
+        .unwrap();
+
+        let result = TestCompile::compile_again(&result);
+        assert_eq!(
+            IndexSet::from(["_part.bar".into(), "bar".into(), "bar.default".into()]),
+            result.glyphs_changed
+        );
+    }
+
+    #[test]
+    fn compile_again_with_modified_corner_component() {
+        let temp_dir = tempdir().unwrap();
+        let source_dir = temp_dir.path().join("sources");
+        fs::create_dir(&source_dir).unwrap();
+        copy_testdata(["glyphs3/CornerComponents.glyphs"], &source_dir);
+
+        let source = source_dir
+            .join("glyphs3/CornerComponents.glyphs")
+            .canonicalize()
+            .unwrap();
+        let result = TestCompile::compile_source(source.to_str().unwrap());
+
+        // Deepen the chamfer, square has a corner hint that uses it
+        fs::write(
+            &source,
+            fs::read_to_string(&source)
+                .unwrap()
+                .replace("(0,10,l),\n(10,0,l)", "(0,20,l),\n(20,0,l)"),
+        )
+        .unwrap();
+
+        let result = TestCompile::compile_again(&result);
+        assert_eq!(
+            IndexSet::from(["_corner.chamfer".into(), "square".into()]),
+            result.glyphs_changed
+        );
+    }
+
     fn build_contour_and_composite_glyph(prefer_simple_glyphs: bool) -> (TestCompile, ir::Glyph) {
         let result = TestCompile::compile("glyphs2/MixedContourComponent.glyphs", |mut args| {
             args.prefer_simple_glyphs = prefer_simple_glyphs; // <-- important :)
@@ -1161,6 +1424,99 @@ mod tests {
         );
     }
 
+    #[test]
+    fn remove_overlaps_from_static_font() {
+        let contours_and_points = |result: &TestCompile| {
+            let plus = result.get_glyph_index("plus").unwrap() as usize;
+            let glyph_data = result.glyphs();
+            let glyphs = glyph_data.read();
+            let Some(glyf::Glyph::Simple(glyph)) = &glyphs[plus] else {
+                panic!("Expected plus to be a simple glyph");
+            };
+            (glyph.number_of_contours(), glyph.num_points())
+        };
+        // See resources/testdata/Overlap-Regular.ufo/glyphs/plus.glif, two overlapping bars
+        assert_eq!(
+            (2, 8),
+            contours_and_points(&TestCompile::compile_source("Overlap-Regular.ufo"))
+        );
+        let result = TestCompile::compile("Overlap-Regular.ufo", |mut args| {
+            args.remove_overlaps = true;
+            args
+        });
+        assert_eq!((1, 12), contours_and_points(&result));
+    }
+
+    #[test]
+    fn flag_overlaps_in_variable_font() {
+        let result = TestCompile::compile("glyphs3/WghtVar.glyphs", |mut args| {
+            args.remove_overlaps = true;
+            args
+        });
+        let glyph_data = result.glyphs();
+        let glyphs = glyph_data.read();
+        let Some(glyf::Glyph::Simple(simple)) =
+            &glyphs[result.get_glyph_index("hyphen").unwrap() as usize]
+        else {
+            panic!("Expected hyphen to be a simple glyph");
+        };
+        assert_eq!(
+            0x40,
+            simple.glyph_data()[0] & 0x40,
+            "OVERLAP_SIMPLE not set"
+        );
+        let Some(glyf::Glyph::Composite(composite)) =
+            &glyphs[result.get_glyph_index("manual-component").unwrap() as usize]
+        else {
+            panic!("Expected manual-component to be a composite glyph");
+        };
+        let mut components = composite.components();
+        assert!(components
+            .next()
+            .unwrap()
+            .flags
+            .contains(glyf::CompositeGlyphFlags::OVERLAP_COMPOUND));
+        assert!(components.all(|c| !c
+            .flags
+            .contains(glyf::CompositeGlyphFlags::OVERLAP_COMPOUND)));
+    }
+
+    #[test]
+    fn write_cubic_glyf() {
+        let result = TestCompile::compile("glyphs3/Oswald-O.glyphs", |mut args| {
+            args.experimental_cubic_glyf = true;
+            args
+        });
+        let glyph_data = result.glyphs();
+        let glyphs = glyph_data.read();
+        let Some(glyf::Glyph::Simple(glyph)) =
+            &glyphs[result.get_glyph_index("O").unwrap() as usize]
+        else {
+            panic!("Expected 'O' to be a simple glyph");
+        };
+
+        // Expand the flags, which may use REPEAT_FLAG
+        let mut data = glyph.glyph_data().iter();
+        let mut flags = Vec::new();
+        while flags.len() < glyph.num_points() {
+            let flag = *data.next().unwrap();
+            let repeat = if flag & 0x08 != 0 {
+                *data.next().unwrap() as usize
+            } else {
+                0
+            };
+            flags.extend(std::iter::repeat(flag).take(repeat + 1));
+        }
+        assert!(flags.iter().any(|flag| flag & 0x01 == 0));
+        // Every off-curve point, and nothing else, is a cubic control point
+        assert!(
+            flags
+                .iter()
+                .all(|flag| (flag & 0x01 == 0) == (flag & 0x80 != 0)),
+            "{flags:x?}"
+        );
+    }
+
     #[test]
     fn compile_composite_glyphs_has_expected_glyph_types() {
         let result = TestCompile::compile_source("glyphs2/Component.glyphs");
@@ -1989,6 +2345,377 @@ mod tests {
         assert_simple_kerning("designspace_from_glyphs/WghtVar.designspace");
     }
 
+    #[test]
+    fn compile_variable_component_of_a_composite() {
+        let result = TestCompile::compile("fontra/nested_component.fontra", |mut args| {
+            args.experimental_varc = true;
+            args
+        });
+        let glyph_data = result.glyphs();
+        let glyphs = glyph_data.read();
+        let top = result.get_glyph_index("top").unwrap() as usize;
+
+        // Drawing top reads bar through bars, at the midpoint of the gap axis
+        let Some(glyf::Glyph::Simple(glyph)) = &glyphs[top] else {
+            panic!("Expected a simple glyph, got {:?}", glyphs[top]);
+        };
+        assert_eq!(
+            (2, 0, 400),
+            (glyph.number_of_contours(), glyph.y_min(), glyph.y_max())
+        );
+    }
+
+    #[test]
+    fn compile_fontra() {
+        let result = TestCompile::compile_source("fontra/kerning.fontra");
+        let font = result.font();
+
+        assert_eq!(
+            vec!["wght"],
+            font.fvar()
+                .unwrap()
+                .axes()
+                .unwrap()
+                .iter()
+                .map(|a| a.axis_tag.get().to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![GlyphId::new(1), GlyphId::new(2), GlyphId::new(3)],
+            ['A', 'V', 'W']
+                .iter()
+                .map(|cp| font.cmap().unwrap().map_codepoint(*cp as u32).unwrap())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Tag::new(b"kern")],
+            font.gpos()
+                .unwrap()
+                .feature_list()
+                .unwrap()
+                .feature_records()
+                .iter()
+                .map(|fr| fr.feature_tag())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn vertical_kerning_from_ufo() {
+        let compile = TestCompile::compile_source("vertical_kern.designspace");
+        let font = compile.font();
+        let gpos = font.gpos().unwrap();
+
+        assert_eq!(
+            vec![Tag::new(b"vkrn")],
+            gpos.feature_list()
+                .unwrap()
+                .feature_records()
+                .iter()
+                .map(|fr| fr.feature_tag())
+                .collect::<Vec<_>>()
+        );
+        assert!(gpos
+            .lookup_list()
+            .unwrap()
+            .lookups()
+            .iter()
+            .all(|l| matches!(l.unwrap(), PositionLookup::Pair(..))));
+
+        // plus => bar is kerned glyph to glyph, 12.5 in the source
+        let plus = compile.get_gid("plus");
+        let bar = compile.get_gid("bar");
+        let mut values = Vec::new();
+        for lookup in gpos.lookup_list().unwrap().lookups().iter() {
+            let PositionLookup::Pair(lookup) = lookup.unwrap() else {
+                panic!("expected only pair lookups");
+            };
+            for subtable in lookup.subtables().iter() {
+                let PairPos::Format1(pairs) = subtable.unwrap() else {
+                    continue;
+                };
+                let coverage = pairs.coverage().unwrap();
+                for (first, pair_set) in coverage.iter().zip(pairs.pair_sets().iter()) {
+                    for record in pair_set.unwrap().pair_value_records().iter() {
+                        let record = record.unwrap();
+                        if (first, record.second_glyph()) == (plus, bar) {
+                            let value = record.value_record1();
+                            values.push((value.x_advance(), value.y_advance()));
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(vec![(None, Some(13))], values);
+    }
+
+    #[test]
+    fn kern_at_insertion_marker() {
+        let compile = TestCompile::compile_source("KernMarker-Regular.ufo");
+        let font = compile.font();
+        let gpos = font.gpos().unwrap();
+        let lookups = gpos.lookup_list().unwrap().lookups();
+
+        // the x advance of the first pair in each lookup
+        let advances = lookups
+            .iter()
+            .map(|lookup| {
+                let PositionLookup::Pair(lookup) = lookup.unwrap() else {
+                    panic!("expected only pair lookups");
+                };
+                let PairPos::Format1(pairs) = lookup.subtables().get(0).unwrap() else {
+                    panic!("expected glyph pairs");
+                };
+                pairs
+                    .pair_sets()
+                    .get(0)
+                    .unwrap()
+                    .pair_value_records()
+                    .get(0)
+                    .unwrap()
+                    .value_record1()
+                    .x_advance()
+            })
+            .collect::<Vec<_>>();
+
+        // the generated kerning goes where the "# Automatic Code" marker is
+        assert_eq!(vec![Some(5), Some(-100), Some(7)], advances);
+    }
+
+    #[test]
+    fn compile_woff2() {
+        let compile = TestCompile::compile("wght_var.designspace", |mut args| {
+            args.flavor = Some(Flavor::Woff2);
+            args
+        });
+        let woff2 = fs::read(compile.build_dir.join("font.woff2")).unwrap();
+        let header = FontData::new(&woff2);
+        let num_tables = compile.font().table_directory.num_tables();
+
+        assert_eq!(Tag::new(b"wOF2"), header.read_at::<Tag>(0).unwrap());
+        assert_eq!(0x00010000, header.read_at::<u32>(4).unwrap());
+        assert_eq!(woff2.len() as u32, header.read_at::<u32>(8).unwrap());
+        assert_eq!(num_tables, header.read_at::<u16>(12).unwrap());
+        assert!(woff2.len() < compile.raw_font.len());
+    }
+
+    #[test]
+    fn compile_woff_with_metadata() {
+        let compile = TestCompile::compile("WoffMetadata-Regular.ufo", |mut args| {
+            args.flavor = Some(Flavor::Woff);
+            args
+        });
+        let woff = fs::read(compile.build_dir.join("font.woff")).unwrap();
+        let header = FontData::new(&woff);
+        let num_tables = compile.font().table_directory.num_tables();
+
+        assert_eq!(Tag::new(b"wOFF"), header.read_at::<Tag>(0).unwrap());
+        assert_eq!(woff.len() as u32, header.read_at::<u32>(8).unwrap());
+        assert_eq!(num_tables, header.read_at::<u16>(12).unwrap());
+        // version from woffMajorVersion and woffMinorVersion
+        assert_eq!(
+            (2, 1),
+            (
+                header.read_at::<u16>(20).unwrap(),
+                header.read_at::<u16>(22).unwrap()
+            )
+        );
+
+        let xml = compile
+            .fe_context
+            .static_metadata
+            .get()
+            .misc
+            .woff
+            .xml
+            .clone()
+            .unwrap();
+        assert!(
+            xml.contains(r#"<uniqueid id="com.example.duck"/>"#),
+            "{xml}"
+        );
+        assert!(
+            xml.contains(r#"<vendor name="Duck Foundry" url="https://example.com"/>"#),
+            "{xml}"
+        );
+        // the metadata block is at the end
+        let meta_offset = header.read_at::<u32>(24).unwrap() as usize;
+        let meta_length = header.read_at::<u32>(28).unwrap() as usize;
+        assert_eq!(woff.len(), meta_offset + meta_length);
+        assert_eq!(xml.len() as u32, header.read_at::<u32>(32).unwrap());
+    }
+
+    #[test]
+    fn compile_static_instances() {
+        let compile = TestCompile::compile("wght_var.designspace", |mut args| {
+            args.static_instances = true;
+            args
+        });
+        let bar = compile.get_gid("bar");
+
+        for (file, style, bar_advance) in [
+            ("WghtVar-Regular.ttf", "Regular", 517),
+            ("WghtVar-Bold.ttf", "Bold", 551),
+        ] {
+            let raw_font = fs::read(compile.build_dir.join("instance_ttf").join(file)).unwrap();
+            let font = FontRef::new(&raw_font).unwrap();
+            assert!(font.fvar().is_err(), "{file} should be static");
+            assert!(font.gvar().is_err(), "{file} should be static");
+            let name = font.name().unwrap();
+            assert_eq!(
+                Some("Wght Var".to_string()),
+                resolve_name(&name, NameId::FAMILY_NAME)
+            );
+            assert_eq!(
+                Some(style.to_string()),
+                resolve_name(&name, NameId::SUBFAMILY_NAME)
+            );
+            assert_eq!(Some(bar_advance), font.hmtx().unwrap().advance(bar));
+        }
+    }
+
+    #[test]
+    fn compile_static_instance_with_its_own_names() {
+        let compile = TestCompile::compile("glyphs3/WghtVar_InstanceNames.glyphs", |mut args| {
+            args.static_instances = true;
+            args
+        });
+
+        let raw_font = fs::read(
+            compile
+                .build_dir
+                .join("instance_ttf")
+                .join("WghtVarDisplay-SmBd.ttf"),
+        )
+        .unwrap();
+        let font = FontRef::new(&raw_font).unwrap();
+        let name = font.name().unwrap();
+        assert_eq!(
+            vec![
+                Some("WghtVar Display SemiBold".to_string()),
+                Some("Regular".to_string()),
+                Some("WghtVar Display".to_string()),
+                Some("SemiBold".to_string()),
+                Some("WghtVarDisplay-SmBd".to_string()),
+            ],
+            [
+                NameId::FAMILY_NAME,
+                NameId::SUBFAMILY_NAME,
+                NameId::TYPOGRAPHIC_FAMILY_NAME,
+                NameId::TYPOGRAPHIC_SUBFAMILY_NAME,
+                NameId::POSTSCRIPT_NAME,
+            ]
+            .into_iter()
+            .map(|name_id| resolve_name(&name, name_id))
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn compile_static_instance_with_variable_feature_values() {
+        let compile = TestCompile::compile("glyphs3/FeaTokens.glyphs", |mut args| {
+            args.static_instances = true;
+            args
+        });
+
+        let raw_font = fs::read(
+            compile
+                .build_dir
+                .join("instance_ttf")
+                .join("FeaTokens-Medium.ttf"),
+        )
+        .unwrap();
+        let font = FontRef::new(&raw_font).unwrap();
+        let gpos = font.gpos().unwrap();
+        let mut kerns = Vec::new();
+        for lookup in gpos.lookup_list().unwrap().lookups().iter() {
+            let PositionLookup::Pair(lookup) = lookup.unwrap() else {
+                panic!("expected only pair lookups");
+            };
+            for subtable in lookup.subtables().iter() {
+                let PairPos::Format1(pairs) = subtable.unwrap() else {
+                    continue;
+                };
+                let coverage = pairs.coverage().unwrap();
+                for (first, pair_set) in coverage.iter().zip(pairs.pair_sets().iter()) {
+                    for record in pair_set.unwrap().pair_value_records().iter() {
+                        let record = record.unwrap();
+                        kerns.push((
+                            first,
+                            record.second_glyph(),
+                            record.value_record1().x_advance(),
+                        ));
+                    }
+                }
+            }
+        }
+        kerns.sort();
+
+        // ${padding} is 10 at wght 400 and 30 at 700, the instance is halfway
+        let a_upper = compile.get_gid("A");
+        let a = compile.get_gid("a");
+        let one = compile.get_gid("one");
+        let mut expected = vec![
+            (a_upper, a_upper, Some(20)),
+            (a, a, Some(41)),
+            (one, one, Some(5)),
+        ];
+        expected.sort();
+        assert_eq!(expected, kerns);
+    }
+
+    #[test]
+    fn static_instances_go_next_to_output_file() {
+        let compile = TestCompile::compile("wght_var.designspace", |mut args| {
+            args.static_instances = true;
+            args.output_file = Some(args.build_dir.join("WghtVar.woff2"));
+            args
+        });
+
+        for file in ["WghtVar-Regular.woff2", "WghtVar-Bold.woff2"] {
+            assert!(compile.build_dir.join(file).is_file(), "{file} missing");
+            assert!(
+                !compile.build_dir.join("instance_ttf").join(file).exists(),
+                "{file} should not be in the build directory"
+            );
+        }
+    }
+
+    #[test]
+    fn compile_collection_of_instances() {
+        let compile = TestCompile::compile("wght_var.designspace", |mut args| {
+            args.static_instances = true;
+            args.output_file = Some(args.build_dir.join("family.ttc"));
+            args.collection_sources = vec![testdata_dir().join("wght_var.designspace")];
+            args
+        });
+
+        let raw_collection = fs::read(compile.build_dir.join("family.ttc")).unwrap();
+        let collection = CollectionRef::new(&raw_collection).unwrap();
+        // Regular and Bold, then Regular and Bold again from the collection source
+        assert_eq!(4, collection.len());
+        let glyf_offset = |index| {
+            collection
+                .get(index)
+                .unwrap()
+                .table_directory
+                .table_records()
+                .iter()
+                .find(|record| record.tag() == Tag::new(b"glyf"))
+                .unwrap()
+                .offset()
+        };
+        assert_ne!(glyf_offset(0), glyf_offset(1));
+        assert_eq!(glyf_offset(0), glyf_offset(2));
+        assert_eq!(glyf_offset(1), glyf_offset(3));
+
+        let name = collection.get(3).unwrap().name().unwrap();
+        assert_eq!(
+            Some("Bold".to_string()),
+            resolve_name(&name, NameId::SUBFAMILY_NAME)
+        );
+    }
+
     fn assert_intermediate_layer(src: &str) {
         let result = TestCompile::compile_source(src);
         let font = result.font();
@@ -2506,6 +3233,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn compile_automatic_locl_declares_languages() {
+        let result = TestCompile::compile_source("glyphs3/AutomaticFeatures.glyphs");
+        let font = result.font();
+        let gsub = font.gsub().unwrap();
+        let script_list = gsub.script_list().unwrap();
+        let languages: Vec<_> = script_list
+            .script_records()
+            .iter()
+            .flat_map(|record| {
+                let script = record.script(script_list.offset_data()).unwrap();
+                script
+                    .lang_sys_records()
+                    .iter()
+                    .map(|lang| (record.script_tag(), lang.lang_sys_tag()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (Tag::new(b"latn"), Tag::new(b"ROM ")),
+                (Tag::new(b"latn"), Tag::new(b"TRK ")),
+            ],
+            languages
+        );
+    }
+
     #[test]
     fn compile_mark_mark() {
         let result = TestCompile::compile_source("glyphs3/Oswald-AE-comb.glyphs");
@@ -2695,6 +3449,44 @@ mod tests {
         assert_hhea_caret_slope("designspace_from_glyphs/SlantedFont.designspace", 1000, 213);
     }
 
+    #[test]
+    fn slant_an_upright() {
+        let result = TestCompile::compile("Overlap-Regular.ufo", |mut args| {
+            args.slant = Some(12.0);
+            args
+        });
+        let font = result.font();
+        assert_eq!(-12.0, font.post().unwrap().italic_angle().to_f32());
+        let hhea = font.hhea().unwrap();
+        assert_eq!(
+            (1000, 213),
+            (hhea.caret_slope_rise(), hhea.caret_slope_run())
+        );
+
+        // See resources/testdata/Overlap-Regular.ufo/glyphs/plus.glif, x moves by y * tan(12°)
+        let plus = result.get_glyph_index("plus").unwrap() as usize;
+        let glyph_data = result.glyphs();
+        let glyphs = glyph_data.read();
+        let Some(glyf::Glyph::Simple(glyph)) = &glyphs[plus] else {
+            panic!("Expected plus to be a simple glyph");
+        };
+        let mut points = glyph.points().map(|p| (p.x, p.y)).collect::<Vec<_>>();
+        points.sort();
+        assert_eq!(
+            vec![
+                (164, 300),
+                (185, 400),
+                (282, 150),
+                (367, 550),
+                (382, 150),
+                (467, 550),
+                (564, 300),
+                (585, 400),
+            ],
+            points
+        );
+    }
+
     fn assert_mvar(
         mvar: write_fonts::read::tables::mvar::Mvar,
         expected_value_records: Vec<(Tag, u16)>,
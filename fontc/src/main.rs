@@ -8,7 +8,8 @@ use clap::Parser;
 
 use fontbe::orchestration::{AnyWorkId, Context as BeContext};
 use fontc::{
-    create_timer, init_paths, write_font_file, Args, ChangeDetector, Config, Error, JobTimer,
+    check_interpolatable, create_timer, init_paths, write_collection, write_font_file,
+    write_static_instances, Args, ChangeDetector, Command, Config, Error, JobTimer,
 };
 use fontir::orchestration::{Context as FeContext, Flags};
 
@@ -49,6 +50,10 @@ fn run() -> Result<(), Error> {
         .init();
     timer.add(time.complete());
 
+    if let Some(command) = &args.command {
+        return run_command(command);
+    }
+
     let time = create_timer(AnyWorkId::InternalTiming("Init config"), 0)
         .queued()
         .run();
@@ -86,11 +91,46 @@ fn run() -> Result<(), Error> {
         timing.write_svg(&mut buf)?
     }
 
+    let instances = if config.args.static_instances {
+        write_static_instances(&config.args, &change_detector, &fe_root)?
+    } else {
+        Vec::new()
+    };
+
     change_detector.finish_successfully()?;
 
+    if config.args.collection() {
+        let fonts = if config.args.static_instances {
+            instances
+        } else {
+            vec![be_root.font.get().get().to_vec()]
+        };
+        return write_collection(&config.args, fonts);
+    }
     write_font_file(&config.args, &be_root)
 }
 
+fn run_command(command: &Command) -> Result<(), Error> {
+    match command {
+        Command::Interpolatable { source, json } => {
+            let report = check_interpolatable(source)?;
+            writeln!(std::io::stdout(), "{report}")?;
+            if let Some(json) = json {
+                let out_file = OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(json)?;
+                serde_json::to_writer_pretty(BufWriter::new(out_file), &report)?;
+            }
+            if !report.is_empty() {
+                return Err(Error::IncompatibleMasters(report.len()));
+            }
+            Ok(())
+        }
+    }
+}
+
 fn print_verbose_version() -> Result<(), std::io::Error> {
     writeln!(
         std::io::stdout(),
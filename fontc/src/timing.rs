@@ -157,6 +157,7 @@ fn short_name(id: &AnyWorkId) -> &'static str {
         AnyWorkId::Be(BeWorkIdentifier::Marks) => "Marks",
         AnyWorkId::Be(BeWorkIdentifier::Maxp) => "maxp",
         AnyWorkId::Be(BeWorkIdentifier::Mvar) => "MVAR",
+        AnyWorkId::Be(BeWorkIdentifier::Varc) => "VARC",
         AnyWorkId::Be(BeWorkIdentifier::Name) => "name",
         AnyWorkId::Be(BeWorkIdentifier::Os2) => "OS/2",
         AnyWorkId::Be(BeWorkIdentifier::Post) => "post",
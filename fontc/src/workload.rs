@@ -15,11 +15,11 @@ use fontbe::{
     orchestration::{AnyWorkId, Context as BeContext, WorkId as BeWorkIdentifier},
 };
 use fontdrasil::{
-    coords::NormalizedLocation,
     orchestration::{Access, AccessBuilder, AccessType, Identifier, IdentifierDiscriminant},
     types::GlyphName,
 };
 use fontir::{
+    ir::KerningGroups,
     orchestration::{Context as FeContext, WorkId as FeWorkIdentifier},
     source::Input,
 };
@@ -212,6 +212,7 @@ impl<'a> Workload<'a> {
     /// * If the glyph has no components the BE for it doesn't use glyph order and needn't block on it
     /// * If the glyph does have components we need to block on glyph order because that might alter them
     ///    * For example, flatten
+    /// * If the glyph has variable components we block on all IR glyphs, as drawing them reads nested bases
     ///
     /// By minimizing dependencies we allow jobs to start earlier and execute with greater concurrency.
     fn update_be_glyph_work(&mut self, fe_root: &FeContext, glyph_name: GlyphName) {
@@ -238,10 +239,17 @@ impl<'a> Workload<'a> {
         let mut deps = AccessBuilder::<AnyWorkId>::new().variant(FeWorkIdentifier::StaticMetadata);
 
         let mut has_components = false;
-        for inst in glyph.sources().values() {
-            for component in inst.components.iter() {
-                has_components = true;
-                deps = deps.specific_instance(FeWorkIdentifier::Glyph(component.base.clone()));
+        if glyph.has_variable_components() {
+            // Variable components are drawn into contours, reading every glyph nested in them,
+            // and the bases of our bases may not have IR yet so we can't name them
+            has_components = true;
+            deps = deps.variant(FeWorkIdentifier::ALL_GLYPHS);
+        } else {
+            for inst in glyph.sources().values() {
+                for component in inst.components.iter() {
+                    has_components = true;
+                    deps = deps.specific_instance(FeWorkIdentifier::Glyph(component.base.clone()));
+                }
             }
         }
 
@@ -258,6 +266,29 @@ impl<'a> Workload<'a> {
         be_job.read_access = deps
     }
 
+    /// Let GatherIrKerning, if it's pending, read the glyph order, the kerning groups and the kerning at each of their locations
+    pub(crate) fn set_gather_ir_kerning_access(&mut self, groups: &KerningGroups) {
+        let Some(job) = self
+            .jobs_pending
+            .get_mut(&AnyWorkId::Be(BeWorkIdentifier::GatherIrKerning))
+        else {
+            return;
+        };
+        job.read_access = groups
+            .locations
+            .iter()
+            .fold(
+                AccessBuilder::<AnyWorkId>::new()
+                    .variant(FeWorkIdentifier::GlyphOrder)
+                    .variant(FeWorkIdentifier::KerningGroups),
+                |access, location| {
+                    access.specific_instance(FeWorkIdentifier::KernInstance(location.clone()))
+                },
+            )
+            .build()
+            .into();
+    }
+
     fn handle_success(
         &mut self,
         fe_root: &FeContext,
@@ -295,15 +326,7 @@ impl<'a> Workload<'a> {
             }
 
             // https://github.com/googlefonts/fontc/pull/655: don't set read access on GatherIrKerning until we spawn kern instance tasks
-            self.jobs_pending
-                .get_mut(&AnyWorkId::Be(BeWorkIdentifier::GatherIrKerning))
-                .expect("Gather IR Kerning has to be pending")
-                .read_access = AccessBuilder::<AnyWorkId>::new()
-                .variant(FeWorkIdentifier::GlyphOrder)
-                .variant(FeWorkIdentifier::KerningGroups)
-                .variant(FeWorkIdentifier::KernInstance(NormalizedLocation::default()))
-                .build()
-                .into();
+            self.set_gather_ir_kerning_access(&groups);
         }
 
         if let AnyWorkId::Be(BeWorkIdentifier::GatherIrKerning) = success {
@@ -289,6 +289,10 @@ impl<Space> Location<Space> {
         self.0.keys()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub fn contains(&self, tag: Tag) -> bool {
         self.0.contains_key(&tag)
     }
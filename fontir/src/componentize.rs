@@ -0,0 +1,546 @@
+//! Automatic componentization.
+//!
+//! Some sources decompose everything, so an accented letter repeats the outlines of the
+//! letter and the accent. Where every contour of a glyph, in every source, is a copy
+//! of the contours of other glyphs we make the glyph out of components instead.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use fontdrasil::coords::NormalizedLocation;
+use kurbo::{Affine, BezPath, PathEl, Point, Vec2};
+use log::debug;
+
+use crate::{
+    error::WorkError,
+    instancer::transform_contour,
+    ir::{Component, Glyph, GlyphBuilder},
+};
+
+/// The 2x2s that map the axes onto themselves: flips and rotations by multiples of 90 degrees
+///
+/// Flipped contours are reversed to keep their direction, see [translation].
+const AXIS_ALIGNED_2X2S: [[f64; 4]; 8] = [
+    [1.0, 0.0, 0.0, 1.0],
+    [-1.0, 0.0, 0.0, 1.0],
+    [1.0, 0.0, 0.0, -1.0],
+    [-1.0, 0.0, 0.0, -1.0],
+    [0.0, 1.0, -1.0, 0.0],
+    [0.0, -1.0, 1.0, 0.0],
+    [0.0, 1.0, 1.0, 0.0],
+    [0.0, -1.0, -1.0, 0.0],
+];
+
+/// Coordinates are compared to a thousandth of a unit
+const PRECISION: f64 = 1000.0;
+
+/// A contour moved to start at the origin, with coordinates rounded so it can be hashed.
+///
+/// A closed contour starts at whichever on-curve point gives the smallest key, so copies
+/// that start elsewhere, such as those reversed to keep their direction when flipped, match.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct ContourKey(Vec<(u8, Vec<(i64, i64)>)>);
+
+impl ContourKey {
+    /// The key of the contour and the point it starts at
+    fn new(contour: &BezPath) -> Option<(Self, Point)> {
+        let elements = contour.elements();
+        let Some(PathEl::MoveTo(first)) = elements.first() else {
+            return None;
+        };
+        let closed = matches!(elements.last(), Some(PathEl::ClosePath));
+        let mut segments: Vec<_> = elements[1..]
+            .iter()
+            .filter(|el| !matches!(el, PathEl::ClosePath))
+            .copied()
+            .collect();
+        // Make the closing segment explicit so every on-curve point starts a segment
+        if closed && segments.last().and_then(end_point) != Some(*first) {
+            segments.push(PathEl::LineTo(*first));
+        }
+
+        let key = |rotation: usize| -> (Self, Point) {
+            let start = match rotation {
+                0 => *first,
+                _ => end_point(&segments[rotation - 1]).unwrap(),
+            };
+            let relative = |pts: &[Point]| -> Vec<(i64, i64)> {
+                pts.iter()
+                    .map(|pt| {
+                        let v = *pt - start;
+                        (
+                            (v.x * PRECISION).round() as i64,
+                            (v.y * PRECISION).round() as i64,
+                        )
+                    })
+                    .collect()
+            };
+            let mut elements: Vec<_> = segments[rotation..]
+                .iter()
+                .chain(segments[..rotation].iter())
+                .map(|el| match el {
+                    PathEl::LineTo(p) => (1, relative(&[*p])),
+                    PathEl::QuadTo(p1, p2) => (2, relative(&[*p1, *p2])),
+                    PathEl::CurveTo(p1, p2, p3) => (3, relative(&[*p1, *p2, *p3])),
+                    // Only the first element moves, and close paths were removed
+                    PathEl::MoveTo(..) | PathEl::ClosePath => (0, Vec::new()),
+                })
+                .collect();
+            if closed {
+                elements.push((4, Vec::new()));
+            }
+            (ContourKey(elements), start)
+        };
+        let rotations = if closed { segments.len().max(1) } else { 1 };
+        (0..rotations).map(key).min_by(|a, b| a.0.cmp(&b.0))
+    }
+}
+
+fn end_point(el: &PathEl) -> Option<Point> {
+    match el {
+        PathEl::MoveTo(p) | PathEl::LineTo(p) | PathEl::QuadTo(_, p) | PathEl::CurveTo(_, _, p) => {
+            Some(*p)
+        }
+        PathEl::ClosePath => None,
+    }
+}
+
+/// How a contour of the target lines up with a transformed contour of a base.
+///
+/// Returns the translation that moves the transformed base contour onto the target
+/// contour, if they are the same shape. A base that's flipped is reversed, as it will be
+/// when the component is drawn.
+fn translation(target: &BezPath, base: &BezPath, transform: Affine) -> Option<Vec2> {
+    let (target_key, target_start) = ContourKey::new(target)?;
+    let (base_key, base_start) = ContourKey::new(&transform_contour(base.clone(), transform))?;
+    (target_key == base_key).then(|| target_start - base_start)
+}
+
+fn same_translation(a: Vec2, b: Vec2) -> bool {
+    (a - b).hypot() * PRECISION < 1.0
+}
+
+/// Contours only, the same in every source
+fn is_simple(glyph: &Glyph) -> bool {
+    glyph
+        .sources()
+        .values()
+        .all(|inst| inst.components.is_empty())
+        && !glyph.default_instance().contours.is_empty()
+}
+
+/// A use of a base glyph as a component of the glyph being componentized
+struct Use<'a> {
+    base: &'a Glyph,
+    transform: Affine,
+    /// The indices of the contours of the glyph the base replaces, in the order of the base's contours
+    contours: Vec<usize>,
+    /// The translation of the component in each source
+    translations: HashMap<NormalizedLocation, Vec2>,
+}
+
+/// Try to use `base`, transformed by `transform`, in place of contour `first` and others
+/// of `target` that are still available.
+fn try_use<'a>(
+    target: &Glyph,
+    base: &'a Glyph,
+    transform: Affine,
+    first: usize,
+    available: &[bool],
+) -> Option<Use<'a>> {
+    // The base must interpolate just as the contours it replaces do
+    let locations: HashSet<_> = target.sources().keys().collect();
+    if base.sources().len() != locations.len()
+        || !base.sources().keys().all(|loc| locations.contains(loc))
+    {
+        return None;
+    }
+
+    // Find the contours in the default, then make sure they line up everywhere else
+    let target_default = target.default_instance();
+    let base_default = base.default_instance();
+    let offset = translation(
+        &target_default.contours[first],
+        &base_default.contours[0],
+        transform,
+    )?;
+    let mut available = available.to_vec();
+    available[first] = false;
+    let mut contours = vec![first];
+    for base_contour in base_default.contours.iter().skip(1) {
+        let found = (0..target_default.contours.len()).find(|i| {
+            available[*i]
+                && translation(&target_default.contours[*i], base_contour, transform)
+                    .is_some_and(|t| same_translation(t, offset))
+        })?;
+        available[found] = false;
+        contours.push(found);
+    }
+
+    let mut translations = HashMap::new();
+    for (loc, target_inst) in target.sources() {
+        let base_inst = base.sources().get(loc)?;
+        if base_inst.contours.len() != contours.len()
+            || target_inst.contours.len() != target_default.contours.len()
+        {
+            return None;
+        }
+        let offset = translation(
+            &target_inst.contours[contours[0]],
+            &base_inst.contours[0],
+            transform,
+        )?;
+        let lines_up = contours
+            .iter()
+            .zip(base_inst.contours.iter())
+            .all(|(i, base_contour)| {
+                translation(&target_inst.contours[*i], base_contour, transform)
+                    .is_some_and(|t| same_translation(t, offset))
+            });
+        if !lines_up {
+            return None;
+        }
+        translations.insert(loc.clone(), offset);
+    }
+
+    Some(Use {
+        base,
+        transform,
+        contours,
+        translations,
+    })
+}
+
+/// Make glyphs whose contours all appear in other glyphs out of components.
+///
+/// `glyphs` are in glyph order. Only glyphs that are emitted to the binary and made
+/// only of contours are used as components. To keep components acyclic a glyph only
+/// uses glyphs with fewer contours, or as many contours that come before it.
+///
+/// If `transformed` is set contours that are flipped or rotated by 90 degrees are also
+/// found, otherwise only translated ones are. Returns the glyphs that now have components.
+pub(crate) fn componentize(
+    glyphs: &[Arc<Glyph>],
+    transformed: bool,
+) -> Result<Vec<Glyph>, WorkError> {
+    let transforms: Vec<_> = if transformed {
+        AXIS_ALIGNED_2X2S
+            .iter()
+            .map(|[a, b, c, d]| Affine::new([*a, *b, *c, *d, 0.0, 0.0]))
+            .collect()
+    } else {
+        vec![Affine::IDENTITY]
+    };
+
+    // Bases by the shape of their first contour; prefer bigger ones, then earlier ones
+    let mut bases: Vec<_> = glyphs
+        .iter()
+        .enumerate()
+        .filter(|(_, glyph)| glyph.emit_to_binary && is_simple(glyph))
+        .collect();
+    bases.sort_by_key(|(i, glyph)| {
+        (
+            std::cmp::Reverse(glyph.default_instance().contours.len()),
+            *i,
+        )
+    });
+    let mut by_first_contour: HashMap<ContourKey, Vec<(usize, Affine)>> = HashMap::new();
+    for (i, glyph) in bases {
+        for transform in transforms.iter() {
+            let first = transform_contour(glyph.default_instance().contours[0].clone(), *transform);
+            if let Some((key, _)) = ContourKey::new(&first) {
+                by_first_contour
+                    .entry(key)
+                    .or_default()
+                    .push((i, *transform));
+            }
+        }
+    }
+
+    let mut componentized = Vec::new();
+    for (target_idx, target) in glyphs.iter().enumerate() {
+        if !target.emit_to_binary || !is_simple(target) {
+            continue;
+        }
+        let contour_count = target.default_instance().contours.len();
+        let may_use = |base_idx: usize| {
+            let base_count = glyphs[base_idx].default_instance().contours.len();
+            base_count < contour_count || (base_count == contour_count && base_idx < target_idx)
+        };
+
+        let mut available = vec![true; contour_count];
+        let mut uses = Vec::new();
+        for first in 0..contour_count {
+            if !available[first] {
+                continue;
+            }
+            let Some((key, _)) = ContourKey::new(&target.default_instance().contours[first]) else {
+                break;
+            };
+            let Some(found) = by_first_contour
+                .get(&key)
+                .into_iter()
+                .flatten()
+                .filter(|(base_idx, _)| may_use(*base_idx))
+                .find_map(|(base_idx, transform)| {
+                    try_use(target, &glyphs[*base_idx], *transform, first, &available)
+                })
+            else {
+                break;
+            };
+            for i in found.contours.iter() {
+                available[*i] = false;
+            }
+            uses.push(found);
+        }
+        // A mix of contours and components would only be decomposed again
+        if available.iter().any(|a| *a) {
+            continue;
+        }
+
+        debug!(
+            "'{}' is made of components {:?}",
+            target.name,
+            uses.iter()
+                .map(|u| u.base.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        let mut glyph = GlyphBuilder::from((**target).clone());
+        for (loc, inst) in glyph.sources.iter_mut() {
+            inst.contours.clear();
+            inst.components = uses
+                .iter()
+                .map(|u| Component {
+                    base: u.base.name.clone(),
+                    transform: Affine::translate(u.translations[loc]) * u.transform,
+                    location: NormalizedLocation::new(),
+                })
+                .collect();
+        }
+        componentized.push(glyph.build()?);
+    }
+    Ok(componentized)
+}
+
+#[cfg(test)]
+mod tests {
+    use fontdrasil::types::GlyphName;
+
+    use crate::ir::GlyphInstance;
+
+    use super::*;
+
+    fn square(x: f64, y: f64, size: f64) -> BezPath {
+        let mut path = BezPath::new();
+        path.move_to((x, y));
+        path.line_to((x + size, y));
+        path.line_to((x + size, y + size));
+        path.line_to((x, y + size));
+        path.close_path();
+        path
+    }
+
+    fn triangle(x: f64, y: f64) -> BezPath {
+        let mut path = BezPath::new();
+        path.move_to((x, y));
+        path.line_to((x + 100.0, y));
+        path.line_to((x, y + 50.0));
+        path.close_path();
+        path
+    }
+
+    fn glyph(name: &str, sources: &[(f64, Vec<BezPath>)]) -> Arc<Glyph> {
+        let mut builder = GlyphBuilder::new(GlyphName::new(name));
+        for (wght, contours) in sources {
+            builder
+                .try_add_source(
+                    &NormalizedLocation::for_pos(&[("wght", *wght)]),
+                    GlyphInstance {
+                        width: 600.0,
+                        contours: contours.clone(),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+        Arc::new(builder.build().unwrap())
+    }
+
+    fn component_summary(glyph: &Glyph) -> Vec<(f64, Vec<(String, [f64; 6])>)> {
+        let mut summary: Vec<_> = glyph
+            .sources()
+            .iter()
+            .map(|(loc, inst)| {
+                assert!(inst.contours.is_empty(), "{inst:?}");
+                (
+                    loc.iter().next().unwrap().1.to_f32() as f64,
+                    inst.components
+                        .iter()
+                        .map(|c| (c.base.to_string(), c.transform.as_coeffs()))
+                        .collect(),
+                )
+            })
+            .collect();
+        summary.sort_by(|a, b| a.0.total_cmp(&b.0));
+        summary
+    }
+
+    #[test]
+    fn translated_copies_become_components() {
+        let glyphs = vec![
+            glyph(
+                "base",
+                &[
+                    (0.0, vec![square(0.0, 0.0, 100.0)]),
+                    (1.0, vec![square(0.0, 0.0, 200.0)]),
+                ],
+            ),
+            glyph(
+                "mark",
+                &[
+                    (0.0, vec![square(0.0, 0.0, 10.0)]),
+                    (1.0, vec![square(0.0, 0.0, 20.0)]),
+                ],
+            ),
+            glyph(
+                "accented",
+                &[
+                    (
+                        0.0,
+                        vec![square(10.0, 0.0, 100.0), square(50.0, 150.0, 10.0)],
+                    ),
+                    (
+                        1.0,
+                        vec![square(10.0, 0.0, 200.0), square(90.0, 250.0, 20.0)],
+                    ),
+                ],
+            ),
+        ];
+        let componentized = componentize(&glyphs, false).unwrap();
+        assert_eq!(1, componentized.len());
+        assert_eq!(
+            vec![
+                (
+                    0.0,
+                    vec![
+                        ("base".to_string(), [1.0, 0.0, 0.0, 1.0, 10.0, 0.0]),
+                        ("mark".to_string(), [1.0, 0.0, 0.0, 1.0, 50.0, 150.0])
+                    ]
+                ),
+                (
+                    1.0,
+                    vec![
+                        ("base".to_string(), [1.0, 0.0, 0.0, 1.0, 10.0, 0.0]),
+                        ("mark".to_string(), [1.0, 0.0, 0.0, 1.0, 90.0, 250.0])
+                    ]
+                ),
+            ],
+            component_summary(&componentized[0])
+        );
+    }
+
+    #[test]
+    fn copy_must_match_in_every_source() {
+        let glyphs = vec![
+            glyph(
+                "base",
+                &[
+                    (0.0, vec![square(0.0, 0.0, 100.0)]),
+                    (1.0, vec![square(0.0, 0.0, 200.0)]),
+                ],
+            ),
+            glyph(
+                "almost",
+                &[
+                    (0.0, vec![square(10.0, 0.0, 100.0)]),
+                    (1.0, vec![square(10.0, 0.0, 190.0)]),
+                ],
+            ),
+        ];
+        assert!(componentize(&glyphs, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn partial_matches_are_left_alone() {
+        let glyphs = vec![
+            glyph("base", &[(0.0, vec![square(0.0, 0.0, 100.0)])]),
+            glyph(
+                "more",
+                &[(0.0, vec![square(0.0, 0.0, 100.0), triangle(300.0, 0.0)])],
+            ),
+        ];
+        assert!(componentize(&glyphs, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn duplicates_use_the_earlier_glyph() {
+        let glyphs = vec![
+            glyph("a", &[(0.0, vec![triangle(0.0, 0.0)])]),
+            glyph("a.alt", &[(0.0, vec![triangle(0.0, 0.0)])]),
+        ];
+        let componentized = componentize(&glyphs, false).unwrap();
+        assert_eq!(
+            vec![GlyphName::new("a.alt")],
+            componentized
+                .iter()
+                .map(|g| g.name.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn skip_glyphs_not_emitted() {
+        let mut hidden = (*glyph("hidden", &[(0.0, vec![triangle(0.0, 0.0)])])).clone();
+        hidden.emit_to_binary = false;
+        let glyphs = vec![
+            Arc::new(hidden),
+            glyph("copy", &[(0.0, vec![triangle(0.0, 0.0)])]),
+        ];
+        assert!(componentize(&glyphs, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn copies_starting_elsewhere_become_components() {
+        let mut copy = BezPath::new();
+        copy.move_to((400.0, 0.0));
+        copy.line_to((300.0, 50.0));
+        copy.line_to((300.0, 0.0));
+        copy.close_path();
+        let glyphs = vec![
+            glyph("a", &[(0.0, vec![triangle(0.0, 0.0)])]),
+            glyph("b", &[(0.0, vec![copy])]),
+        ];
+        let componentized = componentize(&glyphs, false).unwrap();
+        assert_eq!(
+            vec![(
+                0.0,
+                vec![("a".to_string(), [1.0, 0.0, 0.0, 1.0, 300.0, 0.0])]
+            )],
+            component_summary(&componentized[0])
+        );
+    }
+
+    #[test]
+    fn flipped_copies_need_transformed() {
+        // Reversed, as a designer keeping the contour direction would have it
+        let flipped = transform_contour(
+            triangle(0.0, 0.0),
+            Affine::translate((500.0, 0.0)) * Affine::FLIP_X,
+        );
+        let glyphs = vec![
+            glyph("a", &[(0.0, vec![triangle(0.0, 0.0)])]),
+            glyph("b", &[(0.0, vec![flipped])]),
+        ];
+        assert!(componentize(&glyphs, false).unwrap().is_empty());
+        let componentized = componentize(&glyphs, true).unwrap();
+        assert_eq!(
+            vec![(
+                0.0,
+                vec![("a".to_string(), [-1.0, 0.0, 0.0, 1.0, 500.0, 0.0])]
+            )],
+            component_summary(&componentized[0])
+        );
+    }
+}
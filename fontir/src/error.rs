@@ -154,6 +154,10 @@ pub enum WorkError {
     NoSourceForName(String),
     #[error("Source file contained a construct we don't yet support: {0}")]
     UnsupportedConstruct(String),
+    #[error("Unable to expand '{token}' in feature code: {reason}")]
+    InvalidFeatureToken { token: String, reason: String },
+    #[error("Unable to interpolate {what}: {reason}")]
+    InterpolationError { what: String, reason: String },
 }
 
 /// Reasons an anchor can be malformed
@@ -0,0 +1,494 @@
+//! Glyph filters, as ufo2ft runs them.
+//!
+//! See <https://github.com/googlefonts/ufo2ft/tree/main/Lib/ufo2ft/filters>
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use fontdrasil::{coords::NormalizedLocation, types::GlyphName};
+use kurbo::{Affine, BezPath, Circle, Point, Rect, Shape};
+use log::{debug, warn};
+use ordered_float::OrderedFloat;
+use write_fonts::OtRound;
+
+use crate::{
+    error::WorkError,
+    glyph::{convert_components_to_contours, flatten_glyph},
+    ir::{
+        AnchorBuilder, AnchorKind, GlobalMetricsInstance, Glyph, GlyphAnchors, GlyphBuilder,
+        GlyphFilter, GlyphFilterKind, GlyphInstance, GlyphOrder, TransformOrigin, Transformation,
+    },
+    orchestration::{Context, WorkId},
+    propagate_anchors::propagate_anchors,
+};
+
+const DOTTED_CIRCLE: u32 = 0x25CC;
+const DOTTED_CIRCLE_NAME: &str = "uni25CC";
+// <https://github.com/googlefonts/ufo2ft/blob/main/Lib/ufo2ft/filters/dottedCircle.py>
+const DOTTED_CIRCLE_MARGIN: f64 = 80.0;
+const DOTTED_CIRCLE_DOTS: usize = 12;
+
+/// Run a filter over the glyphs in `glyph_order`, which gains any glyph the filter adds
+pub(crate) fn apply_glyph_filter(
+    context: &Context,
+    glyph_order: &mut GlyphOrder,
+    filter: &GlyphFilter,
+) -> Result<(), WorkError> {
+    debug!("Run glyph filter {:?}", filter.kind);
+    let selected: Vec<_> = glyph_order
+        .iter()
+        .filter(|gn| filter.applies_to(gn))
+        .map(|gn| context.glyphs.get(&WorkId::Glyph(gn.clone())))
+        .collect();
+    match &filter.kind {
+        GlyphFilterKind::DecomposeComponents => {
+            for glyph in selected {
+                if !glyph.default_instance().components.is_empty()
+                    && !glyph.has_variable_components()
+                {
+                    convert_components_to_contours(context, &glyph)?;
+                }
+            }
+        }
+        GlyphFilterKind::DecomposeTransformedComponents => {
+            for glyph in selected {
+                if glyph.has_nonidentity_2x2() && !glyph.has_variable_components() {
+                    convert_components_to_contours(context, &glyph)?;
+                }
+            }
+        }
+        GlyphFilterKind::FlattenComponents => {
+            for glyph in selected {
+                if !glyph.has_variable_components() {
+                    flatten_glyph(context, &glyph)?;
+                }
+            }
+        }
+        GlyphFilterKind::PropagateAnchors => {
+            // Components outside the selection still lend their anchors
+            let glyphs: Vec<_> = glyph_order
+                .iter()
+                .map(|gn| context.glyphs.get(&WorkId::Glyph(gn.clone())))
+                .collect();
+            for anchors in propagate_anchors(&glyphs, &all_anchors(context, glyph_order))? {
+                if filter.applies_to(&anchors.glyph_name) {
+                    context.anchors.set(anchors);
+                }
+            }
+        }
+        GlyphFilterKind::SortContours => {
+            for glyph in selected {
+                if let Some(sorted) = sort_contours(&glyph) {
+                    context.glyphs.set(sorted);
+                }
+            }
+        }
+        GlyphFilterKind::Transformations(transformation) => {
+            let global_metrics = context.global_metrics.get();
+            let names: HashSet<_> = selected.iter().map(|g| g.name.clone()).collect();
+            for glyph in selected {
+                let matrices: HashMap<_, _> = glyph
+                    .sources()
+                    .keys()
+                    .map(|loc| (loc.clone(), matrix(transformation, &global_metrics.at(loc))))
+                    .collect();
+                if matrices.values().all(|m| *m == Affine::IDENTITY) {
+                    continue;
+                }
+                context
+                    .glyphs
+                    .set(transform_glyph(&glyph, &matrices, &names)?);
+                if let Some(anchors) = context.anchors.try_get(&WorkId::Anchor(glyph.name.clone()))
+                {
+                    context.anchors.set(transform_anchors(&anchors, &matrices));
+                }
+            }
+        }
+        GlyphFilterKind::DottedCircle => add_dotted_circle(context, glyph_order)?,
+    }
+    Ok(())
+}
+
+fn all_anchors(
+    context: &Context,
+    glyph_order: &GlyphOrder,
+) -> HashMap<GlyphName, std::sync::Arc<GlyphAnchors>> {
+    glyph_order
+        .iter()
+        .filter_map(|gn| {
+            context
+                .anchors
+                .try_get(&WorkId::Anchor(gn.clone()))
+                .map(|anchors| (gn.clone(), anchors))
+        })
+        .collect()
+}
+
+/// The transform ufo2ft's transformations filter applies at a location
+fn matrix(transformation: &Transformation, metrics: &GlobalMetricsInstance) -> Affine {
+    let origin_height = match transformation.origin {
+        TransformOrigin::CapHeight => metrics.cap_height.0 as f64,
+        TransformOrigin::HalfCapHeight => {
+            OtRound::<i32>::ot_round(metrics.cap_height.0 as f64 / 2.0) as f64
+        }
+        TransformOrigin::XHeight => metrics.x_height.0 as f64,
+        TransformOrigin::HalfXHeight => {
+            OtRound::<i32>::ot_round(metrics.x_height.0 as f64 / 2.0) as f64
+        }
+        TransformOrigin::Baseline => 0.0,
+    };
+    Affine::translate((transformation.offset_x.0, transformation.offset_y.0))
+        * Affine::translate((0.0, origin_height))
+        * Affine::scale_non_uniform(
+            transformation.scale_x.0 / 100.0,
+            transformation.scale_y.0 / 100.0,
+        )
+        * Affine::skew(transformation.slant.0.to_radians().tan(), 0.0)
+        * Affine::translate((0.0, -origin_height))
+}
+
+/// Apply the transform for each location to the glyph at that location.
+///
+/// Components of glyphs that are themselves transformed are adjusted so they
+/// aren't transformed twice.
+fn transform_glyph(
+    glyph: &Glyph,
+    matrices: &HashMap<NormalizedLocation, Affine>,
+    transformed: &HashSet<GlyphName>,
+) -> Result<Glyph, WorkError> {
+    let mut glyph = glyph.clone();
+    for (loc, inst) in glyph.sources_mut() {
+        let matrix = matrices[loc];
+        for contour in inst.contours.iter_mut() {
+            contour.apply_affine(matrix);
+        }
+        for component in inst.components.iter_mut() {
+            component.transform = if transformed.contains(&component.base) {
+                matrix * component.transform * matrix.inverse()
+            } else {
+                matrix * component.transform
+            };
+        }
+    }
+    GlyphBuilder::from(glyph).build()
+}
+
+fn transform_anchors(
+    anchors: &GlyphAnchors,
+    matrices: &HashMap<NormalizedLocation, Affine>,
+) -> GlyphAnchors {
+    let mut anchors = anchors.clone();
+    for anchor in anchors.anchors.iter_mut() {
+        for (loc, pos) in anchor.positions.iter_mut() {
+            if let Some(matrix) = matrices.get(loc) {
+                *pos = *matrix * *pos;
+            }
+        }
+    }
+    anchors
+}
+
+type SortKey = [OrderedFloat<f64>; 4];
+
+fn sort_key(contour: &BezPath) -> SortKey {
+    let Rect { x0, y0, x1, y1 } = contour.bounding_box();
+    [x0.into(), y0.into(), x1.into(), y1.into()]
+}
+
+/// Sort contours by their bounds at the default location, in the same order everywhere.
+///
+/// Returns None if the order doesn't change.
+fn sort_contours(glyph: &Glyph) -> Option<Glyph> {
+    let contours = &glyph.default_instance().contours;
+    let mut order: Vec<_> = (0..contours.len()).collect();
+    order.sort_by_key(|i| sort_key(&contours[*i]));
+    if order.iter().enumerate().all(|(i, j)| i == *j) {
+        return None;
+    }
+    if glyph
+        .sources()
+        .values()
+        .any(|inst| inst.contours.len() != contours.len())
+    {
+        warn!(
+            "Not sorting the contours of '{}', it has a different number of contours at different locations",
+            glyph.name
+        );
+        return None;
+    }
+    let mut glyph = glyph.clone();
+    for (_, inst) in glyph.sources_mut() {
+        inst.contours = order.iter().map(|i| inst.contours[*i].clone()).collect();
+    }
+    Some(glyph)
+}
+
+/// Add a dotted circle glyph if there isn't one, and give it a base anchor for every mark anchor.
+fn add_dotted_circle(context: &Context, glyph_order: &mut GlyphOrder) -> Result<(), WorkError> {
+    let anchors = all_anchors(context, glyph_order);
+    let mark_groups: BTreeSet<_> = anchors
+        .values()
+        .flat_map(|a| a.anchors.iter())
+        .filter_map(|anchor| match &anchor.kind {
+            AnchorKind::Mark(group_name) => Some(group_name.clone()),
+            _ => None,
+        })
+        .collect();
+    if mark_groups.is_empty() {
+        return Ok(());
+    }
+
+    let existing = glyph_order
+        .iter()
+        .map(|gn| context.glyphs.get(&WorkId::Glyph(gn.clone())))
+        .find(|glyph| glyph.codepoints.contains(&DOTTED_CIRCLE));
+    let dotted_circle = match existing {
+        Some(glyph) => (*glyph).clone(),
+        None => {
+            let glyph_name = GlyphName::new(DOTTED_CIRCLE_NAME);
+            if glyph_order.contains(&glyph_name) {
+                warn!("Unable to add a dotted circle, '{glyph_name}' is not U+25CC");
+                return Ok(());
+            }
+            let static_metadata = context.static_metadata.get();
+            let metrics = context
+                .global_metrics
+                .get()
+                .at(static_metadata.default_location());
+            let glyph = draw_dotted_circle(
+                glyph_name,
+                static_metadata.default_location(),
+                metrics.x_height.0 as f64,
+            )?;
+            debug!("Add dotted circle '{}'", glyph.name);
+            glyph_order.insert(glyph.name.clone());
+            context.glyphs.set(glyph.clone());
+            glyph
+        }
+    };
+
+    let mut builder = AnchorBuilder::new(dotted_circle.name.clone());
+    let mut has = HashSet::new();
+    if let Some(own) = anchors.get(&dotted_circle.name) {
+        for anchor in own.anchors.iter() {
+            has.insert(anchor.kind.name());
+            for (loc, pos) in anchor.positions.iter() {
+                builder.add(anchor.kind.name(), loc.clone(), *pos)?;
+            }
+        }
+    }
+    for group_name in mark_groups.iter() {
+        if has.contains(group_name) {
+            continue;
+        }
+        // Centered, at the average height of the anchor in the bases that have it
+        for (loc, inst) in dotted_circle.sources() {
+            let heights: Vec<_> = anchors
+                .values()
+                .filter(|a| a.glyph_name != dotted_circle.name)
+                .flat_map(|a| a.anchors.iter())
+                .filter(
+                    |anchor| matches!(&anchor.kind, AnchorKind::Base(name) if name == group_name),
+                )
+                .filter_map(|anchor| anchor.positions.get(loc))
+                .map(|pos| pos.y)
+                .collect();
+            let y = if heights.is_empty() {
+                dotted_circle_bounds(inst).center().y
+            } else {
+                heights.iter().sum::<f64>() / heights.len() as f64
+            };
+            builder.add(
+                group_name.clone(),
+                loc.clone(),
+                Point::new(inst.width / 2.0, y),
+            )?;
+        }
+    }
+    context.anchors.set(builder.build()?);
+    Ok(())
+}
+
+fn dotted_circle_bounds(inst: &GlyphInstance) -> Rect {
+    inst.contours
+        .iter()
+        .map(|c| c.bounding_box())
+        .reduce(|acc, r| acc.union(r))
+        .unwrap_or_default()
+}
+
+/// Twelve dots on a ring that sits on the baseline and reaches the x-height
+fn draw_dotted_circle(
+    glyph_name: GlyphName,
+    default_location: &NormalizedLocation,
+    x_height: f64,
+) -> Result<Glyph, WorkError> {
+    let dot_radius = x_height / 20.0;
+    let ring_radius = x_height / 2.0 - dot_radius;
+    let center = Point::new(DOTTED_CIRCLE_MARGIN + x_height / 2.0, x_height / 2.0);
+    let contours = (0..DOTTED_CIRCLE_DOTS)
+        .map(|i| {
+            let angle = std::f64::consts::TAU * i as f64 / DOTTED_CIRCLE_DOTS as f64;
+            let dot = center + kurbo::Vec2::from_angle(angle) * ring_radius;
+            Circle::new(dot, dot_radius).to_path(0.1)
+        })
+        .collect();
+    let mut builder = GlyphBuilder::new(glyph_name);
+    builder.codepoints.insert(DOTTED_CIRCLE);
+    builder.try_add_source(
+        default_location,
+        GlyphInstance {
+            width: 2.0 * DOTTED_CIRCLE_MARGIN + x_height,
+            contours,
+            ..Default::default()
+        },
+    )?;
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::Component;
+
+    use super::*;
+
+    fn rect(x: f64, y: f64) -> BezPath {
+        Rect::new(x, y, x + 10.0, y + 10.0).to_path(0.1)
+    }
+
+    fn glyph(name: &str, sources: &[(f32, Vec<BezPath>)]) -> Glyph {
+        let mut builder = GlyphBuilder::new(name.into());
+        for (wght, contours) in sources {
+            builder
+                .try_add_source(
+                    &NormalizedLocation::for_pos(&[("wght", *wght)]),
+                    GlyphInstance {
+                        width: 100.0,
+                        contours: contours.clone(),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+        builder.build().unwrap()
+    }
+
+    fn metrics(cap_height: f32, x_height: f32) -> GlobalMetricsInstance {
+        let mut metrics =
+            crate::ir::GlobalMetrics::new(NormalizedLocation::new(), 1000, None, None, None, 0.0)
+                .at(&NormalizedLocation::new());
+        metrics.cap_height = cap_height.into();
+        metrics.x_height = x_height.into();
+        metrics
+    }
+
+    #[test]
+    fn sorts_contours_by_default_bounds() {
+        let glyph = glyph(
+            "a",
+            &[
+                (0.0, vec![rect(50.0, 0.0), rect(0.0, 0.0)]),
+                (1.0, vec![rect(60.0, 0.0), rect(-10.0, 0.0)]),
+            ],
+        );
+        let sorted = sort_contours(&glyph).unwrap();
+        assert_eq!(
+            vec![
+                (0.0, vec![rect(0.0, 0.0), rect(50.0, 0.0)]),
+                (1.0, vec![rect(-10.0, 0.0), rect(60.0, 0.0)]),
+            ],
+            [0.0, 1.0]
+                .into_iter()
+                .map(|wght| (
+                    wght,
+                    sorted.sources()[&NormalizedLocation::for_pos(&[("wght", wght)])]
+                        .contours
+                        .clone()
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn sorted_contours_are_left_alone() {
+        let glyph = glyph("a", &[(0.0, vec![rect(0.0, 0.0), rect(50.0, 0.0)])]);
+        assert_eq!(None, sort_contours(&glyph));
+    }
+
+    #[test]
+    fn slant_about_half_x_height() {
+        let transformation = Transformation {
+            slant: 45.0.into(),
+            origin: TransformOrigin::HalfXHeight,
+            ..Default::default()
+        };
+        let matrix = matrix(&transformation, &metrics(700.0, 501.0));
+        // Half x-height rounds to 251, which stays put
+        assert_eq!(Point::new(0.0, 251.0), matrix * Point::new(0.0, 251.0));
+        let moved = matrix * Point::new(0.0, 351.0);
+        assert!((moved.x - 100.0).abs() < 1e-9, "{moved:?}");
+    }
+
+    #[test]
+    fn offset_and_scale_about_baseline() {
+        let transformation = Transformation {
+            offset_x: 10.0.into(),
+            scale_y: 50.0.into(),
+            ..Default::default()
+        };
+        let matrix = matrix(&transformation, &metrics(700.0, 500.0));
+        assert_eq!(Point::new(110.0, 100.0), matrix * Point::new(100.0, 200.0));
+    }
+
+    #[test]
+    fn components_of_transformed_glyphs_are_not_transformed_twice() {
+        let mut builder = GlyphBuilder::new("b".into());
+        builder
+            .try_add_source(
+                &NormalizedLocation::new(),
+                GlyphInstance {
+                    components: vec![
+                        Component {
+                            base: "a".into(),
+                            transform: Affine::IDENTITY,
+                            location: Default::default(),
+                        },
+                        Component {
+                            base: "c".into(),
+                            transform: Affine::IDENTITY,
+                            location: Default::default(),
+                        },
+                    ],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let glyph = builder.build().unwrap();
+        let matrix = Affine::translate((10.0, 0.0));
+        let matrices = HashMap::from([(NormalizedLocation::new(), matrix)]);
+        let transformed =
+            transform_glyph(&glyph, &matrices, &HashSet::from(["a".into(), "b".into()])).unwrap();
+        assert_eq!(
+            vec![Affine::IDENTITY, matrix],
+            transformed
+                .default_instance()
+                .components
+                .iter()
+                .map(|c| c.transform)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn dotted_circle_sits_on_the_baseline() {
+        let glyph =
+            draw_dotted_circle("uni25CC".into(), &NormalizedLocation::new(), 500.0).unwrap();
+        assert_eq!(HashSet::from([DOTTED_CIRCLE]), glyph.codepoints);
+        let bounds = dotted_circle_bounds(glyph.default_instance());
+        assert!(bounds.y0.abs() < 1e-9, "{bounds:?}");
+        assert!((bounds.y1 - 500.0).abs() < 1e-9, "{bounds:?}");
+        assert!(
+            (bounds.center().x - glyph.default_instance().width / 2.0).abs() < 1e-9,
+            "{bounds:?}"
+        );
+    }
+}
@@ -11,14 +11,19 @@ use fontdrasil::{
     types::GlyphName,
 };
 use kurbo::Affine;
-use log::{debug, log_enabled, trace};
+use log::{debug, info, log_enabled, trace};
 use ordered_float::OrderedFloat;
 use write_fonts::types::GlyphId;
 
 use crate::{
+    componentize::componentize,
     error::WorkError,
+    filters::apply_glyph_filter,
+    instancer::{instantiate_contours, transform_contour},
+    interpolatable::repair_contours,
     ir::{Component, Glyph, GlyphBuilder, GlyphOrder},
     orchestration::{Context, Flags, IrWork, WorkId},
+    propagate_anchors::propagate_anchors,
 };
 
 pub fn create_glyph_order_work() -> Box<IrWork> {
@@ -75,6 +80,7 @@ fn split_glyph(glyph_order: &GlyphOrder, original: &Glyph) -> Result<(Glyph, Gly
         inst.components.push(Component {
             base: simple_glyph_name.clone(),
             transform: Affine::IDENTITY,
+            location: NormalizedLocation::new(),
         });
     });
 
@@ -151,6 +157,7 @@ fn components(glyph: &Glyph, transform: Affine) -> VecDeque<(NormalizedLocation,
                 Component {
                     base: component.base.clone(),
                     transform: transform * component.transform,
+                    location: component.location.clone(),
                 },
             )
         })
@@ -162,7 +169,10 @@ fn components(glyph: &Glyph, transform: Affine) -> VecDeque<(NormalizedLocation,
 /// At time of writing we only support this if every instance uses the same set of components.
 ///
 /// <https://github.com/googlefonts/ufo2ft/blob/dd738cdcddf61cce2a744d1cafab5c9b33e92dd4/Lib/ufo2ft/util.py#L165>
-fn convert_components_to_contours(context: &Context, original: &Glyph) -> Result<(), WorkError> {
+pub(crate) fn convert_components_to_contours(
+    context: &Context,
+    original: &Glyph,
+) -> Result<(), WorkError> {
     let mut simple = GlyphBuilder::from(original.clone());
     simple
         .sources
@@ -179,6 +189,28 @@ fn convert_components_to_contours(context: &Context, original: &Glyph) -> Result
         }
 
         let referenced_glyph = context.glyphs.get(&WorkId::Glyph(component.base.clone()));
+
+        // The referenced glyph isn't necessarily defined where we are, draw it as the backend would
+        if component.is_variable() || referenced_glyph.has_variable_components() {
+            let Some(inst) = simple.sources.get_mut(&loc) else {
+                return Err(WorkError::GlyphUndefAtNormalizedLocation {
+                    glyph_name: simple.name.clone(),
+                    pos: loc.clone(),
+                });
+            };
+            let mut ref_loc = loc.clone();
+            for (tag, coord) in component.location.iter() {
+                ref_loc.insert(*tag, *coord);
+            }
+            let glyphs = |name: &GlyphName| context.glyphs.try_get(&WorkId::Glyph(name.clone()));
+            let axes = &context.static_metadata.get().axes;
+            for contour in instantiate_contours(&glyphs, axes, &referenced_glyph, &ref_loc)? {
+                inst.contours
+                    .push(transform_contour(contour, component.transform));
+            }
+            continue;
+        }
+
         frontier.extend(
             components(&referenced_glyph, component.transform)
                 .iter()
@@ -203,15 +235,8 @@ fn convert_components_to_contours(context: &Context, original: &Glyph) -> Result
         };
 
         for contour in ref_inst.contours.iter() {
-            let mut contour = contour.clone();
-            contour.apply_affine(component.transform);
-
-            // See https://github.com/googlefonts/ufo2ft/blob/dd738cdcddf61cce2a744d1cafab5c9b33e92dd4/Lib/ufo2ft/util.py#L205
-            if component.transform.determinant() < 0.0 {
-                inst.contours.push(contour.reverse_subpaths());
-            } else {
-                inst.contours.push(contour);
-            }
+            inst.contours
+                .push(transform_contour(contour.clone(), component.transform));
         }
     }
 
@@ -253,7 +278,7 @@ fn move_contours_to_new_component(
 /// that no mixed contour+component glyphs exist.
 ///
 /// See <https://github.com/googlefonts/ufo2ft/blob/main/Lib/ufo2ft/filters/flattenComponents.py>
-fn flatten_glyph(context: &Context, glyph: &Glyph) -> Result<(), WorkError> {
+pub(crate) fn flatten_glyph(context: &Context, glyph: &Glyph) -> Result<(), WorkError> {
     // Guard: nothing to see here folks
     if glyph.default_instance().components.is_empty() {
         return Ok(());
@@ -283,6 +308,7 @@ fn flatten_glyph(context: &Context, glyph: &Glyph) -> Result<(), WorkError> {
                     frontier.push_front(Component {
                         base: ref_component.base.clone(),
                         transform: component.transform * ref_component.transform,
+                        location: ref_component.location.clone(),
                     });
                 }
             }
@@ -340,6 +366,7 @@ impl Work<Context, WorkId, WorkError> for GlyphOrderWork {
             .variant(WorkId::PreliminaryGlyphOrder)
             .variant(WorkId::GlobalMetrics)
             .variant(WorkId::ALL_GLYPHS)
+            .variant(WorkId::ALL_ANCHORS)
             .build()
     }
 
@@ -347,6 +374,7 @@ impl Work<Context, WorkId, WorkError> for GlyphOrderWork {
         AccessBuilder::new()
             .variant(WorkId::GlyphOrder)
             .variant(WorkId::ALL_GLYPHS)
+            .variant(WorkId::ALL_ANCHORS)
             .build()
     }
 
@@ -355,7 +383,68 @@ impl Work<Context, WorkId, WorkError> for GlyphOrderWork {
         // Some of it may need to be massaged to produce BE glyphs
         // In particular, glyphs with both paths and components need to push the path into a component
         let arc_current = context.preliminary_glyph_order.get();
-        let current_glyph_order = &*arc_current;
+        let static_metadata = context.static_metadata.get();
+        let glyph_filters = &static_metadata.misc.glyph_filters;
+
+        // Pre filters see the glyphs as the source drew them
+        let mut filtered_glyph_order = (*arc_current).clone();
+        for filter in glyph_filters.iter().filter(|f| f.pre) {
+            apply_glyph_filter(context, &mut filtered_glyph_order, filter)?;
+        }
+        let current_glyph_order = &filtered_glyph_order;
+
+        // Rotated start points and reordered contours are common, and easy to fix before
+        // anything else looks at the contours
+        if context.flags.contains(Flags::REPAIR_CONTOURS) {
+            for glyph_name in current_glyph_order.iter() {
+                let mut glyph = (*context.glyphs.get(&WorkId::Glyph(glyph_name.clone()))).clone();
+                let fixes = repair_contours(&mut glyph);
+                if !fixes.is_empty() {
+                    for fix in fixes {
+                        info!("Repaired {fix}");
+                    }
+                    context.glyphs.set(glyph);
+                }
+            }
+        }
+
+        if context.flags.contains(Flags::AUTO_COMPONENTS) {
+            let glyphs: Vec<_> = current_glyph_order
+                .iter()
+                .map(|gn| context.glyphs.get(&WorkId::Glyph(gn.clone())))
+                .collect();
+            let componentized = componentize(
+                &glyphs,
+                context.flags.contains(Flags::AUTO_COMPONENTS_TRANSFORMED),
+            )?;
+            info!("Made {} glyphs out of components", componentized.len());
+            for glyph in componentized {
+                context.glyphs.set(glyph);
+            }
+        }
+
+        // Before anything decomposes the components we propagate from
+        if context.flags.contains(Flags::PROPAGATE_ANCHORS) {
+            let glyphs: Vec<_> = current_glyph_order
+                .iter()
+                .map(|gn| context.glyphs.get(&WorkId::Glyph(gn.clone())))
+                .collect();
+            let anchors: HashMap<_, _> = current_glyph_order
+                .iter()
+                .filter_map(|gn| {
+                    context
+                        .anchors
+                        .try_get(&WorkId::Anchor(gn.clone()))
+                        .map(|anchors| (gn.clone(), anchors))
+                })
+                .collect();
+            let propagated = propagate_anchors(&glyphs, &anchors)?;
+            info!("Propagated anchors to {} glyphs", propagated.len());
+            for glyph_anchors in propagated {
+                context.anchors.set(glyph_anchors);
+            }
+        }
+
         let original_glyphs: HashMap<_, _> = current_glyph_order
             .iter()
             .map(|gn| (gn, context.glyphs.get(&WorkId::Glyph(gn.clone()))))
@@ -375,8 +464,12 @@ impl Work<Context, WorkId, WorkError> for GlyphOrderWork {
         // 1) need to push their paths to a new glyph that is a component
         // 2) collapse such glyphs into a simple (contour-only) glyph
         // fontmake (Python) prefers option 2.
+        // Glyphs with variable components are drawn into contours by the backend, leave them be.
         for glyph_name in new_glyph_order.clone().iter() {
             let glyph = original_glyphs.get(glyph_name).unwrap();
+            if glyph.has_variable_components() {
+                continue;
+            }
             let inconsistent_components = !glyph.has_consistent_components();
             if inconsistent_components || has_components_and_contours(glyph) {
                 if inconsistent_components {
@@ -401,7 +494,9 @@ impl Work<Context, WorkId, WorkError> for GlyphOrderWork {
         if context.flags.contains(Flags::FLATTEN_COMPONENTS) {
             for glyph_name in new_glyph_order.iter() {
                 let glyph = context.glyphs.get(&WorkId::Glyph(glyph_name.clone()));
-                flatten_glyph(context, &glyph)?;
+                if !glyph.has_variable_components() {
+                    flatten_glyph(context, &glyph)?;
+                }
             }
         }
 
@@ -411,7 +506,7 @@ impl Work<Context, WorkId, WorkError> for GlyphOrderWork {
         {
             for glyph_name in new_glyph_order.iter() {
                 let glyph = context.glyphs.get(&WorkId::Glyph(glyph_name.clone()));
-                if glyph.has_nonidentity_2x2() {
+                if glyph.has_nonidentity_2x2() && !glyph.has_variable_components() {
                     convert_components_to_contours(context, &glyph)?;
                 }
             }
@@ -423,6 +518,9 @@ impl Work<Context, WorkId, WorkError> for GlyphOrderWork {
         for glyph_name in new_glyph_order.iter() {
             // We are only int
             let glyph = context.glyphs.get(&WorkId::Glyph(glyph_name.clone()));
+            if glyph.has_variable_components() {
+                continue;
+            }
             for component in glyph.default_instance().components.iter() {
                 if !new_glyph_order.contains(&component.base) {
                     convert_components_to_contours(context, &glyph)?;
@@ -430,6 +528,10 @@ impl Work<Context, WorkId, WorkError> for GlyphOrderWork {
             }
         }
 
+        for filter in glyph_filters.iter().filter(|f| !f.pre) {
+            apply_glyph_filter(context, &mut new_glyph_order, filter)?;
+        }
+
         ensure_notdef_exists_and_is_gid_0(context, &mut new_glyph_order)?;
 
         // We now have the final static metadata
@@ -474,6 +576,7 @@ mod tests {
             components: vec![Component {
                 base: "component".into(),
                 transform: Affine::translate((3.0, 3.0)),
+                location: NormalizedLocation::new(),
             }],
             ..Default::default()
         }
@@ -615,7 +718,11 @@ mod tests {
 
     fn component_glyph(name: &str, base: GlyphName, transform: Affine) -> Glyph {
         let component = GlyphInstance {
-            components: vec![Component { base, transform }],
+            components: vec![Component {
+                base,
+                transform,
+                location: NormalizedLocation::new(),
+            }],
             ..Default::default()
         };
         let mut glyph = GlyphBuilder::new(name.into());
@@ -719,14 +826,17 @@ mod tests {
                         Component {
                             base: test_data.shallow_component.name.clone(),
                             transform: Affine::IDENTITY,
+                            location: NormalizedLocation::new(),
                         },
                         Component {
                             base: test_data.shallow_component.name,
                             transform: Affine::translate((0.0, 2.0)),
+                            location: NormalizedLocation::new(),
                         },
                         Component {
                             base: test_data.deep_component.name,
                             transform: Affine::translate((0.0, 5.0)),
+                            location: NormalizedLocation::new(),
                         },
                     ],
                     contours: vec![contour()],
@@ -778,6 +888,7 @@ mod tests {
                     components: vec![Component {
                         base: reuse_me.name.clone(),
                         transform: the_neg,
+                        location: NormalizedLocation::new(),
                     }],
                     ..Default::default()
                 },
@@ -0,0 +1,1401 @@
+//! Instantiation of variable font IR at a named instance.
+//!
+//! Interpolates glyphs, anchors, kerning, global metrics and variable values in
+//! feature code at the location of the instance to produce the IR of a static
+//! font, much as fontmake does when asked to interpolate static instances.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use fontdrasil::{
+    coords::{DesignCoord, NormalizedCoord, NormalizedLocation, UserCoord},
+    types::{Axis, GlyphName},
+};
+use kurbo::{Affine, BezPath, PathEl, Point};
+use ordered_float::OrderedFloat;
+use write_fonts::{tables::os2::SelectionFlags, types::NameId, OtRound};
+
+use crate::{
+    error::WorkError,
+    ir::{
+        align_kerning, default_value, normalize_for_postscript, Anchor, Component, FeaturesSource,
+        GlobalMetrics, Glyph, GlyphAnchors, GlyphInstance, KernPair, KerningGroups,
+        KerningInstance, NameKey, NamedInstance, StaticMetadata,
+    },
+    orchestration::{Context, WorkId},
+    variations::VariationModel,
+};
+
+/// Write the IR of a static font for `instance` to `target`.
+///
+/// `source` must hold the IR of the variable font. Features and kerning are
+/// carried over only if `source` has them, e.g. they are absent if feature
+/// compilation is disabled.
+pub fn instantiate(
+    source: &Context,
+    instance: &NamedInstance,
+    target: &Context,
+) -> Result<(), WorkError> {
+    let static_metadata = source.static_metadata.get();
+    let axes_by_tag: HashMap<_, _> = static_metadata
+        .all_source_axes
+        .iter()
+        .map(|axis| (axis.tag, axis))
+        .collect();
+    let mut user_location = instance.location.clone();
+    user_location.retain(|tag, _| axes_by_tag.contains_key(tag));
+    let location = user_location.to_normalized(&axes_by_tag);
+    let axes = &static_metadata.axes;
+
+    target
+        .static_metadata
+        .set(instance_static_metadata(&static_metadata, instance)?);
+    target.global_metrics.set(instantiate_metrics(
+        &source.global_metrics.get(),
+        axes,
+        &location,
+    )?);
+
+    let glyph_order = source.glyph_order.get();
+    for glyph_name in glyph_order.iter() {
+        let glyph = source.glyphs.get(&WorkId::Glyph(glyph_name.clone()));
+        target
+            .glyphs
+            .set(instantiate_glyph(&glyph, axes, &location)?);
+        if let Some(anchors) = source.anchors.try_load(&WorkId::Anchor(glyph_name.clone())) {
+            target
+                .anchors
+                .set(instantiate_anchors(&anchors, axes, &location)?);
+        }
+    }
+    target.preliminary_glyph_order.set((*glyph_order).clone());
+    target.glyph_order.set((*glyph_order).clone());
+
+    if let Some(features) = source.features.try_load() {
+        target
+            .features
+            .set(instantiate_features(&features, axes, &location)?);
+    }
+
+    if let Some(groups) = source.kerning_groups.try_load() {
+        let mut kern_by_pos: HashMap<_, _> = groups
+            .locations
+            .iter()
+            .filter_map(|pos| {
+                source
+                    .kerning_at
+                    .try_load(&WorkId::KernInstance(pos.clone()))
+            })
+            .map(|ki| (ki.location.clone(), (*ki).clone()))
+            .collect();
+        // Interpolation needs a value at the default, which is no kerning at all if unspecified
+        if !kern_by_pos.keys().any(|pos| pos.is_default()) {
+            let default_location = static_metadata.default_location().clone();
+            kern_by_pos.insert(
+                default_location.clone(),
+                KerningInstance {
+                    location: default_location,
+                    ..Default::default()
+                },
+            );
+        }
+        target
+            .kerning_at
+            .set(instantiate_kerning(&groups, kern_by_pos, axes, &location)?);
+        target.kerning_groups.set(KerningGroups {
+            locations: BTreeSet::from([NormalizedLocation::new()]),
+            sparse_locations: BTreeSet::new(),
+            ..(*groups).clone()
+        });
+    }
+
+    Ok(())
+}
+
+/// Interpolate `values`, defined at master locations, at `location`.
+///
+/// Every master must have the same number of values and one must be at the default.
+fn interpolate(
+    axes: &[Axis],
+    values: &HashMap<NormalizedLocation, Vec<f64>>,
+    location: &NormalizedLocation,
+) -> Result<Vec<f64>, String> {
+    let model = VariationModel::new(values.keys().cloned().collect(), axes.to_vec())
+        .map_err(|e| e.to_string())?;
+    let mut result = vec![0.0; values.values().next().map(Vec::len).unwrap_or_default()];
+    for (region, deltas) in model
+        .deltas::<f64, f64>(values)
+        .map_err(|e| e.to_string())?
+    {
+        let scalar = region.scalar_at(location).into_inner() as f64;
+        if scalar == 0.0 {
+            continue;
+        }
+        for (value, delta) in result.iter_mut().zip(deltas) {
+            *value += scalar * delta;
+        }
+    }
+    Ok(result)
+}
+
+/// The static metadata of the instance, with no axes and names for `instance`
+fn instance_static_metadata(
+    static_metadata: &StaticMetadata,
+    instance: &NamedInstance,
+) -> Result<StaticMetadata, WorkError> {
+    let style = StyleMap::new(instance);
+    let mut instance_metadata = StaticMetadata::new(
+        static_metadata.units_per_em,
+        instance_names(static_metadata, instance, &style),
+        Vec::new(),
+        Vec::new(),
+        HashSet::from([NormalizedLocation::new()]),
+        static_metadata.postscript_names.clone(),
+        static_metadata.italic_angle.into_inner(),
+    )?;
+    instance_metadata.misc = static_metadata.misc.clone();
+
+    let style_flags = SelectionFlags::REGULAR | SelectionFlags::BOLD | SelectionFlags::ITALIC;
+    let mut selection_flags = SelectionFlags::from_bits_truncate(
+        static_metadata.misc.selection_flags.bits() & !style_flags.bits(),
+    );
+    if style.bold {
+        selection_flags = selection_flags | SelectionFlags::BOLD;
+    }
+    if style.italic {
+        selection_flags = selection_flags | SelectionFlags::ITALIC;
+    }
+    if !style.bold && !style.italic {
+        selection_flags = selection_flags | SelectionFlags::REGULAR;
+    }
+    instance_metadata.misc.selection_flags = selection_flags;
+
+    Ok(instance_metadata)
+}
+
+/// How a style fits into the legacy, four member (RIBBI), style linking model
+///
+/// See <https://unifiedfontobject.org/versions/ufo3/fontinfo.plist/#generic-naming-attributes>
+struct StyleMap {
+    bold: bool,
+    italic: bool,
+}
+
+impl StyleMap {
+    /// The style map the instance names, else the one implied by its style name
+    fn new(instance: &NamedInstance) -> StyleMap {
+        if let Some(style_map_style_name) = &instance.style_map_style_name {
+            let words: Vec<_> = style_map_style_name.split_whitespace().collect();
+            return StyleMap {
+                bold: words.iter().any(|word| word.eq_ignore_ascii_case("bold")),
+                italic: words.iter().any(|word| word.eq_ignore_ascii_case("italic")),
+            };
+        }
+        let style_name = instance.name.to_lowercase();
+        StyleMap {
+            bold: matches!(style_name.as_str(), "bold" | "bold italic"),
+            italic: style_name.split_whitespace().any(|word| word == "italic"),
+        }
+    }
+
+    /// The legacy subfamily name, one of Regular, Italic, Bold or Bold Italic
+    fn style_name(&self) -> &'static str {
+        match (self.bold, self.italic) {
+            (false, false) => "Regular",
+            (false, true) => "Italic",
+            (true, false) => "Bold",
+            (true, true) => "Bold Italic",
+        }
+    }
+
+    /// What is left of `style_name` once the words of the style map are removed
+    ///
+    /// Removes the last occurrence of each, as glyphsLib does, see
+    /// <https://github.com/googlefonts/glyphsLib/blob/main/Lib/glyphsLib/builder/names.py>.
+    fn linked_style(&self, style_name: &str) -> String {
+        let mut regular = !self.bold && !self.italic;
+        let mut bold = self.bold;
+        let mut italic = self.italic;
+        let mut linked_style = Vec::new();
+        for word in style_name.split_whitespace().rev() {
+            if regular && word.eq_ignore_ascii_case("regular") {
+                regular = false;
+            } else if bold && word.eq_ignore_ascii_case("bold") {
+                bold = false;
+            } else if italic && word.eq_ignore_ascii_case("italic") {
+                italic = false;
+            } else {
+                linked_style.push(word);
+            }
+        }
+        linked_style.reverse();
+        linked_style.join(" ")
+    }
+}
+
+/// The names of the variable font, with those that identify the font replaced to suit the instance
+///
+/// Names the instance gives are used as is, the rest follow the ufo2ft fallbacks, see
+/// <https://github.com/googlefonts/ufo2ft/blob/fca66fe3ea1ea88ffb36f8264b21ce042d3afd05/Lib/ufo2ft/fontInfoData.py>.
+fn instance_names(
+    static_metadata: &StaticMetadata,
+    instance: &NamedInstance,
+    style: &StyleMap,
+) -> HashMap<NameKey, String> {
+    let get = |name_id: NameId| {
+        static_metadata
+            .names
+            .iter()
+            .find(|(key, _)| key.name_id == name_id)
+            .map(|(_, value)| value.as_str())
+    };
+    let style_name = instance.name.as_str();
+    let family = instance
+        .family_name
+        .as_deref()
+        .or_else(|| get(NameId::TYPOGRAPHIC_FAMILY_NAME))
+        .or_else(|| get(NameId::FAMILY_NAME))
+        .or_else(|| default_value(NameId::FAMILY_NAME))
+        .unwrap_or_default();
+    let version = get(NameId::VERSION_STRING)
+        .map(|version| version.replace("Version ", ""))
+        .unwrap_or_else(|| "0.000".to_string());
+
+    let replaced = [
+        NameId::FAMILY_NAME,
+        NameId::SUBFAMILY_NAME,
+        NameId::UNIQUE_ID,
+        NameId::FULL_NAME,
+        NameId::POSTSCRIPT_NAME,
+        NameId::TYPOGRAPHIC_FAMILY_NAME,
+        NameId::TYPOGRAPHIC_SUBFAMILY_NAME,
+    ];
+    // Names from 256 up were claimed for axes and named instances, which a static font has none of
+    let mut names: HashMap<_, _> = static_metadata
+        .names
+        .iter()
+        .filter(|(key, _)| key.name_id.to_u16() < 256 && !replaced.contains(&key.name_id))
+        .map(|(key, value)| (*key, value.clone()))
+        .collect();
+    let mut add = |name_id: NameId, value: String| {
+        names.insert(NameKey::new(name_id, &value), value);
+    };
+
+    // The style map style is the legacy subfamily, the rest of the style goes in the legacy family
+    let legacy_family = instance.style_map_family_name.clone().unwrap_or_else(|| {
+        match style.linked_style(style_name).as_str() {
+            "" => family.to_string(),
+            linked_style => format!("{family} {linked_style}"),
+        }
+    });
+    let legacy_style = style.style_name();
+    // Typographic names are only needed where they differ from the legacy ones
+    if legacy_family != family {
+        add(NameId::TYPOGRAPHIC_FAMILY_NAME, family.to_string());
+    }
+    if !legacy_style.eq_ignore_ascii_case(style_name) {
+        add(NameId::TYPOGRAPHIC_SUBFAMILY_NAME, style_name.to_string());
+    }
+    add(NameId::FAMILY_NAME, legacy_family);
+    add(NameId::SUBFAMILY_NAME, legacy_style.to_string());
+    add(NameId::FULL_NAME, format!("{family} {style_name}"));
+
+    let postscript_name = instance.postscript_name.clone().unwrap_or_else(|| {
+        let mut postscript_name = format!("{family}-{style_name}");
+        normalize_for_postscript(&mut postscript_name, false);
+        postscript_name
+    });
+    add(
+        NameId::UNIQUE_ID,
+        format!(
+            "{version};{};{postscript_name}",
+            static_metadata.misc.vendor_id
+        ),
+    );
+    add(NameId::POSTSCRIPT_NAME, postscript_name);
+
+    names
+}
+
+fn instantiate_metrics(
+    metrics: &GlobalMetrics,
+    axes: &[Axis],
+    location: &NormalizedLocation,
+) -> Result<GlobalMetrics, WorkError> {
+    let mut result = GlobalMetrics(HashMap::new());
+    for (metric, values) in metrics.iter() {
+        let values = values
+            .iter()
+            .map(|(pos, value)| (pos.clone(), vec![value.into_inner() as f64]))
+            .collect();
+        let value = interpolate(axes, &values, location).map_err(|reason| {
+            WorkError::InterpolationError {
+                what: format!("{metric:?}"),
+                reason,
+            }
+        })?;
+        result.set(*metric, NormalizedLocation::new(), value[0] as f32);
+    }
+    Ok(result)
+}
+
+/// Includes nested deeper than this are assumed to be a cycle
+const MAX_INCLUDE_DEPTH: usize = 50;
+
+/// The feature code of the instance, with every variable value resolved at `location`.
+///
+/// A static font has no axes for a variable value such as `(wght=400d:10 wght=700d:30)`
+/// to vary on. Included files are inlined so their values are resolved too.
+fn instantiate_features(
+    features: &FeaturesSource,
+    axes: &[Axis],
+    location: &NormalizedLocation,
+) -> Result<FeaturesSource, WorkError> {
+    let (fea_content, include_dir) = match features {
+        FeaturesSource::Empty => return Ok(FeaturesSource::Empty),
+        FeaturesSource::File {
+            fea_file,
+            include_dir,
+        } => (
+            fs::read_to_string(fea_file)?,
+            include_dir
+                .clone()
+                .or_else(|| fea_file.parent().map(PathBuf::from)),
+        ),
+        FeaturesSource::Memory {
+            fea_content,
+            include_dir,
+        } => (fea_content.clone(), include_dir.clone()),
+    };
+    let fea_content = resolve_variable_values(
+        &fea_content,
+        include_dir.as_deref().unwrap_or(Path::new("")),
+        axes,
+        location,
+        0,
+    )?;
+    Ok(FeaturesSource::Memory {
+        fea_content,
+        include_dir,
+    })
+}
+
+/// Feature code with each variable value replaced by its value at `location`, and each
+/// include replaced by the file it includes, likewise resolved.
+///
+/// Comments and strings are left as they are.
+fn resolve_variable_values(
+    fea: &str,
+    include_dir: &Path,
+    axes: &[Axis],
+    location: &NormalizedLocation,
+    depth: usize,
+) -> Result<String, WorkError> {
+    let mut resolved = String::with_capacity(fea.len());
+    let mut rest = fea;
+    while let Some(i) = rest.find(['#', '"', '(', 'i']) {
+        let (before, from) = rest.split_at(i);
+        resolved.push_str(before);
+        let end = match from.as_bytes()[0] {
+            b'#' => from.find('\n').unwrap_or(from.len()),
+            b'"' => from[1..].find('"').map(|end| end + 2).unwrap_or(from.len()),
+            b'(' => match from.find(')') {
+                Some(end) => match variable_value(&from[1..end], axes, location)? {
+                    Some(value) => {
+                        resolved.push_str(&value);
+                        rest = &from[end + 1..];
+                        continue;
+                    }
+                    None => 1,
+                },
+                None => from.len(),
+            },
+            _ => match include(&resolved, from) {
+                Some((path, end)) => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err(WorkError::InterpolationError {
+                            what: format!("include({path})"),
+                            reason: "includes nest too deeply, is there a cycle?".to_string(),
+                        });
+                    }
+                    let included = fs::read_to_string(include_dir.join(path))?;
+                    resolved.push_str(&resolve_variable_values(
+                        &included,
+                        include_dir,
+                        axes,
+                        location,
+                        depth + 1,
+                    )?);
+                    rest = &from[end..];
+                    continue;
+                }
+                None => 1,
+            },
+        };
+        resolved.push_str(&from[..end]);
+        rest = &from[end..];
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
+
+/// The path of the include statement `from` starts with, and the length of the statement
+///
+/// `preceding` is the code before it, so `include` inside a longer name isn't taken for one.
+fn include<'a>(preceding: &str, from: &'a str) -> Option<(&'a str, usize)> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-');
+    if preceding.ends_with(is_word) {
+        return None;
+    }
+    let args = from.strip_prefix("include")?.trim_start();
+    let args = args.strip_prefix('(')?;
+    let close = args.find(')')?;
+    let path = args[..close].trim();
+    let after = args[close + 1..].trim_start();
+    let after = after.strip_prefix(';').unwrap_or(after);
+    Some((path, from.len() - after.len()))
+}
+
+/// The value at `location` of a variable value, given as the text between its parentheses.
+///
+/// Returns None if the text isn't a variable value. Positions on an axis are user
+/// coordinates unless suffixed with d for design or n for normalized, as in fontTools.
+fn variable_value(
+    text: &str,
+    axes: &[Axis],
+    location: &NormalizedLocation,
+) -> Result<Option<String>, WorkError> {
+    let mut values = HashMap::new();
+    for entry in text.split_whitespace() {
+        let Some((position, value)) = entry.rsplit_once(':') else {
+            return Ok(None);
+        };
+        let Ok(value) = value.parse::<f64>() else {
+            return Ok(None);
+        };
+        let mut pos: NormalizedLocation = axes
+            .iter()
+            .map(|axis| (axis.tag, NormalizedCoord::new(0.0)))
+            .collect::<Vec<_>>()
+            .into();
+        for coord in position.split(',') {
+            let Some((tag, coord)) = coord.split_once('=') else {
+                return Ok(None);
+            };
+            let (coord, space) = match coord.char_indices().last() {
+                Some((i, space @ ('d' | 'n' | 'u'))) => (&coord[..i], space),
+                _ => (coord, 'u'),
+            };
+            let Ok(coord) = coord.parse::<f32>() else {
+                return Ok(None);
+            };
+            let Some(axis) = axes.iter().find(|axis| axis.tag.to_string() == tag) else {
+                return Err(WorkError::InterpolationError {
+                    what: format!("variable value ({text}) in feature code"),
+                    reason: format!("'{tag}' is not an axis of the font"),
+                });
+            };
+            let coord = match space {
+                'd' => DesignCoord::new(coord).to_normalized(&axis.converter),
+                'n' => NormalizedCoord::new(coord),
+                _ => UserCoord::new(coord).to_normalized(&axis.converter),
+            };
+            pos.insert(axis.tag, coord);
+        }
+        values.insert(pos, vec![value]);
+    }
+    if values.is_empty() {
+        return Ok(None);
+    }
+    let value =
+        interpolate(axes, &values, location).map_err(|reason| WorkError::InterpolationError {
+            what: format!("variable value ({text}) in feature code"),
+            reason,
+        })?;
+    Ok(Some(OtRound::<i32>::ot_round(value[0]).to_string()))
+}
+
+/// Apply `f` to every point of a path element, in order
+fn map_points(el: PathEl, mut f: impl FnMut(Point) -> Point) -> PathEl {
+    match el {
+        PathEl::MoveTo(p) => PathEl::MoveTo(f(p)),
+        PathEl::LineTo(p) => PathEl::LineTo(f(p)),
+        PathEl::QuadTo(p0, p1) => PathEl::QuadTo(f(p0), f(p1)),
+        PathEl::CurveTo(p0, p1, p2) => PathEl::CurveTo(f(p0), f(p1), f(p2)),
+        PathEl::ClosePath => PathEl::ClosePath,
+    }
+}
+
+/// The values of a glyph instance that vary, in a consistent order
+///
+/// Fails if `instance` isn't compatible with the default instance.
+fn glyph_values(
+    default: &GlyphInstance,
+    instance: &GlyphInstance,
+    has_height: bool,
+) -> Result<Vec<f64>, String> {
+    let mut values = vec![instance.width];
+    if has_height {
+        values.push(instance.height.unwrap_or_default());
+    }
+
+    if instance.contours.len() != default.contours.len() {
+        return Err(format!(
+            "{} contours, the default has {}",
+            instance.contours.len(),
+            default.contours.len()
+        ));
+    }
+    for (i, (contour, default_contour)) in instance
+        .contours
+        .iter()
+        .zip(default.contours.iter())
+        .enumerate()
+    {
+        let elements = contour.elements();
+        let default_elements = default_contour.elements();
+        if elements.len() != default_elements.len()
+            || elements
+                .iter()
+                .zip(default_elements)
+                .any(|(el, default_el)| {
+                    std::mem::discriminant(el) != std::mem::discriminant(default_el)
+                })
+        {
+            return Err(format!("contour {i} is incompatible with the default"));
+        }
+        for el in elements {
+            map_points(*el, |p| {
+                values.extend([p.x, p.y]);
+                p
+            });
+        }
+    }
+
+    if instance.components.len() != default.components.len()
+        || instance
+            .components
+            .iter()
+            .zip(default.components.iter())
+            .any(|(component, default_component)| component.base != default_component.base)
+    {
+        return Err("components are incompatible with the default".to_string());
+    }
+    for (component, default_component) in instance.components.iter().zip(&default.components) {
+        values.extend(component.transform.as_coeffs());
+        // The default decides which axes a component location has, see glyph_instance
+        values.extend(
+            default_component
+                .location
+                .axis_tags()
+                .map(|tag| component.location.get(*tag).unwrap_or_default().to_f32() as f64),
+        );
+    }
+
+    Ok(values)
+}
+
+/// The inverse of [glyph_values]
+fn glyph_instance(default: &GlyphInstance, values: Vec<f64>, has_height: bool) -> GlyphInstance {
+    let mut values = values.into_iter();
+    let mut next = move || values.next().expect("Too few values for glyph");
+
+    let width = next();
+    let height = if has_height {
+        Some(next())
+    } else {
+        default.height
+    };
+    let contours = default
+        .contours
+        .iter()
+        .map(|contour| {
+            contour
+                .elements()
+                .iter()
+                .map(|el| map_points(*el, |_| Point::new(next(), next())))
+                .collect::<BezPath>()
+        })
+        .collect();
+    let components = default
+        .components
+        .iter()
+        .map(|component| Component {
+            base: component.base.clone(),
+            transform: Affine::new(std::array::from_fn(|_| next())),
+            location: component
+                .location
+                .axis_tags()
+                .map(|tag| (*tag, NormalizedCoord::new(next() as f32)))
+                .collect(),
+        })
+        .collect();
+
+    GlyphInstance {
+        width,
+        height,
+        contours,
+        components,
+    }
+}
+
+/// Interpolate `glyph` at `location`
+fn glyph_instance_at(
+    glyph: &Glyph,
+    axes: &[Axis],
+    location: &NormalizedLocation,
+) -> Result<GlyphInstance, WorkError> {
+    let interpolation_error = |reason| WorkError::InterpolationError {
+        what: format!("glyph '{}'", glyph.name),
+        reason,
+    };
+    let default = glyph.default_instance();
+    let has_height = glyph.sources().values().all(|inst| inst.height.is_some());
+
+    let values = glyph
+        .sources()
+        .iter()
+        .map(|(pos, inst)| Ok((pos.clone(), glyph_values(default, inst, has_height)?)))
+        .collect::<Result<HashMap<_, _>, _>>()
+        .map_err(interpolation_error)?;
+    let values = interpolate(axes, &values, location).map_err(interpolation_error)?;
+    Ok(glyph_instance(default, values, has_height))
+}
+
+fn instantiate_glyph(
+    glyph: &Glyph,
+    axes: &[Axis],
+    location: &NormalizedLocation,
+) -> Result<Glyph, WorkError> {
+    Glyph::new(
+        glyph.name.clone(),
+        glyph.emit_to_binary,
+        glyph.codepoints.clone(),
+        HashMap::from([(
+            NormalizedLocation::new(),
+            glyph_instance_at(glyph, axes, location)?,
+        )]),
+    )
+}
+
+/// The contour moved by `transform`, reversed if that flips it so the direction is kept
+pub fn transform_contour(mut contour: BezPath, transform: Affine) -> BezPath {
+    contour.apply_affine(transform);
+
+    // See https://github.com/googlefonts/ufo2ft/blob/dd738cdcddf61cce2a744d1cafab5c9b33e92dd4/Lib/ufo2ft/util.py#L205
+    if transform.determinant() < 0.0 {
+        contour.reverse_subpaths()
+    } else {
+        contour
+    }
+}
+
+/// Components nested deeper than this are assumed to be a cycle
+const MAX_COMPONENT_DEPTH: usize = 64;
+
+/// The contours of `glyph` at `location`, with every component drawn in.
+///
+/// A variable component draws its base glyph at its own location, taking any
+/// axis it doesn't specify from where it is used; other components draw their
+/// base where they are used. This is how glyphs with variable components get
+/// outlines for glyf, which can't express them.
+pub fn instantiate_contours(
+    glyphs: &impl Fn(&GlyphName) -> Option<Arc<Glyph>>,
+    axes: &[Axis],
+    glyph: &Glyph,
+    location: &NormalizedLocation,
+) -> Result<Vec<BezPath>, WorkError> {
+    let mut contours = Vec::new();
+    add_contours(
+        glyphs,
+        axes,
+        glyph,
+        location,
+        Affine::IDENTITY,
+        0,
+        &mut contours,
+    )?;
+    Ok(contours)
+}
+
+fn add_contours(
+    glyphs: &impl Fn(&GlyphName) -> Option<Arc<Glyph>>,
+    axes: &[Axis],
+    glyph: &Glyph,
+    location: &NormalizedLocation,
+    transform: Affine,
+    depth: usize,
+    contours: &mut Vec<BezPath>,
+) -> Result<(), WorkError> {
+    if depth > MAX_COMPONENT_DEPTH {
+        return Err(WorkError::InterpolationError {
+            what: format!("glyph '{}'", glyph.name),
+            reason: format!("components nest more than {MAX_COMPONENT_DEPTH} deep"),
+        });
+    }
+    let instance = match glyph.sources().get(location) {
+        Some(instance) => instance.clone(),
+        None => glyph_instance_at(glyph, axes, location)?,
+    };
+
+    contours.extend(
+        instance
+            .contours
+            .iter()
+            .map(|contour| transform_contour(contour.clone(), transform)),
+    );
+
+    for component in instance.components.iter() {
+        let base = glyphs(&component.base).ok_or_else(|| WorkError::InterpolationError {
+            what: format!("glyph '{}'", glyph.name),
+            reason: format!("component '{}' doesn't exist", component.base),
+        })?;
+        let mut base_location = location.clone();
+        for (tag, coord) in component.location.iter() {
+            base_location.insert(*tag, *coord);
+        }
+        add_contours(
+            glyphs,
+            axes,
+            &base,
+            &base_location,
+            transform * component.transform,
+            depth + 1,
+            contours,
+        )?;
+    }
+    Ok(())
+}
+
+fn instantiate_anchors(
+    anchors: &GlyphAnchors,
+    axes: &[Axis],
+    location: &NormalizedLocation,
+) -> Result<GlyphAnchors, WorkError> {
+    let instance_anchors = anchors
+        .anchors
+        .iter()
+        .map(|anchor| {
+            let values = anchor
+                .positions
+                .iter()
+                .map(|(pos, p)| (pos.clone(), vec![p.x, p.y]))
+                .collect();
+            let values = interpolate(axes, &values, location).map_err(|reason| {
+                WorkError::InterpolationError {
+                    what: format!("anchor {:?} of '{}'", anchor.kind, anchors.glyph_name),
+                    reason,
+                }
+            })?;
+            Ok(Anchor {
+                kind: anchor.kind.clone(),
+                positions: HashMap::from([(
+                    NormalizedLocation::new(),
+                    Point::new(values[0], values[1]),
+                )]),
+            })
+        })
+        .collect::<Result<_, WorkError>>()?;
+    GlyphAnchors::new(anchors.glyph_name.clone(), instance_anchors)
+}
+
+fn instantiate_kerning(
+    groups: &KerningGroups,
+    mut kern_by_pos: HashMap<NormalizedLocation, KerningInstance>,
+    axes: &[Axis],
+    location: &NormalizedLocation,
+) -> Result<KerningInstance, WorkError> {
+    align_kerning(
+        &groups.groups,
+        &groups.sparse_locations,
+        &mut kern_by_pos,
+        |ki| &mut ki.kerns,
+    );
+    align_kerning(
+        &groups.vertical_groups,
+        &groups.sparse_locations,
+        &mut kern_by_pos,
+        |ki| &mut ki.vertical_kerns,
+    );
+    Ok(KerningInstance {
+        location: NormalizedLocation::new(),
+        kerns: interpolate_kerns(&kern_by_pos, |ki| &ki.kerns, axes, location)?,
+        vertical_kerns: interpolate_kerns(&kern_by_pos, |ki| &ki.vertical_kerns, axes, location)?,
+    })
+}
+
+/// Interpolate aligned kerning
+///
+/// Every location has the same pairs, except sparse locations which may lack some,
+/// so we interpolate pairs in batches that are defined at the same locations.
+fn interpolate_kerns(
+    kern_by_pos: &HashMap<NormalizedLocation, KerningInstance>,
+    kerns_of: impl Fn(&KerningInstance) -> &BTreeMap<KernPair, OrderedFloat<f32>>,
+    axes: &[Axis],
+    location: &NormalizedLocation,
+) -> Result<BTreeMap<KernPair, OrderedFloat<f32>>, WorkError> {
+    let mut values_by_pair: BTreeMap<&KernPair, BTreeMap<&NormalizedLocation, f64>> =
+        BTreeMap::new();
+    for (pos, ki) in kern_by_pos.iter() {
+        for (pair, value) in kerns_of(ki) {
+            values_by_pair
+                .entry(pair)
+                .or_default()
+                .insert(pos, value.into_inner() as f64);
+        }
+    }
+
+    let mut batches: HashMap<Vec<&NormalizedLocation>, Vec<(&KernPair, Vec<f64>)>> = HashMap::new();
+    for (pair, values) in values_by_pair {
+        batches
+            .entry(values.keys().copied().collect())
+            .or_default()
+            .push((pair, values.into_values().collect()));
+    }
+
+    let mut kerns = BTreeMap::new();
+    for (locations, pairs) in batches {
+        let values = locations
+            .iter()
+            .enumerate()
+            .map(|(i, pos)| {
+                (
+                    (*pos).clone(),
+                    pairs.iter().map(|(_, values)| values[i]).collect(),
+                )
+            })
+            .collect();
+        let values = interpolate(axes, &values, location).map_err(|reason| {
+            WorkError::InterpolationError {
+                what: "kerning".to_string(),
+                reason,
+            }
+        })?;
+        kerns.extend(
+            pairs
+                .into_iter()
+                .zip(values)
+                .map(|((pair, _), value)| (pair.clone(), OrderedFloat(value as f32))),
+        );
+    }
+    Ok(kerns)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use fontdrasil::{
+        coords::{CoordConverter, UserCoord, UserLocation},
+        orchestration::Access,
+    };
+    use write_fonts::types::Tag;
+
+    use crate::{orchestration::Flags, paths::Paths, source::Input};
+
+    use super::*;
+
+    fn test_context() -> Context {
+        let mut flags = Flags::default();
+        flags.set(Flags::EMIT_IR, false); // we don't want to write anything down
+        Context::new_root(flags, Paths::new(Path::new("/fake/path")), Input::new())
+            .copy_for_work(Access::All, Access::All)
+    }
+
+    fn wght_axis() -> Axis {
+        let min = UserCoord::new(100.0);
+        let default = UserCoord::new(400.0);
+        let max = UserCoord::new(900.0);
+        Axis {
+            name: "Weight".to_string(),
+            tag: Tag::new(b"wght"),
+            min,
+            default,
+            max,
+            hidden: false,
+            converter: CoordConverter::unmapped(min, default, max),
+        }
+    }
+
+    fn wght(pos: f32) -> NormalizedLocation {
+        NormalizedLocation::for_pos(&[("wght", pos)])
+    }
+
+    fn square(size: f64) -> GlyphInstance {
+        let mut path = BezPath::new();
+        path.move_to((0.0, 0.0));
+        path.line_to((size, 0.0));
+        path.line_to((size, size));
+        path.close_path();
+        GlyphInstance {
+            width: size,
+            contours: vec![path],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn interpolates_between_masters() {
+        let values = HashMap::from([
+            (wght(0.0), vec![10.0, 100.0]),
+            (wght(1.0), vec![20.0, 300.0]),
+        ]);
+        assert_eq!(
+            vec![15.0, 200.0],
+            interpolate(&[wght_axis()], &values, &wght(0.5)).unwrap()
+        );
+    }
+
+    #[test]
+    fn instantiates_glyph() {
+        let glyph = Glyph::new(
+            "square".into(),
+            true,
+            HashSet::from([0x25A0]),
+            HashMap::from([(wght(0.0), square(100.0)), (wght(1.0), square(200.0))]),
+        )
+        .unwrap();
+        let instance = instantiate_glyph(&glyph, &[wght_axis()], &wght(0.5)).unwrap();
+
+        assert_eq!(HashSet::from([0x25A0]), instance.codepoints);
+        assert_eq!(
+            vec![&NormalizedLocation::new()],
+            instance.sources().keys().collect::<Vec<_>>()
+        );
+        assert_eq!(&square(150.0), instance.default_instance());
+    }
+
+    #[test]
+    fn incompatible_glyph_is_an_error() {
+        let mut bold = square(200.0);
+        bold.contours.clear();
+        let glyph = Glyph::new(
+            "square".into(),
+            true,
+            HashSet::new(),
+            HashMap::from([(wght(0.0), square(100.0)), (wght(1.0), bold)]),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            instantiate_glyph(&glyph, &[wght_axis()], &wght(0.5)),
+            Err(WorkError::InterpolationError { .. })
+        ));
+    }
+
+    fn variable_component_glyph(locations: &[(f32, f32)]) -> Glyph {
+        Glyph::new(
+            "boxed".into(),
+            true,
+            HashSet::new(),
+            locations
+                .iter()
+                .map(|(pos, component_pos)| {
+                    (
+                        wght(*pos),
+                        GlyphInstance {
+                            width: 300.0,
+                            components: vec![Component {
+                                base: "square".into(),
+                                transform: Affine::translate((10.0, 0.0)),
+                                location: wght(*component_pos),
+                            }],
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn instantiates_component_location() {
+        let glyph = variable_component_glyph(&[(0.0, 0.5), (1.0, 1.0)]);
+        let instance = instantiate_glyph(&glyph, &[wght_axis()], &wght(0.5)).unwrap();
+        assert_eq!(
+            vec![wght(0.75)],
+            instance
+                .default_instance()
+                .components
+                .iter()
+                .map(|c| c.location.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn instantiates_variable_component_contours() {
+        let square_glyph = Arc::new(
+            Glyph::new(
+                "square".into(),
+                true,
+                HashSet::new(),
+                HashMap::from([(wght(0.0), square(100.0)), (wght(1.0), square(200.0))]),
+            )
+            .unwrap(),
+        );
+        let glyphs = |name: &GlyphName| (name.as_str() == "square").then(|| square_glyph.clone());
+        let glyph = variable_component_glyph(&[(0.0, 0.5)]);
+
+        let contours = instantiate_contours(&glyphs, &[wght_axis()], &glyph, &wght(0.0)).unwrap();
+
+        // The square is drawn at its own location, not that of the glyph that uses it
+        let mut expected = square(150.0).contours.remove(0);
+        expected.apply_affine(Affine::translate((10.0, 0.0)));
+        assert_eq!(
+            vec![expected.to_svg()],
+            contours.iter().map(|c| c.to_svg()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn missing_component_is_an_error() {
+        let glyph = variable_component_glyph(&[(0.0, 0.5)]);
+        assert!(matches!(
+            instantiate_contours(&|_| None, &[wght_axis()], &glyph, &wght(0.0)),
+            Err(WorkError::InterpolationError { .. })
+        ));
+    }
+
+    fn named_instance(style_name: &str) -> NamedInstance {
+        NamedInstance {
+            name: style_name.to_string(),
+            location: UserLocation::for_pos(&[("wght", 700.0)]),
+            family_name: None,
+            postscript_name: None,
+            style_map_family_name: None,
+            style_map_style_name: None,
+        }
+    }
+
+    fn variable_metadata(instance: NamedInstance) -> StaticMetadata {
+        let mut names = HashMap::new();
+        for (name_id, value) in [
+            (NameId::FAMILY_NAME, "Duck Sans"),
+            (NameId::SUBFAMILY_NAME, "Regular"),
+            (NameId::VERSION_STRING, "Version 1.002"),
+        ] {
+            names.insert(NameKey::new(name_id, value), value.to_string());
+        }
+        StaticMetadata::new(
+            1000,
+            names,
+            vec![wght_axis()],
+            vec![instance],
+            HashSet::from([wght(0.0), wght(1.0)]),
+            Default::default(),
+            0.0,
+        )
+        .unwrap()
+    }
+
+    fn instance_metadata(instance: NamedInstance) -> StaticMetadata {
+        instance_static_metadata(&variable_metadata(instance.clone()), &instance).unwrap()
+    }
+
+    fn name(static_metadata: &StaticMetadata, name_id: NameId) -> Option<&str> {
+        static_metadata
+            .names
+            .iter()
+            .find(|(key, _)| key.name_id == name_id)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn ribbi_instance_names() {
+        let static_metadata = instance_metadata(named_instance("Bold"));
+
+        assert!(static_metadata.axes.is_empty());
+        assert!(static_metadata.named_instances.is_empty());
+        assert_eq!(
+            Some("Duck Sans"),
+            name(&static_metadata, NameId::FAMILY_NAME)
+        );
+        assert_eq!(Some("Bold"), name(&static_metadata, NameId::SUBFAMILY_NAME));
+        assert_eq!(
+            None,
+            name(&static_metadata, NameId::TYPOGRAPHIC_FAMILY_NAME)
+        );
+        assert_eq!(
+            Some("DuckSans-Bold"),
+            name(&static_metadata, NameId::POSTSCRIPT_NAME)
+        );
+        assert_eq!(
+            Some("1.002;NONE;DuckSans-Bold"),
+            name(&static_metadata, NameId::UNIQUE_ID)
+        );
+        // the instance name no longer needs a name id of its own
+        assert!(static_metadata
+            .names
+            .keys()
+            .all(|key| key.name_id.to_u16() < 256));
+        assert_eq!(SelectionFlags::BOLD, static_metadata.misc.selection_flags);
+    }
+
+    #[test]
+    fn non_ribbi_instance_names() {
+        let static_metadata = instance_metadata(named_instance("SemiBold Italic"));
+
+        assert_eq!(
+            Some("Duck Sans SemiBold"),
+            name(&static_metadata, NameId::FAMILY_NAME)
+        );
+        assert_eq!(
+            Some("Italic"),
+            name(&static_metadata, NameId::SUBFAMILY_NAME)
+        );
+        assert_eq!(
+            Some("Duck Sans"),
+            name(&static_metadata, NameId::TYPOGRAPHIC_FAMILY_NAME)
+        );
+        assert_eq!(
+            Some("SemiBold Italic"),
+            name(&static_metadata, NameId::TYPOGRAPHIC_SUBFAMILY_NAME)
+        );
+        assert_eq!(
+            Some("Duck Sans SemiBold Italic"),
+            name(&static_metadata, NameId::FULL_NAME)
+        );
+        assert_eq!(
+            Some("DuckSans-SemiBoldItalic"),
+            name(&static_metadata, NameId::POSTSCRIPT_NAME)
+        );
+        assert_eq!(SelectionFlags::ITALIC, static_metadata.misc.selection_flags);
+    }
+
+    #[test]
+    fn names_from_the_instance() {
+        let static_metadata = instance_metadata(NamedInstance {
+            family_name: Some("Duck Sans Display".to_string()),
+            postscript_name: Some("DuckDisplay-Blk".to_string()),
+            style_map_family_name: Some("Duck Sans Display".to_string()),
+            style_map_style_name: Some("bold".to_string()),
+            ..named_instance("Black")
+        });
+
+        assert_eq!(
+            Some("Duck Sans Display"),
+            name(&static_metadata, NameId::FAMILY_NAME)
+        );
+        assert_eq!(Some("Bold"), name(&static_metadata, NameId::SUBFAMILY_NAME));
+        assert_eq!(
+            None,
+            name(&static_metadata, NameId::TYPOGRAPHIC_FAMILY_NAME)
+        );
+        assert_eq!(
+            Some("Black"),
+            name(&static_metadata, NameId::TYPOGRAPHIC_SUBFAMILY_NAME)
+        );
+        assert_eq!(
+            Some("Duck Sans Display Black"),
+            name(&static_metadata, NameId::FULL_NAME)
+        );
+        assert_eq!(
+            Some("DuckDisplay-Blk"),
+            name(&static_metadata, NameId::POSTSCRIPT_NAME)
+        );
+        assert_eq!(
+            Some("1.002;NONE;DuckDisplay-Blk"),
+            name(&static_metadata, NameId::UNIQUE_ID)
+        );
+        assert_eq!(SelectionFlags::BOLD, static_metadata.misc.selection_flags);
+    }
+
+    #[test]
+    fn style_map_family_falls_back_to_linked_style() {
+        let static_metadata = instance_metadata(NamedInstance {
+            style_map_style_name: Some("bold".to_string()),
+            ..named_instance("Bold Condensed")
+        });
+
+        assert_eq!(
+            Some("Duck Sans Condensed"),
+            name(&static_metadata, NameId::FAMILY_NAME)
+        );
+        assert_eq!(Some("Bold"), name(&static_metadata, NameId::SUBFAMILY_NAME));
+        assert_eq!(
+            Some("Duck Sans"),
+            name(&static_metadata, NameId::TYPOGRAPHIC_FAMILY_NAME)
+        );
+        assert_eq!(
+            Some("Bold Condensed"),
+            name(&static_metadata, NameId::TYPOGRAPHIC_SUBFAMILY_NAME)
+        );
+        assert_eq!(
+            Some("DuckSans-BoldCondensed"),
+            name(&static_metadata, NameId::POSTSCRIPT_NAME)
+        );
+        assert_eq!(SelectionFlags::BOLD, static_metadata.misc.selection_flags);
+    }
+
+    #[test]
+    fn instantiates_kerning() {
+        let pair: KernPair = (
+            crate::ir::KernSide::Glyph("a".into()),
+            crate::ir::KernSide::Glyph("b".into()),
+        );
+        let kern_by_pos = [(wght(0.0), -10.0), (wght(1.0), -30.0)]
+            .into_iter()
+            .map(|(pos, value)| {
+                (
+                    pos.clone(),
+                    KerningInstance {
+                        location: pos,
+                        kerns: BTreeMap::from([(pair.clone(), OrderedFloat(value))]),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        let kerning =
+            instantiate_kerning(&Default::default(), kern_by_pos, &[wght_axis()], &wght(0.5))
+                .unwrap();
+
+        assert_eq!(BTreeMap::from([(pair, OrderedFloat(-20.0))]), kerning.kerns);
+        assert!(kerning.location.is_default());
+    }
+
+    #[test]
+    fn instantiates_sparse_kerning() {
+        let glyph = |name: &str| crate::ir::KernSide::Glyph(name.into());
+        let ab: KernPair = (glyph("a"), glyph("b"));
+        let ba: KernPair = (glyph("b"), glyph("a"));
+        let kern_by_pos = [
+            (wght(0.0), vec![(ab.clone(), -10.0)]),
+            (wght(0.5), vec![(ba.clone(), -25.0)]),
+            (wght(1.0), vec![(ab.clone(), -30.0)]),
+        ]
+        .into_iter()
+        .map(|(pos, kerns)| {
+            (
+                pos.clone(),
+                KerningInstance {
+                    location: pos,
+                    kerns: kerns
+                        .into_iter()
+                        .map(|(pair, value)| (pair, OrderedFloat(value)))
+                        .collect(),
+                    ..Default::default()
+                },
+            )
+        })
+        .collect();
+        let groups = KerningGroups {
+            sparse_locations: BTreeSet::from([wght(0.5)]),
+            ..Default::default()
+        };
+        let kerning =
+            instantiate_kerning(&groups, kern_by_pos, &[wght_axis()], &wght(0.5)).unwrap();
+
+        // a b is interpolated across the sparse location, b a is zero at the others
+        assert_eq!(
+            BTreeMap::from([(ab, OrderedFloat(-20.0)), (ba, OrderedFloat(-25.0))]),
+            kerning.kerns
+        );
+    }
+
+    #[test]
+    fn instantiate_context() {
+        let source = test_context();
+        source
+            .static_metadata
+            .set(variable_metadata(named_instance("Medium")));
+        let mut metrics = GlobalMetrics::new(wght(0.0), 1000, None, None, None, 0.0);
+        metrics.set(crate::ir::GlobalMetric::XHeight, wght(1.0), 600.0);
+        source.global_metrics.set(metrics);
+        source.glyphs.set(
+            Glyph::new(
+                "square".into(),
+                true,
+                HashSet::new(),
+                HashMap::from([(wght(0.0), square(100.0)), (wght(1.0), square(200.0))]),
+            )
+            .unwrap(),
+        );
+        let mut glyph_order = crate::ir::GlyphOrder::new();
+        glyph_order.insert("square".into());
+        source.glyph_order.set(glyph_order);
+
+        let target = test_context();
+        let instance = NamedInstance {
+            location: UserLocation::for_pos(&[("wght", 650.0)]),
+            ..named_instance("Medium")
+        };
+        instantiate(&source, &instance, &target).unwrap();
+
+        let default = NormalizedLocation::new();
+        assert_eq!(
+            &square(150.0),
+            target
+                .glyphs
+                .get(&WorkId::Glyph("square".into()))
+                .default_instance()
+        );
+        assert_eq!(
+            OrderedFloat(550.0),
+            target
+                .global_metrics
+                .get()
+                .get(crate::ir::GlobalMetric::XHeight, &default)
+        );
+        assert!(target.kerning_groups.try_get().is_none());
+    }
+
+    fn resolve(fea: &str, location: &NormalizedLocation) -> Result<String, WorkError> {
+        resolve_variable_values(fea, Path::new(""), &[wght_axis()], location, 0)
+    }
+
+    #[test]
+    fn resolves_variable_values() {
+        // wght 550 is halfway from 400, the default, to 700
+        assert_eq!(
+            "pos A A 20;",
+            resolve("pos A A (wght=400:10 wght=700:30);", &wght(0.3)).unwrap()
+        );
+        assert_eq!(
+            "pos A A 30;",
+            resolve("pos A A (wght=400d:10 wght=1n:30);", &wght(1.0)).unwrap()
+        );
+    }
+
+    #[test]
+    fn leaves_comments_strings_and_lists_alone() {
+        let fea = "# (wght=400:10 wght=700:30)\n\
+            feature ss01 { featureNames { name \"(wght=400:1)\"; }; } ss01;\n\
+            pos A <0 0 (wght=400:10 wght=700:30) 0>;";
+        assert_eq!(
+            "# (wght=400:10 wght=700:30)\n\
+            feature ss01 { featureNames { name \"(wght=400:1)\"; }; } ss01;\n\
+            pos A <0 0 20 0>;",
+            resolve(fea, &wght(0.3)).unwrap()
+        );
+    }
+
+    #[test]
+    fn variable_value_on_unknown_axis_fails() {
+        let result = resolve("pos A A (wdth=100:10 wdth=200:30);", &wght(0.3));
+        assert!(
+            matches!(result, Err(WorkError::InterpolationError { .. })),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn instantiates_feature_source() {
+        let features = FeaturesSource::Memory {
+            fea_content: "pos A A (wght=400:10 wght=700:30);".to_string(),
+            include_dir: None,
+        };
+        let FeaturesSource::Memory { fea_content, .. } =
+            instantiate_features(&features, &[wght_axis()], &wght(0.6)).unwrap()
+        else {
+            panic!("expected feature code in memory");
+        };
+        assert_eq!("pos A A 30;", fea_content);
+    }
+}
@@ -0,0 +1,757 @@
+//! Checks that the sources of a glyph are compatible for interpolation.
+//!
+//! Compilation stops at the first problem with a glyph, which makes fixing a
+//! badly broken source tedious. This reports every problem we can find, much
+//! like fontTools' [varLib.interpolatable](https://github.com/fonttools/fonttools/blob/main/Lib/fontTools/varLib/interpolatable.py).
+//!
+//! The most common problems, reordered contours and rotated start points, can also be
+//! repaired, see [`repair_contours`].
+
+use std::{fmt::Display, mem::discriminant};
+
+use fontdrasil::{coords::NormalizedLocation, types::GlyphName};
+use kurbo::{BezPath, PathEl, Point, Shape, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::ir::{Glyph, GlyphAnchors, GlyphInstance};
+
+/// Only claim a better match than the one we have if it's at least this much better.
+///
+/// Matches the tolerance of fontTools.
+const TOLERANCE: f64 = 0.95;
+
+/// Something that stops a source of a glyph interpolating with the default source
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub glyph: GlyphName,
+    /// The location of the source with the problem
+    pub location: NormalizedLocation,
+    pub kind: ProblemKind,
+}
+
+/// What is wrong with a source, relative to the default source of the glyph
+///
+/// Contour and component indices are those of the default source.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProblemKind {
+    ContourCount {
+        expected: usize,
+        actual: usize,
+    },
+    /// The contours are all there but in a different order
+    ContourOrder {
+        contour: usize,
+        /// The index of the contour in the source
+        actual: usize,
+    },
+    PointCount {
+        contour: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// The on and off curve points, or the segment types, don't line up
+    PathElements {
+        contour: usize,
+    },
+    /// The contour would line up if it started elsewhere
+    StartPoint {
+        contour: usize,
+        /// The index of the point in the source contour that should be first
+        proposed_start: usize,
+    },
+    /// The contour runs the other way around
+    Direction {
+        contour: usize,
+    },
+    ComponentCount {
+        expected: usize,
+        actual: usize,
+    },
+    /// The components are all there but in a different order
+    ComponentOrder {
+        component: usize,
+        expected: GlyphName,
+        actual: GlyphName,
+    },
+    ComponentBase {
+        component: usize,
+        expected: GlyphName,
+        actual: GlyphName,
+    },
+    /// The scale, skew or rotation of the component differs, which we can't vary
+    ComponentTransform {
+        component: usize,
+    },
+    MissingAnchor {
+        anchor: String,
+    },
+}
+
+impl Display for ProblemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProblemKind::ContourCount { expected, actual } => {
+                write!(f, "has {actual} contours, the default has {expected}")
+            }
+            ProblemKind::ContourOrder { contour, actual } => {
+                write!(f, "contour {contour} of the default is contour {actual}")
+            }
+            ProblemKind::PointCount {
+                contour,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "contour {contour} has {actual} points, the default has {expected}"
+            ),
+            ProblemKind::PathElements { contour } => {
+                write!(f, "contour {contour} has different segment types")
+            }
+            ProblemKind::StartPoint {
+                contour,
+                proposed_start,
+            } => write!(
+                f,
+                "contour {contour} should probably start at point {proposed_start}"
+            ),
+            ProblemKind::Direction { contour } => {
+                write!(f, "contour {contour} has the opposite direction")
+            }
+            ProblemKind::ComponentCount { expected, actual } => {
+                write!(f, "has {actual} components, the default has {expected}")
+            }
+            ProblemKind::ComponentOrder {
+                component,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "component {component} is {actual}, the default has {expected}; the components are in a different order"
+            ),
+            ProblemKind::ComponentBase {
+                component,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "component {component} is {actual}, the default has {expected}"
+            ),
+            ProblemKind::ComponentTransform { component } => {
+                write!(f, "component {component} has a different 2x2 transform")
+            }
+            ProblemKind::MissingAnchor { anchor } => write!(f, "has no anchor '{anchor}'"),
+        }
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' at {:?} {}", self.glyph, self.location, self.kind)
+    }
+}
+
+/// Find every reason the sources of `glyph`, and its `anchors` if any, can't interpolate
+pub fn check_glyph(glyph: &Glyph, anchors: Option<&GlyphAnchors>) -> Vec<Problem> {
+    let default = glyph.default_instance();
+    let mut locations: Vec<_> = glyph
+        .sources()
+        .keys()
+        .filter(|loc| !loc.is_default())
+        .collect();
+    locations.sort();
+
+    let mut problems = Vec::new();
+    for location in locations {
+        let instance = glyph.sources().get(location).unwrap();
+        let mut kinds = check_contours(default, instance);
+        kinds.extend(check_components(default, instance));
+        if let Some(anchors) = anchors {
+            kinds.extend(
+                anchors
+                    .anchors
+                    .iter()
+                    .filter(|anchor| !anchor.positions.contains_key(location))
+                    .map(|anchor| ProblemKind::MissingAnchor {
+                        anchor: anchor.kind.name().to_string(),
+                    }),
+            );
+        }
+        problems.extend(kinds.into_iter().map(|kind| Problem {
+            glyph: glyph.name.clone(),
+            location: location.clone(),
+            kind,
+        }));
+    }
+    problems
+}
+
+/// Reorder the contours of each source of `glyph`, and rotate their start points, to match
+/// the default source.
+///
+/// Only fixes what [`check_glyph`] would report as [`ProblemKind::ContourOrder`] or
+/// [`ProblemKind::StartPoint`]. Returns the fixes made, if any.
+pub fn repair_contours(glyph: &mut Glyph) -> Vec<Problem> {
+    let name = glyph.name.clone();
+    let default = glyph.default_instance().contours.clone();
+    let mut fixes = Vec::new();
+    for (location, instance) in glyph.sources_mut() {
+        if location.is_default() || instance.contours.len() != default.len() {
+            continue;
+        }
+        let mut fix = |kind| {
+            fixes.push(Problem {
+                glyph: name.clone(),
+                location: location.clone(),
+                kind,
+            })
+        };
+
+        if let Some(order) = match_contours(&default, &instance.contours) {
+            for (contour, actual) in order.iter().enumerate() {
+                if contour != *actual {
+                    fix(ProblemKind::ContourOrder {
+                        contour,
+                        actual: *actual,
+                    });
+                }
+            }
+            instance.contours = order
+                .into_iter()
+                .map(|i| instance.contours[i].clone())
+                .collect();
+        }
+
+        for (contour, (expected, actual)) in
+            default.iter().zip(instance.contours.iter_mut()).enumerate()
+        {
+            if let Some(proposed_start) = best_start(expected, actual) {
+                *actual = rotate_contour(actual, proposed_start);
+                fix(ProblemKind::StartPoint {
+                    contour,
+                    proposed_start,
+                });
+            }
+        }
+    }
+    fixes.sort_by(|a, b| a.location.cmp(&b.location));
+    fixes
+}
+
+fn check_contours(default: &GlyphInstance, instance: &GlyphInstance) -> Vec<ProblemKind> {
+    if default.contours.len() != instance.contours.len() {
+        return vec![ProblemKind::ContourCount {
+            expected: default.contours.len(),
+            actual: instance.contours.len(),
+        }];
+    }
+
+    let mut problems = Vec::new();
+    let order = match_contours(&default.contours, &instance.contours)
+        .unwrap_or_else(|| (0..default.contours.len()).collect());
+    for (contour, actual) in order.iter().enumerate() {
+        if contour != *actual {
+            problems.push(ProblemKind::ContourOrder {
+                contour,
+                actual: *actual,
+            });
+        }
+    }
+
+    for (contour, actual) in order.into_iter().enumerate() {
+        let expected_path = &default.contours[contour];
+        let actual_path = &instance.contours[actual];
+        let expected = contour_points(expected_path);
+        let points = contour_points(actual_path);
+        if expected.len() != points.len() {
+            problems.push(ProblemKind::PointCount {
+                contour,
+                expected: expected.len(),
+                actual: points.len(),
+            });
+            continue;
+        }
+        match best_start(expected_path, actual_path) {
+            Some(proposed_start) => problems.push(ProblemKind::StartPoint {
+                contour,
+                proposed_start,
+            }),
+            None if !same_elements(expected_path, actual_path) => {
+                problems.push(ProblemKind::PathElements { contour })
+            }
+            None => (),
+        }
+        let (expected_area, area) = (expected_path.area(), actual_path.area());
+        if expected_area * area < 0.0 {
+            problems.push(ProblemKind::Direction { contour });
+        }
+    }
+    problems
+}
+
+fn check_components(default: &GlyphInstance, instance: &GlyphInstance) -> Vec<ProblemKind> {
+    if default.components.len() != instance.components.len() {
+        return vec![ProblemKind::ComponentCount {
+            expected: default.components.len(),
+            actual: instance.components.len(),
+        }];
+    }
+
+    let sorted_bases = |instance: &GlyphInstance| {
+        let mut bases: Vec<_> = instance.components.iter().map(|c| &c.base).collect();
+        bases.sort();
+        bases
+    };
+    let reordered = sorted_bases(default) == sorted_bases(instance);
+
+    let mut problems = Vec::new();
+    for (component, (expected, actual)) in default
+        .components
+        .iter()
+        .zip(instance.components.iter())
+        .enumerate()
+    {
+        if expected.base != actual.base {
+            let (expected, actual) = (expected.base.clone(), actual.base.clone());
+            problems.push(if reordered {
+                ProblemKind::ComponentOrder {
+                    component,
+                    expected,
+                    actual,
+                }
+            } else {
+                ProblemKind::ComponentBase {
+                    component,
+                    expected,
+                    actual,
+                }
+            });
+        } else if expected.transform.as_coeffs()[..4] != actual.transform.as_coeffs()[..4] {
+            problems.push(ProblemKind::ComponentTransform { component });
+        }
+    }
+    problems
+}
+
+/// The points of a contour, and whether they are on the curve.
+///
+/// A closed contour that ends at its start doesn't repeat the start.
+pub(crate) fn contour_points(contour: &BezPath) -> Vec<(Point, bool)> {
+    let mut points = Vec::new();
+    for el in contour.elements() {
+        match el {
+            PathEl::MoveTo(p) | PathEl::LineTo(p) => points.push((*p, true)),
+            PathEl::QuadTo(c, p) => points.extend([(*c, false), (*p, true)]),
+            PathEl::CurveTo(c1, c2, p) => points.extend([(*c1, false), (*c2, false), (*p, true)]),
+            PathEl::ClosePath => (),
+        }
+    }
+    if is_closed(contour) && points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    points
+}
+
+pub(crate) fn is_closed(contour: &BezPath) -> bool {
+    contour.elements().last() == Some(&PathEl::ClosePath)
+}
+
+fn same_elements(a: &BezPath, b: &BezPath) -> bool {
+    a.elements().len() == b.elements().len()
+        && a.elements()
+            .iter()
+            .zip(b.elements())
+            .all(|(a, b)| discriminant(a) == discriminant(b))
+}
+
+fn centroid(points: &[(Point, bool)]) -> Point {
+    if points.is_empty() {
+        return Point::ZERO;
+    }
+    let sum = points
+        .iter()
+        .fold(Vec2::ZERO, |sum, (p, _)| sum + p.to_vec2());
+    (sum / points.len() as f64).to_point()
+}
+
+/// How unlike each other two contours are, ignoring their start points
+fn contour_cost(a: &BezPath, b: &BezPath) -> f64 {
+    let (a_points, b_points) = (contour_points(a), contour_points(b));
+    if a_points.len() != b_points.len() {
+        return f64::INFINITY;
+    }
+    let size = |path: &BezPath| path.area().abs().sqrt();
+    (centroid(&a_points) - centroid(&b_points)).hypot2() + (size(a) - size(b)).powi(2)
+}
+
+/// Which contour of `other` goes with each contour of `default`, if not the one at the same index
+///
+/// Contours are paired up greedily, most similar first. Returns None if the contours
+/// are in the right order, or we can't find a better order.
+pub(crate) fn match_contours(default: &[BezPath], other: &[BezPath]) -> Option<Vec<usize>> {
+    if default.len() != other.len() || default.len() < 2 {
+        return None;
+    }
+    let mut costs = Vec::with_capacity(default.len() * other.len());
+    for (i, a) in default.iter().enumerate() {
+        for (j, b) in other.iter().enumerate() {
+            costs.push((contour_cost(a, b), i, j));
+        }
+    }
+    let identity_cost: f64 = costs
+        .iter()
+        .filter(|(_, i, j)| i == j)
+        .map(|(cost, ..)| cost)
+        .sum();
+    costs.retain(|(cost, ..)| cost.is_finite());
+    costs.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut matches = vec![None; default.len()];
+    let mut taken = vec![false; other.len()];
+    let mut matched_cost = 0.0;
+    for (cost, i, j) in costs {
+        if matches[i].is_some() || taken[j] {
+            continue;
+        }
+        matches[i] = Some(j);
+        taken[j] = true;
+        matched_cost += cost;
+    }
+    let matches: Vec<_> = matches.into_iter().collect::<Option<_>>()?;
+    (matches.iter().enumerate().any(|(i, j)| i != *j) && matched_cost < identity_cost * TOLERANCE)
+        .then_some(matches)
+}
+
+/// The point of `other` that should come first to match `default`, if not the current first point
+///
+/// Only closed contours can start elsewhere, and only at a point that keeps the on and
+/// off curve points lined up.
+pub(crate) fn best_start(default: &BezPath, other: &BezPath) -> Option<usize> {
+    if !is_closed(default) || !is_closed(other) {
+        return None;
+    }
+    let (expected, points) = (contour_points(default), contour_points(other));
+    if expected.len() != points.len() || expected.is_empty() {
+        return None;
+    }
+    let (expected_centroid, centroid) = (centroid(&expected), centroid(&points));
+    let rotation_cost = |start: usize| {
+        let rotated = points[start..].iter().chain(points[..start].iter());
+        let mut cost = 0.0;
+        for ((expected, expected_on), (actual, on)) in expected.iter().zip(rotated) {
+            if expected_on != on {
+                return None;
+            }
+            cost += ((*expected - expected_centroid) - (*actual - centroid)).hypot2();
+        }
+        Some(cost)
+    };
+
+    let (best, best_cost) = (0..points.len())
+        .filter_map(|start| rotation_cost(start).map(|cost| (start, cost)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+    match rotation_cost(0) {
+        _ if best == 0 => None,
+        Some(cost) if best_cost >= cost * TOLERANCE => None,
+        _ => Some(best),
+    }
+}
+
+/// Start a closed contour at the on-curve point at `start`, numbered as by [`contour_points`]
+///
+/// The contour is closed the way it was before, explicitly or implicitly.
+pub(crate) fn rotate_contour(contour: &BezPath, start: usize) -> BezPath {
+    let elements = contour.elements();
+    let Some(&PathEl::MoveTo(first)) = elements.first() else {
+        return contour.clone();
+    };
+    if start == 0 || !is_closed(contour) {
+        return contour.clone();
+    }
+
+    // Every segment, including any implicit closing line, so the last one ends at the start
+    let mut segments = elements[1..elements.len() - 1].to_vec();
+    let explicit_close = segments.last().and_then(PathEl::end_point) == Some(first);
+    if !explicit_close {
+        segments.push(PathEl::LineTo(first));
+    }
+    let mut index = 0;
+    let Some(split) = segments.iter().position(|el| {
+        index += match el {
+            PathEl::MoveTo(..) | PathEl::LineTo(..) => 1,
+            PathEl::QuadTo(..) => 2,
+            PathEl::CurveTo(..) => 3,
+            PathEl::ClosePath => 0,
+        };
+        index == start
+    }) else {
+        return contour.clone();
+    };
+    segments.rotate_left(split + 1);
+    let new_first = segments[segments.len() - 1].end_point().unwrap();
+    if !explicit_close && matches!(segments.last(), Some(PathEl::LineTo(..))) {
+        segments.pop();
+    }
+
+    let mut rotated = BezPath::new();
+    rotated.move_to(new_first);
+    for el in segments {
+        rotated.push(el);
+    }
+    rotated.close_path();
+    rotated
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use kurbo::{Affine, Rect};
+
+    use crate::ir::{Anchor, AnchorKind, Component};
+
+    use super::*;
+
+    fn wght(value: f32) -> NormalizedLocation {
+        NormalizedLocation::for_pos(&[("wght", value)])
+    }
+
+    fn rect(x: f64, y: f64, size: f64) -> BezPath {
+        Rect::new(x, y, x + size, y + size).to_path(0.1)
+    }
+
+    fn glyph(instances: Vec<(NormalizedLocation, GlyphInstance)>) -> Glyph {
+        Glyph::new(
+            "test".into(),
+            true,
+            HashSet::new(),
+            instances.into_iter().collect::<HashMap<_, _>>(),
+        )
+        .unwrap()
+    }
+
+    fn contours(contours: Vec<BezPath>) -> GlyphInstance {
+        GlyphInstance {
+            contours,
+            ..Default::default()
+        }
+    }
+
+    fn kinds(problems: Vec<Problem>) -> Vec<ProblemKind> {
+        problems.into_iter().map(|p| p.kind).collect()
+    }
+
+    #[test]
+    fn compatible() {
+        let glyph = glyph(vec![
+            (wght(0.0), contours(vec![rect(0.0, 0.0, 100.0)])),
+            (wght(1.0), contours(vec![rect(0.0, 0.0, 200.0)])),
+        ]);
+        assert_eq!(Vec::<Problem>::new(), check_glyph(&glyph, None));
+    }
+
+    #[test]
+    fn reports_every_source() {
+        let glyph = glyph(vec![
+            (wght(-1.0), contours(vec![])),
+            (wght(0.0), contours(vec![rect(0.0, 0.0, 100.0)])),
+            (wght(1.0), contours(vec![])),
+        ]);
+        let problems = check_glyph(&glyph, None);
+        assert_eq!(
+            vec![wght(-1.0), wght(1.0)],
+            problems
+                .iter()
+                .map(|p| p.location.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            ProblemKind::ContourCount {
+                expected: 1,
+                actual: 0
+            },
+            problems[0].kind
+        );
+    }
+
+    #[test]
+    fn wrong_start_point() {
+        let square = rect(0.0, 0.0, 100.0);
+        let glyph = glyph(vec![
+            (wght(0.0), contours(vec![square.clone()])),
+            (wght(1.0), contours(vec![rotate_contour(&square, 2)])),
+        ]);
+        assert_eq!(
+            vec![ProblemKind::StartPoint {
+                contour: 0,
+                proposed_start: 2
+            }],
+            kinds(check_glyph(&glyph, None))
+        );
+    }
+
+    #[test]
+    fn wrong_direction() {
+        let square = rect(0.0, 0.0, 100.0);
+        let glyph = glyph(vec![
+            (wght(0.0), contours(vec![square.clone()])),
+            (wght(1.0), contours(vec![square.reverse_subpaths()])),
+        ]);
+        assert!(kinds(check_glyph(&glyph, None)).contains(&ProblemKind::Direction { contour: 0 }));
+    }
+
+    #[test]
+    fn wrong_contour_order() {
+        let (a, b) = (rect(0.0, 0.0, 100.0), rect(500.0, 500.0, 50.0));
+        let glyph = glyph(vec![
+            (wght(0.0), contours(vec![a.clone(), b.clone()])),
+            (wght(1.0), contours(vec![b, a])),
+        ]);
+        assert_eq!(
+            vec![
+                ProblemKind::ContourOrder {
+                    contour: 0,
+                    actual: 1
+                },
+                ProblemKind::ContourOrder {
+                    contour: 1,
+                    actual: 0
+                }
+            ],
+            kinds(check_glyph(&glyph, None))
+        );
+    }
+
+    #[test]
+    fn component_problems() {
+        let component = |base: &str, transform: Affine| Component {
+            base: base.into(),
+            transform,
+            location: NormalizedLocation::new(),
+        };
+        let components = |components: Vec<Component>| GlyphInstance {
+            components,
+            ..Default::default()
+        };
+        let glyph = glyph(vec![
+            (
+                wght(0.0),
+                components(vec![
+                    component("a", Affine::IDENTITY),
+                    component("b", Affine::IDENTITY),
+                    component("c", Affine::IDENTITY),
+                ]),
+            ),
+            (
+                wght(1.0),
+                components(vec![
+                    component("b", Affine::IDENTITY),
+                    component("a", Affine::IDENTITY),
+                    component("c", Affine::scale(2.0)),
+                ]),
+            ),
+        ]);
+        assert_eq!(
+            vec![
+                ProblemKind::ComponentOrder {
+                    component: 0,
+                    expected: "a".into(),
+                    actual: "b".into()
+                },
+                ProblemKind::ComponentOrder {
+                    component: 1,
+                    expected: "b".into(),
+                    actual: "a".into()
+                },
+                ProblemKind::ComponentTransform { component: 2 },
+            ],
+            kinds(check_glyph(&glyph, None))
+        );
+    }
+
+    #[test]
+    fn missing_anchor() {
+        let glyph = glyph(vec![
+            (wght(0.0), contours(vec![rect(0.0, 0.0, 100.0)])),
+            (wght(1.0), contours(vec![rect(0.0, 0.0, 200.0)])),
+        ]);
+        let anchors = GlyphAnchors::new(
+            "test".into(),
+            vec![Anchor {
+                kind: AnchorKind::Base("top".into()),
+                positions: HashMap::from([(wght(0.0), Point::new(50.0, 100.0))]),
+            }],
+        )
+        .unwrap();
+        assert_eq!(
+            vec![ProblemKind::MissingAnchor {
+                anchor: "top".to_string()
+            }],
+            kinds(check_glyph(&glyph, Some(&anchors)))
+        );
+    }
+
+    #[test]
+    fn rotate_explicitly_closed_curve() {
+        let mut path = BezPath::new();
+        path.move_to((0.0, 0.0));
+        path.line_to((100.0, 0.0));
+        path.curve_to((100.0, 50.0), (50.0, 100.0), (0.0, 100.0));
+        path.line_to((0.0, 0.0));
+        path.close_path();
+
+        let mut expected = BezPath::new();
+        expected.move_to((100.0, 0.0));
+        expected.curve_to((100.0, 50.0), (50.0, 100.0), (0.0, 100.0));
+        expected.line_to((0.0, 0.0));
+        expected.line_to((100.0, 0.0));
+        expected.close_path();
+        assert_eq!(expected, rotate_contour(&path, 1));
+    }
+
+    #[test]
+    fn rotate_implicitly_closed_curve() {
+        let mut path = BezPath::new();
+        path.move_to((0.0, 0.0));
+        path.line_to((100.0, 0.0));
+        path.curve_to((100.0, 50.0), (50.0, 100.0), (0.0, 100.0));
+        path.close_path();
+
+        let mut expected = BezPath::new();
+        expected.move_to((0.0, 100.0));
+        expected.line_to((0.0, 0.0));
+        expected.line_to((100.0, 0.0));
+        expected.curve_to((100.0, 50.0), (50.0, 100.0), (0.0, 100.0));
+        expected.close_path();
+        assert_eq!(expected, rotate_contour(&path, 4));
+    }
+
+    #[test]
+    fn repair_start_point_and_order() {
+        let (a, b) = (rect(0.0, 0.0, 100.0), rect(500.0, 500.0, 50.0));
+        let mut glyph = glyph(vec![
+            (wght(0.0), contours(vec![a.clone(), b.clone()])),
+            (wght(1.0), contours(vec![b.clone(), rotate_contour(&a, 3)])),
+        ]);
+        let fixes = repair_contours(&mut glyph);
+        assert_eq!(
+            vec![
+                ProblemKind::ContourOrder {
+                    contour: 0,
+                    actual: 1
+                },
+                ProblemKind::ContourOrder {
+                    contour: 1,
+                    actual: 0
+                },
+                ProblemKind::StartPoint {
+                    contour: 0,
+                    proposed_start: 1
+                },
+            ],
+            kinds(fixes)
+        );
+        assert_eq!(vec![a, b], glyph.sources()[&wght(1.0)].contours);
+        assert_eq!(Vec::<Problem>::new(), check_glyph(&glyph, None));
+    }
+}
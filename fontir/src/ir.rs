@@ -105,6 +105,136 @@ pub struct MiscMetadata {
     pub lowest_rec_ppm: u16,
 
     pub created: Option<DateTime<Utc>>,
+
+    /// How generated kerning features combine with the source FEA
+    pub kern_writer_mode: FeatureWriterMode,
+    /// How generated mark features combine with the source FEA
+    pub mark_writer_mode: FeatureWriterMode,
+
+    /// Used only if the font is written as WOFF or WOFF2
+    pub woff: WoffMetadata,
+
+    /// Filters to run over the glyphs, in order
+    pub glyph_filters: Vec<GlyphFilter>,
+}
+
+/// How generated features combine with features of the same tag in the source FEA
+///
+/// Matches the `mode` option of ufo2ft feature writers, see
+/// <https://github.com/googlefonts/ufo2ft/blob/main/Lib/ufo2ft/featureWriters/baseFeatureWriter.py>.
+/// Either way, a feature containing an insertion marker (`# Automatic Code`)
+/// gets the generated lookups at the marker.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureWriterMode {
+    /// Don't generate features the source FEA already has
+    #[default]
+    Skip,
+    /// Add generated lookups after those of the source FEA
+    Append,
+}
+
+/// The version and extended metadata for the header of a WOFF or WOFF2 file
+///
+/// See <https://www.w3.org/TR/WOFF/#Metadata>
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+pub struct WoffMetadata {
+    /// If unset, taken from the head table fontRevision
+    pub major_version: Option<u16>,
+    pub minor_version: Option<u16>,
+    /// The extended metadata XML document
+    pub xml: Option<String>,
+}
+
+/// A ufo2ft filter to run over glyphs
+///
+/// See <https://github.com/googlefonts/ufo2ft/tree/main/Lib/ufo2ft/filters>
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GlyphFilter {
+    pub kind: GlyphFilterKind,
+    /// Pre filters run before glyphs are otherwise processed, post filters after
+    pub pre: bool,
+    pub glyphs: GlyphSelection,
+}
+
+impl GlyphFilter {
+    pub fn new(kind: GlyphFilterKind) -> Self {
+        GlyphFilter {
+            kind,
+            pre: false,
+            glyphs: GlyphSelection::All,
+        }
+    }
+
+    pub fn applies_to(&self, glyph_name: &GlyphName) -> bool {
+        match &self.glyphs {
+            GlyphSelection::All => true,
+            GlyphSelection::Include(names) => names.contains(glyph_name),
+            GlyphSelection::Exclude(names) => !names.contains(glyph_name),
+        }
+    }
+}
+
+/// The glyphs a [GlyphFilter] applies to
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+pub enum GlyphSelection {
+    #[default]
+    All,
+    Include(BTreeSet<GlyphName>),
+    Exclude(BTreeSet<GlyphName>),
+}
+
+/// What a [GlyphFilter] does, named for the ufo2ft filter it matches
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum GlyphFilterKind {
+    DecomposeComponents,
+    DecomposeTransformedComponents,
+    FlattenComponents,
+    PropagateAnchors,
+    /// Sort contours by their bounds, `(xMin, yMin, xMax, yMax)` at the default location
+    SortContours,
+    Transformations(Transformation),
+    /// Add a dotted circle (U+25CC) with anchors for every mark if there isn't one
+    DottedCircle,
+}
+
+/// The options of ufo2ft's transformations filter
+///
+/// Glyphs are slanted and scaled about the origin height, then offset.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Transformation {
+    pub offset_x: OrderedFloat<f64>,
+    pub offset_y: OrderedFloat<f64>,
+    /// As a percentage
+    pub scale_x: OrderedFloat<f64>,
+    /// As a percentage
+    pub scale_y: OrderedFloat<f64>,
+    /// Clockwise, in degrees
+    pub slant: OrderedFloat<f64>,
+    pub origin: TransformOrigin,
+}
+
+impl Default for Transformation {
+    fn default() -> Self {
+        Transformation {
+            offset_x: 0.0.into(),
+            offset_y: 0.0.into(),
+            scale_x: 100.0.into(),
+            scale_y: 100.0.into(),
+            slant: 0.0.into(),
+            origin: Default::default(),
+        }
+    }
+}
+
+/// The height glyphs are scaled and slanted around, matches ufo2ft's numbering
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransformOrigin {
+    CapHeight = 0,
+    HalfCapHeight = 1,
+    XHeight = 2,
+    HalfXHeight = 3,
+    #[default]
+    Baseline = 4,
 }
 
 /// The name of every glyph, in the order it will be emitted
@@ -211,10 +341,23 @@ pub struct KerningGroups {
     /// Must be a subset of the master locations.
     pub locations: BTreeSet<NormalizedLocation>,
 
+    /// The locations whose kerning is sparse, such as a source that only has some glyphs.
+    ///
+    /// A pair a sparse location doesn't define, directly or through a group, is interpolated
+    /// from the locations that do rather than taken to be zero. Must be a subset of
+    /// [KerningGroups::locations] and never include the default location.
+    pub sparse_locations: BTreeSet<NormalizedLocation>,
+
     /// Optional group renaming map, meant for [KerningInstance] to consume
     ///
     /// The rhs should be the name used in the groups map.
     pub old_to_new_group_names: BTreeMap<KernGroup, KernGroup>,
+
+    /// Groups for vertical kerning, side1 is the upper glyph and side2 the lower.
+    ///
+    /// Kept apart from [KerningGroups::groups] because the same name can
+    /// have different members for vertical and horizontal kerning.
+    pub vertical_groups: BTreeMap<KernGroup, BTreeSet<GlyphName>>,
 }
 
 /// IR representation of kerning for a location.
@@ -230,6 +373,11 @@ pub struct KerningInstance {
     /// Used for both LTR and RTL. The BE application differs but the concept
     /// is the same.
     pub kerns: BTreeMap<KernPair, OrderedFloat<f32>>,
+    /// An adjustment to the space between two glyphs in vertical layout.
+    ///
+    /// Side1 is the upper glyph, side2 the lower. Groups refer to
+    /// [KerningGroups::vertical_groups].
+    pub vertical_kerns: BTreeMap<KernPair, OrderedFloat<f32>>,
 }
 
 /// A named set of glyphs with common kerning behaviour
@@ -307,6 +455,105 @@ impl KernSide {
     }
 }
 
+/// 'align' the kerning, ensuring each pair is defined for each location.
+///
+/// missing pairs are filled in via the UFO kerning value lookup algorithm:
+///
+/// <https://unifiedfontobject.org/versions/ufo3/kerning.plist/#kerning-value-lookup-algorithm>
+///
+/// Except at `sparse_locations`, where a pair the lookup doesn't find is left
+/// missing so it can be interpolated.
+pub fn align_kerning(
+    groups: &BTreeMap<KernGroup, BTreeSet<GlyphName>>,
+    sparse_locations: &BTreeSet<NormalizedLocation>,
+    instances: &mut HashMap<NormalizedLocation, KerningInstance>,
+    kerns_of: impl Fn(&mut KerningInstance) -> &mut BTreeMap<KernPair, OrderedFloat<f32>>,
+) {
+    let union_kerning = instances
+        .values_mut()
+        .flat_map(|instance| kerns_of(instance).keys().cloned().collect::<Vec<_>>())
+        .collect::<HashSet<_>>();
+
+    let side1_glyph_to_group_map = groups
+        .iter()
+        .filter(|(group, _)| matches!(group, KernGroup::Side1(_)))
+        .flat_map(|(group, glyphs)| glyphs.iter().map(move |glyph| (glyph, group)))
+        .collect::<HashMap<_, _>>();
+    let side2_glyph_to_group_map = groups
+        .iter()
+        .filter(|(group, _)| matches!(group, KernGroup::Side2(_)))
+        .flat_map(|(group, glyphs)| glyphs.iter().map(move |glyph| (glyph, group)))
+        .collect::<HashMap<_, _>>();
+
+    for (location, instance) in instances.iter_mut() {
+        let sparse = sparse_locations.contains(location);
+        let kerns = kerns_of(instance);
+        let missing_pairs = union_kerning
+            .iter()
+            .filter(|pair| !kerns.contains_key(pair))
+            .collect::<Vec<_>>();
+
+        for pair in missing_pairs {
+            // fallback to zero, unless we're sparse and the value should be interpolated
+            let value = lookup_kerning_value(
+                pair,
+                kerns,
+                &side1_glyph_to_group_map,
+                &side2_glyph_to_group_map,
+            )
+            .or_else(|| (!sparse).then(|| 0.0.into()));
+            if let Some(value) = value {
+                kerns.insert(pair.to_owned(), value);
+            }
+        }
+    }
+}
+
+// <https://github.com/fonttools/fonttools/blob/a3b9eddcafca/Lib/fontTools/ufoLib/kerning.py#L1>
+//
+// Unlike fontTools we return None rather than zero if nothing matches.
+fn lookup_kerning_value(
+    pair: &KernPair,
+    kerning: &BTreeMap<KernPair, OrderedFloat<f32>>,
+    side1_glyphs: &HashMap<&GlyphName, &KernGroup>,
+    side2_glyphs: &HashMap<&GlyphName, &KernGroup>,
+) -> Option<OrderedFloat<f32>> {
+    // if already a group, return it, else look for group for glyph
+    fn get_group_if_glyph(
+        side: &KernSide,
+        map: &HashMap<&GlyphName, &KernGroup>,
+    ) -> Option<KernSide> {
+        match side {
+            KernSide::Glyph(glyph) => map
+                .get(&glyph)
+                .map(|group| KernSide::Group((*group).clone())),
+            KernSide::Group(_) => Some(side.to_owned()),
+        }
+    }
+
+    let (first, second) = pair;
+    // for each side: if it's a group, we only check the group.
+    // if it's a glyph, we check both the glyph as well as the group containing that glyph.
+    let first_group = get_group_if_glyph(first, side1_glyphs);
+    let second_group = get_group_if_glyph(second, side2_glyphs);
+    let first = Some(first).filter(|side| side.is_glyph());
+    let second = Some(second).filter(|side| side.is_glyph());
+
+    for (first, second) in [
+        (first.cloned(), second_group.clone()),
+        (first_group.clone(), second.cloned()),
+        (first_group.clone(), second_group.clone()),
+    ] {
+        if let Some(pair) = first.zip(second) {
+            if let Some(value) = kerning.get(&pair) {
+                return Some(*value);
+            }
+        }
+    }
+
+    None
+}
+
 impl StaticMetadata {
     pub fn new(
         units_per_em: u16,
@@ -365,6 +612,10 @@ impl StaticMetadata {
                 // <https://github.com/googlefonts/ufo2ft/blob/0d2688cd847d003b41104534d16973f72ef26c40/Lib/ufo2ft/fontInfoData.py#L365>
                 head_flags: 3,
                 created: None,
+                kern_writer_mode: Default::default(),
+                mark_writer_mode: Default::default(),
+                woff: Default::default(),
+                glyph_filters: Default::default(),
             },
         })
     }
@@ -862,7 +1113,7 @@ impl NameBuilder {
 }
 
 /// <https://github.com/googlefonts/ufo2ft/blob/fca66fe3ea1ea88ffb36f8264b21ce042d3afd05/Lib/ufo2ft/fontInfoData.py#L263>
-fn normalize_for_postscript(value: &mut String, allow_spaces: bool) {
+pub(crate) fn normalize_for_postscript(value: &mut String, allow_spaces: bool) {
     value.retain(|c| {
         if !allow_spaces && c.is_ascii_whitespace() {
             return false;
@@ -936,6 +1187,13 @@ impl NameKey {
 pub struct NamedInstance {
     pub name: String,
     pub location: UserLocation,
+    /// The typographic family of the instance, if the source names one
+    pub family_name: Option<String>,
+    pub postscript_name: Option<String>,
+    /// The family of the instance in the legacy, four member, style linking model
+    pub style_map_family_name: Option<String>,
+    /// One of "regular", "italic", "bold" or "bold italic"
+    pub style_map_style_name: Option<String>,
 }
 
 /// Source for any feature code (Adobe FEA).
@@ -1059,6 +1317,17 @@ impl AnchorKind {
         }
         Ok(AnchorKind::Base(name.into()))
     }
+
+    /// The name of an anchor of this kind, the inverse of [AnchorKind::new]
+    pub fn name(&self) -> SmolStr {
+        match self {
+            AnchorKind::Base(group_name) => group_name.clone(),
+            AnchorKind::Mark(group_name) => format!("_{group_name}").into(),
+            AnchorKind::Ligature { group_name, index } => format!("{group_name}_{index}").into(),
+            AnchorKind::Enter => "entry".into(),
+            AnchorKind::Exit => "exit".into(),
+        }
+    }
 }
 
 /// A variable definition of an anchor.
@@ -1161,9 +1430,9 @@ impl AnchorBuilder {
 
 /// A variable definition of a single glyph.
 ///
-/// Guarrantees at least one definition. Currently that must be at
-/// the default location. In theory that limitation is unnecessary,
-/// a variable glyph that covers the default location could compile.
+/// Guarrantees at least one definition, which must be at the default location.
+/// A glyph need not be defined at every master location, nor only at master
+/// locations: sparse glyphs get a variation model of their own when compiled.
 ///
 /// If defined in many locations, presumed to vary continuously
 /// between positions and required to have variation compatible structure.
@@ -1273,6 +1542,16 @@ impl Glyph {
         self.has_consistent_2x2_transforms
     }
 
+    /// Does the glyph have any component with a location of its own
+    ///
+    /// Such glyphs are drawn into contours for glyf and may also be written to VARC.
+    pub fn has_variable_components(&self) -> bool {
+        self.sources
+            .values()
+            .flat_map(|inst| inst.components.iter())
+            .any(Component::is_variable)
+    }
+
     /// Does the glyph have any component with a non-identity 2x2
     ///
     /// See <https://github.com/googlefonts/fontc/issues/291#issuecomment-1557358538>
@@ -1514,6 +1793,17 @@ pub struct Component {
     pub base: GlyphName,
     /// Affine transformation to apply to the referenced glyph.
     pub transform: Affine,
+    /// Where to draw the referenced glyph, empty to draw it where the component is.
+    ///
+    /// A component with a location is a variable component, which glyf can't express.
+    /// Axes not in the location take their value from where the component is.
+    pub location: NormalizedLocation,
+}
+
+impl Component {
+    pub fn is_variable(&self) -> bool {
+        !self.location.is_empty()
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -1805,6 +2095,10 @@ mod tests {
             named_instances: vec![NamedInstance {
                 name: "Nobody".to_string(),
                 location: vec![(WGHT, UserCoord::new(100.0))].into(),
+                family_name: None,
+                postscript_name: None,
+                style_map_family_name: None,
+                style_map_style_name: None,
             }],
             variation_model: VariationModel::new(
                 HashSet::from([
@@ -1841,6 +2135,21 @@ mod tests {
                 head_flags: 42,
                 lowest_rec_ppm: 42,
                 created: None,
+                kern_writer_mode: FeatureWriterMode::Append,
+                mark_writer_mode: FeatureWriterMode::Skip,
+                woff: WoffMetadata {
+                    major_version: Some(2),
+                    minor_version: None,
+                    xml: Some("<metadata version=\"1.0\"/>".to_string()),
+                },
+                glyph_filters: vec![GlyphFilter {
+                    kind: GlyphFilterKind::Transformations(Transformation {
+                        slant: 12.0.into(),
+                        ..Default::default()
+                    }),
+                    pre: true,
+                    glyphs: GlyphSelection::Exclude(BTreeSet::from(["a".into()])),
+                }],
             },
         }
     }
@@ -2188,4 +2497,47 @@ mod tests {
         assert_eq!(AnchorKind::new("_"), Err(BadAnchorReason::NilMarkGroup));
         assert_eq!(AnchorKind::new("top_0"), Err(BadAnchorReason::ZeroIndex));
     }
+
+    #[test]
+    fn align_sparse_kerning() {
+        let glyph = |name: &str| KernSide::Glyph(name.into());
+        let ab: KernPair = (glyph("a"), glyph("b"));
+        let ba: KernPair = (glyph("b"), glyph("a"));
+        let regular: NormalizedLocation = vec![(WGHT, NormalizedCoord::new(0.0))].into();
+        let medium: NormalizedLocation = vec![(WGHT, NormalizedCoord::new(0.5))].into();
+        let bold: NormalizedLocation = vec![(WGHT, NormalizedCoord::new(1.0))].into();
+        let mut instances: HashMap<_, _> = [
+            (regular.clone(), vec![(ab.clone(), -10.0)]),
+            (medium.clone(), vec![(ba.clone(), -25.0)]),
+            (bold.clone(), vec![(ab.clone(), -30.0), (ba.clone(), -40.0)]),
+        ]
+        .into_iter()
+        .map(|(location, kerns)| {
+            let kerns = kerns
+                .into_iter()
+                .map(|(pair, value)| (pair, OrderedFloat(value)))
+                .collect();
+            (
+                location.clone(),
+                KerningInstance {
+                    location,
+                    kerns,
+                    ..Default::default()
+                },
+            )
+        })
+        .collect();
+
+        align_kerning(
+            &Default::default(),
+            &BTreeSet::from([medium.clone()]),
+            &mut instances,
+            |ki| &mut ki.kerns,
+        );
+
+        // missing from a full master is zero, missing from a sparse one is left for interpolation
+        assert_eq!(Some(&OrderedFloat(0.0)), instances[&regular].kerns.get(&ba));
+        assert_eq!(None, instances[&medium].kerns.get(&ab));
+        assert_eq!(2, instances[&bold].kerns.len());
+    }
 }
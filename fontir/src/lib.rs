@@ -1,9 +1,16 @@
+pub(crate) mod componentize;
 pub mod error;
+pub(crate) mod filters;
 pub mod glyph;
+pub mod instancer;
+pub mod interpolatable;
 pub mod ir;
 pub mod orchestration;
+pub mod overlaps;
 pub mod paths;
+pub(crate) mod propagate_anchors;
 pub(crate) mod serde;
 pub mod source;
 pub mod stateset;
+pub mod transform;
 pub mod variations;
@@ -37,6 +37,20 @@ bitflags! {
         const KEEP_DIRECTION = 0b01000000;
         // If set, production names are read & used
         const PRODUCTION_NAMES = 0b10000000;
+        // If set, contours are reordered and their start points moved to match the default master
+        const REPAIR_CONTOURS = 0b100000000;
+        // If set, overlapping contours are merged in static fonts and flagged in variable fonts
+        const REMOVE_OVERLAPS = 0b1000000000;
+        // If set, cubic curves are written to glyf as is rather than converted to quadratic
+        const CUBIC_GLYF = 0b10000000000;
+        // If set, glyphs with variable components are also written to VARC
+        const VARC = 0b100000000000;
+        // If set, glyphs whose contours all appear in other glyphs are made of components
+        const AUTO_COMPONENTS = 0b1000000000000;
+        // If set, AUTO_COMPONENTS also finds contours that are flipped or rotated by 90 degrees
+        const AUTO_COMPONENTS_TRANSFORMED = 0b10000000000000;
+        // If set, composites get the anchors of their components, as ufo2ft's propagateAnchors filter does
+        const PROPAGATE_ANCHORS = 0b100000000000000;
     }
 }
 
@@ -90,8 +104,15 @@ where
     /// [Work::read_access]. If these are missing something is horribly
     /// wrong and we should kerplode.
     pub fn get(&self) -> Arc<T> {
+        // if we don't have an answer just give up
+        self.try_load()
+            .unwrap_or_else(|| panic!("{:?} is not available", self.id))
+    }
+
+    /// Read an item that might not exist, restoring it from persistent storage if need be
+    pub fn try_load(&self) -> Option<Arc<T>> {
         if let Some(in_memory) = self.try_get() {
-            return in_memory;
+            return Some(in_memory);
         }
 
         // it's *not* in memory but perhaps it's written down?
@@ -101,10 +122,7 @@ where
                 *self.value.write() = Some(Arc::from(restored));
             }
         }
-
-        // if we still don't have an answer just give up
         self.try_get()
-            .unwrap_or_else(|| panic!("{:?} is not available", self.id))
     }
 
     /// Read an item that might not exist
@@ -222,8 +240,15 @@ where
     /// [Work::read_access]. If these are missing something is horribly
     /// wrong and we should kerplode.
     pub fn get(&self, id: &I) -> Arc<T> {
+        // if we don't have an answer just give up
+        self.try_load(id)
+            .unwrap_or_else(|| panic!("{:?} is not available", id))
+    }
+
+    /// Read an item that might not exist, restoring it from persistent storage if need be
+    pub fn try_load(&self, id: &I) -> Option<Arc<T>> {
         if let Some(in_memory) = self.try_get(id) {
-            return in_memory;
+            return Some(in_memory);
         }
 
         // it's *not* in memory but perhaps it's written down?
@@ -233,10 +258,7 @@ where
                 self.value.write().insert(id.clone(), Arc::from(restored));
             }
         }
-
-        // if we still don't have an answer just give up
         self.try_get(id)
-            .unwrap_or_else(|| panic!("{:?} is not available", id))
     }
 }
 
@@ -0,0 +1,405 @@
+//! Removes overlaps between contours by replacing them with their union.
+//!
+//! Segments are split wherever they cross, then only the pieces on the boundary of the
+//! filled area, by the nonzero winding rule, are kept and joined back up into contours.
+//! This is the job ufo2ft's RemoveOverlapsFilter does for fontmake, which only makes
+//! sense for static fonts: the union of each master would rarely be compatible.
+
+use kurbo::{
+    BezPath, CubicBez, Line, ParamCurve, ParamCurveExtrema, PathEl, PathSeg, Point, QuadBez, Rect,
+    Shape,
+};
+
+/// Curves closer than this, in font units, are considered to touch
+const ACCURACY: f64 = 1e-3;
+
+/// How far either side of a piece of a segment we test for being inside the fill
+const SAMPLE_OFFSET: f64 = 1e-2;
+
+/// Parameters this close to the start or end of a segment are the start or end
+const T_EPSILON: f64 = 1e-6;
+
+/// Past this many intersections between two curves assume they coincide
+const MAX_CURVE_INTERSECTIONS: usize = 16;
+
+/// Curves that share an end, such as neighbours in a contour, often run almost together
+/// near it. Anything this close to the shared end, in font units, is taken to be the end.
+const JOINT_TOLERANCE: f64 = 1.0;
+
+/// The union of `contours`, with the fill on the left of each contour.
+///
+/// Contours that don't overlap are returned as is. Returns None if any contour is open,
+/// or if the boundary of the union couldn't be joined up into closed contours.
+pub fn remove_overlaps(contours: &[BezPath]) -> Option<Vec<BezPath>> {
+    if contours
+        .iter()
+        .any(|c| c.elements().last() != Some(&PathEl::ClosePath))
+    {
+        return None;
+    }
+
+    let segments: Vec<PathSeg> = contours
+        .iter()
+        .flat_map(|contour| contour.segments())
+        .filter(|seg| seg.start() != seg.end() || !matches!(seg, PathSeg::Line(..)))
+        .collect();
+
+    // Where to split each segment
+    let mut splits: Vec<Vec<(f64, Point)>> = vec![Vec::new(); segments.len()];
+    let bboxes: Vec<_> = segments
+        .iter()
+        .map(|seg| seg.bounding_box().inflate(ACCURACY, ACCURACY))
+        .collect();
+    for i in 0..segments.len() {
+        for j in i + 1..segments.len() {
+            if !overlaps(bboxes[i], bboxes[j]) {
+                continue;
+            }
+            let (a, b) = (segments[i], segments[j]);
+            for (ta, tb) in intersections(a, b) {
+                let (ta, tb) = (snap_to_ends(a, ta), snap_to_ends(b, tb));
+                let point = if is_end(ta) {
+                    a.eval(ta.round())
+                } else if is_end(tb) {
+                    b.eval(tb.round())
+                } else {
+                    a.eval(ta)
+                };
+                if !is_end(ta) {
+                    splits[i].push((ta, point));
+                }
+                if !is_end(tb) {
+                    splits[j].push((tb, point));
+                }
+            }
+        }
+    }
+
+    let pieces: Vec<PathSeg> = segments
+        .iter()
+        .zip(splits)
+        .flat_map(|(seg, splits)| split(*seg, splits))
+        .collect();
+    let winding = |pt: Point| contours.iter().map(|c| c.winding(pt)).sum::<i32>() != 0;
+
+    let mut boundary: Vec<PathSeg> = Vec::new();
+    let mut unchanged = true;
+    for piece in pieces.iter() {
+        let mid = piece.eval(0.5);
+        let tangent = piece.eval(0.5 + T_EPSILON * 1e3) - piece.eval(0.5 - T_EPSILON * 1e3);
+        if tangent.hypot2() == 0.0 {
+            continue;
+        }
+        let left = tangent.normalize().turn_90() * SAMPLE_OFFSET;
+        let (inside_left, inside_right) = (winding(mid + left), winding(mid - left));
+        let piece = match (inside_left, inside_right) {
+            (true, false) => *piece,
+            (false, true) => piece.reverse(),
+            _ => {
+                unchanged = false;
+                continue;
+            }
+        };
+        if !boundary.iter().any(|other| same_piece(*other, piece)) {
+            boundary.push(piece);
+        } else {
+            unchanged = false;
+        }
+    }
+    if unchanged && boundary.len() == segments.len() {
+        return Some(contours.to_vec());
+    }
+
+    let mut union = join(boundary)?;
+    // Keep the direction the contours were drawn in
+    let area: f64 = contours.iter().map(|c| c.area()).sum();
+    if area < 0.0 {
+        union = union.iter().map(|c| c.reverse_subpaths()).collect();
+    }
+    Some(union)
+}
+
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.x0 <= b.x1 && b.x0 <= a.x1 && a.y0 <= b.y1 && b.y0 <= a.y1
+}
+
+fn is_end(t: f64) -> bool {
+    t < T_EPSILON || t > 1.0 - T_EPSILON
+}
+
+/// Move `t` to the start or end of `seg` if it's practically there
+fn snap_to_ends(seg: PathSeg, t: f64) -> f64 {
+    let pt = seg.eval(t);
+    if pt.distance(seg.start()) < ACCURACY {
+        0.0
+    } else if pt.distance(seg.end()) < ACCURACY {
+        1.0
+    } else {
+        t
+    }
+}
+
+/// The parameters at which `a` and `b` cross or touch
+fn intersections(a: PathSeg, b: PathSeg) -> Vec<(f64, f64)> {
+    match (a, b) {
+        (PathSeg::Line(a_line), PathSeg::Line(b_line)) => {
+            let hits: Vec<_> = a
+                .intersect_line(b_line)
+                .iter()
+                .map(|hit| (hit.segment_t, hit.line_t))
+                .collect();
+            if hits.is_empty() {
+                collinear_overlaps(a_line, b_line)
+            } else {
+                hits
+            }
+        }
+        (_, PathSeg::Line(line)) => a
+            .intersect_line(line)
+            .iter()
+            .map(|hit| (hit.segment_t, hit.line_t))
+            .collect(),
+        (PathSeg::Line(line), _) => b
+            .intersect_line(line)
+            .iter()
+            .map(|hit| (hit.line_t, hit.segment_t))
+            .collect(),
+        _ => {
+            let mut hits = Vec::new();
+            curve_intersections(a, (0.0, 1.0), b, (0.0, 1.0), &mut hits);
+            if hits.len() > MAX_CURVE_INTERSECTIONS {
+                return Vec::new();
+            }
+            let joints: Vec<_> = [a.start(), a.end()]
+                .into_iter()
+                .filter(|pt| *pt == b.start() || *pt == b.end())
+                .collect();
+            hits.retain(|(ta, _)| {
+                joints
+                    .iter()
+                    .all(|joint| a.eval(*ta).distance(*joint) > JOINT_TOLERANCE)
+            });
+            hits
+        }
+    }
+}
+
+/// Where the ends of one of two lines lying on top of each other touch the other
+fn collinear_overlaps(a: Line, b: Line) -> Vec<(f64, f64)> {
+    let project = |line: Line, pt: Point| {
+        let d = line.p1 - line.p0;
+        let t = (pt - line.p0).dot(d) / d.hypot2();
+        ((0.0..=1.0).contains(&t) && line.eval(t).distance(pt) < ACCURACY).then_some(t)
+    };
+    let mut hits = Vec::new();
+    for (t, pt) in [(0.0, b.p0), (1.0, b.p1)] {
+        if let Some(ta) = project(a, pt) {
+            hits.push((ta, t));
+        }
+    }
+    for (t, pt) in [(0.0, a.p0), (1.0, a.p1)] {
+        if let Some(tb) = project(b, pt) {
+            hits.push((t, tb));
+        }
+    }
+    hits
+}
+
+/// Find where two curves cross by subdividing both until they are tiny
+fn curve_intersections(
+    a: PathSeg,
+    a_range: (f64, f64),
+    b: PathSeg,
+    b_range: (f64, f64),
+    hits: &mut Vec<(f64, f64)>,
+) {
+    if hits.len() > MAX_CURVE_INTERSECTIONS {
+        return;
+    }
+    let a_part = a.subsegment(a_range.0..a_range.1);
+    let b_part = b.subsegment(b_range.0..b_range.1);
+    let (a_box, b_box) = (a_part.bounding_box(), b_part.bounding_box());
+    if !overlaps(
+        a_box.inflate(ACCURACY, ACCURACY),
+        b_box.inflate(ACCURACY, ACCURACY),
+    ) {
+        return;
+    }
+    let size = |r: Rect| r.width().max(r.height());
+    if size(a_box) < ACCURACY && size(b_box) < ACCURACY {
+        let hit = ((a_range.0 + a_range.1) / 2.0, (b_range.0 + b_range.1) / 2.0);
+        // Neighbouring subdivisions often find the same crossing
+        if !hits
+            .iter()
+            .any(|(ta, _)| a.eval(*ta).distance(a.eval(hit.0)) < ACCURACY * 10.0)
+        {
+            hits.push(hit);
+        }
+        return;
+    }
+    let halves = |(t0, t1): (f64, f64)| [(t0, (t0 + t1) / 2.0), ((t0 + t1) / 2.0, t1)];
+    for a_half in halves(a_range) {
+        for b_half in halves(b_range) {
+            curve_intersections(a, a_half, b, b_half, hits);
+        }
+    }
+}
+
+/// Split `seg` at each of `splits`, making the ends of the pieces exactly the given points
+fn split(seg: PathSeg, mut splits: Vec<(f64, Point)>) -> Vec<PathSeg> {
+    splits.sort_by(|a, b| a.0.total_cmp(&b.0));
+    splits.dedup_by(|a, b| (a.0 - b.0).abs() < T_EPSILON);
+
+    let mut pieces = Vec::with_capacity(splits.len() + 1);
+    let (mut t0, mut p0) = (0.0, seg.start());
+    for (t1, p1) in splits.into_iter().chain([(1.0, seg.end())]) {
+        if p0 != p1 {
+            pieces.push(with_ends(seg.subsegment(t0..t1), p0, p1));
+        }
+        (t0, p0) = (t1, p1);
+    }
+    pieces
+}
+
+fn with_ends(seg: PathSeg, p0: Point, p1: Point) -> PathSeg {
+    match seg {
+        PathSeg::Line(..) => Line::new(p0, p1).into(),
+        PathSeg::Quad(q) => QuadBez::new(p0, q.p1, p1).into(),
+        PathSeg::Cubic(c) => CubicBez::new(p0, c.p1, c.p2, p1).into(),
+    }
+}
+
+fn same_piece(a: PathSeg, b: PathSeg) -> bool {
+    a.start().distance(b.start()) < ACCURACY
+        && a.end().distance(b.end()) < ACCURACY
+        && a.eval(0.5).distance(b.eval(0.5)) < ACCURACY
+}
+
+/// Join up pieces of the boundary into closed contours
+fn join(mut pieces: Vec<PathSeg>) -> Option<Vec<BezPath>> {
+    let mut contours = Vec::new();
+    while !pieces.is_empty() {
+        let first = pieces.remove(0);
+        let start = first.start();
+        let mut contour = BezPath::new();
+        contour.move_to(start);
+        let mut current = first;
+        loop {
+            let end = current.end();
+            let closes = end.distance(start) < ACCURACY;
+            if closes && matches!(current, PathSeg::Line(..)) {
+                // implied by the close
+            } else {
+                contour.push(match current {
+                    PathSeg::Line(l) => PathEl::LineTo(l.p1),
+                    PathSeg::Quad(q) => PathEl::QuadTo(q.p1, q.p2),
+                    PathSeg::Cubic(c) => PathEl::CurveTo(c.p1, c.p2, c.p3),
+                });
+            }
+            if closes {
+                break;
+            }
+            let next = pieces
+                .iter()
+                .position(|piece| piece.start().distance(end) < ACCURACY)?;
+            current = pieces.remove(next);
+        }
+        contour.close_path();
+        contours.push(contour);
+    }
+    Some(contours)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x0: f64, y0: f64, x1: f64, y1: f64) -> BezPath {
+        // counter-clockwise, in font coordinates
+        let mut path = BezPath::new();
+        path.move_to((x0, y0));
+        path.line_to((x1, y0));
+        path.line_to((x1, y1));
+        path.line_to((x0, y1));
+        path.close_path();
+        path
+    }
+
+    fn points(contour: &BezPath) -> Vec<Point> {
+        contour.segments().map(|seg| seg.start()).collect()
+    }
+
+    #[test]
+    fn no_overlap_is_unchanged() {
+        let contours = vec![rect(0.0, 0.0, 100.0, 100.0), rect(200.0, 0.0, 300.0, 100.0)];
+        assert_eq!(Some(contours.clone()), remove_overlaps(&contours));
+    }
+
+    #[test]
+    fn union_of_crossing_rects() {
+        // a plus sign
+        let contours = vec![
+            rect(0.0, 100.0, 300.0, 200.0),
+            rect(100.0, 0.0, 200.0, 300.0),
+        ];
+        let union = remove_overlaps(&contours).unwrap();
+        assert_eq!(1, union.len());
+        assert_eq!(12, points(&union[0]).len());
+        let area = 300.0 * 100.0 * 2.0 - 100.0 * 100.0;
+        assert!((union[0].area() - area).abs() < 1e-6, "{}", union[0].area());
+    }
+
+    #[test]
+    fn contained_contour_is_removed() {
+        let contours = vec![
+            rect(0.0, 0.0, 300.0, 300.0),
+            rect(100.0, 100.0, 200.0, 200.0),
+        ];
+        assert_eq!(
+            Some(vec![rect(0.0, 0.0, 300.0, 300.0)]),
+            remove_overlaps(&contours)
+        );
+    }
+
+    #[test]
+    fn counter_is_kept() {
+        let contours = vec![
+            rect(0.0, 0.0, 300.0, 300.0),
+            rect(100.0, 100.0, 200.0, 200.0).reverse_subpaths(),
+        ];
+        assert_eq!(Some(contours.clone()), remove_overlaps(&contours));
+    }
+
+    #[test]
+    fn union_keeps_clockwise_direction() {
+        let contours = vec![
+            rect(0.0, 0.0, 200.0, 100.0).reverse_subpaths(),
+            rect(100.0, 0.0, 300.0, 100.0).reverse_subpaths(),
+        ];
+        let union = remove_overlaps(&contours).unwrap();
+        assert_eq!(1, union.len());
+        assert!((union[0].area() + 300.0 * 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn union_of_curves() {
+        let circle = |x: f64| kurbo::Circle::new((x, 0.0), 100.0).to_path(0.1);
+        let contours = vec![circle(0.0), circle(150.0)];
+        let union = remove_overlaps(&contours).unwrap();
+        assert_eq!(1, union.len());
+        let area: f64 = contours.iter().map(|c| c.area()).sum();
+        assert!(union[0].area().abs() < area.abs());
+        assert!(union[0]
+            .elements()
+            .iter()
+            .any(|el| matches!(el, PathEl::CurveTo(..))));
+    }
+
+    #[test]
+    fn open_contours_are_not_supported() {
+        let mut open = BezPath::new();
+        open.move_to((0.0, 0.0));
+        open.line_to((100.0, 0.0));
+        assert_eq!(None, remove_overlaps(&[open]));
+    }
+}
@@ -0,0 +1,438 @@
+//! Propagating anchors from components to their composites.
+//!
+//! Glyphs sources have their anchors propagated by glyphs-reader, much as Glyphs.app does.
+//! For other sources we do what ufo2ft's propagateAnchors filter does, see
+//! <https://github.com/googlefonts/ufo2ft/blob/main/Lib/ufo2ft/filters/propagateAnchors.py>
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::Arc,
+};
+
+use fontdrasil::{coords::NormalizedLocation, types::GlyphName};
+use kurbo::Point;
+use log::{trace, warn};
+use smol_str::SmolStr;
+
+use crate::{
+    error::WorkError,
+    ir::{AnchorBuilder, Glyph, GlyphAnchors},
+};
+
+type Positions = HashMap<NormalizedLocation, Point>;
+
+struct Propagation<'a> {
+    glyphs: HashMap<&'a GlyphName, &'a Glyph>,
+    /// The anchors of each glyph by name, including any we have propagated
+    anchors: HashMap<GlyphName, BTreeMap<SmolStr, Positions>>,
+    visited: HashSet<GlyphName>,
+    changed: BTreeSet<GlyphName>,
+}
+
+impl Propagation<'_> {
+    fn anchors_of(&self, glyph_name: &GlyphName) -> impl Iterator<Item = &SmolStr> {
+        self.anchors
+            .get(glyph_name)
+            .into_iter()
+            .flat_map(|anchors| anchors.keys())
+    }
+
+    fn has_anchor(&self, glyph_name: &GlyphName, anchor_name: &str) -> bool {
+        self.anchors_of(glyph_name).any(|name| name == anchor_name)
+    }
+
+    /// The positions of an anchor of the component at `index`, in the space of `glyph`
+    ///
+    /// Only locations where both the glyph and the component's glyph are defined have positions.
+    fn transformed(&self, glyph: &Glyph, index: usize, anchor_name: &str) -> Positions {
+        glyph
+            .sources()
+            .iter()
+            .filter_map(|(loc, inst)| {
+                let component = inst.components.get(index)?;
+                let pos = self
+                    .anchors
+                    .get(&component.base)?
+                    .get(anchor_name)?
+                    .get(loc)?;
+                Some((loc.clone(), component.transform * *pos))
+            })
+            .collect()
+    }
+
+    fn propagate(&mut self, glyph_name: &GlyphName) {
+        if !self.visited.insert(glyph_name.clone()) {
+            return;
+        }
+        let Some(glyph) = self.glyphs.get(glyph_name).copied() else {
+            return;
+        };
+        let components = &glyph.default_instance().components;
+        if components.is_empty() {
+            return;
+        }
+
+        // Components with mark anchors are marks, the rest are the bases marks attach to
+        let mut bases = Vec::new();
+        let mut marks = Vec::new();
+        let mut anchor_names = BTreeSet::new();
+        for (index, component) in components.iter().enumerate() {
+            if !self.glyphs.contains_key(&component.base) {
+                continue;
+            }
+            self.propagate(&component.base);
+            if self
+                .anchors_of(&component.base)
+                .any(|name| name.starts_with('_'))
+            {
+                marks.push(index);
+            } else {
+                bases.push(index);
+                anchor_names.extend(self.anchors_of(&component.base).cloned());
+            }
+        }
+
+        let mut to_add = BTreeMap::new();
+        for anchor_name in anchor_names {
+            // Anchors drawn in the composite, including ligature anchors like top_1, win
+            if self
+                .anchors_of(glyph_name)
+                .any(|name| is_same_or_numbered(name, &anchor_name))
+            {
+                continue;
+            }
+            let with_anchor: Vec<_> = bases
+                .iter()
+                .filter(|index| self.has_anchor(&components[**index].base, &anchor_name))
+                .collect();
+            if let [index] = with_anchor.as_slice() {
+                to_add.insert(
+                    anchor_name.clone(),
+                    self.transformed(glyph, **index, &anchor_name),
+                );
+            } else {
+                for (i, index) in with_anchor.iter().enumerate() {
+                    to_add.insert(
+                        format!("{anchor_name}_{}", i + 1).into(),
+                        self.transformed(glyph, **index, &anchor_name),
+                    );
+                }
+            }
+        }
+
+        // A mark attached to a base moves the anchor it attached to, so marks stack
+        for index in marks {
+            let base = &components[index].base;
+            let moved: Vec<_> = self
+                .anchors_of(base)
+                .filter(|name| {
+                    to_add.contains_key(*name) && self.has_anchor(base, &format!("_{name}"))
+                })
+                .cloned()
+                .collect();
+            for anchor_name in moved {
+                let positions = self.transformed(glyph, index, &anchor_name);
+                to_add.insert(anchor_name, positions);
+            }
+        }
+
+        let anchors = self.anchors.entry(glyph_name.clone()).or_default();
+        for (anchor_name, positions) in to_add {
+            if !positions.keys().any(|loc| !loc.has_any_non_zero()) {
+                warn!("Unable to propagate '{anchor_name}' to '{glyph_name}', it has no position at the default location");
+                continue;
+            }
+            trace!("Propagated '{anchor_name}' to '{glyph_name}'");
+            anchors.insert(anchor_name, positions);
+            self.changed.insert(glyph_name.clone());
+        }
+    }
+}
+
+/// Whether `name` is `anchor_name` or a numbered ligature anchor of it, such as `top_1` for `top`
+fn is_same_or_numbered(name: &str, anchor_name: &str) -> bool {
+    name.strip_prefix(anchor_name).is_some_and(|suffix| {
+        suffix.is_empty()
+            || suffix
+                .strip_prefix('_')
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+/// Copy anchors from the components of composite glyphs into the composites.
+///
+/// Anchors already in a composite are kept. Where several components have the same
+/// anchor they are numbered, as for a ligature, and a mark component moves the anchor
+/// it attaches to so further marks stack on top of it.
+///
+/// Returns the anchors of the glyphs that gained any.
+pub(crate) fn propagate_anchors(
+    glyphs: &[Arc<Glyph>],
+    anchors: &HashMap<GlyphName, Arc<GlyphAnchors>>,
+) -> Result<Vec<GlyphAnchors>, WorkError> {
+    let mut propagation = Propagation {
+        glyphs: glyphs.iter().map(|g| (&g.name, g.as_ref())).collect(),
+        anchors: anchors
+            .iter()
+            .map(|(glyph_name, anchors)| {
+                (
+                    glyph_name.clone(),
+                    anchors
+                        .anchors
+                        .iter()
+                        .map(|anchor| (anchor.kind.name(), anchor.positions.clone()))
+                        .collect(),
+                )
+            })
+            .collect(),
+        visited: Default::default(),
+        changed: Default::default(),
+    };
+    for glyph in glyphs {
+        propagation.propagate(&glyph.name);
+    }
+
+    propagation
+        .changed
+        .iter()
+        .map(|glyph_name| {
+            let mut builder = AnchorBuilder::new(glyph_name.clone());
+            for (anchor_name, positions) in propagation.anchors[glyph_name].iter() {
+                for (loc, pos) in positions {
+                    builder.add(anchor_name.clone(), loc.clone(), *pos)?;
+                }
+            }
+            builder.build()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::Affine;
+
+    use crate::ir::{Component, GlyphBuilder, GlyphInstance};
+
+    use super::*;
+
+    fn loc(wght: f32) -> NormalizedLocation {
+        NormalizedLocation::for_pos(&[("wght", wght)])
+    }
+
+    fn glyph(name: &str, components: &[(&str, f64, f64)]) -> Arc<Glyph> {
+        let mut builder = GlyphBuilder::new(name.into());
+        for wght in [0.0, 1.0] {
+            builder
+                .try_add_source(
+                    &loc(wght),
+                    GlyphInstance {
+                        width: 600.0,
+                        components: components
+                            .iter()
+                            .map(|(base, dx, dy)| Component {
+                                base: (*base).into(),
+                                // make bold components move further to see we transform per location
+                                transform: Affine::translate((*dx * (1.0 + wght as f64), *dy)),
+                                location: Default::default(),
+                            })
+                            .collect(),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+        Arc::new(builder.build().unwrap())
+    }
+
+    fn anchors(name: &str, anchors: &[(&str, f64, f64)]) -> (GlyphName, Arc<GlyphAnchors>) {
+        let mut builder = AnchorBuilder::new(name.into());
+        for (anchor, x, y) in anchors {
+            for wght in [0.0, 1.0] {
+                builder
+                    .add((*anchor).into(), loc(wght), Point::new(*x, *y))
+                    .unwrap();
+            }
+        }
+        (name.into(), Arc::new(builder.build().unwrap()))
+    }
+
+    /// name => [(anchor, default pos, bold pos)]
+    fn summary(anchors: &[GlyphAnchors]) -> Vec<(String, Vec<(String, Point, Point)>)> {
+        anchors
+            .iter()
+            .map(|a| {
+                let mut entries: Vec<_> = a
+                    .anchors
+                    .iter()
+                    .map(|anchor| {
+                        (
+                            anchor.kind.name().to_string(),
+                            anchor.positions[&loc(0.0)],
+                            anchor.positions[&loc(1.0)],
+                        )
+                    })
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                (a.glyph_name.to_string(), entries)
+            })
+            .collect()
+    }
+
+    fn test_font() -> (Vec<Arc<Glyph>>, HashMap<GlyphName, Arc<GlyphAnchors>>) {
+        let glyphs = vec![
+            glyph("a", &[]),
+            glyph("acutecomb", &[]),
+            glyph("aacute", &[("a", 10.0, 0.0), ("acutecomb", 100.0, 0.0)]),
+            glyph("aacute.alt", &[("aacute", 0.0, 0.0)]),
+            glyph("a_a", &[("a", 0.0, 0.0), ("a", 500.0, 0.0)]),
+        ];
+        let anchors = [
+            anchors("a", &[("top", 250.0, 500.0), ("bottom", 250.0, 0.0)]),
+            anchors("acutecomb", &[("_top", 0.0, 500.0), ("top", 0.0, 700.0)]),
+            anchors("aacute", &[]),
+            anchors("aacute.alt", &[("bottom", 1.0, 2.0)]),
+            anchors("a_a", &[]),
+        ]
+        .into_iter()
+        .collect();
+        (glyphs, anchors)
+    }
+
+    #[test]
+    fn marks_move_the_anchor_they_attach_to() {
+        let (glyphs, anchors) = test_font();
+        let propagated = propagate_anchors(&glyphs, &anchors).unwrap();
+        let aacute = summary(&propagated)
+            .into_iter()
+            .find(|(name, _)| name == "aacute")
+            .unwrap();
+        assert_eq!(
+            (
+                "aacute".to_string(),
+                vec![
+                    (
+                        "bottom".to_string(),
+                        Point::new(260.0, 0.0),
+                        Point::new(270.0, 0.0)
+                    ),
+                    (
+                        "top".to_string(),
+                        Point::new(100.0, 700.0),
+                        Point::new(200.0, 700.0)
+                    ),
+                ]
+            ),
+            aacute
+        );
+    }
+
+    #[test]
+    fn nested_composites_keep_their_own_anchors() {
+        let (glyphs, anchors) = test_font();
+        let propagated = propagate_anchors(&glyphs, &anchors).unwrap();
+        let alt = summary(&propagated)
+            .into_iter()
+            .find(|(name, _)| name == "aacute.alt")
+            .unwrap();
+        assert_eq!(
+            (
+                "aacute.alt".to_string(),
+                vec![
+                    (
+                        "bottom".to_string(),
+                        Point::new(1.0, 2.0),
+                        Point::new(1.0, 2.0)
+                    ),
+                    (
+                        "top".to_string(),
+                        Point::new(100.0, 700.0),
+                        Point::new(200.0, 700.0)
+                    ),
+                ]
+            ),
+            alt
+        );
+    }
+
+    #[test]
+    fn repeated_bases_are_numbered() {
+        let (glyphs, anchors) = test_font();
+        let propagated = propagate_anchors(&glyphs, &anchors).unwrap();
+        let ligature = summary(&propagated)
+            .into_iter()
+            .find(|(name, _)| name == "a_a")
+            .unwrap();
+        assert_eq!(
+            (
+                "a_a".to_string(),
+                vec![
+                    (
+                        "bottom_1".to_string(),
+                        Point::new(250.0, 0.0),
+                        Point::new(250.0, 0.0)
+                    ),
+                    (
+                        "bottom_2".to_string(),
+                        Point::new(750.0, 0.0),
+                        Point::new(1250.0, 0.0)
+                    ),
+                    (
+                        "top_1".to_string(),
+                        Point::new(250.0, 500.0),
+                        Point::new(250.0, 500.0)
+                    ),
+                    (
+                        "top_2".to_string(),
+                        Point::new(750.0, 500.0),
+                        Point::new(1250.0, 500.0)
+                    ),
+                ]
+            ),
+            ligature
+        );
+    }
+
+    #[test]
+    fn only_the_same_or_numbered_anchor_blocks_propagation() {
+        let glyphs = vec![glyph("a", &[]), glyph("a.ss01", &[("a", 0.0, 0.0)])];
+        let anchors = HashMap::from([
+            anchors("a", &[("top", 250.0, 500.0)]),
+            anchors("a.ss01", &[("topright", 1.0, 2.0)]),
+        ]);
+        let propagated = propagate_anchors(&glyphs, &anchors).unwrap();
+        assert_eq!(
+            vec![(
+                "a.ss01".to_string(),
+                vec![
+                    (
+                        "top".to_string(),
+                        Point::new(250.0, 500.0),
+                        Point::new(250.0, 500.0)
+                    ),
+                    (
+                        "topright".to_string(),
+                        Point::new(1.0, 2.0),
+                        Point::new(1.0, 2.0)
+                    ),
+                ]
+            )],
+            summary(&propagated)
+        );
+        assert!(is_same_or_numbered("top_12", "top"));
+        assert!(!is_same_or_numbered("top_", "top"));
+        assert!(!is_same_or_numbered("top_left", "top"));
+    }
+
+    #[test]
+    fn simple_glyphs_are_unchanged() {
+        let (glyphs, anchors) = test_font();
+        let propagated = propagate_anchors(&glyphs, &anchors).unwrap();
+        assert_eq!(
+            vec!["a_a", "aacute", "aacute.alt"],
+            propagated
+                .iter()
+                .map(|a| a.glyph_name.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,346 @@
+//! Deriving a style, such as an oblique, by transforming the glyphs of another.
+//!
+//! Glyphs and anchors are transformed by a pre [GlyphFilterKind::Transformations] filter,
+//! font-wide data that depends on the slant of the glyphs is updated to match.
+
+use fontdrasil::{
+    coords::NormalizedLocation,
+    orchestration::{Access, AccessBuilder, Work},
+    types::GlyphName,
+};
+use indexmap::IndexSet;
+use log::debug;
+
+use crate::{
+    error::{Error, WorkError},
+    ir::{GlobalMetric, GlobalMetrics, GlyphFilter, GlyphFilterKind, Transformation},
+    orchestration::{Context, IrWork, WorkId},
+    source::{Input, Source},
+};
+
+/// A [Source] whose glyphs are slanted, scaled and offset
+pub struct TransformedSource {
+    source: Box<dyn Source>,
+    transformation: Transformation,
+}
+
+impl TransformedSource {
+    pub fn new(source: Box<dyn Source>, transformation: Transformation) -> Self {
+        TransformedSource {
+            source,
+            transformation,
+        }
+    }
+}
+
+impl Source for TransformedSource {
+    fn inputs(&mut self) -> Result<Input, Error> {
+        let mut input = self.source.inputs()?;
+        // A different transformation changes everything, as a different source would
+        input
+            .static_metadata
+            .track_memory("/transformation".to_string(), &self.transformation)?;
+        input
+            .global_metrics
+            .track_memory("/transformation".to_string(), &self.transformation)?;
+        Ok(input)
+    }
+
+    fn create_static_metadata_work(&self, input: &Input) -> Result<Box<IrWork>, Error> {
+        Ok(Box::new(TransformedWork {
+            work: self.source.create_static_metadata_work(input)?,
+            transformation: self.transformation.clone(),
+        }))
+    }
+
+    fn create_global_metric_work(&self, input: &Input) -> Result<Box<IrWork>, Error> {
+        Ok(Box::new(TransformedWork {
+            work: self.source.create_global_metric_work(input)?,
+            transformation: self.transformation.clone(),
+        }))
+    }
+
+    fn create_glyph_ir_work(
+        &self,
+        glyph_names: &IndexSet<GlyphName>,
+        input: &Input,
+    ) -> Result<Vec<Box<IrWork>>, Error> {
+        self.source.create_glyph_ir_work(glyph_names, input)
+    }
+
+    fn create_feature_ir_work(&self, input: &Input) -> Result<Box<IrWork>, Error> {
+        self.source.create_feature_ir_work(input)
+    }
+
+    fn create_kerning_group_ir_work(&self, input: &Input) -> Result<Box<IrWork>, Error> {
+        self.source.create_kerning_group_ir_work(input)
+    }
+
+    fn create_kerning_instance_ir_work(
+        &self,
+        input: &Input,
+        at: NormalizedLocation,
+    ) -> Result<Box<IrWork>, Error> {
+        self.source.create_kerning_instance_ir_work(input, at)
+    }
+}
+
+/// Runs the work of the source, then transforms what it produced
+#[derive(Debug)]
+struct TransformedWork {
+    work: Box<IrWork>,
+    transformation: Transformation,
+}
+
+impl Work<Context, WorkId, WorkError> for TransformedWork {
+    fn id(&self) -> WorkId {
+        self.work.id()
+    }
+
+    fn also_completes(&self) -> Vec<WorkId> {
+        self.work.also_completes()
+    }
+
+    fn read_access(&self) -> Access<WorkId> {
+        self.work.read_access()
+    }
+
+    fn write_access(&self) -> Access<WorkId> {
+        self.work.write_access()
+    }
+
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        self.work.exec(context)?;
+        // The wrapped work need not read what it writes and we can't declare a read of our
+        // own output up front, we'd wait on ourselves. It's complete now so read it anyway.
+        let context = context.copy_for_work(
+            AccessBuilder::new()
+                .specific_instance(self.id())
+                .variant(WorkId::StaticMetadata)
+                .build(),
+            self.write_access(),
+        );
+        match self.id() {
+            WorkId::StaticMetadata => {
+                let mut static_metadata = (*context.static_metadata.get()).clone();
+                let italic_angle =
+                    transform_italic_angle(&self.transformation, static_metadata.italic_angle.0);
+                debug!(
+                    "Transformation changes the italic angle from {} to {italic_angle}",
+                    static_metadata.italic_angle
+                );
+                static_metadata.italic_angle = italic_angle.into();
+                // Before any filter of the source, they are meant for the glyphs as drawn
+                static_metadata.misc.glyph_filters.insert(
+                    0,
+                    GlyphFilter {
+                        pre: true,
+                        ..GlyphFilter::new(GlyphFilterKind::Transformations(
+                            self.transformation.clone(),
+                        ))
+                    },
+                );
+                context.static_metadata.set(static_metadata);
+            }
+            WorkId::GlobalMetrics => {
+                let static_metadata = context.static_metadata.get();
+                let mut metrics = (*context.global_metrics.get()).clone();
+                transform_metrics(
+                    &self.transformation,
+                    static_metadata.default_location(),
+                    static_metadata.italic_angle.0,
+                    &mut metrics,
+                );
+                context.global_metrics.set(metrics);
+            }
+            id => unreachable!("Unable to transform {id:?}"),
+        }
+        Ok(())
+    }
+}
+
+/// The slant and scale of [Transformation], ignoring the origin and offset
+///
+/// Returns `(scale_x, scale_y, tan(slant))`.
+fn linear(transformation: &Transformation) -> (f64, f64, f64) {
+    (
+        transformation.scale_x.0 / 100.0,
+        transformation.scale_y.0 / 100.0,
+        transformation.slant.0.to_radians().tan(),
+    )
+}
+
+/// The italic angle, counter-clockwise degrees from vertical, of stems that had `italic_angle`
+fn transform_italic_angle(transformation: &Transformation, italic_angle: f64) -> f64 {
+    let (scale_x, scale_y, slant) = linear(transformation);
+    (scale_x / scale_y * (italic_angle.to_radians().tan() - slant))
+        .atan()
+        .to_degrees()
+}
+
+/// The italic angle that [transform_italic_angle] turned into `italic_angle`
+fn untransform_italic_angle(transformation: &Transformation, italic_angle: f64) -> f64 {
+    let (scale_x, scale_y, slant) = linear(transformation);
+    (scale_y / scale_x * italic_angle.to_radians().tan() + slant)
+        .atan()
+        .to_degrees()
+}
+
+/// Transform the caret and the sub and superscripts as the glyphs are transformed
+///
+/// `italic_angle` is the transformed italic angle of the default location.
+/// Only locations that define both the x and y of a vector change.
+fn transform_metrics(
+    transformation: &Transformation,
+    default_location: &NormalizedLocation,
+    italic_angle: f64,
+    metrics: &mut GlobalMetrics,
+) {
+    let (scale_x, scale_y, slant) = linear(transformation);
+    // Sources derive the default caret and script offsets from the italic angle of
+    // static metadata, which is already transformed. Derive them from the angle
+    // as drawn instead so they aren't slanted twice.
+    let offset_at = |y: f64, angle: f64| y * (-angle).to_radians().tan();
+    let source_italic_angle = untransform_italic_angle(transformation, italic_angle);
+
+    // x and y of each vector, and which way up y is
+    for (x_metric, y_metric, y_sign) in [
+        (
+            GlobalMetric::CaretSlopeRun,
+            GlobalMetric::CaretSlopeRise,
+            1.0,
+        ),
+        (
+            GlobalMetric::SubscriptXOffset,
+            GlobalMetric::SubscriptYOffset,
+            -1.0,
+        ),
+        (
+            GlobalMetric::SuperscriptXOffset,
+            GlobalMetric::SuperscriptYOffset,
+            1.0,
+        ),
+    ] {
+        let values = |metric: GlobalMetric| metrics.0.get(&metric).cloned().unwrap_or_default();
+        let xs = values(x_metric);
+        let ys = values(y_metric);
+        for (loc, x) in xs {
+            let Some(y) = ys.get(&loc) else {
+                continue;
+            };
+            let (mut x, y) = (x.0 as f64, y.0 as f64);
+            if loc == *default_location && (x - offset_at(y_sign * y, italic_angle)).abs() < 0.5 {
+                x = offset_at(y_sign * y, source_italic_angle);
+            }
+            metrics.set(y_metric, loc.clone(), (scale_y * y) as f32);
+            metrics.set(x_metric, loc, (scale_x * (x + slant * y_sign * y)) as f32);
+        }
+    }
+
+    for (metric, scale) in [
+        (GlobalMetric::SubscriptXSize, scale_x),
+        (GlobalMetric::SubscriptYSize, scale_y),
+        (GlobalMetric::SuperscriptXSize, scale_x),
+        (GlobalMetric::SuperscriptYSize, scale_y),
+    ] {
+        for (loc, value) in metrics.0.get(&metric).cloned().unwrap_or_default() {
+            metrics.set(metric, loc, (scale * value.0 as f64) as f32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slant(degrees: f64) -> Transformation {
+        Transformation {
+            slant: degrees.into(),
+            ..Default::default()
+        }
+    }
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!((expected - actual).abs() < 1e-3, "{expected} != {actual}");
+    }
+
+    #[test]
+    fn slanting_an_upright_leans_right() {
+        assert_close(-12.0, transform_italic_angle(&slant(12.0), 0.0));
+    }
+
+    #[test]
+    fn scaling_changes_the_angle() {
+        let transformation = Transformation {
+            scale_x: 200.0.into(),
+            ..slant(45.0)
+        };
+        assert_close(
+            -(2.0f64.atan().to_degrees()),
+            transform_italic_angle(&transformation, 0.0),
+        );
+    }
+
+    #[test]
+    fn untransforming_the_angle_undoes_the_transformation() {
+        let transformation = Transformation {
+            scale_x: 80.0.into(),
+            scale_y: 120.0.into(),
+            ..slant(10.0)
+        };
+        let italic_angle = transform_italic_angle(&transformation, -7.0);
+        assert_close(
+            -7.0,
+            untransform_italic_angle(&transformation, italic_angle),
+        );
+    }
+
+    #[test]
+    fn caret_and_script_offsets_slant() {
+        // As a source would, derive the default caret from the transformed angle
+        let transformation = slant(45.0);
+        let italic_angle = transform_italic_angle(&transformation, 0.0);
+        let default_location = NormalizedLocation::new();
+        let mut metrics = GlobalMetrics::new(
+            default_location.clone(),
+            1000,
+            None,
+            None,
+            None,
+            italic_angle,
+        );
+        transform_metrics(
+            &transformation,
+            &default_location,
+            italic_angle,
+            &mut metrics,
+        );
+        let at = |metric| metrics.get(metric, &default_location).0 as f64;
+        assert_close(1000.0, at(GlobalMetric::CaretSlopeRise));
+        assert_close(1000.0, at(GlobalMetric::CaretSlopeRun));
+        // Subscripts move down and so left, superscripts up and so right
+        assert_close(-75.0, at(GlobalMetric::SubscriptXOffset));
+        assert_close(350.0, at(GlobalMetric::SuperscriptXOffset));
+    }
+
+    #[test]
+    fn scaling_scales_script_sizes_and_offsets() {
+        let transformation = Transformation {
+            scale_x: 200.0.into(),
+            scale_y: 50.0.into(),
+            ..Default::default()
+        };
+        let default_location = NormalizedLocation::new();
+        let mut metrics = GlobalMetrics::new(default_location.clone(), 1000, None, None, None, 0.0);
+        transform_metrics(&transformation, &default_location, 0.0, &mut metrics);
+        let at = |metric| metrics.get(metric, &default_location).0 as f64;
+        assert_close(500.0, at(GlobalMetric::CaretSlopeRise));
+        assert_close(0.0, at(GlobalMetric::CaretSlopeRun));
+        assert_close(1300.0, at(GlobalMetric::SubscriptXSize));
+        assert_close(300.0, at(GlobalMetric::SubscriptYSize));
+        assert_close(37.5, at(GlobalMetric::SubscriptYOffset));
+        assert_close(1300.0, at(GlobalMetric::SuperscriptXSize));
+        assert_close(300.0, at(GlobalMetric::SuperscriptYSize));
+        assert_close(175.0, at(GlobalMetric::SuperscriptYOffset));
+    }
+}
@@ -253,6 +253,8 @@ pub enum DeltaError {
     InconsistentNumbersOfPoints,
     #[error("{0:?} is not present in the variation model")]
     UnknownLocation(NormalizedLocation),
+    #[error("No variation model for the locations of the point sequences: {0}")]
+    NoModel(VariationModelError),
 }
 
 /// Gryffindor!
@@ -10,7 +10,9 @@ use std::{
 
 use fontdrasil::{paths::string_to_filename, types::GlyphName};
 use fontir::error::Error;
-use serde::Deserialize;
+use kurbo::Affine;
+use log::warn;
+use serde::{Deserialize, Deserializer};
 use write_fonts::types::Tag;
 
 pub(crate) type AxisName = String;
@@ -35,6 +37,59 @@ pub(crate) struct FontraFontData {
     pub(crate) units_per_em: u16,
     #[serde(default)]
     pub(crate) axes: Vec<FontraAxis>,
+    /// Font sources by identifier, where global metrics and kerning live
+    #[serde(default, deserialize_with = "font_sources")]
+    pub(crate) sources: BTreeMap<String, FontraFontSource>,
+}
+
+/// Older files have a list of sources, newer ones a map of them by identifier
+fn font_sources<'de, D>(deserializer: D) -> Result<BTreeMap<String, FontraFontSource>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Sources {
+        List(Vec<FontraFontSource>),
+        Map(BTreeMap<String, FontraFontSource>),
+    }
+    Ok(match Sources::deserialize(deserializer)? {
+        Sources::List(sources) => sources
+            .into_iter()
+            .enumerate()
+            .map(|(i, source)| (i.to_string(), source))
+            .collect(),
+        Sources::Map(sources) => sources,
+    })
+}
+
+/// Corresponds to a Fontra FontSource
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct FontraFontSource {
+    pub(crate) name: String,
+    /// In design space, by axis name. A missing axis is at its default.
+    #[serde(default)]
+    pub(crate) location: HashMap<AxisName, f64>,
+    #[serde(rename = "italicAngle", default)]
+    pub(crate) italic_angle: f64,
+    /// ascender, descender, xHeight, capHeight and so on
+    #[serde(rename = "lineMetricsHorizontalLayout", default)]
+    pub(crate) line_metrics_horizontal_layout: HashMap<String, FontraLineMetric>,
+    #[serde(rename = "isSparse", default)]
+    pub(crate) is_sparse: bool,
+}
+
+impl FontraFontSource {
+    pub(crate) fn line_metric(&self, name: &str) -> Option<f64> {
+        self.line_metrics_horizontal_layout
+            .get(name)
+            .map(|metric| metric.value)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct FontraLineMetric {
+    pub(crate) value: f64,
 }
 
 impl FontraFontData {
@@ -43,11 +98,12 @@ impl FontraFontData {
     }
 }
 
+/// Only discrete axes have values, try them first so one isn't taken for a continuous axis
 #[derive(Debug, Clone, Deserialize)]
 #[serde(untagged)]
 pub(crate) enum FontraAxis {
-    Continuous(FontraContinuousAxis),
     Discrete(FontraDiscreteAxis),
+    Continuous(FontraContinuousAxis),
 }
 
 #[allow(dead_code)] // TEMPORARY
@@ -128,7 +184,6 @@ pub(crate) struct FontraDiscreteAxis {
 ///
 /// <https://github.com/googlefonts/fontra/blob/a4edd06837118e583804fd963c22ed806a315b04/src/fontra/core/classes.py#L104-L116>
 #[derive(Debug, Clone, Deserialize)]
-#[allow(dead_code)] // TEMPORARY
 pub(crate) struct FontraGlyph {
     pub(crate) name: GlyphName,
     /// Variable component, or glyph-local, axes
@@ -142,7 +197,6 @@ pub(crate) struct FontraGlyph {
 ///
 /// <https://github.com/googlefonts/fontra/blob/15bc0b8401054390484cfb86d509d633d29657a1/src/fontra/core/classes.py#L96-L101>
 #[derive(Debug, Clone, Deserialize)]
-#[allow(dead_code)] // TEMPORARY
 pub(crate) struct FontraGlyphAxis {
     pub(crate) name: String,
     #[serde(rename = "minValue")]
@@ -162,11 +216,38 @@ pub(crate) struct FontraSource {
     pub(crate) layer_name: LayerName,
     #[serde(default)]
     pub(crate) location: HashMap<AxisName, f64>,
-    // TODO: locationBase
+    /// The identifier of a font source whose location this source shares
+    #[serde(rename = "locationBase", default)]
+    pub(crate) location_base: Option<String>,
     #[serde(default)]
     pub(crate) inactive: bool,
 }
 
+impl FontraSource {
+    /// The location of the source, by axis name.
+    ///
+    /// A source with a location base starts from the location of that font source,
+    /// anything in its own location takes precedence.
+    pub(crate) fn resolved_location(
+        &self,
+        font_sources: &BTreeMap<String, FontraFontSource>,
+    ) -> HashMap<AxisName, f64> {
+        let Some(base) = self.location_base.as_ref() else {
+            return self.location.clone();
+        };
+        let Some(font_source) = font_sources.get(base) else {
+            warn!(
+                "Glyph source {} has location base {base}, which isn't a font source",
+                self.name
+            );
+            return self.location.clone();
+        };
+        let mut location = font_source.location.clone();
+        location.extend(self.location.iter().map(|(k, v)| (k.clone(), *v)));
+        location
+    }
+}
+
 /// <https://github.com/googlefonts/fontra/blob/a4edd06837118e583804fd963c22ed806a315b04/src/fontra/core/classes.py#L129-L132>
 #[derive(Debug, Clone, Deserialize)]
 #[allow(dead_code)] // TEMPORARY
@@ -180,28 +261,138 @@ pub(crate) struct FontraLayer {
 pub(crate) struct FontraGlyphInstance {
     #[serde(rename = "xAdvance")]
     pub(crate) x_advance: f64,
-    // TODO: Fontra has two representations, packed and unpacked. This only covers one.
     #[serde(default)]
     pub(crate) path: FontraPath,
     #[serde(default)]
     pub(crate) components: Vec<FontraComponent>,
+    #[serde(default)]
+    pub(crate) anchors: Vec<FontraAnchor>,
+}
+
+/// Corresponds to a Fontra Anchor
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct FontraAnchor {
+    pub(crate) name: String,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
 }
 
 impl FontraGlyph {
-    #[allow(dead_code)] // TEMPORARY
     pub(crate) fn from_file(p: &Path) -> Result<Self, Error> {
         from_file(p)
     }
 }
 
+/// Fontra has two representations of a path, packed and unpacked, we unpack packed paths.
+///
 /// <https://github.com/googlefonts/fontra/blob/a4edd06837118e583804fd963c22ed806a315b04/src/fontra/core/path.py#L34-L53>
 #[derive(Default, Debug, Clone, Deserialize)]
-#[allow(dead_code)] // TEMPORARY
+#[serde(try_from = "RawFontraPath")]
 pub(crate) struct FontraPath {
-    #[serde(default)]
     pub(crate) contours: Vec<FontraContour>,
 }
 
+/// The fields of either representation of a path.
+///
+/// Not an untagged enum: that would take a packed path with a malformed field for an
+/// unpacked path without contours rather than fail.
+#[derive(Deserialize)]
+struct RawFontraPath {
+    contours: Option<Vec<FontraContour>>,
+    coordinates: Option<Vec<f64>>,
+    #[serde(rename = "pointTypes")]
+    point_types: Option<Vec<u8>>,
+    #[serde(rename = "contourInfo")]
+    contour_info: Option<Vec<FontraContourInfo>>,
+}
+
+impl TryFrom<RawFontraPath> for FontraPath {
+    type Error = Error;
+
+    fn try_from(raw: RawFontraPath) -> Result<Self, Self::Error> {
+        let contours = match raw {
+            RawFontraPath {
+                contours,
+                coordinates: None,
+                point_types: None,
+                contour_info: None,
+            } => contours.unwrap_or_default(),
+            RawFontraPath {
+                contours: None,
+                coordinates: Some(coordinates),
+                point_types: Some(point_types),
+                contour_info: Some(contour_info),
+            } => FontraPackedPath {
+                coordinates,
+                point_types,
+                contour_info,
+            }
+            .unpack()?,
+            _ => {
+                return Err(Error::InvalidInputData(
+                    "Expected contours or coordinates, pointTypes and contourInfo".to_string(),
+                ))
+            }
+        };
+        Ok(FontraPath { contours })
+    }
+}
+
+/// A path as parallel arrays of coordinates and point types, with the last point of each contour
+#[derive(Debug, Clone)]
+struct FontraPackedPath {
+    /// x, y of each point
+    coordinates: Vec<f64>,
+    point_types: Vec<u8>,
+    contour_info: Vec<FontraContourInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct FontraContourInfo {
+    #[serde(rename = "endPoint")]
+    end_point: usize,
+    #[serde(rename = "isClosed", default)]
+    is_closed: bool,
+}
+
+impl FontraPackedPath {
+    fn unpack(self) -> Result<Vec<FontraContour>, Error> {
+        if self.coordinates.len() != 2 * self.point_types.len() {
+            return Err(Error::InvalidInputData(format!(
+                "Packed path has {} coordinates for {} points",
+                self.coordinates.len(),
+                self.point_types.len()
+            )));
+        }
+        let mut start = 0;
+        let mut contours = Vec::with_capacity(self.contour_info.len());
+        for info in self.contour_info.iter() {
+            if info.end_point < start || info.end_point >= self.point_types.len() {
+                return Err(Error::InvalidInputData(format!(
+                    "Packed path contour ends at point {}, expected {start}..{}",
+                    info.end_point,
+                    self.point_types.len()
+                )));
+            }
+            let points = (start..=info.end_point)
+                .map(|i| {
+                    FontraPoint::from_packed(
+                        self.coordinates[2 * i],
+                        self.coordinates[2 * i + 1],
+                        self.point_types[i],
+                    )
+                })
+                .collect::<Result<_, _>>()?;
+            contours.push(FontraContour {
+                points,
+                is_closed: info.is_closed,
+            });
+            start = info.end_point + 1;
+        }
+        Ok(contours)
+    }
+}
+
 /// <https://github.com/googlefonts/fontra/blob/a4edd06837118e583804fd963c22ed806a315b04/src/fontra/core/path.py#L28-L31>
 #[derive(Debug, Clone, Deserialize)]
 #[allow(dead_code)] // TEMPORARY
@@ -223,8 +414,28 @@ pub(crate) struct FontraPoint {
 }
 
 impl FontraPoint {
+    /// <https://github.com/googlefonts/fontra/blob/a4edd06837118e583804fd963c22ed806a315b04/src/fontra/core/path.py#L65-L69>
+    fn from_packed(x: f64, y: f64, point_type: u8) -> Result<Self, Error> {
+        let (smooth, raw_type) = match point_type {
+            0x00 => (false, None),
+            0x01 => (false, Some("quad")),
+            0x02 => (false, Some("cubic")),
+            0x08 => (true, None),
+            _ => {
+                return Err(Error::InvalidInputData(format!(
+                    "Unrecognized packed point type {point_type}"
+                )))
+            }
+        };
+        Ok(FontraPoint {
+            x,
+            y,
+            smooth,
+            raw_type: raw_type.map(str::to_string),
+        })
+    }
+
     /// <https://github.com/googlefonts/fontra/blob/a4edd06837118e583804fd963c22ed806a315b04/src/fontra/core/path.py#L396-L406>
-    #[allow(dead_code)] // TEMPORARY
     pub(crate) fn point_type(&self) -> Result<PointType, Error> {
         match (self.smooth, self.raw_type.as_deref()) {
             (false, Some("cubic")) => Ok(PointType::OffCurveCubic),
@@ -261,7 +472,6 @@ impl PointType {
 
 /// <https://github.com/googlefonts/fontra/blob/a4edd06837118e583804fd963c22ed806a315b04/src/fontra/core/classes.py#L154-L158>
 #[derive(Debug, Clone, Deserialize)]
-#[allow(dead_code)] // TEMPORARY
 pub(crate) struct FontraComponent {
     pub(crate) name: GlyphName,
     #[serde(default)]
@@ -300,10 +510,148 @@ pub(crate) struct FontraTransform {
     t_center_y: f64,
 }
 
+impl FontraTransform {
+    /// As fontTools DecomposedTransform.toTransform
+    pub(crate) fn to_affine(&self) -> Affine {
+        Affine::translate((
+            self.translate_x + self.t_center_x,
+            self.translate_y + self.t_center_y,
+        )) * Affine::rotate(self.rotation.to_radians())
+            * Affine::scale_non_uniform(self.scale_x, self.scale_y)
+            * Affine::skew(
+                (-self.skew_x).to_radians().tan(),
+                self.skew_y.to_radians().tan(),
+            )
+            * Affine::translate((-self.t_center_x, -self.t_center_y))
+    }
+}
+
 fn float_one() -> f64 {
     1.0
 }
 
+/// One type of kerning, horizontal (hkrn) or vertical (vkrn), from kerning.csv
+///
+/// <https://github.com/googlefonts/fontra/blob/main/src/fontra/backends/fontra.py>
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct FontraKerning {
+    pub(crate) groups_side1: BTreeMap<String, Vec<GlyphName>>,
+    pub(crate) groups_side2: BTreeMap<String, Vec<GlyphName>>,
+    /// Identifies the font source of each value
+    pub(crate) source_identifiers: Vec<String>,
+    /// (side1, side2) => a value per source identifier, None if the source has no value.
+    ///
+    /// A side that starts with @ names a group.
+    pub(crate) values: BTreeMap<(String, String), Vec<Option<f64>>>,
+}
+
+/// A group reference in [`FontraKerning::values`]
+pub(crate) const GROUP_PREFIX: &str = "@";
+
+pub(crate) const HORIZONTAL_KERNING: &str = "hkrn";
+pub(crate) const VERTICAL_KERNING: &str = "vkrn";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KerningSection {
+    Type,
+    /// Older files have one set of groups used on both sides
+    Groups,
+    Groups1,
+    Groups2,
+    ValuesHeader,
+    Values,
+}
+
+/// Read kerning.csv, returning kerning by type
+pub(crate) fn kerning_from_file(p: &Path) -> Result<BTreeMap<String, FontraKerning>, Error> {
+    let raw = fs::read_to_string(p).map_err(Error::IoError)?;
+    let parse_error = |i: usize, message: &str| {
+        Error::ParseError(p.to_path_buf(), format!("{message} at line {}", i + 1))
+    };
+
+    let mut kerning: BTreeMap<String, FontraKerning> = BTreeMap::new();
+    let mut kern_type: Option<String> = None;
+    let mut section = None;
+    for (i, line) in raw.lines().enumerate() {
+        let parts: Vec<_> = line.split(';').map(str::trim).collect();
+        if parts.iter().all(|part| part.is_empty()) {
+            continue;
+        }
+        if parts.len() == 1 {
+            let header = match parts[0] {
+                "TYPE" => Some(KerningSection::Type),
+                "GROUPS" => Some(KerningSection::Groups),
+                "GROUPS1" => Some(KerningSection::Groups1),
+                "GROUPS2" => Some(KerningSection::Groups2),
+                "VALUES" => Some(KerningSection::ValuesHeader),
+                _ => None,
+            };
+            if header.is_some() {
+                section = header;
+                continue;
+            }
+        }
+
+        let Some(current) = section else {
+            return Err(parse_error(i, "Expected a section header"));
+        };
+        if current == KerningSection::Type {
+            kern_type = Some(parts[0].to_string());
+            kerning.entry(parts[0].to_string()).or_default();
+            continue;
+        }
+        let Some(kerns) = kern_type.as_ref().and_then(|t| kerning.get_mut(t)) else {
+            return Err(parse_error(i, "Expected TYPE before kerning"));
+        };
+        match current {
+            KerningSection::Type => unreachable!("handled above"),
+            KerningSection::Groups | KerningSection::Groups1 | KerningSection::Groups2 => {
+                let name = parts[0].to_string();
+                let glyphs: Vec<_> = parts[1..]
+                    .iter()
+                    .filter(|g| !g.is_empty())
+                    .map(|g| GlyphName::new(g))
+                    .collect();
+                if current != KerningSection::Groups2 {
+                    kerns.groups_side1.insert(name.clone(), glyphs.clone());
+                }
+                if current != KerningSection::Groups1 {
+                    kerns.groups_side2.insert(name, glyphs);
+                }
+            }
+            KerningSection::ValuesHeader => {
+                if parts.len() < 2 || parts[0] != "side1" || parts[1] != "side2" {
+                    return Err(parse_error(i, "Expected side1;side2;<source identifiers>"));
+                }
+                kerns.source_identifiers = parts[2..].iter().map(|s| s.to_string()).collect();
+                section = Some(KerningSection::Values);
+            }
+            KerningSection::Values => {
+                if parts.len() < 2 || parts.len() > kerns.source_identifiers.len() + 2 {
+                    return Err(parse_error(i, "Unexpected number of values"));
+                }
+                let mut values = parts[2..]
+                    .iter()
+                    .map(|v| {
+                        if v.is_empty() {
+                            return Ok(None);
+                        }
+                        v.parse::<f64>()
+                            .map(Some)
+                            .map_err(|e| parse_error(i, &format!("Bad value {v:?}: {e}")))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                // Trailing empty values may be omitted
+                values.resize(kerns.source_identifiers.len(), None);
+                kerns
+                    .values
+                    .insert((parts[0].to_string(), parts[1].to_string()), values);
+            }
+        }
+    }
+    Ok(kerning)
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
@@ -463,6 +811,144 @@ mod tests {
         );
     }
 
+    fn point_tuples(path: &FontraPath) -> Vec<Vec<(f64, f64, PointType)>> {
+        path.contours
+            .iter()
+            .map(|c| {
+                c.points
+                    .iter()
+                    .map(|p| (p.x, p.y, p.point_type().unwrap()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn read_packed_path() {
+        let packed: FontraPath = serde_json::from_str(
+            r#"{
+                "coordinates": [0, 0, 100, 0, 100, 100, 50, 150, 0, 100, 10, 10, 20, 20],
+                "pointTypes": [0, 8, 0, 2, 2, 0, 1],
+                "contourInfo": [{"endPoint": 4, "isClosed": true}, {"endPoint": 6}]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                vec![
+                    (0.0, 0.0, PointType::OnCurve),
+                    (100.0, 0.0, PointType::OnCurveSmooth),
+                    (100.0, 100.0, PointType::OnCurve),
+                    (50.0, 150.0, PointType::OffCurveCubic),
+                    (0.0, 100.0, PointType::OffCurveCubic),
+                ],
+                vec![
+                    (10.0, 10.0, PointType::OnCurve),
+                    (20.0, 20.0, PointType::OffCurveQuad),
+                ],
+            ],
+            point_tuples(&packed)
+        );
+        assert_eq!(
+            vec![true, false],
+            packed
+                .contours
+                .iter()
+                .map(|c| c.is_closed)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn read_unpacked_path() {
+        let unpacked: FontraPath = serde_json::from_str(
+            r#"{"contours": [{"points": [{"x": 1, "y": 2}, {"x": 3, "y": 4, "smooth": true}]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![vec![
+                (1.0, 2.0, PointType::OnCurve),
+                (3.0, 4.0, PointType::OnCurveSmooth),
+            ]],
+            point_tuples(&unpacked)
+        );
+    }
+
+    #[test]
+    fn reject_bad_packed_path() {
+        assert!(serde_json::from_str::<FontraPath>(
+            r#"{"coordinates": [0, 0], "pointTypes": [3], "contourInfo": [{"endPoint": 0}]}"#,
+        )
+        .is_err());
+        assert!(serde_json::from_str::<FontraPath>(
+            r#"{"coordinates": [0, 0], "pointTypes": [0], "contourInfo": [{"endPoint": 1}]}"#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn axis_with_values_is_discrete() {
+        // The second was taken for a continuous axis, dropping its values, when continuous
+        // axes were tried first
+        for json in [
+            r#"{"name": "Italic", "tag": "ital", "defaultValue": 0, "values": [0, 1]}"#,
+            r#"{"name": "Italic", "tag": "ital", "defaultValue": 0, "minValue": 0, "maxValue": 1, "values": [0, 1]}"#,
+        ] {
+            let axis: FontraAxis = serde_json::from_str(json).unwrap();
+            let FontraAxis::Discrete(discrete) = &axis else {
+                panic!("{json} should be discrete, got {axis:?}");
+            };
+            assert_eq!(vec![0.0, 1.0], discrete.values, "{json}");
+        }
+    }
+
+    #[test]
+    fn axis_without_values_is_continuous() {
+        let axis: FontraAxis = serde_json::from_str(
+            r#"{"name": "Weight", "tag": "wght", "defaultValue": 400, "minValue": 100, "maxValue": 900}"#,
+        )
+        .unwrap();
+        assert!(matches!(axis, FontraAxis::Continuous(..)), "{axis:?}");
+    }
+
+    #[test]
+    fn reject_malformed_packed_path() {
+        // Each would be an empty unpacked path if packed paths were only tried first
+        for json in [
+            r#"{"coordinates": [0, 0], "pointTypes": ["x"], "contourInfo": [{"endPoint": 0}]}"#,
+            r#"{"coordinates": [0, 0], "pointTypes": [0]}"#,
+            r#"{"coordinates": [0, 0], "pointTypes": [0], "contourInfo": [{"isClosed": true}]}"#,
+        ] {
+            assert!(serde_json::from_str::<FontraPath>(json).is_err(), "{json}");
+        }
+    }
+
+    #[test]
+    fn glyph_source_location_base() {
+        let font_sources: BTreeMap<String, FontraFontSource> = serde_json::from_str(
+            r#"{"bold": {"name": "Bold", "location": {"Weight": 700, "Width": 100}}}"#,
+        )
+        .unwrap();
+        let source: FontraSource = serde_json::from_str(
+            r#"{"name": "Bold Narrow", "layerName": "bn", "locationBase": "bold", "location": {"Width": 75}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            HashMap::from([("Weight".to_string(), 700.0), ("Width".to_string(), 75.0)]),
+            source.resolved_location(&font_sources)
+        );
+
+        // An unknown base leaves the location as it is
+        let source = FontraSource {
+            location_base: Some("nope".to_string()),
+            ..source
+        };
+        assert_eq!(
+            HashMap::from([("Width".to_string(), 75.0)]),
+            source.resolved_location(&font_sources)
+        );
+    }
+
     #[test]
     fn match_python_string_to_filename() {
         // expected is as observed in Python with .json appended
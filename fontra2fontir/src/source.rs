@@ -1,30 +1,44 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fs::File,
     io::{BufRead, BufReader},
     path::{Path, PathBuf},
     sync::Arc,
 };
 
-use fontdrasil::{orchestration::Work, types::GlyphName};
+use fontdrasil::{
+    coords::{NormalizedCoord, NormalizedLocation},
+    orchestration::{Access, AccessBuilder, Work},
+    types::{Axis, GlyphName},
+};
 use fontir::{
     error::{Error, WorkError},
-    ir::StaticMetadata,
-    orchestration::{Context, WorkId},
+    instancer::instantiate_contours,
+    ir::{
+        FeaturesSource, GlobalMetric, GlobalMetrics, Glyph, GlyphAnchors, GlyphBuilder, GlyphOrder,
+        KernGroup, KernSide, KerningGroups, KerningInstance, StaticMetadata,
+    },
+    orchestration::{Context, Flags, WorkId},
     source::{Input, Source},
     stateset::StateSet,
 };
-use log::debug;
+use log::{debug, trace, warn};
+use smol_str::SmolStr;
 
 use crate::{
-    fontra::{self, FontraFontData},
-    toir::to_ir_static_metadata,
+    fontra::{self, FontraFontData, FontraFontSource, FontraGlyph, FontraGlyphAxis, FontraKerning},
+    toir::{
+        default_font_source, glyph_sources, local_axis_tag, to_ir_axes, to_ir_glyph,
+        to_ir_local_axes, to_ir_location, to_ir_static_metadata,
+    },
 };
 
 pub struct FontraIrSource {
     fontdata_file: PathBuf,
     glyphinfo_file: PathBuf,
     glyph_dir: PathBuf,
+    features_file: PathBuf,
+    kerning_file: PathBuf,
     glyph_info: Arc<BTreeMap<GlyphName, (PathBuf, Vec<u32>)>>,
 }
 
@@ -42,22 +56,50 @@ impl FontraIrSource {
         if !glyph_dir.is_dir() {
             return Err(Error::DirectoryExpected(glyph_dir));
         }
+        // Fontra writes features.txt but accept the more conventional extension too
+        let features_file = ["features.fea", "features.txt"]
+            .into_iter()
+            .map(|name| fontra_dir.join(name))
+            .find(|file| file.is_file())
+            .unwrap_or_else(|| fontra_dir.join("features.fea"));
         Ok(FontraIrSource {
             fontdata_file,
             glyphinfo_file,
             glyph_dir,
+            features_file,
+            kerning_file: fontra_dir.join("kerning.csv"),
             glyph_info: Default::default(),
         })
     }
 
+    // Features and kerning are optional
+    fn features_state(&self) -> Result<StateSet, Error> {
+        let mut state = StateSet::new();
+        for file in [&self.features_file, &self.kerning_file] {
+            if file.is_file() {
+                state.track_file(file)?;
+            }
+        }
+        Ok(state)
+    }
+
     // When things like upem may have changed forget incremental and rebuild the whole thing
-    fn static_metadata_state(&self) -> Result<StateSet, Error> {
+    fn font_info_state(&self) -> Result<StateSet, Error> {
         let mut font_info = StateSet::new();
         font_info.track_file(&self.fontdata_file)?;
         font_info.track_file(&self.glyphinfo_file)?;
         Ok(font_info)
     }
 
+    // Glyph local axes can add hidden fvar axes so static metadata also depends on every glyph
+    fn static_metadata_state(&self) -> Result<StateSet, Error> {
+        let mut static_metadata = self.font_info_state()?;
+        for (glyph_file, _) in self.glyph_info.values() {
+            static_metadata.track_file(glyph_file)?;
+        }
+        Ok(static_metadata)
+    }
+
     fn load_glyphinfo(&mut self) -> Result<(), Error> {
         if !self.glyph_info.is_empty() {
             return Ok(());
@@ -137,18 +179,17 @@ impl FontraIrSource {
 
 impl Source for FontraIrSource {
     fn inputs(&mut self) -> Result<fontir::source::Input, fontir::error::Error> {
-        let static_metadata = self.static_metadata_state()?;
         self.load_glyphinfo()?;
+        let static_metadata = self.static_metadata_state()?;
+        let global_metrics = self.font_info_state()?;
         let glyphs = self.glyph_state()?;
 
-        // Not sure how fontra features work
-        let features = StateSet::new();
+        // Kerning and features are tracked together, as for .ufo
+        let features = self.features_state()?;
 
-        // fontinfo.plist spans static metadata and global metrics.
-        // Just use the same change detection for both.
         Ok(Input {
-            static_metadata: static_metadata.clone(),
-            global_metrics: static_metadata,
+            static_metadata,
+            global_metrics,
             glyphs,
             features,
         })
@@ -169,37 +210,63 @@ impl Source for FontraIrSource {
         &self,
         _input: &fontir::source::Input,
     ) -> Result<Box<fontir::orchestration::IrWork>, fontir::error::Error> {
-        todo!()
+        Ok(Box::new(GlobalMetricsWork {
+            fontdata_file: self.fontdata_file.clone(),
+        }))
     }
 
     fn create_glyph_ir_work(
         &self,
-        _glyph_names: &indexmap::IndexSet<fontdrasil::types::GlyphName>,
+        glyph_names: &indexmap::IndexSet<fontdrasil::types::GlyphName>,
         _input: &fontir::source::Input,
     ) -> Result<Vec<Box<fontir::orchestration::IrWork>>, fontir::error::Error> {
-        todo!()
+        glyph_names
+            .iter()
+            .map(|glyph_name| {
+                let Some((glyph_file, codepoints)) = self.glyph_info.get(glyph_name) else {
+                    return Err(Error::NoGlyphForName(glyph_name.clone()));
+                };
+                let work: Box<fontir::orchestration::IrWork> = Box::new(GlyphIrWork {
+                    glyph_name: glyph_name.clone(),
+                    glyph_file: glyph_file.clone(),
+                    fontdata_file: self.fontdata_file.clone(),
+                    codepoints: codepoints.iter().copied().collect(),
+                    glyph_info: self.glyph_info.clone(),
+                });
+                Ok(work)
+            })
+            .collect()
     }
 
     fn create_feature_ir_work(
         &self,
         _input: &fontir::source::Input,
     ) -> Result<Box<fontir::orchestration::IrWork>, fontir::error::Error> {
-        todo!()
+        Ok(Box::new(FeatureWork {
+            features_file: self.features_file.clone(),
+        }))
     }
 
     fn create_kerning_group_ir_work(
         &self,
         _input: &fontir::source::Input,
     ) -> Result<Box<fontir::orchestration::IrWork>, fontir::error::Error> {
-        todo!()
+        Ok(Box::new(KerningGroupWork {
+            fontdata_file: self.fontdata_file.clone(),
+            kerning_file: self.kerning_file.clone(),
+        }))
     }
 
     fn create_kerning_instance_ir_work(
         &self,
         _input: &fontir::source::Input,
-        _at: fontdrasil::coords::NormalizedLocation,
+        at: fontdrasil::coords::NormalizedLocation,
     ) -> Result<Box<fontir::orchestration::IrWork>, fontir::error::Error> {
-        todo!()
+        Ok(Box::new(KerningInstanceWork {
+            fontdata_file: self.fontdata_file.clone(),
+            kerning_file: self.kerning_file.clone(),
+            location: at,
+        }))
     }
 }
 
@@ -209,11 +276,60 @@ struct StaticMetadataWork {
     glyph_info: Arc<BTreeMap<GlyphName, (PathBuf, Vec<u32>)>>,
 }
 
-fn create_static_metadata(fontdata_file: &Path) -> Result<StaticMetadata, WorkError> {
+fn read_font_data(fontdata_file: &Path) -> Result<FontraFontData, WorkError> {
+    FontraFontData::from_file(fontdata_file)
+        .map_err(|e| WorkError::ParseError(fontdata_file.to_path_buf(), format!("{e}")))
+}
+
+fn read_glyph(glyph_file: &Path) -> Result<FontraGlyph, WorkError> {
+    FontraGlyph::from_file(glyph_file)
+        .map_err(|e| WorkError::ParseError(glyph_file.to_path_buf(), format!("{e}")))
+}
+
+/// Fontra has no list of masters so the glyph locations come from every glyph, and the font sources
+///
+/// With `local_axes`, for VARC, glyph-local axes become hidden font axes, see [local_axis_tag].
+/// Otherwise variable components are drawn into contours, see [to_ir_glyph_without_local_axes],
+/// and the font has no use for them. Glyph sources off the default of their local axes are only
+/// used to draw variable components so aren't glyph locations.
+fn create_static_metadata(
+    fontdata_file: &Path,
+    glyph_info: &BTreeMap<GlyphName, (PathBuf, Vec<u32>)>,
+    local_axes: bool,
+) -> Result<StaticMetadata, WorkError> {
     debug!("Static metadata for {:#?}", fontdata_file);
-    let font_data = FontraFontData::from_file(fontdata_file)
-        .map_err(|e| WorkError::ParseError(fontdata_file.to_path_buf(), format!("{e}")))?;
-    to_ir_static_metadata(&font_data)
+    let font_data = read_font_data(fontdata_file)?;
+    let glyphs = glyph_info
+        .values()
+        .map(|(glyph_file, _)| read_glyph(glyph_file))
+        .collect::<Result<Vec<_>, _>>()?;
+    let local_axis_count = glyphs
+        .iter()
+        .map(|glyph| glyph.axes.len())
+        .max()
+        .unwrap_or_default();
+
+    let mut axes = to_ir_axes(&font_data)?;
+    if local_axes {
+        axes.extend(to_ir_local_axes(local_axis_count));
+    }
+
+    let mut glyph_locations = HashSet::new();
+    for glyph in glyphs.iter() {
+        glyph_locations.extend(
+            glyph_sources(&axes, &font_data.sources, glyph)
+                .into_iter()
+                .map(|(_, location)| location)
+                .filter(|location| {
+                    (0..glyph.axes.len()).all(|i| {
+                        location
+                            .get(local_axis_tag(i))
+                            .map_or(true, |coord| coord.to_f32() == 0.0)
+                    })
+                }),
+        );
+    }
+    to_ir_static_metadata(&font_data, axes, glyph_locations)
 }
 
 impl Work<Context, WorkId, WorkError> for StaticMetadataWork {
@@ -230,22 +346,617 @@ impl Work<Context, WorkId, WorkError> for StaticMetadataWork {
         context
             .preliminary_glyph_order
             .set(self.glyph_info.keys().cloned().collect());
-        context
-            .static_metadata
-            .set(create_static_metadata(&self.fontdata_file)?);
+        context.static_metadata.set(create_static_metadata(
+            &self.fontdata_file,
+            &self.glyph_info,
+            context.flags.contains(Flags::VARC),
+        )?);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct GlobalMetricsWork {
+    fontdata_file: PathBuf,
+}
+
+impl Work<Context, WorkId, WorkError> for GlobalMetricsWork {
+    fn id(&self) -> WorkId {
+        WorkId::GlobalMetrics
+    }
+
+    fn read_access(&self) -> Access<WorkId> {
+        Access::Variant(WorkId::StaticMetadata)
+    }
+
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        debug!("Global metrics for {:#?}", self.fontdata_file);
+        let static_metadata = context.static_metadata.get();
+        let axes = &static_metadata.all_source_axes;
+        let font_data = read_font_data(&self.fontdata_file)?;
+
+        let default_source = default_font_source(axes, &font_data);
+        let mut metrics = GlobalMetrics::new(
+            static_metadata.default_location().clone(),
+            static_metadata.units_per_em,
+            default_source.and_then(|s| s.line_metric("xHeight")),
+            default_source.and_then(|s| s.line_metric("ascender")),
+            default_source.and_then(|s| s.line_metric("descender")),
+            static_metadata.italic_angle.into_inner(),
+        );
+        for source in font_data.sources.values() {
+            let pos = to_ir_location(axes, &source.location);
+            if !pos.is_default() {
+                metrics.populate_defaults(
+                    &pos,
+                    static_metadata.units_per_em,
+                    source.line_metric("xHeight"),
+                    source.line_metric("ascender"),
+                    source.line_metric("descender"),
+                    Some(source.italic_angle),
+                );
+            }
+            metrics.set_if_some(
+                GlobalMetric::CapHeight,
+                pos.clone(),
+                source.line_metric("capHeight"),
+            );
+        }
+
+        trace!("{:#?}", metrics);
+        context.global_metrics.set(metrics);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct GlyphIrWork {
+    glyph_name: GlyphName,
+    glyph_file: PathBuf,
+    // To find the font sources glyph sources take their location from
+    fontdata_file: PathBuf,
+    codepoints: HashSet<u32>,
+    // To find the local axes of the glyphs used as components
+    glyph_info: Arc<BTreeMap<GlyphName, (PathBuf, Vec<u32>)>>,
+}
+
+/// The glyph-local axes of every glyph used as a component of the glyph
+fn component_axes(
+    fontra_glyph: &FontraGlyph,
+    glyph_info: &BTreeMap<GlyphName, (PathBuf, Vec<u32>)>,
+) -> Result<HashMap<GlyphName, Vec<FontraGlyphAxis>>, WorkError> {
+    let mut base_axes = HashMap::new();
+    for component in fontra_glyph
+        .layers
+        .values()
+        .flat_map(|layer| layer.glyph.components.iter())
+    {
+        if base_axes.contains_key(&component.name) {
+            continue;
+        }
+        // A missing base is reported when components are resolved
+        let Some((glyph_file, _)) = glyph_info.get(&component.name) else {
+            continue;
+        };
+        let base = read_glyph(glyph_file)?;
+        base_axes.insert(component.name.clone(), base.axes);
+    }
+    Ok(base_axes)
+}
+
+/// The glyph and every glyph it uses as a component, directly or not, by name
+fn used_glyphs(
+    fontra_glyph: &FontraGlyph,
+    glyph_info: &BTreeMap<GlyphName, (PathBuf, Vec<u32>)>,
+) -> Result<HashMap<GlyphName, FontraGlyph>, WorkError> {
+    let mut used = HashMap::from([(fontra_glyph.name.clone(), fontra_glyph.clone())]);
+    let mut frontier = vec![fontra_glyph.clone()];
+    while let Some(glyph) = frontier.pop() {
+        for component in glyph
+            .layers
+            .values()
+            .flat_map(|layer| layer.glyph.components.iter())
+        {
+            if used.contains_key(&component.name) {
+                continue;
+            }
+            // A missing base is reported when components are drawn
+            let Some((glyph_file, _)) = glyph_info.get(&component.name) else {
+                continue;
+            };
+            let base = read_glyph(glyph_file)?;
+            used.insert(component.name.clone(), base.clone());
+            frontier.push(base);
+        }
+    }
+    Ok(used)
+}
+
+/// The glyph for a font whose glyph-local axes aren't font axes, that is one without VARC.
+///
+/// Only sources at the default of the glyph's local axes are kept. Variable components
+/// are drawn into contours here, where the local axes of the glyphs they use are known.
+fn to_ir_glyph_without_local_axes(
+    axes: &[Axis],
+    font_sources: &BTreeMap<String, FontraFontSource>,
+    codepoints: HashSet<u32>,
+    fontra_glyph: &FontraGlyph,
+    glyph_info: &BTreeMap<GlyphName, (PathBuf, Vec<u32>)>,
+) -> Result<(Glyph, GlyphAnchors), WorkError> {
+    let mut at_local_default = fontra_glyph.clone();
+    at_local_default.sources.retain(|source| {
+        let location = source.resolved_location(font_sources);
+        fontra_glyph.axes.iter().all(|axis| {
+            location
+                .get(&axis.name)
+                .map_or(true, |value| *value == axis.default_value)
+        })
+    });
+    let (glyph, anchors) = to_ir_glyph(
+        axes,
+        font_sources,
+        codepoints,
+        &at_local_default,
+        &Default::default(),
+    )?;
+    let has_variable_components = fontra_glyph
+        .layers
+        .values()
+        .flat_map(|layer| layer.glyph.components.iter())
+        .any(|component| !component.location.is_empty());
+    if !has_variable_components {
+        return Ok((glyph, anchors));
+    }
+
+    // Draw with every glyph involved, and all their local axes, in hand
+    let used = used_glyphs(fontra_glyph, glyph_info)?;
+    let local_axes = to_ir_local_axes(
+        used.values()
+            .map(|glyph| glyph.axes.len())
+            .max()
+            .unwrap_or_default(),
+    );
+    let all_axes: Vec<_> = axes.iter().chain(local_axes.iter()).cloned().collect();
+    let base_axes: HashMap<_, _> = used
+        .iter()
+        .map(|(name, glyph)| (name.clone(), glyph.axes.clone()))
+        .collect();
+    let ir_glyphs = used
+        .values()
+        .map(|glyph| {
+            let (ir_glyph, _) = to_ir_glyph(
+                &all_axes,
+                font_sources,
+                Default::default(),
+                glyph,
+                &base_axes,
+            )?;
+            Ok((glyph.name.clone(), Arc::new(ir_glyph)))
+        })
+        .collect::<Result<HashMap<_, _>, WorkError>>()?;
+    let glyphs = |name: &GlyphName| ir_glyphs.get(name).cloned();
+    let with_local_axes = &ir_glyphs[&fontra_glyph.name];
+
+    let mut drawn = GlyphBuilder::from(glyph);
+    for (location, instance) in drawn.sources.iter_mut() {
+        let mut location = location.clone();
+        for axis in local_axes.iter() {
+            location.insert(axis.tag, NormalizedCoord::new(0.0));
+        }
+        instance.contours = instantiate_contours(&glyphs, &all_axes, with_local_axes, &location)?;
+        instance.components.clear();
+    }
+    Ok((drawn.build()?, anchors))
+}
+
+impl Work<Context, WorkId, WorkError> for GlyphIrWork {
+    fn id(&self) -> WorkId {
+        WorkId::Glyph(self.glyph_name.clone())
+    }
+
+    fn read_access(&self) -> Access<WorkId> {
+        Access::Variant(WorkId::StaticMetadata)
+    }
+
+    fn write_access(&self) -> Access<WorkId> {
+        AccessBuilder::new()
+            .specific_instance(WorkId::Glyph(self.glyph_name.clone()))
+            .specific_instance(WorkId::Anchor(self.glyph_name.clone()))
+            .build()
+    }
+
+    fn also_completes(&self) -> Vec<WorkId> {
+        vec![WorkId::Anchor(self.glyph_name.clone())]
+    }
+
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        trace!(
+            "Generate glyph IR for {:?} from {:#?}",
+            self.glyph_name,
+            self.glyph_file
+        );
+        let static_metadata = context.static_metadata.get();
+        let font_data = read_font_data(&self.fontdata_file)?;
+        let fontra_glyph = read_glyph(&self.glyph_file)?;
+        let (glyph, anchors) = if context.flags.contains(Flags::VARC) {
+            let base_axes = component_axes(&fontra_glyph, &self.glyph_info)?;
+            to_ir_glyph(
+                &static_metadata.all_source_axes,
+                &font_data.sources,
+                self.codepoints.clone(),
+                &fontra_glyph,
+                &base_axes,
+            )?
+        } else {
+            to_ir_glyph_without_local_axes(
+                &static_metadata.all_source_axes,
+                &font_data.sources,
+                self.codepoints.clone(),
+                &fontra_glyph,
+                &self.glyph_info,
+            )?
+        };
+        context.anchors.set(anchors);
+        context.glyphs.set(glyph);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct FeatureWork {
+    features_file: PathBuf,
+}
+
+impl Work<Context, WorkId, WorkError> for FeatureWork {
+    fn id(&self) -> WorkId {
+        WorkId::Features
+    }
+
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        debug!("Features for {:#?}", self.features_file);
+        // Includes resolve relative to the .fontra directory
+        context.features.set(if self.features_file.is_file() {
+            FeaturesSource::from_file(
+                self.features_file.clone(),
+                self.features_file.parent().map(Path::to_path_buf),
+            )
+        } else {
+            FeaturesSource::empty()
+        });
+        Ok(())
+    }
+}
+
+/// Kerning by type, empty if there's no kerning file
+fn read_kerning(kerning_file: &Path) -> Result<BTreeMap<String, FontraKerning>, WorkError> {
+    if !kerning_file.is_file() {
+        return Ok(Default::default());
+    }
+    fontra::kerning_from_file(kerning_file)
+        .map_err(|e| WorkError::ParseError(kerning_file.to_path_buf(), format!("{e}")))
+}
+
+/// The location of each font source, by identifier
+fn source_locations(
+    axes: &[Axis],
+    font_data: &FontraFontData,
+) -> HashMap<String, NormalizedLocation> {
+    font_data
+        .sources
+        .iter()
+        .map(|(identifier, source)| (identifier.clone(), to_ir_location(axes, &source.location)))
+        .collect()
+}
+
+/// The locations of the kerning sources we know, warning about the rest
+fn kerning_locations(
+    kerning: &FontraKerning,
+    source_locations: &HashMap<String, NormalizedLocation>,
+) -> Vec<Option<NormalizedLocation>> {
+    kerning
+        .source_identifiers
+        .iter()
+        .map(|identifier| {
+            let location = source_locations.get(identifier).cloned();
+            if location.is_none() {
+                warn!("Kerning for unknown source '{identifier}' ignored");
+            }
+            location
+        })
+        .collect()
+}
+
+fn kern_groups(
+    groups: &BTreeMap<String, Vec<GlyphName>>,
+    glyph_order: &GlyphOrder,
+    to_group: impl Fn(SmolStr) -> KernGroup,
+) -> BTreeMap<KernGroup, BTreeSet<GlyphName>> {
+    groups
+        .iter()
+        .map(|(name, glyphs)| {
+            (
+                to_group(name.as_str().into()),
+                glyphs
+                    .iter()
+                    .filter(|glyph_name| {
+                        let exists = glyph_order.contains(glyph_name);
+                        if !exists {
+                            warn!("Kerning group '{name}' refers to non-existent '{glyph_name}'");
+                        }
+                        exists
+                    })
+                    .cloned()
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct KerningGroupWork {
+    fontdata_file: PathBuf,
+    kerning_file: PathBuf,
+}
+
+impl Work<Context, WorkId, WorkError> for KerningGroupWork {
+    fn id(&self) -> WorkId {
+        WorkId::KerningGroups
+    }
+
+    fn read_access(&self) -> Access<WorkId> {
+        AccessBuilder::new()
+            .variant(WorkId::StaticMetadata)
+            .variant(WorkId::GlyphOrder)
+            .build()
+    }
+
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        debug!("Kerning groups for {:#?}", self.kerning_file);
+        let static_metadata = context.static_metadata.get();
+        let glyph_order = context.glyph_order.get();
+        let font_data = read_font_data(&self.fontdata_file)?;
+        let source_locations = source_locations(&static_metadata.all_source_axes, &font_data);
+        let kerning = read_kerning(&self.kerning_file)?;
+
+        let mut kerning_groups = KerningGroups::default();
+        if let Some(kerning) = kerning.get(fontra::HORIZONTAL_KERNING) {
+            kerning_groups.groups =
+                kern_groups(&kerning.groups_side1, &glyph_order, KernGroup::Side1);
+            kerning_groups.groups.extend(kern_groups(
+                &kerning.groups_side2,
+                &glyph_order,
+                KernGroup::Side2,
+            ));
+        }
+        if let Some(kerning) = kerning.get(fontra::VERTICAL_KERNING) {
+            kerning_groups.vertical_groups =
+                kern_groups(&kerning.groups_side1, &glyph_order, KernGroup::Side1);
+            kerning_groups.vertical_groups.extend(kern_groups(
+                &kerning.groups_side2,
+                &glyph_order,
+                KernGroup::Side2,
+            ));
+        }
+        kerning_groups.old_to_new_group_names = kerning_groups
+            .groups
+            .keys()
+            .map(|name| (name.clone(), name.clone()))
+            .collect();
+
+        for kerning in kerning.values() {
+            kerning_groups.locations.extend(
+                kerning_locations(kerning, &source_locations)
+                    .into_iter()
+                    .flatten(),
+            );
+        }
+        // A sparse source only has some of the kerning
+        kerning_groups.sparse_locations = font_data
+            .sources
+            .iter()
+            .filter(|(_, source)| source.is_sparse)
+            .filter_map(|(identifier, _)| source_locations.get(identifier))
+            .filter(|location| {
+                kerning_groups.locations.contains(*location) && !location.is_default()
+            })
+            .cloned()
+            .collect();
+
+        context.kerning_groups.set(kerning_groups);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct KerningInstanceWork {
+    fontdata_file: PathBuf,
+    kerning_file: PathBuf,
+    location: NormalizedLocation,
+}
+
+impl Work<Context, WorkId, WorkError> for KerningInstanceWork {
+    fn id(&self) -> WorkId {
+        WorkId::KernInstance(self.location.clone())
+    }
+
+    fn read_access(&self) -> Access<WorkId> {
+        AccessBuilder::new()
+            .variant(WorkId::StaticMetadata)
+            .variant(WorkId::GlyphOrder)
+            .variant(WorkId::KerningGroups)
+            .build()
+    }
+
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        debug!(
+            "Kerning for {:#?} at {:?}",
+            self.kerning_file, self.location
+        );
+        let static_metadata = context.static_metadata.get();
+        let glyph_order = context.glyph_order.get();
+        let groups = context.kerning_groups.get();
+        let font_data = read_font_data(&self.fontdata_file)?;
+        let source_locations = source_locations(&static_metadata.all_source_axes, &font_data);
+        let kerning = read_kerning(&self.kerning_file)?;
+
+        let resolve = |name: &str,
+                       groups: &BTreeMap<KernGroup, BTreeSet<GlyphName>>,
+                       to_group: fn(SmolStr) -> KernGroup| {
+            if let Some(group_name) = name.strip_prefix(fontra::GROUP_PREFIX) {
+                let group = to_group(group_name.into());
+                if !groups.contains_key(&group) {
+                    warn!("'{name}' is not a valid group name; ignored");
+                    return None;
+                }
+                Some(KernSide::Group(group))
+            } else {
+                let glyph_name = GlyphName::new(name);
+                if !glyph_order.contains(&glyph_name) {
+                    warn!("'{name}' refers to a non-existent glyph; ignored");
+                    return None;
+                }
+                Some(KernSide::Glyph(glyph_name))
+            }
+        };
+
+        let mut kerns = KerningInstance {
+            location: self.location.clone(),
+            ..Default::default()
+        };
+        for (kern_type, kerning) in kerning.iter() {
+            let (groups, pairs) = match kern_type.as_str() {
+                fontra::HORIZONTAL_KERNING => (&groups.groups, &mut kerns.kerns),
+                fontra::VERTICAL_KERNING => (&groups.vertical_groups, &mut kerns.vertical_kerns),
+                _ => {
+                    warn!("Unsupported kerning type '{kern_type}' ignored");
+                    continue;
+                }
+            };
+            let Some(idx) = kerning_locations(kerning, &source_locations)
+                .iter()
+                .position(|location| location.as_ref() == Some(&self.location))
+            else {
+                continue;
+            };
+            for ((side1, side2), values) in kerning.values.iter() {
+                let Some(adjustment) = values[idx] else {
+                    continue;
+                };
+                let (Some(side1), Some(side2)) = (
+                    resolve(side1, groups, KernGroup::Side1),
+                    resolve(side2, groups, KernGroup::Side2),
+                ) else {
+                    warn!("{kern_type} kerning unable to resolve at least one of '{side1}', '{side2}'; ignoring");
+                    continue;
+                };
+                pairs.insert((side1, side2), (adjustment as f32).into());
+            }
+        }
+
+        context.kerning_at.set(kerns);
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use fontdrasil::types::GlyphName;
+    use fontdrasil::{
+        orchestration::{Access, AccessBuilder},
+        types::GlyphName,
+    };
+    use fontir::{
+        orchestration::{Context, Flags, WorkId},
+        paths::Paths,
+    };
     use pretty_assertions::assert_eq;
 
     use crate::test::testdata_dir;
 
     use super::*;
 
+    fn build_static_metadata(name: &str) -> (impl Source, Context) {
+        build_static_metadata_with_flags(name, Flags::default())
+    }
+
+    fn build_static_metadata_with_flags(name: &str, flags: Flags) -> (impl Source, Context) {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let mut source = FontraIrSource::new(testdata_dir().join(name)).unwrap();
+        let input = source.inputs().unwrap();
+        let context = Context::new_root(
+            flags - Flags::EMIT_IR,
+            Paths::new(Path::new("/nothing/should/write/here")),
+            input,
+        );
+        let task_context = context.copy_for_work(
+            Access::None,
+            AccessBuilder::new()
+                .variant(WorkId::StaticMetadata)
+                .variant(WorkId::PreliminaryGlyphOrder)
+                .build(),
+        );
+        source
+            .create_static_metadata_work(&context.input)
+            .unwrap()
+            .exec(&task_context)
+            .unwrap();
+        (source, context)
+    }
+
+    fn build_global_metrics(name: &str) -> (impl Source, Context) {
+        let (source, context) = build_static_metadata(name);
+        let task_context = context.copy_for_work(
+            Access::Variant(WorkId::StaticMetadata),
+            Access::Variant(WorkId::GlobalMetrics),
+        );
+        source
+            .create_global_metric_work(&context.input)
+            .unwrap()
+            .exec(&task_context)
+            .unwrap();
+        (source, context)
+    }
+
+    fn build_kerning(name: &str) -> (impl Source, Context) {
+        let (source, context) = build_static_metadata(name);
+
+        // static metadata includes preliminary glyph order; just copy it to be the final one
+        context
+            .copy_for_work(
+                Access::Variant(WorkId::PreliminaryGlyphOrder),
+                Access::Variant(WorkId::GlyphOrder),
+            )
+            .glyph_order
+            .set((*context.preliminary_glyph_order.get()).clone());
+
+        let work = source.create_kerning_group_ir_work(&context.input).unwrap();
+        work.exec(&context.copy_for_work(work.read_access(), work.write_access()))
+            .unwrap();
+
+        for location in context.kerning_groups.get().locations.iter() {
+            let work = source
+                .create_kerning_instance_ir_work(&context.input, location.clone())
+                .unwrap();
+            work.exec(&context.copy_for_work(work.read_access(), work.write_access()))
+                .unwrap();
+        }
+
+        (source, context)
+    }
+
+    fn wght_location(value: f32) -> NormalizedLocation {
+        NormalizedLocation::for_pos(&[("wght", value)])
+    }
+
+    #[test]
+    fn static_metadata_tracks_glyph_files() {
+        let fontra_dir = testdata_dir().join("2glyphs.fontra");
+        let mut source = FontraIrSource::new(fontra_dir.clone()).unwrap();
+        let input = source.inputs().unwrap();
+        let glyph_file = fontra_dir.join("glyphs").join("u20089.json");
+        assert!(input.static_metadata.contains(&glyph_file));
+        assert!(!input.global_metrics.contains(&glyph_file));
+    }
+
     #[test]
     fn glyph_info_of_minimal() {
         let mut source = FontraIrSource::new(testdata_dir().join("minimal.fontra")).unwrap();
@@ -323,4 +1034,187 @@ mod tests {
                 .collect::<Vec<_>>()
         )
     }
+
+    #[test]
+    fn static_metadata_includes_font_sources() {
+        let (_, context) = build_static_metadata("kerning.fontra");
+        let static_metadata = context.static_metadata.get();
+        assert_eq!(
+            HashSet::from([wght_location(0.0), wght_location(1.0)]),
+            static_metadata
+                .variation_model
+                .locations()
+                .cloned()
+                .collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn local_axes_become_hidden_font_axes() {
+        let (_, context) =
+            build_static_metadata_with_flags("component.fontra", Flags::default() | Flags::VARC);
+        let static_metadata = context.static_metadata.get();
+        assert_eq!(
+            vec!["wght", "V000", "V001", "V002", "V003", "V004", "V005"],
+            static_metadata
+                .axes
+                .iter()
+                .map(|a| a.tag.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert!(static_metadata.axes.iter().skip(1).all(|a| a.hidden));
+        // Sources off the default of their local axes only matter to variable components
+        assert!(static_metadata.variation_model.locations().all(|loc| loc
+            .iter()
+            .filter(|(tag, _)| tag.to_string().starts_with('V'))
+            .all(|(_, coord)| coord.to_f32() == 0.0)));
+    }
+
+    #[test]
+    fn no_local_axes_without_varc() {
+        let (_, context) = build_static_metadata("component.fontra");
+        assert_eq!(
+            vec!["wght"],
+            context
+                .static_metadata
+                .get()
+                .axes
+                .iter()
+                .map(|a| a.tag.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn variable_components_drawn_without_varc() {
+        let (source, context) = build_static_metadata("component.fontra");
+        let glyph_name = GlyphName::new("uni4E00");
+        for work in source
+            .create_glyph_ir_work(
+                &indexmap::IndexSet::from([glyph_name.clone()]),
+                &context.input,
+            )
+            .unwrap()
+        {
+            work.exec(&context.copy_for_work(work.read_access(), work.write_access()))
+                .unwrap();
+        }
+        let glyph = context.glyphs.get(&WorkId::Glyph(glyph_name));
+        assert!(glyph
+            .sources()
+            .keys()
+            .all(|loc| loc.axis_tags().all(|tag| tag.to_string() == "wght")));
+        assert!(glyph
+            .sources()
+            .values()
+            .all(|inst| inst.components.is_empty() && !inst.contours.is_empty()));
+    }
+
+    #[test]
+    fn global_metrics_from_font_sources() {
+        let (_, context) = build_global_metrics("kerning.fontra");
+        let metrics = context.global_metrics.get();
+        assert_eq!(
+            vec![(750.0, -250.0, 500.0, 700.0), (760.0, -240.0, 520.0, 710.0)],
+            [wght_location(0.0), wght_location(1.0)]
+                .iter()
+                .map(|loc| metrics.at(loc))
+                .map(|m| (
+                    m.ascender.into_inner(),
+                    m.descender.into_inner(),
+                    m.x_height.into_inner(),
+                    m.cap_height.into_inner()
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn kerning_groups_from_csv() {
+        let (_, context) = build_kerning("kerning.fontra");
+        let groups = context.kerning_groups.get();
+        assert_eq!(
+            vec![
+                (KernGroup::Side1("V".into()), vec!["V", "W"]),
+                (KernGroup::Side2("A".into()), vec!["A"]),
+            ],
+            groups
+                .groups
+                .iter()
+                .map(|(name, entries)| (
+                    name.clone(),
+                    entries.iter().map(|e| e.as_str()).collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![wght_location(0.0), wght_location(1.0)],
+            groups.locations.iter().cloned().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn kerning_values_from_csv() {
+        let (_, context) = build_kerning("kerning.fontra");
+        let kern_at = |location: NormalizedLocation| {
+            context
+                .kerning_at
+                .get(&WorkId::KernInstance(location))
+                .kerns
+                .iter()
+                .map(|((side1, side2), value)| (side1.clone(), side2.clone(), value.0))
+                .collect::<Vec<_>>()
+        };
+        let group_kern = |value| {
+            (
+                KernSide::Group(KernGroup::Side1("V".into())),
+                KernSide::Group(KernGroup::Side2("A".into())),
+                value,
+            )
+        };
+        assert_eq!(
+            vec![
+                (
+                    KernSide::Glyph("A".into()),
+                    KernSide::Glyph("V".into()),
+                    -50.0
+                ),
+                group_kern(-100.0),
+            ],
+            kern_at(wght_location(0.0))
+        );
+        // The bold source has no value for A/V
+        assert_eq!(vec![group_kern(-120.0)], kern_at(wght_location(1.0)));
+    }
+
+    #[test]
+    fn features_from_fileset() {
+        let (source, context) = build_static_metadata("kerning.fontra");
+        let task_context = context.copy_for_work(Access::None, Access::Variant(WorkId::Features));
+        source
+            .create_feature_ir_work(&context.input)
+            .unwrap()
+            .exec(&task_context)
+            .unwrap();
+        assert!(
+            matches!(
+                &*context.features.get(),
+                FeaturesSource::File { fea_file, .. } if fea_file.ends_with("kerning.fontra/features.txt")
+            ),
+            "{:?}",
+            context.features.get()
+        );
+    }
+
+    #[test]
+    fn no_features_without_file() {
+        let (source, context) = build_static_metadata("2glyphs.fontra");
+        let task_context = context.copy_for_work(Access::None, Access::Variant(WorkId::Features));
+        source
+            .create_feature_ir_work(&context.input)
+            .unwrap()
+            .exec(&task_context)
+            .unwrap();
+        assert_eq!(FeaturesSource::empty(), *context.features.get());
+    }
 }
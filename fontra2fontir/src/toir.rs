@@ -1,6 +1,9 @@
 //! Functions to convert fontra things to fontc IR things
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    str::FromStr,
+};
 
 use fontdrasil::{
     coords::{CoordConverter, DesignCoord, NormalizedCoord, NormalizedLocation, UserCoord},
@@ -8,18 +11,22 @@ use fontdrasil::{
 };
 use fontir::{
     error::WorkError,
-    ir::{Glyph, GlyphInstance, GlyphPathBuilder, StaticMetadata},
+    ir::{
+        AnchorBuilder, Component, Glyph, GlyphAnchors, GlyphInstance, GlyphPathBuilder,
+        StaticMetadata,
+    },
 };
 use kurbo::BezPath;
 use log::trace;
 use write_fonts::types::Tag;
 
-use crate::fontra::{AxisName, FontraContour, FontraFontData, FontraGlyph, FontraPoint, PointType};
+use crate::fontra::{
+    AxisName, FontraComponent, FontraContour, FontraFontData, FontraFontSource, FontraGlyph,
+    FontraGlyphAxis, FontraPoint, FontraSource, PointType,
+};
 
-pub(crate) fn to_ir_static_metadata(
-    font_data: &FontraFontData,
-) -> Result<StaticMetadata, WorkError> {
-    let axes = font_data
+pub(crate) fn to_ir_axes(font_data: &FontraFontData) -> Result<Vec<Axis>, WorkError> {
+    font_data
         .axes
         .iter()
         .map(|a| match a {
@@ -76,58 +83,212 @@ pub(crate) fn to_ir_static_metadata(
                 converter,
             })
         })
-        .collect::<Result<_, _>>()?;
+        .collect()
+}
+
+/// The tag of the hidden font axis for the n'th glyph-local axis of every glyph.
+///
+/// Glyph-local axes have neither tag nor unique name so they are matched up by position.
+pub(crate) fn local_axis_tag(index: usize) -> Tag {
+    Tag::from_str(&format!("V{index:03}")).unwrap()
+}
+
+/// Hidden font axes for glyph-local axes, the most any glyph has.
+///
+/// Local axes are normalized, see [normalize_local], so all of them range -1..1.
+pub(crate) fn to_ir_local_axes(count: usize) -> Vec<Axis> {
+    let min = UserCoord::new(-1.0);
+    let default = UserCoord::new(0.0);
+    let max = UserCoord::new(1.0);
+    (0..count)
+        .map(|i| {
+            let tag = local_axis_tag(i);
+            Axis {
+                tag,
+                name: tag.to_string(),
+                hidden: true,
+                min,
+                default,
+                max,
+                converter: CoordConverter::unmapped(min, default, max),
+            }
+        })
+        .collect()
+}
+
+/// Glyph-local axes have no mapping, values are clamped and normalized against min/default/max
+fn normalize_local(axis: &FontraGlyphAxis, value: f64) -> NormalizedCoord {
+    let value = value.max(axis.min_value).min(axis.max_value);
+    let normalized = if value < axis.default_value {
+        (value - axis.default_value) / (axis.default_value - axis.min_value)
+    } else if value > axis.default_value {
+        (value - axis.default_value) / (axis.max_value - axis.default_value)
+    } else {
+        0.0
+    };
+    NormalizedCoord::new(normalized as f32)
+}
+
+/// A location that may name glyph-local axes, which shadow global axes of the same name.
+fn to_ir_local_location(
+    axes: &[Axis],
+    local_axes: &[FontraGlyphAxis],
+    location: &HashMap<AxisName, f64>,
+) -> NormalizedLocation {
+    let global_location: HashMap<_, _> = location
+        .iter()
+        .filter(|(name, _)| local_axes.iter().all(|local| local.name != **name))
+        .map(|(name, value)| (name.clone(), *value))
+        .collect();
+    let mut ir_location = to_ir_location(axes, &global_location);
+    set_local_axes(axes, local_axes, location, &mut ir_location);
+    ir_location
+}
+
+/// Set the hidden font axis of each local axis, those missing from `values` to their default
+fn set_local_axes(
+    axes: &[Axis],
+    local_axes: &[FontraGlyphAxis],
+    values: &HashMap<AxisName, f64>,
+    location: &mut NormalizedLocation,
+) {
+    for (i, local_axis) in local_axes.iter().enumerate() {
+        let tag = local_axis_tag(i);
+        if !axes.iter().any(|axis| axis.tag == tag) {
+            continue;
+        }
+        let coord = values
+            .get(&local_axis.name)
+            .map(|value| normalize_local(local_axis, *value))
+            .unwrap_or_else(|| NormalizedCoord::new(0.0));
+        location.insert(tag, coord);
+    }
+}
+
+/// Fontra locations are by axis name, in design space. A missing axis is at its default.
+pub(crate) fn to_ir_location(
+    axes: &[Axis],
+    location: &HashMap<AxisName, f64>,
+) -> NormalizedLocation {
+    axes.iter()
+        .map(|axis| {
+            let coord = match location.get(&axis.name) {
+                Some(value) => DesignCoord::new(*value as f32).to_normalized(&axis.converter),
+                None => NormalizedCoord::new(0.0),
+            };
+            (axis.tag, coord)
+        })
+        .collect()
+}
+
+/// The font source at the default location, if any
+pub(crate) fn default_font_source<'a>(
+    axes: &[Axis],
+    font_data: &'a FontraFontData,
+) -> Option<&'a FontraFontSource> {
+    font_data
+        .sources
+        .values()
+        .find(|source| to_ir_location(axes, &source.location).is_default())
+}
+
+pub(crate) fn to_ir_static_metadata(
+    font_data: &FontraFontData,
+    axes: Vec<Axis>,
+    mut glyph_locations: HashSet<NormalizedLocation>,
+) -> Result<StaticMetadata, WorkError> {
+    glyph_locations.extend(
+        font_data
+            .sources
+            .values()
+            .map(|source| to_ir_location(&axes, &source.location)),
+    );
+    let italic_angle = default_font_source(&axes, font_data)
+        .map(|source| source.italic_angle)
+        .unwrap_or_default();
 
     StaticMetadata::new(
         font_data.units_per_em,
         Default::default(),
         axes,
         Default::default(),
-        Default::default(), // TODO: glyph locations we really do need
-        Default::default(),
+        glyph_locations,
         Default::default(),
+        italic_angle,
     )
     .map_err(WorkError::VariationModelError)
 }
 
+/// The active sources of a glyph, with their location including any glyph-local axes.
 ///
-#[allow(dead_code)] // TEMPORARY
-fn to_ir_glyph(
-    global_axes: HashMap<AxisName, Tag>,
-    codepoints: HashSet<u32>,
-    fontra_glyph: &FontraGlyph,
-) -> Result<Glyph, WorkError> {
-    let _local_axes: HashMap<_, _> = fontra_glyph
-        .axes
+/// `font_sources` are the font sources by identifier, which glyph sources may take their
+/// location from.
+pub(crate) fn glyph_sources<'a>(
+    axes: &[Axis],
+    font_sources: &BTreeMap<String, FontraFontSource>,
+    fontra_glyph: &'a FontraGlyph,
+) -> Vec<(&'a FontraSource, NormalizedLocation)> {
+    fontra_glyph
+        .sources
         .iter()
-        .map(|a| (a.name.as_str(), a))
-        .collect();
+        .filter(|source| !source.inactive)
+        .map(|source| {
+            (
+                source,
+                to_ir_local_location(
+                    axes,
+                    &fontra_glyph.axes,
+                    &source.resolved_location(font_sources),
+                ),
+            )
+        })
+        .collect()
+}
 
-    let layer_locations: HashMap<_, _> = fontra_glyph
-        .sources
+/// Where a component draws its base glyph.
+///
+/// Every local axis of the base is set, to its default if the component doesn't say, so
+/// the base doesn't pick up the local location of the glyph using it. Global axes are only
+/// set if the component names them.
+fn to_ir_component_location(
+    axes: &[Axis],
+    base_axes: &[FontraGlyphAxis],
+    component: &FontraComponent,
+) -> NormalizedLocation {
+    let mut location: NormalizedLocation = axes
         .iter()
-        .map(|s| (&s.layer_name, &s.location))
+        .filter(|axis| {
+            component.location.contains_key(&axis.name)
+                && base_axes.iter().all(|local| local.name != axis.name)
+        })
+        .map(|axis| {
+            let value = component.location[&axis.name];
+            (
+                axis.tag,
+                DesignCoord::new(value as f32).to_normalized(&axis.converter),
+            )
+        })
         .collect();
+    set_local_axes(axes, base_axes, &component.location, &mut location);
+    location
+}
 
+/// The glyph, with variable components where components set axes.
+///
+/// `base_axes` has the glyph-local axes of the glyphs used as components.
+pub(crate) fn to_ir_glyph(
+    axes: &[Axis],
+    font_sources: &BTreeMap<String, FontraFontSource>,
+    codepoints: HashSet<u32>,
+    fontra_glyph: &FontraGlyph,
+    base_axes: &HashMap<GlyphName, Vec<FontraGlyphAxis>>,
+) -> Result<(Glyph, GlyphAnchors), WorkError> {
     let mut instances = HashMap::new();
-    for (layer_name, layer) in fontra_glyph.layers.iter() {
-        // TODO: we need IR VARC support to proceed
-        if !fontra_glyph.axes.is_empty() {
-            todo!("Support local axes");
-        }
-
-        let Some(location) = layer_locations.get(layer_name) else {
-            return Err(WorkError::NoSourceForName(layer_name.to_string()));
+    let mut anchors = AnchorBuilder::new(fontra_glyph.name.clone());
+    for (source, global_location) in glyph_sources(axes, font_sources, fontra_glyph) {
+        let Some(layer) = fontra_glyph.layers.get(&source.layer_name) else {
+            return Err(WorkError::NoSourceForName(source.layer_name.to_string()));
         };
-        let global_location: NormalizedLocation = global_axes
-            .iter()
-            .map(|(name, tag)| {
-                (
-                    *tag,
-                    NormalizedCoord::new(location.get(name).copied().unwrap_or_default() as f32),
-                )
-            })
-            .collect();
 
         let contours: Vec<_> = layer
             .glyph
@@ -136,12 +297,37 @@ fn to_ir_glyph(
             .iter()
             .map(|c| to_ir_path(fontra_glyph.name.clone(), c))
             .collect::<Result<_, _>>()?;
+        let components = layer
+            .glyph
+            .components
+            .iter()
+            .map(|component| Component {
+                base: component.name.clone(),
+                transform: component.transformation.to_affine(),
+                location: to_ir_component_location(
+                    axes,
+                    base_axes
+                        .get(&component.name)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default(),
+                    component,
+                ),
+            })
+            .collect();
+        for anchor in layer.glyph.anchors.iter() {
+            anchors.add(
+                anchor.name.as_str().into(),
+                global_location.clone(),
+                (anchor.x, anchor.y).into(),
+            )?;
+        }
         if instances
             .insert(
                 global_location.clone(),
                 GlyphInstance {
                     width: layer.glyph.x_advance,
                     contours,
+                    components,
                     ..Default::default()
                 },
             )
@@ -154,10 +340,12 @@ fn to_ir_glyph(
         };
     }
 
-    Glyph::new(fontra_glyph.name.clone(), true, codepoints, instances)
+    Ok((
+        Glyph::new(fontra_glyph.name.clone(), true, codepoints, instances)?,
+        anchors.build()?,
+    ))
 }
 
-#[allow(dead_code)] // TEMPORARY
 fn add_to_path<'a>(
     glyph_name: GlyphName,
     path_builder: &'a mut GlyphPathBuilder,
@@ -231,15 +419,18 @@ fn to_ir_path(glyph_name: GlyphName, contour: &FontraContour) -> Result<BezPath,
 mod tests {
     use std::collections::{HashMap, HashSet};
 
-    use fontdrasil::types::Axis;
+    use fontdrasil::{
+        coords::NormalizedLocation,
+        types::{Axis, GlyphName},
+    };
     use fontir::ir::Glyph;
     use kurbo::{BezPath, PathEl};
     use write_fonts::types::Tag;
 
     use crate::{
-        fontra::{FontraFontData, FontraGlyph},
+        fontra::{FontraFontData, FontraGlyph, FontraTransform},
         test::testdata_dir,
-        toir::to_ir_static_metadata,
+        toir::{to_ir_axes, to_ir_local_axes, to_ir_static_metadata},
     };
 
     use super::to_ir_glyph;
@@ -291,7 +482,8 @@ mod tests {
     fn static_metadata_of_2glyphs() {
         let fontdata_file = testdata_dir().join("2glyphs.fontra/font-data.json");
         let font_data = FontraFontData::from_file(&fontdata_file).unwrap();
-        let static_metadata = to_ir_static_metadata(&font_data).unwrap();
+        let axes = to_ir_axes(&font_data).unwrap();
+        let static_metadata = to_ir_static_metadata(&font_data, axes, Default::default()).unwrap();
         assert_eq!(1000, static_metadata.units_per_em);
         assert_eq!(
             vec![
@@ -304,12 +496,18 @@ mod tests {
 
     #[test]
     fn ir_of_glyph_u20089() {
+        let font_data =
+            FontraFontData::from_file(&testdata_dir().join("2glyphs.fontra/font-data.json"))
+                .unwrap();
+        let axes = to_ir_axes(&font_data).unwrap();
         let glyph_file = testdata_dir().join("2glyphs.fontra/glyphs/u20089.json");
         let fontra_glyph = FontraGlyph::from_file(&glyph_file).unwrap();
-        let glyph = to_ir_glyph(
-            HashMap::from([("Weight".to_string(), Tag::new(b"wght"))]),
+        let (glyph, _) = to_ir_glyph(
+            &axes,
+            &Default::default(),
             Default::default(),
             &fontra_glyph,
+            &Default::default(),
         )
         .unwrap();
         assert_eq!(
@@ -321,5 +519,151 @@ mod tests {
                 .collect::<Vec<_>>()
         );
         assert_contour_compatibility(&glyph);
+        assert_eq!(
+            HashSet::from([
+                NormalizedLocation::for_pos(&[("wght", 0.0), ("wdth", 0.0)]),
+                NormalizedLocation::for_pos(&[("wght", 1.0), ("wdth", 0.0)]),
+            ]),
+            glyph.sources().keys().cloned().collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn ir_of_component_glyph() {
+        let font_data =
+            FontraFontData::from_file(&testdata_dir().join("component.fontra/font-data.json"))
+                .unwrap();
+        let axes = to_ir_axes(&font_data).unwrap();
+        let glyph_file = testdata_dir().join("component.fontra/glyphs/uni4E00^G.json");
+        let fontra_glyph = FontraGlyph::from_file(&glyph_file).unwrap();
+        let (glyph, _) = to_ir_glyph(
+            &axes,
+            &Default::default(),
+            Default::default(),
+            &fontra_glyph,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![(0, 1), (0, 1)],
+            glyph
+                .sources()
+                .iter()
+                .map(|(_, s)| (s.contours.len(), s.components.len()))
+                .collect::<Vec<_>>()
+        );
+        assert!(glyph
+            .sources()
+            .values()
+            .flat_map(|s| s.components.iter())
+            .all(|c| c.base == GlyphName::new("VG_4E00_00")));
+    }
+
+    fn component_fontra_axes() -> Vec<Axis> {
+        let font_data =
+            FontraFontData::from_file(&testdata_dir().join("component.fontra/font-data.json"))
+                .unwrap();
+        let mut axes = to_ir_axes(&font_data).unwrap();
+        axes.extend(to_ir_local_axes(6));
+        axes
+    }
+
+    fn read_component_fontra_glyph(file_name: &str) -> FontraGlyph {
+        FontraGlyph::from_file(
+            &testdata_dir()
+                .join("component.fontra/glyphs")
+                .join(file_name),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn local_axes_are_hidden() {
+        assert_eq!(
+            vec![
+                ("V000", Tag::new(b"V000"), -1.0, 0.0, 1.0),
+                ("V001", Tag::new(b"V001"), -1.0, 0.0, 1.0),
+            ],
+            axis_tuples(&to_ir_local_axes(2))
+        );
+        assert!(to_ir_local_axes(2).iter().all(|a| a.hidden));
+    }
+
+    #[test]
+    fn ir_of_glyph_with_local_axes() {
+        let axes = component_fontra_axes();
+        let fontra_glyph = read_component_fontra_glyph("VG_4E00_01^J.json");
+        let (glyph, _) = to_ir_glyph(
+            &axes,
+            &Default::default(),
+            Default::default(),
+            &fontra_glyph,
+            &Default::default(),
+        )
+        .unwrap();
+        let location = |v0, v1, v2| {
+            NormalizedLocation::for_pos(&[
+                ("wght", 0.0),
+                ("V000", v0),
+                ("V001", v1),
+                ("V002", v2),
+                ("V003", 0.0),
+                ("V004", 0.0),
+                ("V005", 0.0),
+            ])
+        };
+        assert_eq!(
+            HashSet::from([
+                location(0.0, 0.0, 0.0),
+                location(-1.0, 0.0, 0.0),
+                location(0.0, 1.0, 0.0),
+                location(0.0, 0.0, 1.0),
+                location(0.0, 0.0, -1.0),
+            ]),
+            glyph.sources().keys().cloned().collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn ir_of_variable_component() {
+        let axes = component_fontra_axes();
+        let fontra_glyph = read_component_fontra_glyph("uni4E00^G.json");
+        let base = read_component_fontra_glyph("VG_4E00_00^J.json");
+        let base_axes = HashMap::from([(base.name.clone(), base.axes.clone())]);
+        let (glyph, _) = to_ir_glyph(
+            &axes,
+            &Default::default(),
+            Default::default(),
+            &fontra_glyph,
+            &base_axes,
+        )
+        .unwrap();
+
+        // width 900 on 200..1000..1000, every other local axis of the base is at its default
+        let component = &glyph.default_instance().components[0];
+        assert!(component.is_variable());
+        assert_eq!(
+            NormalizedLocation::for_pos(&[
+                ("V000", -0.125),
+                ("V001", 0.0),
+                ("V002", 0.0),
+                ("V003", 0.0),
+                ("V004", 0.0),
+                ("V005", 0.0),
+            ]),
+            component.location
+        );
+    }
+
+    #[test]
+    fn decomposed_transform_to_affine() {
+        let transform: FontraTransform = serde_json::from_str(
+            r#"{"translateX": 10, "translateY": 20, "scaleX": 2, "rotation": 90}"#,
+        )
+        .unwrap();
+        let affine = transform.to_affine();
+        let point = affine * kurbo::Point::new(1.0, 0.0);
+        assert!((point.x - 10.0).abs() < 1e-9, "{point:?}");
+        assert!((point.y - 22.0).abs() < 1e-9, "{point:?}");
     }
 }
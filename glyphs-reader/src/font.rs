@@ -13,6 +13,7 @@ use std::{fs, path};
 
 use crate::glyphdata::{Category, GlyphData, Subcategory};
 use crate::plist::FromPlist;
+use icu_properties::BidiClass;
 use kurbo::{Affine, Point, Vec2};
 use log::{debug, warn};
 use ordered_float::OrderedFloat;
@@ -32,6 +33,9 @@ const V3_METRIC_NAMES: [&str; 6] = [
     "italic angle",
 ];
 
+/// Glyph name script suffixes, as used by Glyphs, for right-to-left scripts
+const RTL_SCRIPT_SUFFIXES: [&str; 6] = ["ar", "hb", "syr", "thaa", "nko", "samr"];
+
 #[derive(Clone, Debug, Default, PartialEq, Hash)]
 pub struct RawUserToDesignMapping(BTreeMap<String, RawAxisUserToDesignMap>);
 
@@ -41,7 +45,7 @@ pub struct RawAxisUserToDesignMap(Vec<(OrderedFloat<f32>, OrderedFloat<f32>)>);
 /// A tidied up font from a plist.
 ///
 /// Normalized representation of Glyphs 2/3 content
-#[derive(Debug, PartialEq, Hash)]
+#[derive(Debug, Default, PartialEq, Hash)]
 pub struct Font {
     pub units_per_em: u16,
     pub fs_type: Option<u16>,
@@ -64,6 +68,10 @@ pub struct Font {
 
     // master id => { (name or class, name or class) => adjustment }
     pub kerning_ltr: Kerning,
+    // as kerning_ltr, pairs are in logical order
+    pub kerning_rtl: Kerning,
+    // as kerning_ltr, first is the upper glyph
+    pub kerning_vertical: Kerning,
 }
 
 /// master id => { (name or class, name or class) => adjustment }
@@ -157,11 +165,29 @@ impl FromPlist for Kerning {
 pub struct FeatureSnippet {
     pub content: String,
     pub disabled: bool,
+    /// The tag, if this snippet is a feature block
+    pub feature_tag: Option<String>,
+    /// Whether Glyphs is expected to generate the code from the glyph set
+    pub automatic: bool,
 }
 
 impl FeatureSnippet {
     pub fn new(content: String, disabled: bool) -> Self {
-        FeatureSnippet { content, disabled }
+        FeatureSnippet {
+            content,
+            disabled,
+            feature_tag: None,
+            automatic: false,
+        }
+    }
+
+    pub fn feature(tag: String, content: String, disabled: bool, automatic: bool) -> Self {
+        FeatureSnippet {
+            content,
+            disabled,
+            feature_tag: Some(tag),
+            automatic,
+        }
     }
 
     pub fn str_if_enabled(&self) -> Option<&str> {
@@ -179,11 +205,39 @@ pub struct Glyph {
     pub left_kern: Option<SmolStr>,
     /// The right kerning group
     pub right_kern: Option<SmolStr>,
+    /// The top kerning group, used for vertical kerning
+    pub top_kern: Option<SmolStr>,
+    /// The bottom kerning group, used for vertical kerning
+    pub bottom_kern: Option<SmolStr>,
     pub category: Option<Category>,
     pub sub_category: Option<Subcategory>,
+    /// The axes of a smart component, empty for glyphs that aren't one
+    pub smart_component_axes: Vec<SmartComponentAxis>,
+    /// The metrics key of the left sidebearing, such as `=n` or `=|o`
+    ///
+    /// Applies to every layer that doesn't have a key of its own.
+    pub metric_left: Option<SmolStr>,
+    /// The metrics key of the right sidebearing
+    pub metric_right: Option<SmolStr>,
+    /// The metrics key of the advance width
+    pub metric_width: Option<SmolStr>,
+}
+
+/// An axis of a smart component, known to Glyphs as a part setting
+///
+/// <https://docu.glyphsapp.com/#GSSmartComponentAxis>
+#[derive(Clone, Default, Debug, PartialEq, Eq, Hash, FromPlist)]
+pub struct SmartComponentAxis {
+    pub name: SmolStr,
+    pub bottom_value: OrderedFloat<f64>,
+    pub top_value: OrderedFloat<f64>,
 }
 
 impl Glyph {
+    pub fn is_smart_component(&self) -> bool {
+        !self.smart_component_axes.is_empty()
+    }
+
     pub fn is_nonspacing_mark(&self) -> bool {
         matches!(
             (self.category, self.sub_category),
@@ -194,6 +248,25 @@ impl Glyph {
         )
     }
 
+    /// Whether the glyph belongs to a right-to-left script.
+    ///
+    /// Decided by the first strongly directional codepoint, falling back to
+    /// a script suffix on the name (e.g. `beh-ar.init`) for unencoded glyphs.
+    pub fn is_rtl(&self) -> bool {
+        for cp in self.unicode.iter() {
+            match icu_properties::maps::bidi_class().get32(*cp) {
+                BidiClass::RightToLeft | BidiClass::ArabicLetter => return true,
+                BidiClass::LeftToRight => return false,
+                _ => (),
+            }
+        }
+        let base_name = self.name.split('.').next().unwrap_or_default();
+        base_name
+            .rsplit_once('-')
+            .map(|(_, script)| RTL_SCRIPT_SUFFIXES.contains(&script))
+            .unwrap_or_default()
+    }
+
     pub(crate) fn has_components(&self) -> bool {
         self.layers
             .iter()
@@ -211,6 +284,18 @@ pub struct Layer {
     pub shapes: Vec<Shape>,
     pub anchors: Vec<Anchor>,
     pub attributes: LayerAttributes,
+    /// For layers of a smart component, the pole of each smart axis the layer is at.
+    ///
+    /// 1 is the bottom value, 2 the top. Axes that aren't listed are at the bottom.
+    pub part_selection: BTreeMap<SmolStr, i64>,
+    /// Corner, cap and segment hints, other types of hint aren't kept
+    pub hints: Vec<Hint>,
+    /// The metrics key of the left sidebearing, used instead of that of the glyph
+    pub metric_left: Option<SmolStr>,
+    /// The metrics key of the right sidebearing, used instead of that of the glyph
+    pub metric_right: Option<SmolStr>,
+    /// The metrics key of the advance width, used instead of that of the glyph
+    pub metric_width: Option<SmolStr>,
 }
 
 impl Layer {
@@ -222,6 +307,13 @@ impl Layer {
         self.associated_master_id.is_some() && !self.attributes.coordinates.is_empty()
     }
 
+    pub(crate) fn paths(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.shapes.iter().filter_map(|shape| match shape {
+            Shape::Path(path) => Some(path),
+            Shape::Component(_) => None,
+        })
+    }
+
     pub(crate) fn components(&self) -> impl Iterator<Item = &Component> + '_ {
         self.shapes.iter().filter_map(|shape| match shape {
             Shape::Path(_) => None,
@@ -232,6 +324,73 @@ impl Layer {
     // TODO add is_alternate, is_color, etc.
 }
 
+/// The types of hint that insert a piece of outline drawn in another glyph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HintType {
+    /// Drawn in a `_corner.*` glyph, replaces the node it is attached to
+    Corner,
+    /// Drawn in a `_cap.*` glyph, replaces the segment starting at the node it is attached to
+    Cap,
+    /// Drawn in a `_segment.*` glyph, replaces the segment starting at the node it is attached to
+    Segment,
+}
+
+impl HintType {
+    // Glyphs 2 uses numbers, Glyphs 3 names
+    fn from_raw(raw: &str) -> Option<Self> {
+        match raw.to_ascii_lowercase().as_str() {
+            "corner" | "16" => Some(HintType::Corner),
+            "cap" | "17" => Some(HintType::Cap),
+            "segment" => Some(HintType::Segment),
+            _ => None,
+        }
+    }
+}
+
+/// How a corner is turned to fit the node it is attached to
+///
+/// A corner is drawn for a corner whose outgoing segment leaves the origin along the x axis
+/// and whose incoming segment arrives down the y axis.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CornerAlignment {
+    /// The x axis is turned onto the outgoing segment
+    #[default]
+    Outstroke,
+    /// The y axis is turned onto the incoming segment
+    Instroke,
+    /// Halfway between the two
+    Middle,
+    /// The corner isn't turned
+    Unaligned,
+}
+
+impl CornerAlignment {
+    // <https://github.com/googlefonts/glyphsLib/blob/main/Lib/glyphsLib/filters/cornerComponents.py>
+    fn from_options(options: i64) -> Self {
+        match options {
+            1 => CornerAlignment::Instroke,
+            2 => CornerAlignment::Middle,
+            4 => CornerAlignment::Unaligned,
+            _ => CornerAlignment::Outstroke,
+        }
+    }
+}
+
+/// A hint that attaches a piece of outline, drawn in another glyph, to a node of a path
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Hint {
+    pub type_: HintType,
+    /// The name of the glyph the piece of outline is drawn in
+    pub name: SmolStr,
+    /// The index of the path among the paths of the layer
+    pub path_index: usize,
+    /// The index of the node in the path
+    pub node_index: usize,
+    pub scale: (OrderedFloat<f64>, OrderedFloat<f64>),
+    /// Only meaningful for corners
+    pub alignment: CornerAlignment,
+}
+
 #[derive(Clone, Default, Debug, PartialEq, Hash)]
 pub struct LayerAttributes {
     pub coordinates: Vec<OrderedFloat<f64>>,
@@ -267,6 +426,74 @@ impl FromPlist for LayerAttributes {
     }
 }
 
+#[derive(Default, Clone, Debug, PartialEq)]
+struct RawHint {
+    type_: Option<String>,
+    name: Option<SmolStr>,
+    origin: Option<Point>,
+    scale: Option<Point>,
+    options: Option<i64>,
+}
+
+// hand-parse because Glyphs 2 points are strings and Glyphs 3 points are arrays
+impl FromPlist for RawHint {
+    fn parse(tokenizer: &mut Tokenizer<'_>) -> Result<Self, crate::plist::Error> {
+        fn parse_point(tokenizer: &mut Tokenizer<'_>) -> Result<Point, crate::plist::Error> {
+            if let Token::String(..) = tokenizer.peek()? {
+                let raw: String = tokenizer.parse()?;
+                Point::parse_plist(&raw)
+            } else {
+                tokenizer.parse()
+            }
+        }
+
+        let mut hint = RawHint::default();
+
+        tokenizer.eat(b'{')?;
+
+        loop {
+            if tokenizer.eat(b'}').is_ok() {
+                break;
+            }
+
+            let key: String = tokenizer.parse()?;
+            tokenizer.eat(b'=')?;
+            match key.as_str() {
+                "type" => hint.type_ = Some(tokenizer.parse()?),
+                "name" => hint.name = Some(tokenizer.parse()?),
+                "origin" => hint.origin = Some(parse_point(tokenizer)?),
+                "scale" => hint.scale = Some(parse_point(tokenizer)?),
+                "options" => hint.options = Some(tokenizer.parse()?),
+                // stems, ghosts, TrueType instructions and the like
+                _ => tokenizer.skip_rec()?,
+            }
+            tokenizer.eat(b';')?;
+        }
+
+        Ok(hint)
+    }
+}
+
+impl RawHint {
+    /// The hint, if it inserts a piece of outline
+    fn to_hint(&self) -> Option<Hint> {
+        let type_ = HintType::from_raw(self.type_.as_deref()?)?;
+        let (Some(name), Some(origin)) = (self.name.as_ref(), self.origin) else {
+            warn!("Ignoring {type_:?} hint without a name or origin");
+            return None;
+        };
+        let scale = self.scale.unwrap_or(Point::new(1.0, 1.0));
+        Some(Hint {
+            type_,
+            name: name.clone(),
+            path_index: origin.x as usize,
+            node_index: origin.y as usize,
+            scale: (scale.x.into(), scale.y.into()),
+            alignment: CornerAlignment::from_options(self.options.unwrap_or_default()),
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Hash)]
 pub enum Shape {
     Path(Path),
@@ -284,6 +511,7 @@ struct RawFont {
     format_version: i64,
     units_per_em: Option<i64>,
     metrics: Vec<RawMetric>,
+    numbers: Vec<RawNumber>,
     family_name: String,
     date: Option<String>,
     copyright: Option<String>,
@@ -303,6 +531,8 @@ struct RawFont {
     properties: Vec<RawName>,
     #[fromplist(alt_name = "kerning")]
     kerning_LTR: Kerning,
+    kerning_RTL: Kerning,
+    kerning_vertical: Kerning,
     custom_parameters: CustomParameters,
 }
 
@@ -560,6 +790,12 @@ struct RawMetric {
     type_: Option<String>,
 }
 
+/// A named number, referenced from feature code as `${name}`
+#[derive(Default, Clone, Debug, PartialEq, Eq, Hash, FromPlist)]
+struct RawNumber {
+    name: String,
+}
+
 #[derive(Default, Clone, Debug, PartialEq, Eq, Hash, FromPlist)]
 struct RawName {
     key: String,
@@ -603,9 +839,20 @@ struct RawGlyph {
     kern_left: Option<SmolStr>,
     #[fromplist(alt_name = "rightKerningGroup")]
     kern_right: Option<SmolStr>,
+    #[fromplist(alt_name = "topKerningGroup")]
+    kern_top: Option<SmolStr>,
+    #[fromplist(alt_name = "bottomKerningGroup")]
+    kern_bottom: Option<SmolStr>,
     unicode: Option<String>,
     category: Option<SmolStr>,
     sub_category: Option<SmolStr>,
+    parts_settings: Vec<SmartComponentAxis>,
+    #[fromplist(alt_name = "leftMetricsKey")]
+    metric_left: Option<SmolStr>,
+    #[fromplist(alt_name = "rightMetricsKey")]
+    metric_right: Option<SmolStr>,
+    #[fromplist(alt_name = "widthMetricsKey")]
+    metric_width: Option<SmolStr>,
     #[fromplist(ignore)]
     other_stuff: BTreeMap<String, Plist>,
 }
@@ -622,6 +869,14 @@ struct RawLayer {
     anchors: Vec<RawAnchor>,
     #[fromplist(alt_name = "attr")]
     attributes: LayerAttributes,
+    part_selection: BTreeMap<SmolStr, i64>,
+    hints: Vec<RawHint>,
+    #[fromplist(alt_name = "leftMetricsKey")]
+    metric_left: Option<SmolStr>,
+    #[fromplist(alt_name = "rightMetricsKey")]
+    metric_right: Option<SmolStr>,
+    #[fromplist(alt_name = "widthMetricsKey")]
+    metric_width: Option<SmolStr>,
     #[fromplist(ignore)]
     other_stuff: BTreeMap<String, Plist>,
 }
@@ -633,7 +888,9 @@ impl RawLayer {
     /// Without 'attributes' that specify whether it's a special intermediate, alternate or
     /// color layer, we can assume the non-master layer is a draft.
     fn is_draft(&self) -> bool {
-        self.associated_master_id.is_some() && self.attributes == Default::default()
+        self.associated_master_id.is_some()
+            && self.attributes == Default::default()
+            && self.part_selection.is_empty()
     }
 
     fn v2_to_v3_attributes(&mut self) {
@@ -676,6 +933,10 @@ struct RawShape {
     // for components, an optional name to rename an anchor
     // on the target glyph during anchor propagation
     anchor: Option<SmolStr>,
+    // for smart components, the location on the axes of the referenced glyph
+    piece: BTreeMap<SmolStr, OrderedFloat<f64>>,
+    // for components, -1 if automatic alignment is disabled
+    alignment: Option<i64>,
     transform: Option<String>, // v2
     pos: Vec<f64>,             // v3
     angle: Option<f64>,        // v3
@@ -699,12 +960,25 @@ pub struct Component {
     /// For instance, if an acute accent is a component of a ligature glyph,
     /// we might rename its 'top' anchor to 'top_2'
     pub anchor: Option<SmolStr>,
+    /// Where to draw a smart component, by smart axis name
+    ///
+    /// Axes that aren't listed are at their bottom value.
+    pub piece: BTreeMap<SmolStr, OrderedFloat<f64>>,
+    /// -1 if the component is positioned by hand rather than automatically aligned
+    pub alignment: Option<i64>,
+}
+
+impl Component {
+    pub fn is_auto_aligned(&self) -> bool {
+        self.alignment != Some(-1)
+    }
 }
 
 impl PartialEq for Component {
     fn eq(&self, other: &Self) -> bool {
         self.name == other.name
             && Into::<AffineForEqAndHash>::into(self.transform) == other.transform.into()
+            && self.piece == other.piece
     }
 }
 
@@ -714,6 +988,7 @@ impl Hash for Component {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.name.hash(state);
         Into::<AffineForEqAndHash>::into(self.transform).hash(state);
+        self.piece.hash(state);
     }
 }
 
@@ -786,6 +1061,8 @@ pub struct FontMaster {
     pub name: String,
     pub axes_values: Vec<OrderedFloat<f64>>,
     metric_values: BTreeMap<String, RawMetricValue>,
+    /// Values for the font's named numbers, keyed by name
+    pub number_values: BTreeMap<String, OrderedFloat<f64>>,
     pub typo_ascender: Option<i64>,
     pub typo_descender: Option<i64>,
     pub typo_line_gap: Option<i64>,
@@ -859,7 +1136,8 @@ struct RawFontMaster {
     win_descender: Option<OrderedFloat<f64>>,
 
     axes_values: Vec<OrderedFloat<f64>>,
-    metric_values: Vec<RawMetricValue>, // v3
+    metric_values: Vec<RawMetricValue>,    // v3
+    number_values: Vec<OrderedFloat<f64>>, // v3
 
     ascender: Option<OrderedFloat<f64>>,   // v2
     baseline: Option<OrderedFloat<f64>>,   // v2
@@ -897,6 +1175,11 @@ pub struct Instance {
     pub type_: InstanceType,
     pub axis_mappings: BTreeMap<String, RawAxisUserToDesignMap>,
     pub axes_values: Vec<OrderedFloat<f64>>,
+    pub family_name: Option<String>,
+    pub postscript_font_name: Option<String>,
+    pub style_map_family_name: Option<String>,
+    /// One of "regular", "italic", "bold" or "bold italic"
+    pub style_map_style_name: Option<String>,
 }
 
 /// <https://github.com/googlefonts/glyphsLib/blob/6f243c1f732ea1092717918d0328f3b5303ffe56/Lib/glyphsLib/classes.py#L150>
@@ -934,6 +1217,11 @@ struct RawInstance {
 
     weight_class: Option<String>,
     width_class: Option<String>,
+
+    properties: Vec<RawName>,
+    custom_parameters: CustomParameters,
+    is_bold: Option<i64>,
+    is_italic: Option<i64>,
 }
 
 impl RawInstance {
@@ -942,6 +1230,33 @@ impl RawInstance {
     fn is_active(&self) -> bool {
         self.exports.unwrap_or(1) != 0 && self.active.unwrap_or(1) != 0
     }
+
+    // TODO: we only support dflt, .glyphs l10n names are ignored
+    fn property(&self, key: &str) -> Option<String> {
+        let name = self.properties.iter().find(|name| name.key == key)?;
+        name.value.clone().or_else(|| {
+            name.values
+                .iter()
+                .find(|v| v.language == "dflt")
+                .map(|v| v.value.clone())
+        })
+    }
+
+    /// The style map style, from the explicit property if any, otherwise from isBold and isItalic
+    fn style_map_style_name(&self) -> Option<String> {
+        if let Some(name) = self.property("styleMapStyleNames") {
+            return Some(name.to_lowercase());
+        }
+        match (
+            self.is_bold.unwrap_or(0) != 0,
+            self.is_italic.unwrap_or(0) != 0,
+        ) {
+            (false, false) => None,
+            (true, false) => Some("bold".to_string()),
+            (false, true) => Some("italic".to_string()),
+            (true, true) => Some("bold italic".to_string()),
+        }
+    }
 }
 
 trait GlyphsV2OrderedAxes {
@@ -1319,6 +1634,17 @@ impl RawFont {
 
     fn v2_to_v3_instances(&mut self) -> Result<(), Error> {
         for instance in self.instances.iter_mut() {
+            // the names of an instance move from custom parameters to properties in v3
+            for (v2_name, v3_name) in [
+                ("familyName", "familyNames"),
+                ("postscriptFontName", "postscriptFontName"),
+                ("styleMapFamilyName", "styleMapFamilyNames"),
+                ("styleMapStyleName", "styleMapStyleNames"),
+            ] {
+                let value = instance.custom_parameters.string(v2_name);
+                v2_to_v3_name(&mut instance.properties, value, v3_name);
+            }
+
             // named clases become #s in v3
             for (tag, opt) in [
                 ("wght", &mut instance.weight_class),
@@ -1614,6 +1940,8 @@ impl TryFrom<RawShape> for Shape {
                 name: glyph_name,
                 transform,
                 anchor: from.anchor,
+                piece: from.piece,
+                alignment: from.alignment,
             })
         } else {
             // no ref; presume it's a path
@@ -1667,6 +1995,11 @@ impl TryFrom<RawLayer> for Layer {
             shapes,
             anchors,
             attributes: from.attributes,
+            part_selection: from.part_selection,
+            hints: from.hints.iter().filter_map(RawHint::to_hint).collect(),
+            metric_left: from.metric_left,
+            metric_right: from.metric_right,
+            metric_width: from.metric_width,
         })
     }
 }
@@ -1723,9 +2056,15 @@ impl RawGlyph {
             layers: instances,
             left_kern: self.kern_left,
             right_kern: self.kern_right,
+            top_kern: self.kern_top,
+            bottom_kern: self.kern_bottom,
             unicode: codepoints,
             category,
             sub_category,
+            smart_component_axes: self.parts_settings,
+            metric_left: self.metric_left,
+            metric_right: self.metric_right,
+            metric_width: self.metric_width,
         })
     }
 }
@@ -1761,6 +2100,10 @@ impl RawFeature {
     fn disabled(&self) -> bool {
         self.disabled == Some(1)
     }
+
+    fn is_automatic(&self) -> bool {
+        self.automatic == Some(1)
+    }
 }
 
 // https://github.com/googlefonts/glyphsLib/blob/24b4d340e4c82948ba121dcfe563c1450a8e69c9/Lib/glyphsLib/builder/features.py#L90
@@ -1795,7 +2138,12 @@ fn raw_feature_to_feature(feature: RawFeature) -> Result<FeatureSnippet, Error>
         feature.autostr(),
         feature.code
     );
-    Ok(FeatureSnippet::new(code, feature.disabled()))
+    Ok(FeatureSnippet::feature(
+        name,
+        code,
+        feature.disabled(),
+        feature.is_automatic(),
+    ))
 }
 
 /// <https://github.com/googlefonts/glyphsLib/blob/6f243c1f732ea1092717918d0328f3b5303ffe56/Lib/glyphsLib/classes.py#L220-L249>
@@ -1907,6 +2255,10 @@ impl Instance {
                 .unwrap_or(InstanceType::Single),
             axis_mappings,
             axes_values: value.axes_values.clone(),
+            family_name: value.property("familyNames"),
+            postscript_font_name: value.property("postscriptFontName"),
+            style_map_family_name: value.property("styleMapFamilyNames"),
+            style_map_style_name: value.style_map_style_name(),
         }
     }
 }
@@ -1985,6 +2337,8 @@ impl TryFrom<RawFont> for Font {
             .filter_map(|(idx, metric)| metric.type_.map(|name| (idx, name)))
             .collect();
 
+        let number_names: Vec<_> = from.numbers.into_iter().map(|n| n.name).collect();
+
         let masters = from
             .font_master
             .into_iter()
@@ -2001,6 +2355,7 @@ impl TryFrom<RawFont> for Font {
                     })
                     .filter(|(_, metric)| !metric.is_empty())
                     .collect(),
+                number_values: number_names.iter().cloned().zip(m.number_values).collect(),
                 typo_ascender: m.custom_parameters.int("typoAscender"),
                 typo_descender: m.custom_parameters.int("typoDescender"),
                 typo_line_gap: m.custom_parameters.int("typoLineGap"),
@@ -2058,6 +2413,8 @@ impl TryFrom<RawFont> for Font {
             version_minor: from.versionMinor.unwrap_or_default() as u32,
             date: from.date,
             kerning_ltr: from.kerning_LTR,
+            kerning_rtl: from.kerning_RTL,
+            kerning_vertical: from.kerning_vertical,
         })
     }
 }
@@ -2074,11 +2431,12 @@ fn preprocess_unparsed_plist(s: &str) -> Cow<str> {
 impl Font {
     pub fn load(glyphs_file: &path::Path) -> Result<Font, Error> {
         let mut font = Self::load_impl(glyphs_file)?;
+        font.apply_metrics_keys();
         font.propagate_all_anchors();
         Ok(font)
     }
 
-    // load without propagating anchors
+    // load without applying metrics keys or propagating anchors
     pub(crate) fn load_impl(glyphs_file: impl AsRef<path::Path>) -> Result<Font, Error> {
         let glyphs_file = glyphs_file.as_ref();
         if glyphs_file.extension() == Some(OsStr::new("glyphspackage")) {
@@ -2205,9 +2563,12 @@ impl From<Affine> for AffineForEqAndHash {
 #[cfg(test)]
 mod tests {
     use crate::{
-        font::{RawAxisUserToDesignMap, RawFeature, RawUserToDesignMapping},
+        font::{
+            CornerAlignment, Hint, HintType, Kerning, RawAxisUserToDesignMap, RawFeature, RawHint,
+            RawUserToDesignMapping,
+        },
         plist::FromPlist,
-        Font, Node, Shape,
+        Font, Node, Shape, SmartComponentAxis,
     };
     use std::{
         collections::{BTreeMap, BTreeSet, HashSet},
@@ -2371,6 +2732,11 @@ mod tests {
         assert_load_v2_matches_load_v3("WghtVar_Instances", LoadCompare::GlyphsAndPackage);
     }
 
+    #[test]
+    fn read_wght_var_instance_names_2_and_3() {
+        assert_load_v2_matches_load_v3("WghtVar_InstanceNames", LoadCompare::Glyphs);
+    }
+
     #[test]
     fn read_wght_var_os2_2_and_3() {
         assert_load_v2_matches_load_v3("WghtVar_OS2", LoadCompare::GlyphsAndPackage);
@@ -2759,6 +3125,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn instance_names() {
+        let font = Font::load(&glyphs3_dir().join("WghtVar_InstanceNames.glyphs")).unwrap();
+        assert_eq!(
+            vec![
+                ("Regular", None, None, None, None),
+                ("Bold", None, None, None, Some("bold")),
+                (
+                    "SemiBold",
+                    Some("WghtVar Display"),
+                    Some("WghtVarDisplay-SmBd"),
+                    Some("WghtVar Display SemiBold"),
+                    None
+                ),
+            ],
+            font.instances
+                .iter()
+                .map(|inst| (
+                    inst.name.as_str(),
+                    inst.family_name.as_deref(),
+                    inst.postscript_font_name.as_deref(),
+                    inst.style_map_family_name.as_deref(),
+                    inst.style_map_style_name.as_deref(),
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn read_typo_whatsits() {
         let font = Font::load(&glyphs2_dir().join("WghtVar_OS2.glyphs")).unwrap();
@@ -2841,6 +3235,75 @@ mod tests {
         );
     }
 
+    #[test]
+    fn read_rtl_and_vertical_kerning() {
+        let font = Font::load(&glyphs3_dir().join("KernRTL.glyphs")).unwrap();
+        let kerns = |kerning: &Kerning| {
+            kerning
+                .get("m01")
+                .unwrap()
+                .iter()
+                .map(|((n1, n2), value)| (n1.clone(), n2.clone(), *value))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            (
+                vec![("@MMK_L_a".to_string(), "@MMK_R_a".to_string(), -10)],
+                vec![
+                    ("@MMK_R_alef".to_string(), "@MMK_L_bet".to_string(), -40),
+                    ("bet-hb".to_string(), "alef-hb".to_string(), 12),
+                ],
+                vec![("@MMK_T_a".to_string(), "@MMK_B_a".to_string(), -7)],
+            ),
+            (
+                kerns(&font.kerning_ltr),
+                kerns(&font.kerning_rtl),
+                kerns(&font.kerning_vertical)
+            )
+        );
+        let a = font.glyphs.get("a").unwrap();
+        assert_eq!(
+            (Some("a"), Some("a")),
+            (a.top_kern.as_deref(), a.bottom_kern.as_deref())
+        );
+    }
+
+    #[test]
+    fn read_number_values() {
+        let font = Font::load(&glyphs3_dir().join("FeaTokens.glyphs")).unwrap();
+        assert_eq!(
+            vec![
+                vec![("padding", 10.0), ("tracking", 5.0)],
+                vec![("padding", 30.0), ("tracking", 5.0)],
+            ],
+            font.masters
+                .iter()
+                .map(|m| m
+                    .number_values
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.into_inner()))
+                    .collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn glyph_direction() {
+        let font = Font::load(&glyphs3_dir().join("KernRTL.glyphs")).unwrap();
+        assert_eq!(
+            vec![
+                ("a", false),
+                ("alef-hb", true),
+                ("bet-hb", true),
+                ("bet-hb.alt", true)
+            ],
+            font.glyphs
+                .iter()
+                .map(|(name, glyph)| (name.as_str(), glyph.is_rtl()))
+                .collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn read_simple_anchor() {
         let font = Font::load(&glyphs3_dir().join("WghtVar_Anchors.glyphs")).unwrap();
@@ -2914,4 +3377,82 @@ mod tests {
             .unwrap();
         assert_eq!(acute_comb.anchor.as_deref(), Some("top_2"));
     }
+
+    #[test]
+    fn smart_component_parts() {
+        let font = Font::load(&glyphs3_dir().join("SmartComponent.glyphs")).unwrap();
+        let part = font.glyphs.get("_part.bar").unwrap();
+        assert!(part.is_smart_component());
+        assert_eq!(
+            vec![SmartComponentAxis {
+                name: "Width".into(),
+                bottom_value: OrderedFloat(0.0),
+                top_value: OrderedFloat(100.0),
+            }],
+            part.smart_component_axes
+        );
+        assert_eq!(
+            vec![BTreeMap::new(), BTreeMap::from([("Width".into(), 2)])],
+            part.layers
+                .iter()
+                .map(|l| l.part_selection.clone())
+                .collect::<Vec<_>>()
+        );
+
+        let bar = font.glyphs.get("bar").unwrap();
+        assert!(!bar.is_smart_component());
+        let Shape::Component(component) = &bar.layers[0].shapes[0] else {
+            panic!("Expected a component");
+        };
+        assert_eq!(
+            BTreeMap::from([("Width".into(), OrderedFloat(50.0))]),
+            component.piece
+        );
+    }
+
+    #[test]
+    fn read_outline_hints() {
+        let font = Font::load(&glyphs3_dir().join("CornerComponents.glyphs")).unwrap();
+        let square = font.glyphs.get("square").unwrap();
+        // The stem hint isn't kept
+        assert_eq!(
+            vec![
+                Hint {
+                    type_: HintType::Corner,
+                    name: "_corner.chamfer".into(),
+                    path_index: 0,
+                    node_index: 1,
+                    scale: (OrderedFloat(1.0), OrderedFloat(1.0)),
+                    alignment: CornerAlignment::Instroke,
+                },
+                Hint {
+                    type_: HintType::Cap,
+                    name: "_cap.point".into(),
+                    path_index: 0,
+                    node_index: 3,
+                    scale: (OrderedFloat(1.0), OrderedFloat(0.5)),
+                    alignment: CornerAlignment::Outstroke,
+                },
+            ],
+            square.layers[0].hints
+        );
+    }
+
+    #[test]
+    fn read_v2_outline_hint() {
+        let hint = RawHint::parse_plist(
+            r#"{
+            name = _corner.chamfer;
+            origin = "{1, 3}";
+            type = 16;
+        }"#,
+        )
+        .unwrap()
+        .to_hint()
+        .unwrap();
+        assert_eq!(
+            (HintType::Corner, 1, 3),
+            (hint.type_, hint.path_index, hint.node_index)
+        );
+    }
 }
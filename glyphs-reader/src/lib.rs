@@ -3,11 +3,12 @@
 pub mod error;
 mod font;
 pub mod glyphdata;
+mod metrics_keys;
 mod plist;
 mod propagate_anchors;
 
 pub use font::{
-    Axis, Component, FeatureSnippet, Font, FontMaster, Glyph, InstanceType, Layer, Node, NodeType,
-    Path, Shape,
+    Axis, Component, CornerAlignment, FeatureSnippet, Font, FontMaster, Glyph, Hint, HintType,
+    InstanceType, Layer, Node, NodeType, Path, Shape, SmartComponentAxis,
 };
 pub use plist::Plist;
@@ -0,0 +1,773 @@
+//! Evaluating metrics keys and automatic alignment
+//!
+//! Glyphs.app lets the sidebearings and width of a glyph follow those of another,
+//! using metrics keys such as `=n`, `=|o` or `=a+20`, and sizes automatically aligned
+//! composites after their components. The widths saved in a source can be stale, for
+//! instance if a glyph was edited after the glyphs that depend on it were last updated,
+//! so we work them out again. Components are left where the source put them.
+//!
+//! The sidebearings of italic masters are measured slanted by the italic angle, as
+//! Glyphs does. Glyphs also measures them from the `*origin` anchor, keys of or
+//! referring to layers with one are left as the source has them.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use kurbo::{Affine, BezPath, Point, Rect, Shape as _, Vec2};
+use log::{debug, warn};
+use ordered_float::OrderedFloat;
+use smol_str::SmolStr;
+
+use crate::{
+    glyphdata::{Category, Subcategory},
+    Font, Glyph, Layer, Node, NodeType, Path, Shape,
+};
+
+/// How deep components may nest, a cycle of components would otherwise never end
+const MAX_COMPONENT_DEPTH: usize = 64;
+
+/// Metrics closer than this are the same, the difference is floating point noise
+const TOLERANCE: f64 = 1e-6;
+
+impl Font {
+    /// Evaluate metrics keys and update the widths of automatically aligned composites
+    pub fn apply_metrics_keys(&mut self) {
+        let skews = self
+            .masters
+            .iter()
+            .filter_map(|master| {
+                let italic_angle = master.italic_angle().filter(|angle| *angle != 0.0)?;
+                let skew = unslant(italic_angle, master.x_height().unwrap_or_default());
+                Some((master.id.clone(), skew))
+            })
+            .collect();
+        apply_metrics_keys_impl(&mut self.glyphs, &skews);
+    }
+}
+
+/// The transform, by master id, that sidebearings are measured after
+///
+/// Masters that aren't italic measure them as drawn and aren't included.
+type Skews = HashMap<String, Affine>;
+
+/// Undo the slant of an italic master, so slanted sidebearings can be measured upright
+///
+/// Glyphs slants around half the x-height, where the sidebearings it shows are measured.
+fn unslant(italic_angle: f64, x_height: f64) -> Affine {
+    let tan = italic_angle.to_radians().tan();
+    Affine::new([1.0, 0.0, -tan, 1.0, tan * x_height / 2.0, 0.0])
+}
+
+// the actual implementation: it's easier to test a free fn
+fn apply_metrics_keys_impl(glyphs: &mut BTreeMap<SmolStr, Glyph>, skews: &Skews) {
+    let names: Vec<_> = glyphs.keys().cloned().collect();
+    let mut done = HashSet::new();
+    for name in names {
+        update_glyph(glyphs, skews, &name, &mut done, &mut HashSet::new());
+    }
+}
+
+/// Update a glyph, after the glyphs its components and metrics keys refer to
+fn update_glyph(
+    glyphs: &mut BTreeMap<SmolStr, Glyph>,
+    skews: &Skews,
+    name: &SmolStr,
+    done: &mut HashSet<SmolStr>,
+    in_progress: &mut HashSet<SmolStr>,
+) {
+    let Some(glyph) = glyphs.get(name) else {
+        return;
+    };
+    if done.contains(name) {
+        return;
+    }
+    if !in_progress.insert(name.clone()) {
+        warn!("'{name}' depends on itself through its components or metrics keys");
+        return;
+    }
+    for dependency in dependencies(glyph) {
+        update_glyph(glyphs, skews, &dependency, done, in_progress);
+    }
+
+    let mut glyph = glyphs.remove(name).unwrap();
+    let mut layers = std::mem::take(&mut glyph.layers);
+    for layer in layers.iter_mut() {
+        // Glyphs ignores the metrics keys of automatically aligned layers
+        if !align_width(&glyph, layer, glyphs) {
+            apply_keys(&glyph, layer, glyphs, skews);
+        }
+    }
+    glyph.layers = layers;
+    glyphs.insert(name.clone(), glyph);
+
+    in_progress.remove(name);
+    done.insert(name.clone());
+}
+
+/// The names of the glyphs the layers of a glyph depend on
+fn dependencies(glyph: &Glyph) -> Vec<SmolStr> {
+    let keys = [&glyph.metric_left, &glyph.metric_right, &glyph.metric_width]
+        .into_iter()
+        .chain(
+            glyph
+                .layers
+                .iter()
+                .flat_map(|layer| [&layer.metric_left, &layer.metric_right, &layer.metric_width]),
+        )
+        .flatten()
+        .filter_map(|key| match MetricsKey::parse(key)?.value {
+            KeyValue::Glyph {
+                name: Some(name), ..
+            } => Some(SmolStr::new(name)),
+            _ => None,
+        });
+    let components = glyph
+        .layers
+        .iter()
+        .flat_map(Layer::components)
+        .map(|component| component.name.clone());
+    keys.chain(components)
+        .filter(|name| *name != glyph.name)
+        .collect()
+}
+
+/// A metrics key, such as `=a`, `=|o` or `=H+20`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MetricsKey<'a> {
+    value: KeyValue<'a>,
+    /// An operator, one of `+-*/`, and a number to apply to the value
+    arithmetic: Option<(char, f64)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeyValue<'a> {
+    Number(f64),
+    /// The same metric of a glyph, or the other sidebearing if `opposite`
+    ///
+    /// A missing name is the glyph the key belongs to, as in `=|`.
+    Glyph {
+        name: Option<&'a str>,
+        opposite: bool,
+    },
+}
+
+impl<'a> MetricsKey<'a> {
+    fn parse(key: &'a str) -> Option<Self> {
+        let key = key.trim();
+        let key = key.strip_prefix('=').unwrap_or(key).trim();
+        if let Ok(number) = key.parse() {
+            return Some(MetricsKey {
+                value: KeyValue::Number(number),
+                arithmetic: None,
+            });
+        }
+        let (key, opposite) = match key.strip_prefix('|') {
+            Some(key) => (key.trim_start(), true),
+            None => (key, false),
+        };
+
+        // Glyph names may contain '-' so only split where a number follows the operator
+        let (name, arithmetic) = key
+            .char_indices()
+            .rev()
+            .filter(|(i, c)| *i > 0 && matches!(c, '+' | '-' | '*' | '/'))
+            .find_map(|(i, op)| {
+                let operand: f64 = key[i + 1..].trim().parse().ok()?;
+                (op != '/' || operand != 0.0).then_some((key[..i].trim_end(), Some((op, operand))))
+            })
+            .unwrap_or((key, None));
+
+        let value = match name.parse() {
+            Ok(number) if !opposite => KeyValue::Number(number),
+            _ if name.is_empty() && !opposite => return None,
+            _ => KeyValue::Glyph {
+                name: (!name.is_empty()).then_some(name),
+                opposite,
+            },
+        };
+        Some(MetricsKey { value, arithmetic })
+    }
+
+    fn apply(&self, value: f64) -> f64 {
+        match self.arithmetic {
+            Some(('+', operand)) => value + operand,
+            Some(('-', operand)) => value - operand,
+            Some(('*', operand)) => value * operand,
+            Some(('/', operand)) => value / operand,
+            _ => value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+    Left,
+    Right,
+    Width,
+}
+
+impl Metric {
+    fn opposite(self) -> Self {
+        match self {
+            Metric::Left => Metric::Right,
+            Metric::Right => Metric::Left,
+            Metric::Width => Metric::Width,
+        }
+    }
+}
+
+/// Apply the metrics keys of a layer, or failing that those of its glyph
+fn apply_keys(glyph: &Glyph, layer: &mut Layer, glyphs: &BTreeMap<SmolStr, Glyph>, skews: &Skews) {
+    let left = layer
+        .metric_left
+        .clone()
+        .or_else(|| glyph.metric_left.clone());
+    let right = layer
+        .metric_right
+        .clone()
+        .or_else(|| glyph.metric_right.clone());
+    let width = layer
+        .metric_width
+        .clone()
+        .or_else(|| glyph.metric_width.clone());
+    if [&left, &right, &width].iter().any(|key| key.is_some()) && has_origin(layer) {
+        debug!(
+            "Not applying the metrics keys of '{}' layer '{}', it has an origin anchor",
+            glyph.name, layer.layer_id
+        );
+        return;
+    }
+
+    if let Some(key) = left {
+        let value = evaluate(&key, Metric::Left, layer, glyphs, skews);
+        match (value, metric_of(layer, Metric::Left, glyphs, skews)) {
+            (Some(value), Some(current)) if (value - current).abs() > TOLERANCE => {
+                let delta = value - current;
+                translate(layer, delta);
+                layer.width = OrderedFloat(layer.width.0 + delta);
+            }
+            (Some(_), Some(_)) => (),
+            _ => debug!(
+                "Unable to apply left metrics key '{key}' to '{}' layer '{}'",
+                glyph.name, layer.layer_id
+            ),
+        }
+    }
+
+    // A width key takes precedence over a right one, as they set the same thing
+    if let Some(key) = width {
+        match evaluate(&key, Metric::Width, layer, glyphs, skews) {
+            Some(value) => layer.width = value.into(),
+            None => debug!(
+                "Unable to apply width metrics key '{key}' to '{}' layer '{}'",
+                glyph.name, layer.layer_id
+            ),
+        }
+    } else if let Some(key) = right {
+        let value = evaluate(&key, Metric::Right, layer, glyphs, skews);
+        match (value, metric_of(layer, Metric::Right, glyphs, skews)) {
+            (Some(value), Some(current)) => {
+                layer.width = OrderedFloat(layer.width.0 + value - current);
+            }
+            _ => debug!(
+                "Unable to apply right metrics key '{key}' to '{}' layer '{}'",
+                glyph.name, layer.layer_id
+            ),
+        }
+    }
+}
+
+/// The value a metrics key gives `metric` of `layer`
+fn evaluate(
+    key: &str,
+    metric: Metric,
+    layer: &Layer,
+    glyphs: &BTreeMap<SmolStr, Glyph>,
+    skews: &Skews,
+) -> Option<f64> {
+    let key = MetricsKey::parse(key)?;
+    let value = match key.value {
+        KeyValue::Number(number) => number,
+        KeyValue::Glyph { name, opposite } => {
+            let source = match name {
+                Some(name) => matching_layer(glyphs.get(name)?, layer)?,
+                None => layer,
+            };
+            if has_origin(source) {
+                return None;
+            }
+            let metric = if opposite { metric.opposite() } else { metric };
+            metric_of(source, metric, glyphs, skews)?
+        }
+    };
+    Some(key.apply(value))
+}
+
+/// The sidebearings or width of a layer
+///
+/// None for the sidebearings of a layer without an outline, or whose outline we don't know.
+fn metric_of(
+    layer: &Layer,
+    metric: Metric,
+    glyphs: &BTreeMap<SmolStr, Glyph>,
+    skews: &Skews,
+) -> Option<f64> {
+    let master_id = layer
+        .associated_master_id
+        .as_ref()
+        .unwrap_or(&layer.layer_id);
+    let skew = skews.get(master_id).copied().unwrap_or(Affine::IDENTITY);
+    match metric {
+        Metric::Left => Some(bounds(layer, glyphs, skew)?.min_x()),
+        Metric::Right => Some(layer.width.0 - bounds(layer, glyphs, skew)?.max_x()),
+        Metric::Width => Some(layer.width.0),
+    }
+}
+
+/// Whether a layer has an `*origin` anchor, which Glyphs measures sidebearings from
+fn has_origin(layer: &Layer) -> bool {
+    layer.anchors.iter().any(|anchor| anchor.is_origin())
+}
+
+/// The layer of a glyph at the same place in the designspace as a layer of another
+fn matching_layer<'a>(glyph: &'a Glyph, layer: &Layer) -> Option<&'a Layer> {
+    glyph.layers.iter().find(|candidate| {
+        candidate.layer_id == layer.layer_id
+            || (!layer.is_master()
+                && candidate.associated_master_id == layer.associated_master_id
+                && candidate.attributes == layer.attributes
+                && candidate.part_selection == layer.part_selection)
+    })
+}
+
+/// Move the outline and anchors of a layer horizontally
+fn translate(layer: &mut Layer, dx: f64) {
+    let delta = Vec2::new(dx, 0.0);
+    for shape in layer.shapes.iter_mut() {
+        match shape {
+            Shape::Path(path) => path.nodes.iter_mut().for_each(|node| node.pt += delta),
+            Shape::Component(component) => {
+                component.transform = component.transform.then_translate(delta)
+            }
+        }
+    }
+    for anchor in layer.anchors.iter_mut() {
+        anchor.pos += delta;
+    }
+}
+
+/// Set the width of an automatically aligned composite from its components
+///
+/// The first component, or the first that isn't a mark, sets the width. A ligature
+/// instead ends where its last component that isn't a mark does. Components aren't
+/// moved. Returns false, leaving the layer untouched, if the layer isn't automatically
+/// aligned or we can't tell how wide its components are.
+fn align_width(glyph: &Glyph, layer: &mut Layer, glyphs: &BTreeMap<SmolStr, Glyph>) -> bool {
+    let Some(first) = layer.components().next() else {
+        return false;
+    };
+    if layer.paths().next().is_some() || !first.is_auto_aligned() {
+        return false;
+    }
+
+    // the offset and width of each aligned component that isn't a mark
+    let mut bases = Vec::new();
+    let mut first_width = None;
+    for component in layer.components() {
+        if !component.is_auto_aligned() {
+            continue;
+        }
+        let Some(referenced) = glyphs.get(&component.name) else {
+            return false;
+        };
+        if referenced.is_smart_component() || !component.piece.is_empty() {
+            return false;
+        }
+        let Some(referenced_layer) = matching_layer(referenced, layer) else {
+            return false;
+        };
+        first_width.get_or_insert(referenced_layer.width.0);
+        if referenced.category != Some(Category::Mark) {
+            bases.push((
+                component.transform.translation().x,
+                referenced_layer.width.0,
+            ));
+        }
+    }
+
+    let width = match bases.as_slice() {
+        // a composite of marks takes the width of the first
+        [] => first_width.unwrap(),
+        [(_, width), ..] if glyph.sub_category != Some(Subcategory::Ligature) => *width,
+        [.., (x, width)] => x + width,
+    };
+
+    if (width - layer.width.0).abs() > TOLERANCE {
+        debug!(
+            "Automatic alignment changes the width of '{}' layer '{}' from {} to {width}",
+            glyph.name, layer.layer_id, layer.width
+        );
+        layer.width = width.into();
+    }
+    true
+}
+
+/// The bounds of the outline of a layer, including that of its components, once transformed
+fn bounds(layer: &Layer, glyphs: &BTreeMap<SmolStr, Glyph>, transform: Affine) -> Option<Rect> {
+    let mut outline = BezPath::new();
+    if !append_outline(layer, glyphs, transform, 0, &mut outline) || outline.is_empty() {
+        return None;
+    }
+    Some(outline.bounding_box())
+}
+
+/// Add the transformed outline of a layer to `outline`
+///
+/// Returns false if the outline of a component can't be known without interpolating.
+fn append_outline(
+    layer: &Layer,
+    glyphs: &BTreeMap<SmolStr, Glyph>,
+    transform: Affine,
+    depth: usize,
+    outline: &mut BezPath,
+) -> bool {
+    if depth > MAX_COMPONENT_DEPTH {
+        return false;
+    }
+    for shape in layer.shapes.iter() {
+        match shape {
+            Shape::Path(path) => {
+                outline.extend((transform * to_bez_path(path)).elements().iter().copied())
+            }
+            Shape::Component(component) => {
+                let Some(referenced) = glyphs.get(&component.name) else {
+                    return false;
+                };
+                if referenced.is_smart_component() {
+                    return false;
+                }
+                let Some(referenced_layer) = matching_layer(referenced, layer) else {
+                    return false;
+                };
+                if !append_outline(
+                    referenced_layer,
+                    glyphs,
+                    transform * component.transform,
+                    depth + 1,
+                    outline,
+                ) {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
+/// A path, good enough to measure
+fn to_bez_path(path: &Path) -> BezPath {
+    let mut bez = BezPath::new();
+    let Some(last) = path.nodes.last() else {
+        return bez;
+    };
+    // In Glyphs.app, the starting node of a closed contour is stored at the end
+    let (start, rest) = if path.closed {
+        (last, &path.nodes[..path.nodes.len() - 1])
+    } else {
+        (&path.nodes[0], &path.nodes[1..])
+    };
+    bez.move_to(start.pt);
+    let mut off_curve = Vec::new();
+    for node in rest {
+        add_node(&mut bez, &mut off_curve, node);
+    }
+    if path.closed {
+        add_node(&mut bez, &mut off_curve, start);
+        bez.close_path();
+    }
+    bez
+}
+
+fn add_node(bez: &mut BezPath, off_curve: &mut Vec<Point>, node: &Node) {
+    match node.node_type {
+        NodeType::OffCurve => {
+            off_curve.push(node.pt);
+            return;
+        }
+        NodeType::Curve | NodeType::CurveSmooth if off_curve.len() == 2 => {
+            bez.curve_to(off_curve[0], off_curve[1], node.pt)
+        }
+        _ if !off_curve.is_empty() => {
+            // quadratic, with on-curve points implied between consecutive off-curves
+            for pair in off_curve.windows(2) {
+                bez.quad_to(pair[0], pair[0].midpoint(pair[1]));
+            }
+            bez.quad_to(*off_curve.last().unwrap(), node.pt);
+        }
+        _ => bez.line_to(node.pt),
+    }
+    off_curve.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{font::Anchor, Component};
+
+    use super::*;
+
+    fn key(value: KeyValue, arithmetic: Option<(char, f64)>) -> Option<MetricsKey> {
+        Some(MetricsKey { value, arithmetic })
+    }
+
+    fn glyph_key(name: &str, opposite: bool) -> KeyValue {
+        KeyValue::Glyph {
+            name: Some(name),
+            opposite,
+        }
+    }
+
+    // a rectangle from x0 to x1
+    fn rect(x0: f64, x1: f64) -> Shape {
+        Shape::Path(Path {
+            closed: true,
+            nodes: [(x0, 0.0), (x1, 0.0), (x1, 100.0), (x0, 100.0)]
+                .into_iter()
+                .map(|pt| Node {
+                    pt: pt.into(),
+                    node_type: NodeType::Line,
+                })
+                .collect(),
+        })
+    }
+
+    fn component(name: &str, x: f64) -> Shape {
+        Shape::Component(Component {
+            name: name.into(),
+            transform: Affine::translate((x, 0.0)),
+            ..Default::default()
+        })
+    }
+
+    fn glyph(name: &str, width: f64, shapes: Vec<Shape>) -> Glyph {
+        let info = crate::glyphdata::GlyphData::bundled().get_glyph(name, None);
+        Glyph {
+            name: name.into(),
+            export: true,
+            layers: vec![Layer {
+                layer_id: "m01".into(),
+                width: width.into(),
+                shapes,
+                ..Default::default()
+            }],
+            category: info.as_ref().map(|i| i.category),
+            sub_category: info.as_ref().map(|i| i.subcategory),
+            ..Default::default()
+        }
+    }
+
+    fn apply(glyphs: Vec<Glyph>) -> BTreeMap<SmolStr, Glyph> {
+        apply_with_skews(glyphs, &Skews::new())
+    }
+
+    fn apply_with_skews(glyphs: Vec<Glyph>, skews: &Skews) -> BTreeMap<SmolStr, Glyph> {
+        let mut glyphs = glyphs
+            .into_iter()
+            .map(|glyph| (glyph.name.clone(), glyph))
+            .collect();
+        apply_metrics_keys_impl(&mut glyphs, skews);
+        glyphs
+    }
+
+    fn width_and_bounds(glyphs: &BTreeMap<SmolStr, Glyph>, name: &str) -> (f64, f64, f64) {
+        let layer = &glyphs[name].layers[0];
+        let bounds = bounds(layer, glyphs, Affine::IDENTITY).unwrap();
+        (layer.width.0, bounds.min_x(), bounds.max_x())
+    }
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(key(glyph_key("n", false), None), MetricsKey::parse("=n"));
+        assert_eq!(key(glyph_key("n", false), None), MetricsKey::parse("n"));
+        assert_eq!(key(glyph_key("o", true), None), MetricsKey::parse("=|o"));
+        assert_eq!(
+            key(glyph_key("a", false), Some(('+', 20.0))),
+            MetricsKey::parse("=a+20")
+        );
+        assert_eq!(
+            key(glyph_key("ain-ar", false), Some(('-', 10.0))),
+            MetricsKey::parse("=ain-ar-10")
+        );
+        assert_eq!(
+            key(glyph_key("H", false), Some(('*', 0.5))),
+            MetricsKey::parse("=H * 0.5")
+        );
+        assert_eq!(
+            key(KeyValue::Number(-40.0), None),
+            MetricsKey::parse("=-40")
+        );
+        assert_eq!(
+            key(
+                KeyValue::Glyph {
+                    name: None,
+                    opposite: true
+                },
+                None
+            ),
+            MetricsKey::parse("=|")
+        );
+        assert_eq!(None, MetricsKey::parse("="));
+    }
+
+    #[test]
+    fn left_key_moves_the_outline() {
+        let mut m = glyph("m", 500.0, vec![rect(100.0, 400.0)]);
+        m.metric_left = Some("=n".into());
+        let glyphs = apply(vec![glyph("n", 500.0, vec![rect(50.0, 450.0)]), m]);
+        // the right sidebearing stays as it was
+        assert_eq!((450.0, 50.0, 350.0), width_and_bounds(&glyphs, "m"));
+    }
+
+    #[test]
+    fn right_key_of_the_opposite_side() {
+        let mut d = glyph("d", 500.0, vec![rect(100.0, 400.0)]);
+        d.metric_right = Some("=|b+10".into());
+        let glyphs = apply(vec![glyph("b", 500.0, vec![rect(30.0, 450.0)]), d]);
+        assert_eq!((440.0, 100.0, 400.0), width_and_bounds(&glyphs, "d"));
+    }
+
+    // a rectangle from x0 to x1, 0 to height high
+    fn tall_rect(x0: f64, x1: f64, height: f64) -> Shape {
+        let Shape::Path(mut path) = rect(x0, x1) else {
+            unreachable!()
+        };
+        for node in path.nodes.iter_mut().filter(|node| node.pt.y > 0.0) {
+            node.pt.y = height;
+        }
+        Shape::Path(path)
+    }
+
+    #[test]
+    fn italic_sidebearings_are_slanted() {
+        // Upright, both left sidebearings are 50. Slanted 10° around half the x-height of
+        // 500, the top left corners are what count, and m's is further left as it's taller.
+        let mut m = glyph("m", 500.0, vec![tall_rect(50.0, 350.0, 700.0)]);
+        m.metric_left = Some("=n".into());
+        let skews = Skews::from([("m01".to_string(), unslant(10.0, 500.0))]);
+        let glyphs = apply_with_skews(
+            vec![glyph("n", 500.0, vec![tall_rect(50.0, 350.0, 500.0)]), m],
+            &skews,
+        );
+        let tan = 10.0f64.to_radians().tan();
+        let n_left = 50.0 - tan * 250.0;
+        let m_left = 50.0 - tan * 450.0;
+        let (width, min_x, _) = width_and_bounds(&glyphs, "m");
+        assert!((width - (500.0 + n_left - m_left)).abs() < 1e-9, "{width}");
+        assert!((min_x - (50.0 + n_left - m_left)).abs() < 1e-9, "{min_x}");
+    }
+
+    #[test]
+    fn keys_involving_an_origin_are_left_alone() {
+        let origin = || Anchor {
+            name: "*origin".into(),
+            pos: (-20.0, 0.0).into(),
+        };
+        let mut a = glyph("a", 500.0, vec![rect(50.0, 450.0)]);
+        a.layers[0].anchors.push(origin());
+        let mut aa = glyph("aa", 1000.0, vec![rect(100.0, 950.0)]);
+        aa.metric_left = Some("=a".into());
+        let mut n = glyph("n", 500.0, vec![rect(100.0, 400.0)]);
+        n.metric_left = Some("=40".into());
+        n.layers[0].anchors.push(origin());
+        let glyphs = apply(vec![a, aa, n]);
+        assert_eq!((1000.0, 100.0, 950.0), width_and_bounds(&glyphs, "aa"));
+        assert_eq!((500.0, 100.0, 400.0), width_and_bounds(&glyphs, "n"));
+    }
+
+    #[test]
+    fn layer_key_overrides_glyph_key() {
+        let mut nbspace = glyph("nbspace", 250.0, vec![]);
+        nbspace.metric_width = Some("space".into());
+        nbspace.layers[0].metric_width = Some("=300".into());
+        let glyphs = apply(vec![glyph("space", 200.0, vec![]), nbspace]);
+        assert_eq!(300.0, glyphs["nbspace"].layers[0].width.0);
+    }
+
+    #[test]
+    fn keys_follow_keys() {
+        let mut o = glyph("o", 500.0, vec![rect(40.0, 460.0)]);
+        o.metric_left = Some("=20".into());
+        let mut c = glyph("c", 500.0, vec![rect(40.0, 400.0)]);
+        c.metric_left = Some("=o".into());
+        // the names sort so that c comes before the glyph it depends on
+        let glyphs = apply(vec![c, o]);
+        assert_eq!(20.0, width_and_bounds(&glyphs, "c").1);
+    }
+
+    #[test]
+    fn composite_takes_the_width_of_its_base() {
+        let mut a = glyph("a", 500.0, vec![rect(50.0, 450.0)]);
+        a.metric_right = Some("=60".into());
+        let glyphs = apply(vec![
+            a,
+            glyph("acutecomb", 0.0, vec![rect(-100.0, -50.0)]),
+            // the mark goes first, the width is still that of a
+            glyph(
+                "aacute",
+                500.0,
+                vec![component("acutecomb", 300.0), component("a", 0.0)],
+            ),
+        ]);
+        assert_eq!(510.0, glyphs["aacute"].layers[0].width.0);
+    }
+
+    #[test]
+    fn ligature_ends_after_its_last_base() {
+        let glyphs = apply(vec![
+            glyph("f", 300.0, vec![rect(20.0, 280.0)]),
+            glyph("i", 200.0, vec![rect(20.0, 180.0)]),
+            glyph("dotaccentcomb", 0.0, vec![rect(-60.0, -40.0)]),
+            glyph(
+                "f_i",
+                400.0,
+                vec![
+                    component("f", 0.0),
+                    component("i", 250.0),
+                    component("dotaccentcomb", 350.0),
+                ],
+            ),
+        ]);
+        let layer = &glyphs["f_i"].layers[0];
+        assert_eq!(450.0, layer.width.0);
+        // components stay where they are
+        assert_eq!(
+            vec![0.0, 250.0, 350.0],
+            layer
+                .components()
+                .map(|c| c.transform.translation().x)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn manually_aligned_composite_keeps_its_width() {
+        let mut aacute = glyph(
+            "aacute",
+            450.0,
+            vec![component("a", 0.0), component("acutecomb", 300.0)],
+        );
+        if let Shape::Component(component) = &mut aacute.layers[0].shapes[0] {
+            component.alignment = Some(-1);
+        }
+        let glyphs = apply(vec![
+            glyph("a", 500.0, vec![rect(50.0, 450.0)]),
+            glyph("acutecomb", 0.0, vec![rect(-100.0, -50.0)]),
+            aacute,
+        ]);
+        assert_eq!(450.0, glyphs["aacute"].layers[0].width.0);
+    }
+
+    #[test]
+    fn composite_with_a_component_we_cannot_see_is_untouched() {
+        let glyphs = apply(vec![glyph("aacute", 450.0, vec![component("a", 0.0)])]);
+        assert_eq!(450.0, glyphs["aacute"].layers[0].width.0);
+    }
+}
@@ -707,6 +707,26 @@ impl VecDelimiters {
     };
 }
 
+/// A dictionary with values of a single type, e.g. `{ Width = 2; Height = 1; }`
+impl<K, V> FromPlist for BTreeMap<K, V>
+where
+    K: FromPlist + Ord,
+    V: FromPlist,
+{
+    fn parse(tokenizer: &mut Tokenizer<'_>) -> Result<Self, Error> {
+        tokenizer.eat(b'{')?;
+        let mut map = BTreeMap::new();
+        while tokenizer.eat(b'}').is_err() {
+            let key = tokenizer.parse()?;
+            tokenizer.eat(b'=')?;
+            let value = tokenizer.parse()?;
+            tokenizer.eat(b';')?;
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
 impl FromPlist for OrderedFloat<f64> {
     fn parse(tokenizer: &mut Tokenizer<'_>) -> Result<Self, Error> {
         let val: f64 = tokenizer.parse()?;
@@ -504,6 +504,8 @@ mod tests {
                     name: name.into(),
                     transform: Affine::translate((pos.0 as f64, pos.1 as f64)),
                     anchor: None,
+                    piece: Default::default(),
+                    alignment: None,
                 }));
             self
         }
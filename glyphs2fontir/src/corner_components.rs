@@ -0,0 +1,730 @@
+//! Insertion of corner components, caps and segment components.
+//!
+//! Glyphs attaches these reusable pieces of outline to the nodes of a path with
+//! [hints](Hint). Each piece is drawn as an open path in a glyph of its own. Like
+//! glyphsLib's corner components filter, we splice the pieces into the paths they
+//! are attached to before building outlines.
+
+use std::{
+    collections::HashMap,
+    f64::consts::{FRAC_PI_2, PI, TAU},
+    ops::Range,
+};
+
+use fontdrasil::types::GlyphName;
+use glyphs_reader::{CornerAlignment, Font, Hint, HintType, Layer, Node, NodeType, Path, Shape};
+use kurbo::{
+    Affine, CubicBez, Line, ParamCurve, ParamCurveExtrema, ParamCurveNearest, PathSeg, Point,
+    QuadBez, Vec2,
+};
+use log::warn;
+
+/// How close to the piece of outline we trim the segments it's inserted between
+const NEAREST_ACCURACY: f64 = 1e-3;
+
+/// The ends of a piece of outline closer than this to where it meets a segment are on it
+const ON_SEGMENT_TOLERANCE: f64 = 1e-3;
+
+/// The paths of a layer that hints insert pieces of outline into, by path index.
+///
+/// Paths without such hints aren't included.
+pub(crate) fn apply_hints(
+    glyph_name: &GlyphName,
+    layer: &Layer,
+    font: &Font,
+    master_id: &str,
+) -> HashMap<usize, Path> {
+    let mut hinted = HashMap::new();
+    if layer.hints.is_empty() {
+        return hinted;
+    }
+    let paths: Vec<_> = layer
+        .shapes
+        .iter()
+        .filter_map(|shape| match shape {
+            Shape::Path(path) => Some(path),
+            Shape::Component(_) => None,
+        })
+        .collect();
+
+    for (path_index, path) in paths.into_iter().enumerate() {
+        let mut hints: Vec<_> = layer
+            .hints
+            .iter()
+            .filter(|hint| hint.path_index == path_index)
+            .collect();
+        if hints.is_empty() {
+            continue;
+        }
+        hints.sort_by_key(|hint| hint.node_index);
+
+        let mut segments = Segments::new(path);
+        for hint in hints {
+            let Some(piece) = piece_of_outline(font, &hint.name, master_id) else {
+                warn!(
+                    "{glyph_name} has a {:?} hint using {}, which has no open path for master {master_id}",
+                    hint.type_, hint.name
+                );
+                continue;
+            };
+            if let Err(message) = segments.insert(hint, &piece) {
+                warn!(
+                    "{glyph_name} ignores {:?} hint using {} on node {} of path {path_index}: {message}",
+                    hint.type_, hint.name, hint.node_index
+                );
+            }
+        }
+        hinted.insert(path_index, segments.into_path());
+    }
+    hinted
+}
+
+/// The open path a corner, cap or segment glyph draws for a master
+struct Piece<'a> {
+    path: &'a Path,
+    /// Where the piece is attached to the node, the `origin` anchor if there is one
+    origin: Point,
+}
+
+fn piece_of_outline<'a>(font: &'a Font, name: &str, master_id: &str) -> Option<Piece<'a>> {
+    let layer = font
+        .glyphs
+        .get(name)?
+        .layers
+        .iter()
+        .find(|layer| layer.layer_id == master_id)?;
+    let path = layer.shapes.iter().find_map(|shape| match shape {
+        Shape::Path(path) if !path.closed && path.nodes.len() > 1 => Some(path),
+        _ => None,
+    })?;
+    let origin = layer
+        .anchors
+        .iter()
+        .find(|anchor| anchor.name == "origin")
+        .map(|anchor| anchor.pos)
+        .unwrap_or(Point::ZERO);
+    Some(Piece { path, origin })
+}
+
+/// A segment of a path, ending at an on-curve node
+#[derive(Debug)]
+struct Segment {
+    /// The index of the on-curve node that ends the segment
+    node_index: usize,
+    /// The nodes of the segment, off-curves followed by the on-curve
+    nodes: Vec<Node>,
+    /// The part of the segment that is kept, to make room for pieces of outline
+    start_t: f64,
+    end_t: f64,
+    /// The nodes that replace the segment, such as a cap
+    replacement: Option<Vec<Node>>,
+    /// The nodes inserted after the segment, such as a corner
+    insertion: Vec<Node>,
+}
+
+/// A path split into [Segment]s that pieces of outline can be inserted between
+struct Segments {
+    closed: bool,
+    /// For an open path, where it starts
+    start: Option<Node>,
+    segments: Vec<Segment>,
+}
+
+impl Segments {
+    fn new(path: &Path) -> Self {
+        let mut start = None;
+        let mut segments = Vec::new();
+        let mut nodes = Vec::new();
+        for (node_index, node) in path.nodes.iter().enumerate() {
+            if !path.closed && node_index == 0 {
+                start = Some(node.clone());
+                continue;
+            }
+            nodes.push(node.clone());
+            if node.node_type != NodeType::OffCurve {
+                segments.push(Segment {
+                    node_index,
+                    nodes: std::mem::take(&mut nodes),
+                    start_t: 0.0,
+                    end_t: 1.0,
+                    replacement: None,
+                    insertion: Vec::new(),
+                });
+            }
+        }
+        // Off-curves at the end of a closed path lead to its first on-curve
+        if let Some(first) = segments.first_mut().filter(|_| path.closed) {
+            nodes.append(&mut first.nodes);
+            first.nodes = nodes;
+        }
+        Segments {
+            closed: path.closed,
+            start,
+            segments,
+        }
+    }
+
+    /// The index of the segment before `index`, if there is one
+    fn prev(&self, index: usize) -> Option<usize> {
+        match index {
+            0 if self.closed => Some(self.segments.len() - 1),
+            0 => None,
+            _ => Some(index - 1),
+        }
+    }
+
+    /// The index of the segment after `index`, if there is one
+    fn next(&self, index: usize) -> Option<usize> {
+        if index + 1 < self.segments.len() {
+            Some(index + 1)
+        } else if self.closed {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// The segment at `index` as a curve, ignoring any trimming
+    fn curve(&self, index: usize) -> Result<PathSeg, String> {
+        let start = match self.prev(index) {
+            Some(prev) => self.segments[prev].nodes.last().unwrap().pt,
+            None => self.start.as_ref().unwrap().pt,
+        };
+        let points: Vec<_> = self.segments[index].nodes.iter().map(|n| n.pt).collect();
+        match points[..] {
+            [p1] => Ok(PathSeg::Line(Line::new(start, p1))),
+            [p1, p2] => Ok(PathSeg::Quad(QuadBez::new(start, p1, p2))),
+            [p1, p2, p3] => Ok(PathSeg::Cubic(CubicBez::new(start, p1, p2, p3))),
+            _ => Err(format!(
+                "a segment with {} off-curve points isn't supported",
+                points.len() - 1
+            )),
+        }
+    }
+
+    /// Insert a piece of outline as directed by a hint
+    fn insert(&mut self, hint: &Hint, piece: &Piece) -> Result<(), String> {
+        let Some(index) = self
+            .segments
+            .iter()
+            .position(|segment| segment.node_index == hint.node_index)
+        else {
+            return Err("it isn't on an on-curve node".to_string());
+        };
+        match hint.type_ {
+            HintType::Corner => self.insert_corner(index, hint, piece),
+            HintType::Cap | HintType::Segment => self.replace_segment(index, hint, piece),
+        }
+    }
+
+    /// Replace the node at the end of segment `index` with a corner.
+    ///
+    /// The corner is drawn around its origin, which is placed on the node, and turned as
+    /// the hint's [CornerAlignment] says. Corners are drawn for right angles, so at other
+    /// angles an end may fall short of or overshoot the segment it should meet. Like
+    /// glyphsLib, the first and last segments of the corner are then stretched along
+    /// their direction until they meet the incoming and outgoing segments, which are
+    /// trimmed where they do.
+    fn insert_corner(&mut self, index: usize, hint: &Hint, piece: &Piece) -> Result<(), String> {
+        let Some(next) = self.next(index) else {
+            return Err("there is no outgoing segment".to_string());
+        };
+        if self.segments[index].replacement.is_some() || self.segments[next].replacement.is_some() {
+            return Err("a neighbouring segment is already replaced".to_string());
+        }
+        let incoming = self.curve(index)?;
+        let outgoing = self.curve(next)?;
+        let corner = incoming.end();
+
+        let outstroke = start_tangent(outgoing);
+        let instroke = -end_tangent(incoming);
+        if outstroke == Vec2::ZERO || instroke == Vec2::ZERO {
+            return Err("a segment at the node has no length".to_string());
+        }
+        // Angles that turn the x axis onto the outgoing segment and the y axis onto the incoming one
+        let out_angle = outstroke.atan2();
+        let in_angle = instroke.atan2() - FRAC_PI_2;
+        let angle = match hint.alignment {
+            CornerAlignment::Outstroke => out_angle,
+            CornerAlignment::Instroke => in_angle,
+            CornerAlignment::Middle => {
+                let turn = (in_angle - out_angle + PI).rem_euclid(TAU) - PI;
+                out_angle + turn / 2.0
+            }
+            CornerAlignment::Unaligned => 0.0,
+        };
+        let transform = Affine::translate(corner.to_vec2())
+            * Affine::rotate(angle)
+            * Affine::scale_non_uniform(hint.scale.0.into_inner(), hint.scale.1.into_inner())
+            * Affine::translate(-piece.origin.to_vec2());
+
+        let mut nodes: Vec<_> = piece
+            .path
+            .nodes
+            .iter()
+            .map(|node| Node {
+                pt: transform * node.pt,
+                node_type: node.node_type,
+            })
+            .collect();
+        let last = nodes.len() - 1;
+        let too_large = || "the corner is larger than the segments around it".to_string();
+        let start_t = meet(
+            incoming,
+            nodes[0].pt,
+            nodes[1].pt,
+            self.segments[index].start_t..1.0,
+        )
+        .ok_or_else(too_large)?;
+        let end_t = meet(
+            outgoing,
+            nodes[last].pt,
+            nodes[last - 1].pt,
+            0.0..self.segments[next].end_t,
+        )
+        .ok_or_else(too_large)?;
+
+        // The trimmed incoming segment ends where the corner starts
+        nodes.remove(0);
+        nodes.last_mut().unwrap().pt = outgoing.eval(end_t);
+        self.segments[index].end_t = start_t;
+        self.segments[index].insertion = nodes;
+        self.segments[next].start_t = end_t;
+        Ok(())
+    }
+
+    /// Replace the segment starting at the end of segment `index`.
+    ///
+    /// The piece is fitted between the nodes at either end of the segment, with its origin
+    /// on the first, keeping its depth unless the hint scales it.
+    fn replace_segment(&mut self, index: usize, hint: &Hint, piece: &Piece) -> Result<(), String> {
+        let Some(next) = self.next(index) else {
+            return Err("there is no outgoing segment".to_string());
+        };
+        let segment = &self.segments[next];
+        if segment.replacement.is_some() || segment.start_t > 0.0 || segment.end_t < 1.0 {
+            return Err("the segment is already modified".to_string());
+        }
+        let outgoing = self.curve(next)?;
+        let (start, end) = (outgoing.start(), outgoing.end());
+        let piece_start = piece.path.nodes.first().unwrap().pt;
+        let piece_end = piece.path.nodes.last().unwrap().pt;
+
+        let from = piece_end - piece_start;
+        let to = end - start;
+        if from.hypot() == 0.0 || to.hypot() == 0.0 {
+            return Err("the piece or the segment has no length".to_string());
+        }
+        let transform = Affine::translate(start.to_vec2())
+            * Affine::rotate(to.atan2())
+            * Affine::scale_non_uniform(to.hypot() / from.hypot(), hint.scale.1.into_inner())
+            * Affine::rotate(-from.atan2())
+            * Affine::translate(-piece.origin.to_vec2());
+
+        let mut nodes: Vec<_> = piece
+            .path
+            .nodes
+            .iter()
+            .map(|node| Node {
+                pt: transform * node.pt,
+                node_type: node.node_type,
+            })
+            .collect();
+        if is_on(start, nodes[0].pt) {
+            nodes.remove(0);
+        } else {
+            nodes[0].node_type = NodeType::Line;
+        }
+        // Land exactly on the node we replace up to
+        let last = nodes.last_mut().unwrap();
+        if is_on(end, last.pt) {
+            last.pt = end;
+        } else {
+            nodes.push(Node {
+                pt: end,
+                node_type: NodeType::Line,
+            });
+        }
+        self.segments[next].replacement = Some(nodes);
+        Ok(())
+    }
+
+    fn into_path(self) -> Path {
+        let mut nodes = Vec::new();
+        if let Some(start) = &self.start {
+            nodes.push(start.clone());
+        }
+        for index in 0..self.segments.len() {
+            let segment = &self.segments[index];
+            if let Some(replacement) = &segment.replacement {
+                nodes.extend(replacement.iter().cloned());
+                continue;
+            }
+            if segment.start_t == 0.0 && segment.end_t == 1.0 {
+                nodes.extend(segment.nodes.iter().cloned());
+            } else {
+                // curve can't fail, we only trim segments we could make a curve of
+                let trimmed = self
+                    .curve(index)
+                    .unwrap()
+                    .subsegment(segment.start_t..segment.end_t);
+                let off_curves = match trimmed {
+                    PathSeg::Line(_) => vec![],
+                    PathSeg::Quad(quad) => vec![quad.p1],
+                    PathSeg::Cubic(cubic) => vec![cubic.p1, cubic.p2],
+                };
+                nodes.extend(off_curves.into_iter().map(|pt| Node {
+                    pt,
+                    node_type: NodeType::OffCurve,
+                }));
+                nodes.push(Node {
+                    pt: trimmed.end(),
+                    node_type: segment.nodes.last().unwrap().node_type,
+                });
+            }
+            nodes.extend(segment.insertion.iter().cloned());
+        }
+        Path {
+            closed: self.closed,
+            nodes,
+        }
+    }
+}
+
+/// Where on `segment` the end of a piece of outline at `end` meets it, if within `range`
+///
+/// An end that is already on the segment meets it there. Otherwise the piece is stretched
+/// or shortened along the line from `end` to `towards`, its next node, and meets the
+/// segment where that line crosses it closest to `end`.
+fn meet(segment: PathSeg, end: Point, towards: Point, range: Range<f64>) -> Option<f64> {
+    let in_range = |t: f64| range.start < t && t < range.end;
+    let nearest = segment.nearest(end, NEAREST_ACCURACY).t;
+    if is_on(segment.eval(nearest), end) {
+        return Some(nearest).filter(|t| in_range(*t));
+    }
+    let direction = towards - end;
+    if direction.hypot() == 0.0 {
+        return None;
+    }
+    // Long enough to cross the segment anywhere
+    let bbox = segment.bounding_box().union_pt(end);
+    let reach = bbox.width().hypot(bbox.height()) + 1.0;
+    let direction = direction * (reach / direction.hypot());
+    let line = Line::new(end - direction, end + direction);
+    segment
+        .intersect_line(line)
+        .into_iter()
+        .filter(|hit| in_range(hit.segment_t))
+        .min_by(|a, b| (a.line_t - 0.5).abs().total_cmp(&(b.line_t - 0.5).abs()))
+        .map(|hit| hit.segment_t)
+}
+
+fn is_on(segment_point: Point, piece_point: Point) -> bool {
+    (segment_point - piece_point).hypot() < ON_SEGMENT_TOLERANCE
+}
+
+/// The direction a segment arrives at its end in
+fn end_tangent(segment: PathSeg) -> Vec2 {
+    let end = segment.end();
+    let candidates = match segment {
+        PathSeg::Line(line) => vec![line.p0],
+        PathSeg::Quad(quad) => vec![quad.p1, quad.p0],
+        PathSeg::Cubic(cubic) => vec![cubic.p2, cubic.p1, cubic.p0],
+    };
+    first_direction(candidates.into_iter().map(|p| end - p))
+}
+
+/// The direction a segment leaves its start in
+fn start_tangent(segment: PathSeg) -> Vec2 {
+    let start = segment.start();
+    let candidates = match segment {
+        PathSeg::Line(line) => vec![line.p1],
+        PathSeg::Quad(quad) => vec![quad.p1, quad.p2],
+        PathSeg::Cubic(cubic) => vec![cubic.p1, cubic.p2, cubic.p3],
+    };
+    first_direction(candidates.into_iter().map(|p| p - start))
+}
+
+fn first_direction(mut candidates: impl Iterator<Item = Vec2>) -> Vec2 {
+    candidates.find(|v| v.hypot() > 0.0).unwrap_or(Vec2::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::{BezPath, Point, Rect, Shape as _};
+    use ordered_float::OrderedFloat;
+
+    use super::*;
+
+    fn path(closed: bool, points: &[(f64, f64)]) -> Path {
+        Path {
+            closed,
+            nodes: points
+                .iter()
+                .map(|(x, y)| Node {
+                    pt: Point::new(*x, *y),
+                    node_type: NodeType::Line,
+                })
+                .collect(),
+        }
+    }
+
+    fn hint(type_: HintType, node_index: usize) -> Hint {
+        Hint {
+            type_,
+            name: "_piece".into(),
+            path_index: 0,
+            node_index,
+            scale: (OrderedFloat(1.0), OrderedFloat(1.0)),
+            alignment: CornerAlignment::Outstroke,
+        }
+    }
+
+    fn piece(path: &Path) -> Piece {
+        Piece {
+            path,
+            origin: Point::ZERO,
+        }
+    }
+
+    fn points(path: &Path) -> Vec<(f64, f64)> {
+        path.nodes
+            .iter()
+            .map(|node| (node.pt.x.round(), node.pt.y.round()))
+            .collect()
+    }
+
+    // A 100x100 square, counter-clockwise, starting at the bottom left
+    fn square() -> Path {
+        path(
+            true,
+            &[(100.0, 0.0), (100.0, 100.0), (0.0, 100.0), (0.0, 0.0)],
+        )
+    }
+
+    #[test]
+    fn corner_replaces_node() {
+        // A chamfer for a bottom left corner, from the left side to the bottom
+        let corner = path(false, &[(0.0, 10.0), (10.0, 0.0)]);
+        let mut segments = Segments::new(&square());
+        segments
+            .insert(&hint(HintType::Corner, 3), &piece(&corner))
+            .unwrap();
+        assert_eq!(
+            vec![
+                (100.0, 0.0),
+                (100.0, 100.0),
+                (0.0, 100.0),
+                (0.0, 10.0),
+                (10.0, 0.0)
+            ],
+            points(&segments.into_path())
+        );
+    }
+
+    #[test]
+    fn corner_is_rotated_onto_node() {
+        // The same chamfer on the top right corner
+        let corner = path(false, &[(0.0, 10.0), (10.0, 0.0)]);
+        let mut segments = Segments::new(&square());
+        segments
+            .insert(&hint(HintType::Corner, 1), &piece(&corner))
+            .unwrap();
+        assert_eq!(
+            vec![
+                (100.0, 0.0),
+                (100.0, 90.0),
+                (90.0, 100.0),
+                (0.0, 100.0),
+                (0.0, 0.0)
+            ],
+            points(&segments.into_path())
+        );
+    }
+
+    #[test]
+    fn corner_is_drawn_around_origin_anchor() {
+        // The chamfer of corner_replaces_node, drawn away from the origin
+        let corner = path(false, &[(5.0, 15.0), (15.0, 5.0)]);
+        let mut segments = Segments::new(&square());
+        segments
+            .insert(
+                &hint(HintType::Corner, 3),
+                &Piece {
+                    path: &corner,
+                    origin: Point::new(5.0, 5.0),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            vec![
+                (100.0, 0.0),
+                (100.0, 100.0),
+                (0.0, 100.0),
+                (0.0, 10.0),
+                (10.0, 0.0)
+            ],
+            points(&segments.into_path())
+        );
+    }
+
+    // A right triangle whose first node is a 135 degree corner
+    fn triangle() -> Path {
+        path(true, &[(100.0, 0.0), (0.0, 100.0), (0.0, 0.0)])
+    }
+
+    fn corner_on_triangle(alignment: CornerAlignment) -> Vec<(f64, f64)> {
+        let corner = path(false, &[(0.0, 10.0), (10.0, 0.0)]);
+        let mut segments = Segments::new(&triangle());
+        segments
+            .insert(
+                &Hint {
+                    alignment,
+                    ..hint(HintType::Corner, 0)
+                },
+                &piece(&corner),
+            )
+            .unwrap();
+        points(&segments.into_path())
+    }
+
+    #[test]
+    fn corner_aligned_to_outstroke() {
+        // The corner ends on the outgoing segment and would start below the incoming one,
+        // its first segment is shortened to start on it instead
+        assert_eq!(
+            vec![(93.0, 0.0), (93.0, 7.0), (0.0, 100.0), (0.0, 0.0)],
+            corner_on_triangle(CornerAlignment::Outstroke)
+        );
+    }
+
+    #[test]
+    fn corner_aligned_to_instroke() {
+        // The corner starts on the incoming segment and would end outside the outgoing one,
+        // its last segment is shortened to end on it instead
+        assert_eq!(
+            vec![(90.0, 0.0), (95.0, 5.0), (0.0, 100.0), (0.0, 0.0)],
+            corner_on_triangle(CornerAlignment::Instroke)
+        );
+    }
+
+    #[test]
+    fn corner_aligned_to_middle() {
+        // Turned halfway, both ends miss and the corner is cut evenly from either side
+        let points = corner_on_triangle(CornerAlignment::Middle);
+        assert_eq!(
+            vec![(92.0, 0.0), (95.0, 5.0), (0.0, 100.0), (0.0, 0.0)],
+            points
+        );
+    }
+
+    #[test]
+    fn corner_stays_inside_outline() {
+        // Whichever way it's turned, the corner only cuts into the triangle
+        for alignment in [
+            CornerAlignment::Outstroke,
+            CornerAlignment::Instroke,
+            CornerAlignment::Middle,
+        ] {
+            for (x, y) in corner_on_triangle(alignment) {
+                assert!(
+                    x >= 0.0 && y >= 0.0 && x + y <= 100.0,
+                    "{alignment:?} puts ({x}, {y}) outside the triangle"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unaligned_corner_isnt_turned() {
+        // Drawn as is, the chamfer ends outside the outgoing segment and is shortened to it
+        let corner = path(false, &[(-10.0, 0.0), (0.0, 10.0)]);
+        let mut segments = Segments::new(&triangle());
+        segments
+            .insert(
+                &Hint {
+                    alignment: CornerAlignment::Unaligned,
+                    ..hint(HintType::Corner, 0)
+                },
+                &piece(&corner),
+            )
+            .unwrap();
+        assert_eq!(
+            vec![(90.0, 0.0), (95.0, 5.0), (0.0, 100.0), (0.0, 0.0)],
+            points(&segments.into_path())
+        );
+    }
+
+    #[test]
+    fn corner_meets_curved_segment() {
+        // The chamfer of corner_replaces_node, on a square whose left side bulges out
+        let mut bulging = square();
+        bulging.nodes.insert(
+            3,
+            Node {
+                pt: Point::new(-40.0, 50.0),
+                node_type: NodeType::OffCurve,
+            },
+        );
+        bulging.nodes[4].node_type = NodeType::QCurve;
+        let corner = path(false, &[(0.0, 10.0), (10.0, 0.0)]);
+        let mut segments = Segments::new(&bulging);
+        segments
+            .insert(&hint(HintType::Corner, 4), &piece(&corner))
+            .unwrap();
+        let path = segments.into_path();
+        // The chamfer is stretched back along its direction until it meets the curve
+        let points = points(&path);
+        assert_eq!(
+            vec![(-15.0, 25.0), (10.0, 0.0)],
+            points[points.len() - 2..].to_vec()
+        );
+        let left_side = QuadBez::new((0.0, 100.0), (-40.0, 50.0), (0.0, 0.0));
+        let start = path.nodes[path.nodes.len() - 2].pt;
+        assert!(left_side.nearest(start, NEAREST_ACCURACY).distance_sq < 1e-6);
+    }
+
+    #[test]
+    fn cap_is_fitted_to_segment() {
+        // A 50 wide cap that sticks out 20 below the bottom of the square
+        let cap = path(false, &[(0.0, 0.0), (25.0, -20.0), (50.0, 0.0)]);
+        let mut segments = Segments::new(&square());
+        segments
+            .insert(&hint(HintType::Cap, 3), &piece(&cap))
+            .unwrap();
+        let path = segments.into_path();
+        assert_eq!(
+            vec![
+                (50.0, -20.0),
+                (100.0, 0.0),
+                (100.0, 100.0),
+                (0.0, 100.0),
+                (0.0, 0.0)
+            ],
+            points(&path)
+        );
+        let mut bez = BezPath::new();
+        bez.move_to(path.nodes.last().unwrap().pt);
+        for node in path.nodes.iter() {
+            bez.line_to(node.pt);
+        }
+        assert_eq!(Rect::new(0.0, -20.0, 100.0, 100.0), bez.bounding_box());
+    }
+
+    #[test]
+    fn hint_on_off_curve_is_an_error() {
+        let mut curved = square();
+        curved.nodes.insert(
+            0,
+            Node {
+                pt: Point::new(50.0, -10.0),
+                node_type: NodeType::OffCurve,
+            },
+        );
+        let corner = path(false, &[(0.0, 10.0), (10.0, 0.0)]);
+        let mut segments = Segments::new(&curved);
+        assert!(segments
+            .insert(&hint(HintType::Corner, 0), &piece(&corner))
+            .is_err());
+    }
+}
@@ -0,0 +1,577 @@
+//! Generates the code for features Glyphs marks as automatic.
+//!
+//! Glyphs saves the code of automatic features but regenerates it from
+//! glyph name suffixes when exporting. We do the same so the saved code
+//! can't be stale.
+//!
+//! Only the substitution features driven by glyph names are generated: `aalt`,
+//! `c2sc`, `dlig`, `frac`, `liga`, `locl`, `salt`, `smcp`, stylistic sets and
+//! character variants, and the single substitution features in [SUFFIX_FEATURES].
+//! Other automatic features, such as `ccmp` and `calt`, use the code saved in the
+//! source, which is only as fresh as the last time Glyphs regenerated it. `kern`,
+//! `mark`, `mkmk` and `curs` are written by our feature writers from kerning and
+//! anchors instead, as Glyphs does.
+//!
+//! See <https://glyphsapp.com/learn/features-part-1-simple-substitutions>
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use glyphs_reader::{glyphdata::GlyphData, Font};
+use icu_properties::Script;
+use log::{debug, warn};
+
+/// Single substitution features driven by glyph name suffixes
+const SUFFIX_FEATURES: &[(&str, &[&str])] = &[
+    ("case", &["case"]),
+    ("dnom", &["dnom"]),
+    ("lnum", &["lf"]),
+    ("numr", &["numr"]),
+    ("onum", &["osf"]),
+    ("ordn", &["ordn"]),
+    ("pnum", &["pf"]),
+    ("sinf", &["sinf"]),
+    ("smcp", &["sc", "smcp"]),
+    ("subs", &["subs", "inferior"]),
+    ("sups", &["sups", "superior"]),
+    ("tnum", &["tf"]),
+    ("zero", &["zero"]),
+];
+
+const DIGITS: [&str; 10] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+/// Unicode script => OpenType script tag, for the glyphs `.locl` alternates replace
+///
+/// Indic scripts use their version 2 tags, as Glyphs does.
+const SCRIPT_TAGS: &[(Script, &str)] = &[
+    (Script::Arabic, "arab"),
+    (Script::Armenian, "armn"),
+    (Script::Bengali, "bng2"),
+    (Script::Cyrillic, "cyrl"),
+    (Script::Devanagari, "dev2"),
+    (Script::Georgian, "geor"),
+    (Script::Greek, "grek"),
+    (Script::Gujarati, "gjr2"),
+    (Script::Gurmukhi, "gur2"),
+    (Script::Han, "hani"),
+    (Script::Hangul, "hang"),
+    (Script::Hebrew, "hebr"),
+    (Script::Hiragana, "kana"),
+    (Script::Kannada, "knd2"),
+    (Script::Katakana, "kana"),
+    (Script::Khmer, "khmr"),
+    (Script::Lao, "lao"),
+    (Script::Latin, "latn"),
+    (Script::Malayalam, "mlm2"),
+    (Script::Myanmar, "mym2"),
+    (Script::Oriya, "ory2"),
+    (Script::Sinhala, "sinh"),
+    (Script::Syriac, "syrc"),
+    (Script::Tamil, "tml2"),
+    (Script::Telugu, "tel2"),
+    (Script::Thaana, "thaa"),
+    (Script::Thai, "thai"),
+    (Script::Tibetan, "tibt"),
+];
+
+/// Exported glyphs, in glyph order, and a set of the same for lookups
+struct GlyphSet<'a> {
+    font: &'a Font,
+    names: Vec<&'a str>,
+    lookup: HashSet<&'a str>,
+}
+
+impl<'a> GlyphSet<'a> {
+    fn new(font: &'a Font) -> Self {
+        let names: Vec<_> = font
+            .glyph_order
+            .iter()
+            .filter(|name| font.glyphs.get(*name).map(|g| g.export).unwrap_or_default())
+            .map(|name| name.as_str())
+            .collect();
+        let lookup = names.iter().copied().collect();
+        GlyphSet {
+            font,
+            names,
+            lookup,
+        }
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.lookup.contains(name)
+    }
+
+    /// (base, alternate) for every alternate named base.suffix where base exists
+    fn suffixed(&self, suffix: &str) -> Vec<(&'a str, &'a str)> {
+        let suffix = format!(".{suffix}");
+        self.names
+            .iter()
+            .filter_map(|name| {
+                name.strip_suffix(&suffix)
+                    .filter(|base| self.contains(base))
+                    .map(|base| (base, *name))
+            })
+            .collect()
+    }
+
+    /// The OpenType script of a glyph, from its codepoints or failing that those of GlyphData
+    fn script(&self, name: &str) -> Option<&'static str> {
+        let codepoints: BTreeSet<u32> = match self.font.glyphs.get(name) {
+            Some(glyph) if !glyph.unicode.is_empty() => glyph.unicode.clone(),
+            _ => GlyphData::bundled()
+                .get_glyph(name, None)
+                .and_then(|info| info.unicode)
+                .into_iter()
+                .collect(),
+        };
+        let script = codepoints
+            .into_iter()
+            .map(|cp| icu_properties::maps::script().get32(cp))
+            .find(|script| *script != Script::Common && *script != Script::Inherited)?;
+        SCRIPT_TAGS
+            .iter()
+            .find_map(|(s, tag)| (*s == script).then_some(*tag))
+    }
+
+    /// The uppercase counterpart of a glyph name, e.g. a => A, ae => AE
+    fn uppercase(&self, name: &str) -> Option<&'a str> {
+        let mut chars = name.chars();
+        let capitalized = chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect::<String>())?;
+        [name.to_uppercase(), capitalized]
+            .into_iter()
+            .filter(|candidate| candidate != name)
+            .find_map(|candidate| self.lookup.get(candidate.as_str()).copied())
+    }
+}
+
+fn single_subs(subs: &[(&str, &str)]) -> String {
+    subs.iter()
+        .map(|(base, alt)| format!("sub {base} by {alt};"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn stylistic_set_suffix(tag: &str) -> bool {
+    (tag.starts_with("ss") || tag.starts_with("cv"))
+        && tag.len() == 4
+        && tag[2..].chars().all(|c| c.is_ascii_digit())
+}
+
+fn smcp(glyphs: &GlyphSet) -> String {
+    let subs: Vec<_> = glyphs
+        .suffixed("sc")
+        .into_iter()
+        .filter(|(base, _)| base.starts_with(|c: char| c.is_lowercase()))
+        .chain(glyphs.suffixed("smcp"))
+        .collect();
+    single_subs(&subs)
+}
+
+fn c2sc(glyphs: &GlyphSet) -> String {
+    let subs: Vec<_> = glyphs
+        .suffixed("sc")
+        .into_iter()
+        .filter_map(|(base, alt)| glyphs.uppercase(base).map(|upper| (upper, alt)))
+        .chain(glyphs.suffixed("c2sc"))
+        .collect();
+    single_subs(&subs)
+}
+
+fn salt(glyphs: &GlyphSet) -> String {
+    let mut alternates: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let mut order = Vec::new();
+    for name in glyphs.names.iter() {
+        let Some((base, suffix)) = name.rsplit_once('.') else {
+            continue;
+        };
+        if !(suffix == "salt" || stylistic_set_suffix(suffix)) || !glyphs.contains(base) {
+            continue;
+        }
+        if !alternates.contains_key(base) {
+            order.push(base);
+        }
+        alternates.entry(base).or_default().push(*name);
+    }
+    order
+        .into_iter()
+        .map(|base| match alternates[base].as_slice() {
+            [alt] => format!("sub {base} by {alt};"),
+            alts => format!("sub {base} from [{}];", alts.join(" ")),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether the suffix of a `.loclXXX` glyph looks like an OpenType language tag
+fn is_language_tag(lang: &str) -> bool {
+    (2..=4).contains(&lang.len())
+        && lang
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+/// The substitutions of `.loclXXX` glyphs, by (script, language)
+fn locl_substitutions<'a>(
+    glyphs: &GlyphSet<'a>,
+) -> BTreeMap<(&'static str, &'a str), Vec<(&'a str, &'a str)>> {
+    let mut by_lang: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for name in glyphs.names.iter() {
+        let Some((base, suffix)) = name.rsplit_once('.') else {
+            continue;
+        };
+        let Some(lang) = suffix.strip_prefix("locl") else {
+            continue;
+        };
+        if !glyphs.contains(base) {
+            continue;
+        }
+        if !is_language_tag(lang) {
+            warn!("Unable to use {name} in locl, '{lang}' isn't an OpenType language tag");
+            continue;
+        }
+        let Some(script) = glyphs.script(base) else {
+            warn!("Unable to use {name} in locl, the script of {base} is unknown");
+            continue;
+        };
+        by_lang
+            .entry((script, lang))
+            .or_default()
+            .push((base, *name));
+    }
+    by_lang
+}
+
+fn locl(glyphs: &GlyphSet) -> String {
+    locl_substitutions(glyphs)
+        .into_iter()
+        .map(|((script, lang), subs)| {
+            format!("script {script};\nlanguage {lang};\n{}", single_subs(&subs))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The language systems the generated features use, other than `DFLT dflt`
+///
+/// Each script with languages comes with its default language, as in the
+/// `Languagesystems` prefix Glyphs writes.
+pub(crate) fn language_systems(font: &Font) -> Vec<(String, String)> {
+    let has_automatic_locl = font
+        .features
+        .iter()
+        .any(|f| !f.disabled && f.automatic && f.feature_tag.as_deref() == Some("locl"));
+    if !has_automatic_locl {
+        return Vec::new();
+    }
+    let glyphs = GlyphSet::new(font);
+    let mut language_systems: Vec<(String, String)> = Vec::new();
+    for (script, lang) in locl_substitutions(&glyphs).into_keys() {
+        if !language_systems.iter().any(|(s, _)| s == script) {
+            language_systems.push((script.to_string(), "dflt".to_string()));
+        }
+        language_systems.push((script.to_string(), lang.to_string()));
+    }
+    language_systems
+}
+
+/// Ligatures are named for their components joined by underscores, e.g. f_f_i
+fn ligatures(glyphs: &GlyphSet, suffix: Option<&str>) -> String {
+    let mut ligatures: Vec<(Vec<&str>, &str)> = glyphs
+        .names
+        .iter()
+        .filter_map(|name| {
+            let base = match (suffix, name.split_once('.')) {
+                (None, None) => *name,
+                (Some(suffix), Some((base, s))) if s == suffix => base,
+                (None, Some((base, "liga"))) => base,
+                _ => return None,
+            };
+            if base.starts_with('_') || !base.contains('_') {
+                return None;
+            }
+            let components: Vec<_> = base.split('_').collect();
+            components
+                .iter()
+                .all(|c| glyphs.contains(c))
+                .then_some((components, *name))
+        })
+        .collect();
+    // longest first, so they aren't shadowed by their prefixes
+    ligatures.sort_by_key(|(components, _)| std::cmp::Reverse(components.len()));
+    ligatures
+        .into_iter()
+        .map(|(components, lig)| format!("sub {} by {lig};", components.join(" ")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn frac(glyphs: &GlyphSet) -> String {
+    let numr: Vec<_> = DIGITS.iter().map(|d| format!("{d}.numr")).collect();
+    let dnom: Vec<_> = DIGITS.iter().map(|d| format!("{d}.dnom")).collect();
+    let has_all = |names: &[String]| names.iter().all(|n| glyphs.contains(n));
+    if !glyphs.contains("fraction")
+        || !DIGITS.iter().all(|d| glyphs.contains(d))
+        || !has_all(&numr)
+        || !has_all(&dnom)
+    {
+        debug!("frac requires fraction and digits with .numr and .dnom");
+        return String::new();
+    }
+    let digits = DIGITS.join(" ");
+    let numr = numr.join(" ");
+    let dnom = dnom.join(" ");
+    let mut lookups = Vec::new();
+    if glyphs.contains("slash") {
+        lookups.push("lookup FRAC {\n\tsub slash by fraction;\n} FRAC;".to_string());
+    }
+    lookups.push(format!(
+        "lookup UP {{\n\tsub [{digits}] by [{numr}];\n}} UP;"
+    ));
+    lookups.push(format!(
+        "lookup DOWN {{\n\tsub fraction [{numr}]' by [{dnom}];\n\tsub [{dnom}] [{numr}]' by [{dnom}];\n}} DOWN;"
+    ));
+    lookups.join("\n")
+}
+
+/// Whether a feature is made up of single or alternate substitutions and so belongs in aalt
+fn is_aalt_member(tag: &str) -> bool {
+    SUFFIX_FEATURES.iter().any(|(t, _)| *t == tag)
+        || stylistic_set_suffix(tag)
+        || matches!(tag, "c2sc" | "locl" | "salt")
+}
+
+/// Generate the body of an automatic feature from the glyph set.
+///
+/// Returns None if we don't know how to generate the feature, in which
+/// case the code saved in the source should be used. Returns an empty
+/// string if the feature has nothing to do, in which case it should be
+/// omitted, as Glyphs does.
+pub(crate) fn automatic_feature(font: &Font, tag: &str) -> Option<String> {
+    let code = generate(font, &GlyphSet::new(font), tag);
+    if code.is_none() {
+        warn!("Unable to generate automatic feature {tag}, using the code saved in the source");
+    }
+    code
+}
+
+fn generate(font: &Font, glyphs: &GlyphSet, tag: &str) -> Option<String> {
+    let code = match tag {
+        "aalt" => font
+            .features
+            .iter()
+            .filter(|f| !f.disabled)
+            .filter_map(|f| f.feature_tag.as_deref().map(|t| (t, f)))
+            .filter(|(t, _)| is_aalt_member(t))
+            // automatic features that generate nothing are dropped
+            .filter(|(t, f)| {
+                !f.automatic || !matches!(generate(font, glyphs, t).as_deref(), Some(""))
+            })
+            .map(|(t, _)| format!("feature {t};"))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "c2sc" => c2sc(glyphs),
+        "dlig" => ligatures(glyphs, Some("dlig")),
+        "frac" => frac(glyphs),
+        "liga" => ligatures(glyphs, None),
+        "locl" => locl(glyphs),
+        "salt" => salt(glyphs),
+        "smcp" => smcp(glyphs),
+        _ if stylistic_set_suffix(tag) => single_subs(&glyphs.suffixed(tag)),
+        _ => {
+            let (_, suffixes) = SUFFIX_FEATURES.iter().find(|(t, _)| *t == tag)?;
+            let subs: Vec<_> = suffixes
+                .iter()
+                .flat_map(|suffix| glyphs.suffixed(suffix))
+                .collect();
+            single_subs(&subs)
+        }
+    };
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, path::Path};
+
+    use glyphs_reader::{FeatureSnippet, Font, Glyph};
+
+    use super::{automatic_feature, language_systems};
+
+    fn font(glyph_names: &[&str], features: &[&str]) -> Font {
+        let glyphs: BTreeMap<_, _> = glyph_names
+            .iter()
+            .map(|name| {
+                (
+                    (*name).into(),
+                    Glyph {
+                        name: (*name).into(),
+                        export: true,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        Font {
+            glyphs,
+            glyph_order: glyph_names.iter().map(|n| (*n).into()).collect(),
+            features: features
+                .iter()
+                .map(|tag| FeatureSnippet::feature(tag.to_string(), String::new(), false, true))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn small_caps() {
+        let font = font(&["A", "Q", "a", "b", "a.sc", "b.sc", "Q.c2sc"], &[]);
+        assert_eq!(
+            (
+                Some("sub a by a.sc;\nsub b by b.sc;".to_string()),
+                Some("sub A by a.sc;\nsub Q by Q.c2sc;".to_string()),
+            ),
+            (
+                automatic_feature(&font, "smcp"),
+                automatic_feature(&font, "c2sc")
+            )
+        );
+    }
+
+    #[test]
+    fn ligatures_longest_first() {
+        let font = font(&["f", "i", "l", "f_i", "f_f_i", "f_l.dlig", "x_y"], &[]);
+        assert_eq!(
+            (
+                Some("sub f f i by f_f_i;\nsub f i by f_i;".to_string()),
+                Some("sub f l by f_l.dlig;".to_string()),
+            ),
+            (
+                automatic_feature(&font, "liga"),
+                automatic_feature(&font, "dlig")
+            )
+        );
+    }
+
+    #[test]
+    fn locl_by_language() {
+        let font = font(
+            &[
+                "i",
+                "i.loclTRK",
+                "be-cy",
+                "be-cy.loclSRB",
+                "Scedilla",
+                "Scedilla.loclROM",
+            ],
+            &[],
+        );
+        assert_eq!(
+            Some(
+                concat!(
+                    "script cyrl;\nlanguage SRB;\nsub be-cy by be-cy.loclSRB;\n",
+                    "script latn;\nlanguage ROM;\nsub Scedilla by Scedilla.loclROM;\n",
+                    "script latn;\nlanguage TRK;\nsub i by i.loclTRK;",
+                )
+                .to_string()
+            ),
+            automatic_feature(&font, "locl")
+        );
+    }
+
+    #[test]
+    fn salt_and_stylistic_sets() {
+        let font = font(&["a", "g", "a.ss01", "a.ss02", "g.salt"], &[]);
+        assert_eq!(
+            (
+                Some("sub a from [a.ss01 a.ss02];\nsub g by g.salt;".to_string()),
+                Some("sub a by a.ss02;".to_string()),
+            ),
+            (
+                automatic_feature(&font, "salt"),
+                automatic_feature(&font, "ss02")
+            )
+        );
+    }
+
+    #[test]
+    fn aalt_lists_nonempty_features() {
+        let font = font(
+            &["a", "a.sc", "one", "one.tf"],
+            &["aalt", "smcp", "tnum", "onum", "liga"],
+        );
+        assert_eq!(
+            Some("feature smcp;\nfeature tnum;".to_string()),
+            automatic_feature(&font, "aalt")
+        );
+    }
+
+    #[test]
+    fn unknown_feature_uses_saved_code() {
+        let font = font(&["a"], &[]);
+        assert_eq!(None, automatic_feature(&font, "ccmp"));
+    }
+
+    #[test]
+    fn frac_requires_numr_and_dnom() {
+        let font = font(&["one", "fraction"], &[]);
+        assert_eq!(Some(String::new()), automatic_feature(&font, "frac"));
+    }
+
+    #[test]
+    fn language_systems_of_locl() {
+        let font = font(
+            &["i", "i.loclTRK", "i.loclTurkish", "be-cy", "be-cy.loclSRB"],
+            &["locl"],
+        );
+        assert_eq!(
+            vec![
+                ("cyrl".to_string(), "dflt".to_string()),
+                ("cyrl".to_string(), "SRB".to_string()),
+                ("latn".to_string(), "dflt".to_string()),
+                ("latn".to_string(), "TRK".to_string()),
+            ],
+            language_systems(&font)
+        );
+    }
+
+    #[test]
+    fn no_language_systems_without_locl() {
+        let font = font(&["i", "i.loclTRK"], &["smcp"]);
+        assert!(language_systems(&font).is_empty());
+    }
+
+    // The fixture is hand written, its saved automatic code is what we generated when it was
+    // written. Guards against unintended changes, not a comparison with Glyphs itself.
+    #[test]
+    fn regenerates_saved_automatic_features() {
+        let font = Font::load(Path::new(
+            "../resources/testdata/glyphs3/AutomaticFeatures.glyphs",
+        ))
+        .unwrap();
+        let statements = |code: &str| {
+            code.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        let mut checked = 0;
+        for snippet in font.features.iter().filter(|f| f.automatic) {
+            let Some(tag) = snippet.feature_tag.as_deref() else {
+                continue;
+            };
+            let generated = automatic_feature(&font, tag).unwrap();
+            let generated = format!("feature {tag} {{\n# automatic\n{generated}\n}} {tag};");
+            assert_eq!(
+                statements(&snippet.content),
+                statements(&generated),
+                "{tag}"
+            );
+            checked += 1;
+        }
+        assert_eq!(6, checked);
+    }
+}
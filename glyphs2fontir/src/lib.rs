@@ -1,2 +1,6 @@
+mod corner_components;
+mod features;
+mod smart_components;
 pub mod source;
 mod toir;
+mod tokens;
@@ -0,0 +1,228 @@
+//! Decomposition of Glyphs smart components.
+//!
+//! A smart component is a glyph with axes of its own, drawn at a location on them by
+//! the components that use it. Like glyphsLib, we draw those into outlines, see
+//! <https://github.com/googlefonts/glyphsLib/blob/main/Lib/glyphsLib/builder/smart_components.py>
+
+use std::str::FromStr;
+
+use fontdrasil::{
+    coords::{CoordConverter, NormalizedCoord, NormalizedLocation, UserCoord},
+    types::{Axis, GlyphName},
+};
+use fontir::{
+    error::WorkError,
+    instancer::{instantiate_contours, transform_contour},
+    ir::{self, GlyphBuilder, GlyphInstance},
+};
+use glyphs_reader::{Component, Glyph, Layer, Shape, SmartComponentAxis};
+use kurbo::BezPath;
+use write_fonts::types::Tag;
+
+use crate::toir::to_ir_path;
+
+/// The [Layer::part_selection] of a layer at the top of a smart axis
+const TOP_POLE: i64 = 2;
+
+/// Smart axes have names rather than tags, tag them by position to interpolate
+fn smart_axis_tag(index: usize) -> Tag {
+    Tag::from_str(&format!("S{index:03}")).unwrap()
+}
+
+fn to_ir_smart_axes(axes: &[SmartComponentAxis]) -> Vec<Axis> {
+    let min = UserCoord::new(0.0);
+    let max = UserCoord::new(1.0);
+    axes.iter()
+        .enumerate()
+        .map(|(i, axis)| Axis {
+            name: axis.name.to_string(),
+            tag: smart_axis_tag(i),
+            hidden: true,
+            min,
+            default: min,
+            max,
+            converter: CoordConverter::unmapped(min, min, max),
+        })
+        .collect()
+}
+
+/// The location of a layer of a smart component, bottom is 0 and top is 1
+fn layer_location(axes: &[SmartComponentAxis], layer: &Layer) -> NormalizedLocation {
+    axes.iter()
+        .enumerate()
+        .map(|(i, axis)| {
+            let at_top = layer.part_selection.get(&axis.name) == Some(&TOP_POLE);
+            (
+                smart_axis_tag(i),
+                NormalizedCoord::new(if at_top { 1.0 } else { 0.0 }),
+            )
+        })
+        .collect()
+}
+
+/// The location a component draws a smart component at, clamped to the axis range
+fn component_location(axes: &[SmartComponentAxis], component: &Component) -> NormalizedLocation {
+    axes.iter()
+        .enumerate()
+        .map(|(i, axis)| {
+            let bottom = axis.bottom_value.into_inner();
+            let top = axis.top_value.into_inner();
+            let value = component
+                .piece
+                .get(&axis.name)
+                .map(|value| value.into_inner())
+                .unwrap_or(bottom);
+            let normalized = if top != bottom {
+                ((value - bottom) / (top - bottom)).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            (smart_axis_tag(i), NormalizedCoord::new(normalized as f32))
+        })
+        .collect()
+}
+
+/// Draw a smart component as it looks in the given master.
+///
+/// Returns the transformed outline, plus any regular components of the smart glyph
+/// which aren't interpolated.
+pub(crate) fn instantiate_smart_component(
+    glyph_name: &GlyphName,
+    smart_glyph: &Glyph,
+    master_id: &str,
+    component: &Component,
+) -> Result<(Vec<BezPath>, Vec<ir::Component>), WorkError> {
+    let smart_axes = &smart_glyph.smart_component_axes;
+    let smart_name: GlyphName = smart_glyph.name.as_str().into();
+
+    // The layers of the smart glyph for this master, at the poles of its axes
+    let layers: Vec<_> = smart_glyph
+        .layers
+        .iter()
+        .filter(|layer| {
+            layer
+                .associated_master_id
+                .as_deref()
+                .unwrap_or(&layer.layer_id)
+                == master_id
+                && (layer.is_master() || !layer.part_selection.is_empty())
+        })
+        .collect();
+    let Some(master_layer) = layers.iter().find(|layer| layer.is_master()) else {
+        return Err(WorkError::NoMasterForGlyph {
+            master: master_id.to_string(),
+            glyph: smart_name,
+        });
+    };
+
+    let mut builder = GlyphBuilder::new(smart_name.clone());
+    for layer in layers.iter() {
+        let contours = layer
+            .shapes
+            .iter()
+            .filter_map(|shape| match shape {
+                Shape::Path(path) => Some(to_ir_path(smart_name.clone(), path)),
+                Shape::Component(_) => None,
+            })
+            .collect::<Result<_, _>>()?;
+        builder
+            .try_add_source(
+                &layer_location(smart_axes, layer),
+                GlyphInstance {
+                    contours,
+                    ..Default::default()
+                },
+            )
+            .map_err(|e| {
+                WorkError::AddGlyphSource(format!(
+                    "Unable to add smart component layer {} of {smart_name} used by {glyph_name}: {e}",
+                    layer.layer_id
+                ))
+            })?;
+    }
+    let smart_ir_glyph = builder.build()?;
+
+    // There are no components to look up, those of the smart glyph are kept as they are
+    let contours = instantiate_contours(
+        &|_| None,
+        &to_ir_smart_axes(smart_axes),
+        &smart_ir_glyph,
+        &component_location(smart_axes, component),
+    )?
+    .into_iter()
+    .map(|contour| transform_contour(contour, component.transform))
+    .collect();
+
+    let components = master_layer
+        .shapes
+        .iter()
+        .filter_map(|shape| match shape {
+            Shape::Component(inner) => Some(ir::Component {
+                base: inner.name.as_str().into(),
+                transform: component.transform * inner.transform,
+                location: NormalizedLocation::new(),
+            }),
+            Shape::Path(_) => None,
+        })
+        .collect();
+
+    Ok((contours, components))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ordered_float::OrderedFloat;
+
+    use super::*;
+
+    fn width_axis() -> Vec<SmartComponentAxis> {
+        vec![SmartComponentAxis {
+            name: "Width".into(),
+            bottom_value: OrderedFloat(0.0),
+            top_value: OrderedFloat(100.0),
+        }]
+    }
+
+    fn component_at(width: Option<f64>) -> Component {
+        Component {
+            name: "_part.bar".into(),
+            piece: width
+                .into_iter()
+                .map(|w| ("Width".into(), OrderedFloat(w)))
+                .collect::<BTreeMap<_, _>>(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn component_location_is_normalized() {
+        let axes = width_axis();
+        assert_eq!(
+            vec![0.0, 0.25, 1.0, 1.0],
+            [None, Some(25.0), Some(100.0), Some(150.0)]
+                .into_iter()
+                .map(|w| component_location(&axes, &component_at(w))
+                    .get(smart_axis_tag(0))
+                    .unwrap()
+                    .to_f32())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn layer_location_by_pole() {
+        let axes = width_axis();
+        let mut layer = Layer::default();
+        assert_eq!(
+            NormalizedLocation::for_pos(&[("S000", 0.0)]),
+            layer_location(&axes, &layer)
+        );
+        layer.part_selection.insert("Width".into(), TOP_POLE);
+        assert_eq!(
+            NormalizedLocation::for_pos(&[("S000", 1.0)]),
+            layer_location(&axes, &layer)
+        );
+    }
+}
@@ -25,7 +25,7 @@ use fontir::{
     source::{Input, Source},
     stateset::StateSet,
 };
-use glyphs_reader::{Font, InstanceType};
+use glyphs_reader::{Font, Glyph, InstanceType, Shape};
 use write_fonts::{
     tables::os2::SelectionFlags,
     types::{NameId, Tag},
@@ -53,12 +53,43 @@ fn glyph_identifier(glyph_name: &str) -> String {
     format!("/glyph/{glyph_name}")
 }
 
+/// The glyphs whose outlines are drawn into those of `glyph`, rather than referenced
+///
+/// That is smart components and the corner, cap and segment glyphs of hints.
+fn glyphs_drawn_into<'a>(font: &'a Font, glyph: &'a Glyph) -> BTreeSet<&'a str> {
+    let smart_components = glyph
+        .layers
+        .iter()
+        .flat_map(|layer| layer.shapes.iter())
+        .filter_map(|shape| match shape {
+            Shape::Component(component) => Some(component.name.as_str()),
+            Shape::Path(_) => None,
+        })
+        .filter(|name| {
+            font.glyphs
+                .get(*name)
+                .map(|glyph| glyph.is_smart_component())
+                .unwrap_or_default()
+        });
+    let pieces = glyph
+        .layers
+        .iter()
+        .flat_map(|layer| layer.hints.iter())
+        .map(|hint| hint.name.as_str())
+        .filter(|name| font.glyphs.contains_key(*name));
+    smart_components.chain(pieces).collect()
+}
+
 fn glyph_states(font: &Font) -> Result<HashMap<GlyphName, StateSet>, Error> {
     let mut glyph_states = HashMap::new();
 
     for (glyphname, glyph) in font.glyphs.iter() {
         let mut state = StateSet::new();
         state.track_memory(glyph_identifier(glyphname), glyph)?;
+        // The glyph changes when anything drawn into it does
+        for name in glyphs_drawn_into(font, glyph) {
+            state.track_memory(glyph_identifier(name), &font.glyphs[name])?;
+        }
         glyph_states.insert(glyphname.as_str().into(), state);
     }
 
@@ -75,6 +106,36 @@ impl GlyphsIrSource {
     fn feature_inputs(&self, font: &Font) -> Result<StateSet, Error> {
         let mut state = StateSet::new();
         state.track_memory("/features".to_string(), &font.features)?;
+        // automatic features and $[...] tokens are generated from properties of exported glyphs
+        let exported: Vec<_> = font
+            .glyph_order
+            .iter()
+            .filter_map(|name| font.glyphs.get(name))
+            .filter(|glyph| glyph.export)
+            .map(|glyph| {
+                (
+                    &glyph.name,
+                    &glyph.unicode,
+                    glyph.category,
+                    glyph.sub_category,
+                    [
+                        &glyph.left_kern,
+                        &glyph.right_kern,
+                        &glyph.top_kern,
+                        &glyph.bottom_kern,
+                    ],
+                    glyph.layers.len(),
+                )
+            })
+            .collect();
+        state.track_memory("/exported_glyphs".to_string(), &exported)?;
+        // ${...} tokens take values from masters
+        let numbers: Vec<_> = font
+            .masters
+            .iter()
+            .map(|m| (&m.axes_values, &m.number_values))
+            .collect();
+        state.track_memory("/number_values".to_string(), &numbers)?;
         Ok(state)
     }
 
@@ -102,6 +163,8 @@ impl GlyphsIrSource {
             version_minor: Default::default(),
             date: None,
             kerning_ltr: Default::default(),
+            kerning_rtl: Default::default(),
+            kerning_vertical: Default::default(),
         };
         state.track_memory("/font_master".to_string(), &font)?;
         Ok(state)
@@ -131,6 +194,8 @@ impl GlyphsIrSource {
             version_minor: Default::default(),
             date: None,
             kerning_ltr: font.kerning_ltr.clone(),
+            kerning_rtl: font.kerning_rtl.clone(),
+            kerning_vertical: font.kerning_vertical.clone(),
         };
         state.track_memory("/font_master".to_string(), &font)?;
         Ok(state)
@@ -345,6 +410,10 @@ impl Work<Context, WorkId, WorkError> for StaticMetadataWork {
                         .get(&inst.axes_values)
                         .map(|nc| nc.to_user(&axis_map))
                         .unwrap(),
+                    family_name: inst.family_name.clone(),
+                    postscript_name: inst.postscript_font_name.clone(),
+                    style_map_family_name: inst.style_map_family_name.clone(),
+                    style_map_style_name: inst.style_map_style_name.clone(),
                 })
             })
             .collect();
@@ -600,22 +669,29 @@ impl Work<Context, WorkId, WorkError> for FeatureWork {
         let font_info = self.font_info.as_ref();
         let font = &font_info.font;
 
-        context.features.set(to_ir_features(&font.features)?);
+        context.features.set(to_ir_features(font)?);
         Ok(())
     }
 }
 
-fn parse_kern_group(name: &str) -> Option<KernGroup> {
-    name.strip_prefix(SIDE1_PREFIX)
+fn parse_kern_group(name: &str, (side1_prefix, side2_prefix): KernPrefixes) -> Option<KernGroup> {
+    name.strip_prefix(side1_prefix)
         .map(|name| KernGroup::Side1(name.into()))
         .or_else(|| {
-            name.strip_prefix(SIDE2_PREFIX)
+            name.strip_prefix(side2_prefix)
                 .map(|name| KernGroup::Side2(name.into()))
         })
 }
 
-const SIDE1_PREFIX: &str = "@MMK_L_";
-const SIDE2_PREFIX: &str = "@MMK_R_";
+/// The (first, second) group prefixes of a kerning dictionary
+type KernPrefixes = (&'static str, &'static str);
+
+/// Pairs in kerningLTR are first glyph's right group, second glyph's left group
+const LTR_PREFIXES: KernPrefixes = ("@MMK_L_", "@MMK_R_");
+/// Pairs in kerningRTL are in logical order, first glyph's left group, second glyph's right group
+const RTL_PREFIXES: KernPrefixes = ("@MMK_R_", "@MMK_L_");
+/// Pairs in kerningVertical are upper glyph's bottom group, lower glyph's top group
+const VERTICAL_PREFIXES: KernPrefixes = ("@MMK_T_", "@MMK_B_");
 
 #[derive(Debug)]
 struct KerningGroupWork {
@@ -637,10 +713,11 @@ struct AnchorWork {
 fn kern_participant(
     glyph_order: &GlyphOrder,
     groups: &BTreeMap<KernGroup, BTreeSet<GlyphName>>,
+    prefixes: KernPrefixes,
     expect_prefix: &str,
     raw_side: &str,
 ) -> Option<KernSide> {
-    if let Some(group) = parse_kern_group(raw_side) {
+    if let Some(group) = parse_kern_group(raw_side, prefixes) {
         if !raw_side.starts_with(expect_prefix) {
             warn!("Invalid kern side: {raw_side}, should have prefix {expect_prefix}",);
             return None;
@@ -679,17 +756,23 @@ impl Work<Context, WorkId, WorkError> for KerningGroupWork {
         let mut groups = KerningGroups::default();
 
         // If glyph uses a group for either side it goes in that group
+        // For RTL glyphs the first (logical) side is the left side, as in glyphsLib
+        // <https://github.com/googlefonts/glyphsLib/blob/42bc1db912fd4b66f130fb3bdc63a0c1e774eb38/Lib/glyphsLib/builder/groups.py#L208-L222>
         font.glyphs
             .iter()
             // ignore non-export glyphs
             .filter(|(_, glyph)| glyph.export)
             .flat_map(|(glyph_name, glyph)| {
-                glyph
-                    .right_kern
+                let (side1, side2) = if glyph.is_rtl() {
+                    (&glyph.left_kern, &glyph.right_kern)
+                } else {
+                    (&glyph.right_kern, &glyph.left_kern)
+                };
+                side1
                     .iter()
                     .cloned()
                     .map(KernGroup::Side1)
-                    .chain(glyph.left_kern.iter().cloned().map(KernGroup::Side2))
+                    .chain(side2.iter().cloned().map(KernGroup::Side2))
                     .map(|group| (group, GlyphName::from(glyph_name.as_str())))
             })
             .for_each(|(group_name, glyph_name)| {
@@ -700,21 +783,55 @@ impl Work<Context, WorkId, WorkError> for KerningGroupWork {
                     .insert(glyph_name);
             });
 
-        groups.locations = font
-            .kerning_ltr
+        // Vertical kerning is between the bottom of the upper glyph and the top of the lower
+        font.glyphs
             .iter()
-            .filter_map(
-                |(master_id, _)| match font_info.master_positions.get(master_id) {
-                    Some(pos) => Some(pos),
-                    None => {
-                        warn!("Kerning is present for non-existent master {master_id}");
-                        None
-                    }
-                },
-            )
-            .cloned()
+            .filter(|(_, glyph)| glyph.export)
+            .flat_map(|(glyph_name, glyph)| {
+                glyph
+                    .bottom_kern
+                    .iter()
+                    .cloned()
+                    .map(KernGroup::Side1)
+                    .chain(glyph.top_kern.iter().cloned().map(KernGroup::Side2))
+                    .map(|group| (group, GlyphName::from(glyph_name.as_str())))
+            })
+            .for_each(|(group_name, glyph_name)| {
+                groups
+                    .vertical_groups
+                    .entry(group_name)
+                    .or_default()
+                    .insert(glyph_name);
+            });
+
+        let kerned_masters: HashSet<_> = font
+            .kerning_ltr
+            .keys()
+            .chain(font.kerning_rtl.keys())
+            .chain(font.kerning_vertical.keys())
+            .filter(|master_id| {
+                let exists = font_info.master_positions.contains_key(*master_id);
+                if !exists {
+                    warn!("Kerning is present for non-existent master {master_id}");
+                }
+                exists
+            })
             .collect();
 
+        // Every master is a kerning location. As glyphsLib gives a master without kerning an
+        // empty kerning.plist, which ufo2ft deems sparse, pairs are interpolated there rather than zero.
+        for (idx, master) in font.masters.iter().enumerate() {
+            let pos = font_info.master_positions.get(&master.id).unwrap();
+            groups.locations.insert(pos.clone());
+            if idx != font.default_master_idx && !kerned_masters.contains(&master.id) {
+                debug!(
+                    "{} has no kerning, its pairs will be interpolated",
+                    master.name
+                );
+                groups.sparse_locations.insert(pos.clone());
+            }
+        }
+
         context.kerning_groups.set(groups);
         Ok(())
     }
@@ -746,23 +863,30 @@ impl Work<Context, WorkId, WorkError> for KerningInstanceWork {
             ..Default::default()
         };
 
+        // IR kerning is in logical order, same as kerningRTL, so RTL only differs in prefixes
         font.kerning_ltr
             .iter()
+            .map(|kerns| (LTR_PREFIXES, kerns))
+            .chain(font.kerning_rtl.iter().map(|kerns| (RTL_PREFIXES, kerns)))
             // Only the kerns at our location
-            .filter_map(|(master_id, kerns)| {
+            .filter_map(|(prefixes, (master_id, kerns))| {
                 font_info
                     .master_positions
                     .get(master_id)
-                    .and_then(|pos| (*pos == self.location).then_some((pos, kerns)))
+                    .and_then(|pos| (*pos == self.location).then_some((prefixes, pos, kerns)))
             })
-            .flat_map(|(master_pos, kerns)| {
-                kerns.iter().map(|((side1, side2), adjustment)| {
-                    ((side1, side2), ((*master_pos).clone(), *adjustment))
+            .flat_map(|(prefixes, master_pos, kerns)| {
+                kerns.iter().map(move |((side1, side2), adjustment)| {
+                    (
+                        prefixes,
+                        (side1, side2),
+                        ((*master_pos).clone(), *adjustment),
+                    )
                 })
             })
-            .filter_map(|((side1, side2), pos_adjust)| {
-                let side1 = kern_participant(glyph_order, groups, SIDE1_PREFIX, side1);
-                let side2 = kern_participant(glyph_order, groups, SIDE2_PREFIX, side2);
+            .filter_map(|(prefixes, (side1, side2), pos_adjust)| {
+                let side1 = kern_participant(glyph_order, groups, prefixes, prefixes.0, side1);
+                let side2 = kern_participant(glyph_order, groups, prefixes, prefixes.1, side2);
                 let (Some(side1), Some(side2)) = (side1, side2) else {
                     return None;
                 };
@@ -772,6 +896,39 @@ impl Work<Context, WorkId, WorkError> for KerningInstanceWork {
                 *kerning.kerns.entry(participants).or_default() = (value as f32).into();
             });
 
+        if let Some(kerns) = font
+            .kerning_vertical
+            .iter()
+            .find(|(master_id, _)| {
+                font_info.master_positions.get(*master_id) == Some(&self.location)
+            })
+            .map(|(_, kerns)| kerns)
+        {
+            let groups = &kerning_groups.vertical_groups;
+            for ((side1, side2), value) in kerns.iter() {
+                let side1 = kern_participant(
+                    glyph_order,
+                    groups,
+                    VERTICAL_PREFIXES,
+                    VERTICAL_PREFIXES.0,
+                    side1,
+                );
+                let side2 = kern_participant(
+                    glyph_order,
+                    groups,
+                    VERTICAL_PREFIXES,
+                    VERTICAL_PREFIXES.1,
+                    side2,
+                );
+                let (Some(side1), Some(side2)) = (side1, side2) else {
+                    continue;
+                };
+                kerning
+                    .vertical_kerns
+                    .insert((side1, side2), (*value as f32).into());
+            }
+        }
+
         context.kerning_at.set(kerning);
         Ok(())
     }
@@ -884,7 +1041,7 @@ impl Work<Context, WorkId, WorkError> for GlyphIrWork {
 
             // TODO populate width and height properly
             let (contours, components) =
-                to_ir_contours_and_components(self.glyph_name.clone(), &instance.shapes)?;
+                to_ir_contours_and_components(self.glyph_name.clone(), instance, font, master_id)?;
             let glyph_instance = GlyphInstance {
                 width: if !zero_width {
                     instance.width.into_inner()
@@ -945,7 +1102,7 @@ mod tests {
     };
     use fontir::{
         error::WorkError,
-        ir::{AnchorKind, GlobalMetricsInstance, GlyphOrder, NameKey},
+        ir::{AnchorKind, GlobalMetricsInstance, GlyphOrder, KernGroup, KernSide, NameKey},
         orchestration::{Context, Flags, WorkId},
         paths::Paths,
         source::Source,
@@ -953,6 +1110,7 @@ mod tests {
     };
     use glyphs_reader::Font;
     use indexmap::IndexSet;
+    use kurbo::{Rect, Shape};
     use write_fonts::types::{NameId, Tag};
 
     use crate::source::names;
@@ -1546,6 +1704,55 @@ mod tests {
         }
     }
 
+    #[test]
+    fn smart_component_is_decomposed() {
+        let (source, context) = build_static_metadata(glyphs3_dir().join("SmartComponent.glyphs"));
+        build_glyphs(&source, &context, &[&"bar".into(), &"bar.default".into()]).unwrap();
+        let default_location = context.static_metadata.get().default_location().clone();
+
+        // Width = 50 is halfway between the bottom and top layers, 100 and 300 wide
+        let mut boxes = Vec::new();
+        for glyph_name in ["bar", "bar.default"] {
+            let glyph = context.glyphs.get(&WorkId::Glyph(glyph_name.into()));
+            let instance = glyph.sources().get(&default_location).unwrap();
+            assert!(instance.components.is_empty(), "{instance:?}");
+            assert_eq!(1, instance.contours.len(), "{instance:?}");
+            boxes.push(instance.contours[0].bounding_box());
+        }
+        assert_eq!(
+            vec![
+                Rect::new(10.0, 0.0, 210.0, 100.0),
+                Rect::new(0.0, 0.0, 100.0, 100.0)
+            ],
+            boxes
+        );
+    }
+
+    #[test]
+    fn corner_and_cap_are_inserted() {
+        let (source, context) =
+            build_static_metadata(glyphs3_dir().join("CornerComponents.glyphs"));
+        build_glyphs(&source, &context, &[&"square".into()]).unwrap();
+        let glyph = context.glyphs.get(&WorkId::Glyph("square".into()));
+        let instance = glyph
+            .sources()
+            .get(context.static_metadata.get().default_location())
+            .unwrap();
+        assert_eq!(1, instance.contours.len(), "{instance:?}");
+
+        // The top right corner is chamfered and a half-depth point is capped onto the bottom
+        let points: HashSet<_> = instance.contours[0]
+            .elements()
+            .iter()
+            .filter_map(|el| el.end_point())
+            .map(|pt| (pt.x.round() as i32, pt.y.round() as i32))
+            .collect();
+        assert_eq!(
+            HashSet::from([(0, 0), (50, -10), (100, 0), (100, 90), (90, 100), (0, 100)]),
+            points
+        );
+    }
+
     // .glyphs v2 defaults to Weight, Width, Custom if no axes are specified
     // Avoid ending up with kerning for locations like {XXXX: 0.00, wdth: 0.00, wght: 1.00}
     // when XXXX and wdth are point axes that won't be in fvar. Oswald was hitting this.
@@ -1563,6 +1770,114 @@ mod tests {
         assert!(bad_kerns.is_empty(), "{bad_kerns:#?}");
     }
 
+    #[test]
+    fn masters_without_kerning_are_sparse() {
+        let (_, context) = build_kerning(glyphs3_dir().join("WghtVar_SparseKern.glyphs"));
+        let groups = context.kerning_groups.get();
+        assert_eq!(
+            (
+                vec![
+                    NormalizedLocation::for_pos(&[("wght", 0.0)]),
+                    NormalizedLocation::for_pos(&[("wght", 1.0)])
+                ],
+                vec![NormalizedLocation::for_pos(&[("wght", 1.0)])]
+            ),
+            (
+                groups.locations.iter().cloned().collect::<Vec<_>>(),
+                groups.sparse_locations.iter().cloned().collect::<Vec<_>>()
+            )
+        );
+    }
+
+    #[test]
+    fn kern_rtl_in_logical_order() {
+        let (_, context) = build_kerning(glyphs3_dir().join("KernRTL.glyphs"));
+
+        let groups = context.kerning_groups.get();
+        let group_members = |group: KernGroup| {
+            groups
+                .groups
+                .get(&group)
+                .unwrap()
+                .iter()
+                .map(|g| g.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            (vec!["alef-hb"], vec!["bet-hb", "bet-hb.alt"], vec!["a"]),
+            (
+                group_members(KernGroup::Side1("alef".into())),
+                group_members(KernGroup::Side2("bet".into())),
+                group_members(KernGroup::Side1("a".into())),
+            )
+        );
+
+        let kerns = context.kerning_at.all();
+        assert_eq!(1, kerns.len());
+        let actual: Vec<_> = kerns[0]
+            .1
+            .kerns
+            .iter()
+            .map(|((side1, side2), value)| (side1.clone(), side2.clone(), value.0))
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    KernSide::Glyph("bet-hb".into()),
+                    KernSide::Glyph("alef-hb".into()),
+                    12.0
+                ),
+                (
+                    KernSide::Group(KernGroup::Side1("a".into())),
+                    KernSide::Group(KernGroup::Side2("a".into())),
+                    -10.0
+                ),
+                (
+                    KernSide::Group(KernGroup::Side1("alef".into())),
+                    KernSide::Group(KernGroup::Side2("bet".into())),
+                    -40.0
+                ),
+            ],
+            actual
+        );
+    }
+
+    #[test]
+    fn kern_vertical() {
+        let (_, context) = build_kerning(glyphs3_dir().join("KernRTL.glyphs"));
+
+        let groups = context.kerning_groups.get();
+        assert_eq!(
+            vec![
+                (KernGroup::Side1("a".into()), vec!["a"]),
+                (KernGroup::Side2("a".into()), vec!["a"]),
+            ],
+            groups
+                .vertical_groups
+                .iter()
+                .map(|(group, members)| (
+                    group.clone(),
+                    members.iter().map(|g| g.as_str()).collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        let kerns = context.kerning_at.all();
+        assert_eq!(
+            vec![(
+                KernSide::Group(KernGroup::Side1("a".into())),
+                KernSide::Group(KernGroup::Side2("a".into())),
+                -7.0
+            )],
+            kerns[0]
+                .1
+                .vertical_kerns
+                .iter()
+                .map(|((side1, side2), value)| (side1.clone(), side2.clone(), value.0))
+                .collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn captures_anchors() {
         let base_name = "A".into();
@@ -17,22 +17,51 @@ use fontir::{
     error::{Error, WorkError},
     ir::{self, GlyphPathBuilder},
 };
-use glyphs_reader::{Component, FeatureSnippet, Font, NodeType, Path, Shape};
+use glyphs_reader::{Component, Font, Layer, NodeType, Path, Shape};
 
+use crate::{
+    corner_components::apply_hints, features, smart_components::instantiate_smart_component,
+    tokens::expand_tokens,
+};
+
+/// The contours and components of a layer in the given master.
+///
+/// Smart components are drawn into contours, see [instantiate_smart_component], and
+/// corner components, caps and segment components are inserted into the contours they
+/// are attached to, see [apply_hints].
 pub(crate) fn to_ir_contours_and_components(
     glyph_name: GlyphName,
-    shapes: &[Shape],
+    layer: &Layer,
+    font: &Font,
+    master_id: &str,
 ) -> Result<(Vec<BezPath>, Vec<ir::Component>), WorkError> {
+    let shapes = &layer.shapes;
     // For most glyphs in most fonts all the shapes are contours so it's a good guess
     let mut contours = Vec::with_capacity(shapes.len());
     let mut components = Vec::new();
 
+    let hinted_paths = apply_hints(&glyph_name, layer, font, master_id);
+    let mut path_index = 0;
     for shape in shapes.iter() {
         match shape {
-            Shape::Component(component) => {
-                components.push(to_ir_component(glyph_name.clone(), component))
+            Shape::Component(component) => match font.glyphs.get(&component.name) {
+                Some(smart_glyph) if smart_glyph.is_smart_component() => {
+                    let (smart_contours, smart_components) = instantiate_smart_component(
+                        &glyph_name,
+                        smart_glyph,
+                        master_id,
+                        component,
+                    )?;
+                    contours.extend(smart_contours);
+                    components.extend(smart_components);
+                }
+                _ => components.push(to_ir_component(glyph_name.clone(), component)),
+            },
+            Shape::Path(path) => {
+                let path = hinted_paths.get(&path_index).unwrap_or(path);
+                contours.push(to_ir_path(glyph_name.clone(), path)?);
+                path_index += 1;
             }
-            Shape::Path(path) => contours.push(to_ir_path(glyph_name.clone(), path)?),
         }
     }
 
@@ -49,6 +78,7 @@ fn to_ir_component(glyph_name: GlyphName, component: &Component) -> ir::Componen
     ir::Component {
         base: component.name.as_str().into(),
         transform: component.transform,
+        location: NormalizedLocation::new(),
     }
 }
 
@@ -78,7 +108,7 @@ fn add_to_path<'a>(
     Ok(())
 }
 
-fn to_ir_path(glyph_name: GlyphName, src_path: &Path) -> Result<BezPath, WorkError> {
+pub(crate) fn to_ir_path(glyph_name: GlyphName, src_path: &Path) -> Result<BezPath, WorkError> {
     // Based on https://github.com/googlefonts/glyphsLib/blob/24b4d340e4c82948ba121dcfe563c1450a8e69c9/Lib/glyphsLib/builder/paths.py#L20
     // See also https://github.com/fonttools/ufoLib2/blob/4d8a9600148b670b0840120658d9aab0b38a9465/src/ufoLib2/pointPens/glyphPointPen.py#L16
     if src_path.nodes.is_empty() {
@@ -120,17 +150,71 @@ fn to_ir_path(glyph_name: GlyphName, src_path: &Path) -> Result<BezPath, WorkErr
     Ok(path)
 }
 
-pub(crate) fn to_ir_features(features: &[FeatureSnippet]) -> Result<ir::FeaturesSource, WorkError> {
+pub(crate) fn to_ir_features(font: &Font) -> Result<ir::FeaturesSource, WorkError> {
     // Based on https://github.com/googlefonts/glyphsLib/blob/24b4d340e4c82948ba121dcfe563c1450a8e69c9/Lib/glyphsLib/builder/features.py#L74
-    // TODO: token expansion
     // TODO: implement notes and labels
-    let fea_snippets: Vec<_> = features.iter().filter_map(|f| f.str_if_enabled()).collect();
+    let mut fea_snippets = Vec::new();
+    for snippet in font.features.iter().filter(|f| !f.disabled) {
+        let code = match (&snippet.feature_tag, snippet.automatic) {
+            (Some(tag), true) => match features::automatic_feature(font, tag) {
+                Some(code) if code.is_empty() => continue,
+                Some(code) => format!("feature {tag} {{\n# automatic\n{code}\n}} {tag};"),
+                None => snippet.content.clone(),
+            },
+            _ => snippet.content.clone(),
+        };
+        fea_snippets.push(expand_tokens(font, &code)?);
+    }
+
+    // The languages of generated features must be declared before any feature uses them
+    let declared: Vec<_> = fea_snippets
+        .iter()
+        .map(|code| declared_language_systems(code))
+        .collect();
+    let all_declared: Vec<_> = declared.iter().flatten().collect();
+    let mut missing: Vec<_> = features::language_systems(font)
+        .into_iter()
+        .filter(|language_system| !all_declared.contains(&language_system))
+        .collect();
+    if !missing.is_empty() {
+        // Without any declarations DFLT dflt is implied, and it must come first
+        if all_declared.is_empty() {
+            missing.insert(0, ("DFLT".to_string(), "dflt".to_string()));
+        }
+        let index = declared
+            .iter()
+            .rposition(|language_systems| !language_systems.is_empty())
+            .map(|i| i + 1)
+            .unwrap_or_default();
+        let code = missing
+            .iter()
+            .map(|(script, lang)| format!("languagesystem {script} {lang};"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        fea_snippets.insert(index, code);
+    }
+
     Ok(ir::FeaturesSource::Memory {
         fea_content: fea_snippets.join("\n\n"),
         include_dir: None,
     })
 }
 
+/// The (script, language) of each `languagesystem` statement in feature code
+fn declared_language_systems(code: &str) -> Vec<(String, String)> {
+    code.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split(';'))
+        .filter_map(|statement| {
+            let mut parts = statement.split_whitespace();
+            if parts.next()? != "languagesystem" {
+                return None;
+            }
+            Some((parts.next()?.to_string(), parts.next()?.to_string()))
+        })
+        .collect()
+}
+
 pub(crate) fn design_location(
     axes: &[fontdrasil::types::Axis],
     axes_values: &[OrderedFloat<f64>],
@@ -334,9 +418,9 @@ impl TryFrom<Font> for FontInfo {
 
 #[cfg(test)]
 mod tests {
-    use glyphs_reader::{Node, Path};
+    use glyphs_reader::{Font, Node, Path};
 
-    use super::to_ir_path;
+    use super::{declared_language_systems, to_ir_features, to_ir_path};
 
     #[test]
     fn the_last_of_a_closed_contour_is_first() {
@@ -360,4 +444,29 @@ mod tests {
         let bez = to_ir_path("test".into(), &path).unwrap();
         assert_eq!("M32,32 C64,64 64,0 32,32 Z", bez.to_svg());
     }
+
+    #[test]
+    fn declares_languages_of_generated_locl() {
+        let font = Font::load(std::path::Path::new(
+            "../resources/testdata/glyphs3/AutomaticFeatures.glyphs",
+        ))
+        .unwrap();
+        let fontir::ir::FeaturesSource::Memory { fea_content, .. } = to_ir_features(&font).unwrap()
+        else {
+            panic!("Expected in-memory features");
+        };
+        assert_eq!(
+            vec![
+                ("DFLT".to_string(), "dflt".to_string()),
+                ("latn".to_string(), "dflt".to_string()),
+                ("latn".to_string(), "ROM".to_string()),
+                ("latn".to_string(), "TRK".to_string()),
+            ],
+            declared_language_systems(&fea_content)
+        );
+        // Declarations must precede the first feature
+        assert!(
+            fea_content.rfind("languagesystem").unwrap() < fea_content.find("feature").unwrap()
+        );
+    }
 }
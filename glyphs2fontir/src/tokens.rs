@@ -0,0 +1,654 @@
+//! Expands the tokens Glyphs allows in feature code.
+//!
+//! `$[predicate]` is replaced by the exported glyphs matching an NSPredicate
+//! style predicate, e.g. `$[category == "Letter"]`. `${expression}` is replaced
+//! by the value of an arithmetic expression over the font's named numbers,
+//! e.g. `${padding * 2}`. Numbers usually differ by master, in which case the
+//! result is a variable value.
+//!
+//! See <https://glyphsapp.com/learn/tokens>
+
+use std::collections::BTreeMap;
+
+use fontir::error::WorkError;
+use glyphs_reader::{Font, Glyph};
+use ordered_float::OrderedFloat;
+
+pub(crate) fn expand_tokens(font: &Font, code: &str) -> Result<String, WorkError> {
+    let mut expanded = String::with_capacity(code.len());
+    let mut rest = code;
+    while let Some(pos) = rest.find(['$', '#', '"']) {
+        expanded.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        // comments and strings are copied through as is
+        let skip_to = match rest.as_bytes()[0] {
+            b'#' => Some(rest.find('\n').unwrap_or(rest.len())),
+            b'"' => Some(rest[1..].find('"').map(|end| end + 2).unwrap_or(rest.len())),
+            _ => None,
+        };
+        if let Some(end) = skip_to {
+            expanded.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        let (open, close) = match rest.as_bytes().get(1) {
+            Some(b'[') => ('[', ']'),
+            Some(b'{') => ('{', '}'),
+            _ => {
+                expanded.push('$');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        // $, open, body, close
+        let Some(end) = token_end(&rest[2..], open, close).map(|end| end + 3) else {
+            return Err(WorkError::InvalidFeatureToken {
+                token: rest.lines().next().unwrap_or_default().to_string(),
+                reason: format!("no closing '{close}'"),
+            });
+        };
+        let token = &rest[..end];
+        let body = &token[2..end - 1];
+        let value = match open {
+            '[' => glyph_predicate(font, body),
+            _ => number_value(font, body),
+        }
+        .map_err(|reason| WorkError::InvalidFeatureToken {
+            token: token.to_string(),
+            reason,
+        })?;
+        expanded.push_str(&value);
+        rest = &rest[end..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// The offset of the close that ends a token, skipping nested pairs and strings
+fn token_end(body: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in body.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == open => depth += 1,
+            None if c == close && depth == 0 => return Some(i),
+            None if c == close => depth -= 1,
+            None => (),
+        }
+    }
+    None
+}
+
+/// The names of the exported glyphs matching predicate, in glyph order
+fn glyph_predicate(font: &Font, predicate: &str) -> Result<String, String> {
+    let tokens = tokenize(predicate)?;
+    let mut parser = Parser::new(&tokens);
+    let predicate = parser.predicate()?;
+    parser.expect_end()?;
+
+    let mut names = Vec::new();
+    for name in font.glyph_order.iter() {
+        let Some(glyph) = font.glyphs.get(name) else {
+            continue;
+        };
+        if glyph.export && predicate.matches(glyph)? {
+            names.push(name.as_str());
+        }
+    }
+    Ok(names.join(" "))
+}
+
+/// The value of expression, as a variable metric if it varies by master
+fn number_value(font: &Font, expression: &str) -> Result<String, String> {
+    let tokens = tokenize(expression)?;
+    let values = font
+        .masters
+        .iter()
+        .map(|master| {
+            let mut parser = Parser::new(&tokens);
+            let value = parser.number(&master.number_values)?;
+            parser.expect_end()?;
+            Ok(value.round() as i64)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let Some(first) = values.first() else {
+        return Err("no masters to take values from".to_string());
+    };
+    if values.iter().all(|v| v == first) {
+        return Ok(first.to_string());
+    }
+
+    // a variable metric, positioned by master design location
+    let locations: Vec<_> = font
+        .masters
+        .iter()
+        .zip(values.iter())
+        .map(|(master, value)| {
+            let location: Vec<_> = font
+                .axes
+                .iter()
+                .zip(master.axes_values.iter())
+                .map(|(axis, pos)| format!("{}={}d", axis.tag, pos.into_inner()))
+                .collect();
+            format!("{}:{value}", location.join(","))
+        })
+        .collect();
+    Ok(format!("({})", locations.join(" ")))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Op(&'static str),
+}
+
+// longest first, so e.g. <= isn't lexed as <
+const OPS: &[&str] = &[
+    "==", "!=", "<>", "<=", ">=", "&&", "||", "<", ">", "=", "!", "(", ")", "{", "}", "[", "]",
+    ",", "+", "-", "*", "/",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c == '"' || c == '\'' {
+            let end = rest[1..]
+                .find(c)
+                .ok_or_else(|| format!("unterminated string {rest}"))?;
+            tokens.push(Token::Str(rest[1..end + 1].to_string()));
+            rest = &rest[end + 2..];
+        } else if c.is_ascii_digit() || c == '.' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let num = rest[..end]
+                .parse()
+                .map_err(|_| format!("invalid number {}", &rest[..end]))?;
+            tokens.push(Token::Num(num));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(*op));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(format!("unexpected '{c}'"));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+    List(Vec<Value>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Like,
+    BeginsWith,
+    EndsWith,
+    Contains,
+    In,
+}
+
+#[derive(Debug)]
+enum Predicate {
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+    Not(Box<Predicate>),
+    Compare {
+        attribute: String,
+        comparison: Comparison,
+        value: Value,
+        case_insensitive: bool,
+    },
+}
+
+impl Predicate {
+    fn matches(&self, glyph: &Glyph) -> Result<bool, String> {
+        Ok(match self {
+            Predicate::And(lhs, rhs) => lhs.matches(glyph)? && rhs.matches(glyph)?,
+            Predicate::Or(lhs, rhs) => lhs.matches(glyph)? || rhs.matches(glyph)?,
+            Predicate::Not(predicate) => !predicate.matches(glyph)?,
+            Predicate::Compare {
+                attribute,
+                comparison,
+                value,
+                case_insensitive,
+            } => compare(
+                &glyph_attribute(glyph, attribute)?,
+                *comparison,
+                value,
+                *case_insensitive,
+            )?,
+        })
+    }
+}
+
+fn glyph_attribute(glyph: &Glyph, attribute: &str) -> Result<Value, String> {
+    let string = |value: Option<&str>| Value::Str(value.unwrap_or_default().to_string());
+    Ok(match attribute {
+        "name" => Value::Str(glyph.name.to_string()),
+        "category" => Value::Str(glyph.category.map(|c| c.to_string()).unwrap_or_default()),
+        "subCategory" => Value::Str(
+            glyph
+                .sub_category
+                .map(|c| c.to_string())
+                .unwrap_or_default(),
+        ),
+        "export" => Value::Bool(glyph.export),
+        "unicode" => Value::Str(
+            glyph
+                .unicode
+                .first()
+                .map(|u| format!("{u:04X}"))
+                .unwrap_or_default(),
+        ),
+        "countOfUnicodes" => Value::Num(glyph.unicode.len() as f64),
+        "countOfLayers" => Value::Num(glyph.layers.len() as f64),
+        "leftKerningGroup" => string(glyph.left_kern.as_deref()),
+        "rightKerningGroup" => string(glyph.right_kern.as_deref()),
+        "topKerningGroup" => string(glyph.top_kern.as_deref()),
+        "bottomKerningGroup" => string(glyph.bottom_kern.as_deref()),
+        _ => return Err(format!("unsupported glyph attribute '{attribute}'")),
+    })
+}
+
+fn compare(
+    lhs: &Value,
+    comparison: Comparison,
+    rhs: &Value,
+    case_insensitive: bool,
+) -> Result<bool, String> {
+    if let (Comparison::In, Value::List(items)) = (comparison, rhs) {
+        for item in items {
+            if compare(lhs, Comparison::Eq, item, case_insensitive)? {
+                return Ok(true);
+            }
+        }
+        return Ok(false);
+    }
+    let fold = |s: &str| {
+        if case_insensitive {
+            s.to_lowercase()
+        } else {
+            s.to_string()
+        }
+    };
+    let result = match (lhs, rhs) {
+        (Value::Str(lhs), Value::Str(rhs)) => {
+            let (lhs, rhs) = (fold(lhs), fold(rhs));
+            match comparison {
+                Comparison::Eq => lhs == rhs,
+                Comparison::Ne => lhs != rhs,
+                Comparison::Lt => lhs < rhs,
+                Comparison::Le => lhs <= rhs,
+                Comparison::Gt => lhs > rhs,
+                Comparison::Ge => lhs >= rhs,
+                Comparison::Like => like(&lhs, &rhs),
+                Comparison::BeginsWith => lhs.starts_with(&rhs),
+                Comparison::EndsWith => lhs.ends_with(&rhs),
+                Comparison::Contains => lhs.contains(&rhs),
+                Comparison::In => rhs.contains(&lhs),
+            }
+        }
+        (Value::Num(lhs), Value::Num(rhs)) => match comparison {
+            Comparison::Eq => lhs == rhs,
+            Comparison::Ne => lhs != rhs,
+            Comparison::Lt => lhs < rhs,
+            Comparison::Le => lhs <= rhs,
+            Comparison::Gt => lhs > rhs,
+            Comparison::Ge => lhs >= rhs,
+            _ => return Err(format!("{comparison:?} doesn't apply to numbers")),
+        },
+        // NSPredicate treats YES and NO as 1 and 0
+        (Value::Bool(lhs), Value::Bool(_) | Value::Num(_)) => {
+            let rhs = match rhs {
+                Value::Num(n) => *n != 0.0,
+                _ => *rhs == Value::Bool(true),
+            };
+            match comparison {
+                Comparison::Eq => *lhs == rhs,
+                Comparison::Ne => *lhs != rhs,
+                _ => return Err(format!("{comparison:?} doesn't apply to booleans")),
+            }
+        }
+        _ => return Err(format!("can't compare {lhs:?} with {rhs:?}")),
+    };
+    Ok(result)
+}
+
+/// NSPredicate LIKE, where * matches any run of characters and ? any one character
+fn like(text: &str, pattern: &str) -> bool {
+    fn like_chars(text: &[char], pattern: &[char]) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some(('*', rest)) => (0..=text.len()).any(|i| like_chars(&text[i..], rest)),
+            Some(('?', rest)) => !text.is_empty() && like_chars(&text[1..], rest),
+            Some((c, rest)) => text.first() == Some(c) && like_chars(&text[1..], rest),
+        }
+    }
+    let text: Vec<_> = text.chars().collect();
+    let pattern: Vec<_> = pattern.chars().collect();
+    like_chars(&text, &pattern)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: &'a [Token]) -> Self {
+        Parser { tokens, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Option<&'a Token> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Op(o)) if *o == op);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Ident(i)) if i.eq_ignore_ascii_case(keyword));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_op(&mut self, op: &str) -> Result<(), String> {
+        if !self.eat_op(op) {
+            return Err(format!("expected '{op}', found {:?}", self.peek()));
+        }
+        Ok(())
+    }
+
+    fn expect_end(&self) -> Result<(), String> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(format!("unexpected {token:?}")),
+        }
+    }
+
+    fn predicate(&mut self) -> Result<Predicate, String> {
+        let mut lhs = self.conjunction()?;
+        while self.eat_op("||") || self.eat_keyword("or") {
+            lhs = Predicate::Or(Box::new(lhs), Box::new(self.conjunction()?));
+        }
+        Ok(lhs)
+    }
+
+    fn conjunction(&mut self) -> Result<Predicate, String> {
+        let mut lhs = self.negation()?;
+        while self.eat_op("&&") || self.eat_keyword("and") {
+            lhs = Predicate::And(Box::new(lhs), Box::new(self.negation()?));
+        }
+        Ok(lhs)
+    }
+
+    fn negation(&mut self) -> Result<Predicate, String> {
+        if self.eat_op("!") || self.eat_keyword("not") {
+            return Ok(Predicate::Not(Box::new(self.negation()?)));
+        }
+        if self.eat_op("(") {
+            let predicate = self.predicate()?;
+            self.expect_op(")")?;
+            return Ok(predicate);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Predicate, String> {
+        let attribute = match self.advance() {
+            Some(Token::Ident(attribute)) => attribute.clone(),
+            other => return Err(format!("expected a glyph attribute, found {other:?}")),
+        };
+        // reject unknown attributes even if there are no glyphs to test
+        glyph_attribute(&Glyph::default(), &attribute)?;
+
+        let Some(comparison) = self.comparison_op() else {
+            // a bare attribute, e.g. $[export]
+            return Ok(Predicate::Compare {
+                attribute,
+                comparison: Comparison::Eq,
+                value: Value::Bool(true),
+                case_insensitive: false,
+            });
+        };
+        let case_insensitive = self.modifiers()?;
+        let value = self.value()?;
+        Ok(Predicate::Compare {
+            attribute,
+            comparison,
+            value,
+            case_insensitive,
+        })
+    }
+
+    fn comparison_op(&mut self) -> Option<Comparison> {
+        let comparison = match self.peek()? {
+            Token::Op("==" | "=") => Comparison::Eq,
+            Token::Op("!=" | "<>") => Comparison::Ne,
+            Token::Op("<") => Comparison::Lt,
+            Token::Op("<=") => Comparison::Le,
+            Token::Op(">") => Comparison::Gt,
+            Token::Op(">=") => Comparison::Ge,
+            Token::Ident(keyword) => match keyword.to_ascii_lowercase().as_str() {
+                "like" => Comparison::Like,
+                "beginswith" => Comparison::BeginsWith,
+                "endswith" => Comparison::EndsWith,
+                "contains" => Comparison::Contains,
+                "in" => Comparison::In,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(comparison)
+    }
+
+    /// String comparison options, e.g. like[c]; only c, case insensitive, affects us
+    fn modifiers(&mut self) -> Result<bool, String> {
+        if !self.eat_op("[") {
+            return Ok(false);
+        }
+        let options = match self.advance() {
+            Some(Token::Ident(options)) => options.clone(),
+            other => return Err(format!("expected comparison options, found {other:?}")),
+        };
+        self.expect_op("]")?;
+        Ok(options.contains('c'))
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.advance() {
+            Some(Token::Str(s)) => Ok(Value::Str(s.clone())),
+            Some(Token::Num(n)) => Ok(Value::Num(*n)),
+            Some(Token::Op("-")) => match self.advance() {
+                Some(Token::Num(n)) => Ok(Value::Num(-n)),
+                other => Err(format!("expected a number, found {other:?}")),
+            },
+            Some(Token::Op("{")) => {
+                let mut items = Vec::new();
+                if !self.eat_op("}") {
+                    loop {
+                        items.push(self.value()?);
+                        if self.eat_op("}") {
+                            break;
+                        }
+                        self.expect_op(",")?;
+                    }
+                }
+                Ok(Value::List(items))
+            }
+            Some(Token::Ident(keyword)) => match keyword.to_ascii_lowercase().as_str() {
+                "true" | "yes" => Ok(Value::Bool(true)),
+                "false" | "no" => Ok(Value::Bool(false)),
+                _ => Err(format!("expected a value, found {keyword}")),
+            },
+            other => Err(format!("expected a value, found {other:?}")),
+        }
+    }
+
+    fn number(&mut self, values: &BTreeMap<String, OrderedFloat<f64>>) -> Result<f64, String> {
+        let mut lhs = self.term(values)?;
+        loop {
+            if self.eat_op("+") {
+                lhs += self.term(values)?;
+            } else if self.eat_op("-") {
+                lhs -= self.term(values)?;
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    fn term(&mut self, values: &BTreeMap<String, OrderedFloat<f64>>) -> Result<f64, String> {
+        let mut lhs = self.factor(values)?;
+        loop {
+            if self.eat_op("*") {
+                lhs *= self.factor(values)?;
+            } else if self.eat_op("/") {
+                lhs /= self.factor(values)?;
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    fn factor(&mut self, values: &BTreeMap<String, OrderedFloat<f64>>) -> Result<f64, String> {
+        match self.advance() {
+            Some(Token::Num(n)) => Ok(*n),
+            Some(Token::Ident(name)) => values
+                .get(name)
+                .map(|v| v.into_inner())
+                .ok_or_else(|| format!("no number named '{name}'")),
+            Some(Token::Op("-")) => Ok(-self.factor(values)?),
+            Some(Token::Op("(")) => {
+                let value = self.number(values)?;
+                self.expect_op(")")?;
+                Ok(value)
+            }
+            other => Err(format!("expected a number, found {other:?}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use glyphs_reader::Font;
+
+    use super::expand_tokens;
+
+    fn font() -> Font {
+        Font::load(Path::new("../resources/testdata/glyphs3/FeaTokens.glyphs")).unwrap()
+    }
+
+    fn expand(code: &str) -> String {
+        expand_tokens(&font(), code).unwrap()
+    }
+
+    #[test]
+    fn glyph_predicates() {
+        // b isn't exported so never matches
+        assert_eq!(
+            vec!["one", "A a", "a.sc", "A a", "one", ""],
+            vec![
+                expand("$[category == \"Number\"]"),
+                expand("$[category == 'Letter' && !(name endswith '.sc')]"),
+                expand("$[name LIKE \"?.*\"]"),
+                expand("$[name like[c] 'a' or unicode == \"0041\"]"),
+                expand("$[name in {\"one\", \"two\"}]"),
+                expand("$[countOfUnicodes > 1]"),
+            ]
+        );
+    }
+
+    #[test]
+    fn number_values() {
+        assert_eq!(
+            vec![
+                "pos A A (wght=400d:10 wght=700d:30);",
+                "pos a a (wght=400d:21 wght=700d:61);",
+                "pos one one 5;",
+                "pos one one 3;",
+            ],
+            vec![
+                expand("pos A A ${padding};"),
+                expand("pos a a ${padding * 2 + 1};"),
+                expand("pos one one ${tracking};"),
+                expand("pos one one ${(tracking + 1) / 2};"),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_other_dollars_alone() {
+        assert_eq!("# costs $5", expand("# costs $5"));
+    }
+
+    #[test]
+    fn skips_comments_and_strings() {
+        assert_eq!(
+            vec![
+                "# sub $[category == \"Number\"] by ${margin\nsub one by one;",
+                "sub A by A; # ${padding}",
+                "name \"$[broken ${\";",
+            ],
+            vec![
+                expand("# sub $[category == \"Number\"] by ${margin\nsub $[category == \"Number\"] by one;"),
+                expand("sub $[name == 'A'] by A; # ${padding}"),
+                expand("name \"$[broken ${\";"),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_tokens() {
+        let font = font();
+        for code in [
+            "$[category == \"Letter\"",
+            "$[colour == \"red\"]",
+            "${margin}",
+            "${padding +}",
+        ] {
+            assert!(expand_tokens(&font, code).is_err(), "{code}");
+        }
+    }
+}
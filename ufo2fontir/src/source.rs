@@ -14,9 +14,10 @@ use fontdrasil::{
 use fontir::{
     error::{Error, WorkError},
     ir::{
-        AnchorBuilder, FeaturesSource, GlobalMetric, GlobalMetrics, GlyphOrder, KernGroup,
-        KernSide, KerningGroups, KerningInstance, NameBuilder, NameKey, NamedInstance,
-        PostscriptNames, StaticMetadata, DEFAULT_VENDOR_ID,
+        AnchorBuilder, FeatureWriterMode, FeaturesSource, GlobalMetric, GlobalMetrics, GlyphFilter,
+        GlyphFilterKind, GlyphOrder, GlyphSelection, KernGroup, KernSide, KerningGroups,
+        KerningInstance, NameBuilder, NameKey, NamedInstance, PostscriptNames, StaticMetadata,
+        TransformOrigin, Transformation, DEFAULT_VENDOR_ID,
     },
     orchestration::{Context, Flags, IrWork, WorkId},
     source::{Input, Source},
@@ -34,10 +35,18 @@ use write_fonts::{
     OtRound,
 };
 
-use crate::toir::{master_locations, to_design_location, to_ir_axes, to_ir_glyph};
+use crate::toir::{
+    master_locations, to_design_location, to_ir_axes, to_ir_glyph, to_ir_woff_metadata,
+};
 
 const UFO_KERN1_PREFIX: &str = "public.kern1.";
 const UFO_KERN2_PREFIX: &str = "public.kern2.";
+const UFO_VKERN1_PREFIX: &str = "public.vkern1.";
+const UFO_VKERN2_PREFIX: &str = "public.vkern2.";
+/// Vertical kerning in lib.plist, shaped like kerning.plist
+const UFO_VERTICAL_KERNING: &str = "public.verticalKerning";
+const UFO2FT_FEATURE_WRITERS: &str = "com.github.googlei18n.ufo2ft.featureWriters";
+const UFO2FT_FILTERS: &str = "com.github.googlei18n.ufo2ft.filters";
 
 pub struct DesignSpaceIrSource {
     designspace_or_ufo: PathBuf,
@@ -582,6 +591,170 @@ fn glyph_order(
     Ok(glyph_order)
 }
 
+/// The mode of the kern and mark feature writers, if configured in the lib
+///
+/// See <https://github.com/googlefonts/ufo2ft/blob/main/Lib/ufo2ft/featureWriters/__init__.py>
+fn feature_writer_modes(
+    lib_plist: &plist::Dictionary,
+) -> Result<(FeatureWriterMode, FeatureWriterMode), WorkError> {
+    let mut kern_mode = FeatureWriterMode::default();
+    let mut mark_mode = FeatureWriterMode::default();
+    let Some(writers) = lib_plist.get(UFO2FT_FEATURE_WRITERS) else {
+        return Ok((kern_mode, mark_mode));
+    };
+    let writers = writers.as_array().ok_or_else(|| {
+        WorkError::ParseError(
+            PathBuf::from("lib.plist"),
+            format!("{UFO2FT_FEATURE_WRITERS} isn't an array"),
+        )
+    })?;
+    for writer in writers.iter().filter_map(|w| w.as_dictionary()) {
+        let Some(class) = writer.get("class").and_then(|c| c.as_string()) else {
+            continue;
+        };
+        let mode = match writer
+            .get("options")
+            .and_then(|o| o.as_dictionary())
+            .and_then(|o| o.get("mode"))
+            .and_then(|m| m.as_string())
+        {
+            Some("append") => FeatureWriterMode::Append,
+            Some("skip") | None => FeatureWriterMode::Skip,
+            Some(other) => {
+                warn!("{class} has unrecognized mode '{other}', using skip");
+                FeatureWriterMode::Skip
+            }
+        };
+        // glyphsLib substitutes its own subclasses, e.g. ContextualMarkFeatureWriter
+        if class.ends_with("KernFeatureWriter") {
+            kern_mode = mode;
+        } else if class.ends_with("MarkFeatureWriter") {
+            mark_mode = mode;
+        }
+    }
+    Ok((kern_mode, mark_mode))
+}
+
+fn filter_error(message: String) -> WorkError {
+    WorkError::ParseError(PathBuf::from("lib.plist"), message)
+}
+
+fn glyph_names(
+    filter: &str,
+    key: &str,
+    value: &plist::Value,
+) -> Result<BTreeSet<GlyphName>, WorkError> {
+    value
+        .as_array()
+        .ok_or_else(|| filter_error(format!("{filter} {key} isn't an array")))?
+        .iter()
+        .map(|name| {
+            name.as_string()
+                .map(GlyphName::new)
+                .ok_or_else(|| filter_error(format!("{filter} {key} has a non-string entry")))
+        })
+        .collect()
+}
+
+fn transformation(kwargs: Option<&plist::Dictionary>) -> Result<Transformation, WorkError> {
+    let mut transformation = Transformation::default();
+    let Some(kwargs) = kwargs else {
+        return Ok(transformation);
+    };
+    for (key, value) in kwargs.iter() {
+        let number = value
+            .as_real()
+            .or_else(|| value.as_signed_integer().map(|v| v as f64))
+            .ok_or_else(|| filter_error(format!("transformations {key} isn't a number")))?;
+        match key.as_str() {
+            "OffsetX" => transformation.offset_x = number.into(),
+            "OffsetY" => transformation.offset_y = number.into(),
+            "ScaleX" => transformation.scale_x = number.into(),
+            "ScaleY" => transformation.scale_y = number.into(),
+            "Slant" => transformation.slant = number.into(),
+            "Origin" => {
+                transformation.origin = match number as i64 {
+                    0 => TransformOrigin::CapHeight,
+                    1 => TransformOrigin::HalfCapHeight,
+                    2 => TransformOrigin::XHeight,
+                    3 => TransformOrigin::HalfXHeight,
+                    4 => TransformOrigin::Baseline,
+                    _ => {
+                        return Err(filter_error(format!(
+                            "transformations has unrecognized Origin {number}"
+                        )))
+                    }
+                }
+            }
+            _ => warn!("transformations has unrecognized option {key}, ignoring it"),
+        }
+    }
+    Ok(transformation)
+}
+
+/// The filters ufo2ft would run, in the order it would run them
+///
+/// See <https://github.com/googlefonts/ufo2ft/blob/main/Lib/ufo2ft/filters/__init__.py>
+fn glyph_filters(lib_plist: &plist::Dictionary) -> Result<Vec<GlyphFilter>, WorkError> {
+    let Some(filters) = lib_plist.get(UFO2FT_FILTERS) else {
+        return Ok(Vec::new());
+    };
+    let filters = filters
+        .as_array()
+        .ok_or_else(|| filter_error(format!("{UFO2FT_FILTERS} isn't an array")))?;
+    let mut glyph_filters = Vec::new();
+    for filter in filters {
+        let filter = filter
+            .as_dictionary()
+            .ok_or_else(|| filter_error(format!("{UFO2FT_FILTERS} has a non-dictionary entry")))?;
+        let name = filter
+            .get("name")
+            .and_then(|n| n.as_string())
+            .ok_or_else(|| filter_error(format!("{UFO2FT_FILTERS} has a filter without a name")))?;
+        if let Some(namespace) = filter.get("namespace").and_then(|n| n.as_string()) {
+            if namespace != "ufo2ft.filters" {
+                warn!("Unable to run filter {name} from {namespace}, skipping it");
+                continue;
+            }
+        }
+        let kwargs = filter.get("kwargs").and_then(|k| k.as_dictionary());
+        let kind = match name {
+            "decomposeComponents" => GlyphFilterKind::DecomposeComponents,
+            "decomposeTransformedComponents" => GlyphFilterKind::DecomposeTransformedComponents,
+            "flattenComponents" => GlyphFilterKind::FlattenComponents,
+            "propagateAnchors" => GlyphFilterKind::PropagateAnchors,
+            "sortContours" => GlyphFilterKind::SortContours,
+            "transformations" => GlyphFilterKind::Transformations(transformation(kwargs)?),
+            "dottedCircle" => GlyphFilterKind::DottedCircle,
+            _ => {
+                warn!("Filter {name} is not supported, skipping it");
+                continue;
+            }
+        };
+        let mut glyph_filter = GlyphFilter::new(kind);
+        glyph_filter.pre = filter
+            .get("pre")
+            .and_then(|p| p.as_boolean())
+            .unwrap_or_default();
+        glyph_filter.glyphs = match (filter.get("include"), filter.get("exclude")) {
+            (None, None) => GlyphSelection::All,
+            (Some(include), None) => {
+                GlyphSelection::Include(glyph_names(name, "include", include)?)
+            }
+            (None, Some(exclude)) => {
+                GlyphSelection::Exclude(glyph_names(name, "exclude", exclude)?)
+            }
+            (Some(_), Some(_)) => {
+                return Err(filter_error(format!(
+                    "{name} has both include and exclude, they are mutually exclusive"
+                )))
+            }
+        };
+        glyph_filters.push(glyph_filter);
+    }
+    Ok(glyph_filters)
+}
+
 fn postscript_names(lib_plist: &plist::Dictionary) -> Result<PostscriptNames, WorkError> {
     let postscript_names = match lib_plist.get("public.postscriptNames") {
         Some(value) => {
@@ -826,6 +999,10 @@ impl Work<Context, WorkId, WorkError> for StaticMetadataWork {
                     }),
                     location: to_design_location(&tags_by_name, &inst.location)
                         .to_user(&axes_by_tag),
+                    family_name: inst.familyname.clone(),
+                    postscript_name: inst.postscriptfontname.clone(),
+                    style_map_family_name: inst.stylemapfamilyname.clone(),
+                    style_map_style_name: inst.stylemapstylename.clone(),
                 }
             })
             .collect();
@@ -912,6 +1089,14 @@ impl Work<Context, WorkId, WorkError> for StaticMetadataWork {
             try_parse_date(font_info_at_default.open_type_head_created.as_ref())
                 .or(static_metadata.misc.created);
 
+        (
+            static_metadata.misc.kern_writer_mode,
+            static_metadata.misc.mark_writer_mode,
+        ) = feature_writer_modes(&lib_plist)?;
+        static_metadata.misc.glyph_filters = glyph_filters(&lib_plist)?;
+
+        static_metadata.misc.woff = to_ir_woff_metadata(font_info_at_default);
+
         context.preliminary_glyph_order.set(glyph_order);
         context.static_metadata.set(static_metadata);
         Ok(())
@@ -1193,51 +1378,76 @@ impl Work<Context, WorkId, WorkError> for FeatureWork {
     }
 }
 
+/// Whether the UFO has any kerning, horizontal or vertical
+fn has_kerning(ufo_dir: &Path) -> Result<bool, WorkError> {
+    let data_request = norad::DataRequest::none().kerning(true).lib(true);
+    let font = norad::Font::load_requested_data(ufo_dir, data_request)
+        .map_err(|e| WorkError::ParseError(ufo_dir.to_path_buf(), format!("{e}")))?;
+    Ok(!font.kerning.is_empty() || font.lib.contains_key(UFO_VERTICAL_KERNING))
+}
+
+/// Returns the (horizontal, vertical) kerning groups of a source
+#[allow(clippy::type_complexity)]
 fn kerning_groups_for(
     designspace_dir: &Path,
     glyph_order: &GlyphOrder,
     source: &norad::designspace::Source,
-) -> Result<BTreeMap<KernGroup, BTreeSet<GlyphName>>, WorkError> {
+) -> Result<
+    (
+        BTreeMap<KernGroup, BTreeSet<GlyphName>>,
+        BTreeMap<KernGroup, BTreeSet<GlyphName>>,
+    ),
+    WorkError,
+> {
     let ufo_dir = designspace_dir.join(&source.filename);
     let data_request = norad::DataRequest::none().groups(true);
-    Ok(norad::Font::load_requested_data(&ufo_dir, data_request)
+    let groups = norad::Font::load_requested_data(&ufo_dir, data_request)
         .map_err(|e| WorkError::ParseError(ufo_dir, format!("{e}")))?
-        .groups
-        .into_iter()
-        .filter_map(|(group_name, entries)| {
-            KernGroup::from_group_name(group_name.as_str())
-                .filter(|_| !entries.is_empty())
-                .map(|name| (name, entries))
-        })
-        .filter_map(|(group_name, entries)| {
-            let members: BTreeSet<_> = entries
-                .into_iter()
-                .filter_map(|glyph_name| {
-                    let glyph_name = GlyphName::new(glyph_name);
-                    if glyph_order.contains(&glyph_name) {
-                        Some(glyph_name)
-                    } else {
-                        debug!(
-                            "{} kerning group '{}' references non-existent glyph '{}'; ignoring",
-                            source.filename, group_name, glyph_name
-                        );
-                        None
-                    }
-                })
-                .collect();
-            if !members.is_empty() {
-                Some((group_name, members))
-            } else {
-                None
-            }
-        })
-        .collect())
+        .groups;
+    let to_kern_groups = |parse: fn(&str) -> Option<KernGroup>| -> BTreeMap<_, _> {
+        groups
+            .iter()
+            .filter_map(|(group_name, entries)| {
+                parse(group_name.as_str())
+                    .filter(|_| !entries.is_empty())
+                    .map(|name| (name, entries))
+            })
+            .filter_map(|(group_name, entries)| {
+                let members: BTreeSet<_> = entries
+                    .iter()
+                    .filter_map(|glyph_name| {
+                        let glyph_name = GlyphName::new(glyph_name);
+                        if glyph_order.contains(&glyph_name) {
+                            Some(glyph_name)
+                        } else {
+                            debug!(
+                                "{} kerning group '{}' references non-existent glyph '{}'; ignoring",
+                                source.filename, group_name, glyph_name
+                            );
+                            None
+                        }
+                    })
+                    .collect();
+                if !members.is_empty() {
+                    Some((group_name, members))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    };
+    Ok((
+        to_kern_groups(KernGroup::from_group_name),
+        to_kern_groups(KernGroup::from_vertical_group_name),
+    ))
 }
 
 /// UFO specific behaviour for kern groups
 trait KernGroupExt {
     fn from_group_name(name: &str) -> Option<KernGroup>;
 
+    fn from_vertical_group_name(name: &str) -> Option<KernGroup>;
+
     // used when computing the 'reverse groups'
     fn side_ord(&self) -> u8;
 }
@@ -1252,6 +1462,15 @@ impl KernGroupExt for KernGroup {
             })
     }
 
+    fn from_vertical_group_name(name: &str) -> Option<KernGroup> {
+        name.strip_prefix(UFO_VKERN1_PREFIX)
+            .map(|name| Self::Side1(name.into()))
+            .or_else(|| {
+                name.strip_prefix(UFO_VKERN2_PREFIX)
+                    .map(|name| Self::Side2(name.into()))
+            })
+    }
+
     fn side_ord(&self) -> u8 {
         match self {
             KernGroup::Side1(_) => 1,
@@ -1289,8 +1508,11 @@ impl Work<Context, WorkId, WorkError> for KerningGroupWork {
 
         // Based on discussion on https://github.com/googlefonts/ufo2ft/pull/635, take the groups of
         // the default master as the authoritative source on groups
+        let (groups, vertical_groups) =
+            kerning_groups_for(designspace_dir, glyph_order.as_ref(), default_master)?;
         let mut kerning_groups = KerningGroups {
-            groups: kerning_groups_for(designspace_dir, glyph_order.as_ref(), default_master)?,
+            groups,
+            vertical_groups,
             ..Default::default()
         };
 
@@ -1340,14 +1562,24 @@ impl Work<Context, WorkId, WorkError> for KerningGroupWork {
             }
         }
 
-        for source in self
+        // Like ufo2ft, a source without kerning data is sparse so the pairs it doesn't kern are
+        // interpolated rather than zero. Sparse layer sources don't contribute kerning at all.
+        for (idx, source) in self
             .designspace
             .sources
             .iter()
-            .filter(|s| !is_glyph_only(s))
+            .enumerate()
+            .filter(|(_, s)| !is_glyph_only(s))
         {
             let pos = master_locations.get(source.name.as_ref().unwrap()).unwrap();
             kerning_groups.locations.insert(pos.clone());
+            if idx != default_master_idx && !has_kerning(&designspace_dir.join(&source.filename))? {
+                debug!(
+                    "{} has no kerning, its pairs will be interpolated",
+                    source.name.as_ref().unwrap()
+                );
+                kerning_groups.sparse_locations.insert(pos.clone());
+            }
         }
 
         context.kerning_groups.set(kerning_groups);
@@ -1394,9 +1626,9 @@ impl Work<Context, WorkId, WorkError> for KerningInstanceWork {
             .unwrap();
 
         let ufo_dir = designspace_dir.join(&source.filename);
-        let data_request = norad::DataRequest::none().kerning(true);
+        let data_request = norad::DataRequest::none().kerning(true).lib(true);
         let font = norad::Font::load_requested_data(&ufo_dir, data_request)
-            .map_err(|e| WorkError::ParseError(ufo_dir, format!("{e}")))?;
+            .map_err(|e| WorkError::ParseError(ufo_dir.clone(), format!("{e}")))?;
 
         let resolve = |name: &norad::Name, group_prefix: &str| {
             if let Some(group_name) = KernGroup::from_group_name(name.as_str()) {
@@ -1444,6 +1676,65 @@ impl Work<Context, WorkId, WorkError> for KerningInstanceWork {
             *kerns.kerns.entry((side1, side2)).or_default() = (adjustment as f32).into();
         }
 
+        if let Some(vertical_kerning) = font.lib.get(UFO_VERTICAL_KERNING) {
+            let Some(vertical_kerning) = vertical_kerning.as_dictionary() else {
+                return Err(WorkError::ParseError(
+                    ufo_dir.join("lib.plist"),
+                    format!("{UFO_VERTICAL_KERNING} must be a dictionary"),
+                ));
+            };
+            let resolve_vertical = |name: &str, group_prefix: &str| {
+                if let Some(group_name) = KernGroup::from_vertical_group_name(name) {
+                    if !name.starts_with(group_prefix) {
+                        warn!("'{name}' should have prefix {group_prefix}; ignored");
+                        return None;
+                    }
+                    if !groups.vertical_groups.contains_key(&group_name) {
+                        warn!("'{name}' is not a valid vertical group name; ignored");
+                        return None;
+                    }
+                    Some(KernSide::Group(group_name))
+                } else {
+                    let glyph_name = GlyphName::from(name);
+                    if !glyph_order.contains(&glyph_name) {
+                        warn!("'{name}' refers to a non-existent glyph; ignored");
+                        return None;
+                    }
+                    Some(KernSide::Glyph(glyph_name))
+                }
+            };
+            for (side1, side2, adjustment) in vertical_kerning
+                .iter()
+                .filter_map(|(side1, kerns)| kerns.as_dictionary().map(|kerns| (side1, kerns)))
+                .flat_map(|(side1, kerns)| {
+                    kerns
+                        .iter()
+                        .map(move |(side2, adjustment)| (side1, side2, adjustment))
+                })
+            {
+                let Some(adjustment) = adjustment
+                    .as_real()
+                    .or_else(|| adjustment.as_signed_integer().map(|v| v as f64))
+                else {
+                    warn!("Vertical kerning '{side1}', '{side2}' is not a number; ignoring");
+                    continue;
+                };
+                let (Some(side1), Some(side2)) = (
+                    resolve_vertical(side1, UFO_VKERN1_PREFIX),
+                    resolve_vertical(side2, UFO_VKERN2_PREFIX),
+                ) else {
+                    warn!(
+                        "{} vertical kerning unable to resolve at least one of '{side1}', '{side2}'; ignoring",
+                        source.name.as_ref().unwrap(),
+                    );
+                    continue;
+                };
+                kerns
+                    .vertical_kerns
+                    .insert((side1, side2), (adjustment as f32).into());
+            }
+        }
+
         debug!("{:?} has {} kern entries", self.location, kerns.kerns.len());
         context.kerning_at.set(kerns);
         Ok(())
@@ -2024,6 +2315,68 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sources_without_kerning_are_sparse() {
+        let (_, context) = build_kerning("sparse_kern.designspace");
+        let groups = context.kerning_groups.get();
+        assert_eq!(4, groups.locations.len());
+        // Only SemiBold, which has no kerning, is sparse. Medium lacks b but kerns.
+        let wght = context.static_metadata.get().axes[0].clone();
+        assert_eq!(
+            vec![UserCoord::new(600.0)],
+            groups
+                .sparse_locations
+                .iter()
+                .map(|loc| only_coord(loc).to_user(&wght.converter))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn reads_vertical_kerning() {
+        let (_, context) = build_kerning("vertical_kern.designspace");
+        let groups = context.kerning_groups.get();
+        assert!(groups.groups.is_empty(), "{:?}", groups.groups);
+        assert_eq!(
+            vec![
+                (KernGroup::Side1("bars".into()), vec!["bar", "element_of"]),
+                (KernGroup::Side2("plus".into()), vec!["plus"]),
+            ],
+            groups
+                .vertical_groups
+                .iter()
+                .map(|(name, entries)| (
+                    name.clone(),
+                    entries.iter().map(|e| e.as_str()).collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        let kerns = context.kerning_at.all();
+        assert_eq!(1, kerns.len());
+        let kerns = &kerns[0].1;
+        assert!(kerns.kerns.is_empty(), "{:?}", kerns.kerns);
+        assert_eq!(
+            vec![
+                (
+                    KernSide::Glyph("plus".into()),
+                    KernSide::Glyph("bar".into()),
+                    12.5
+                ),
+                (
+                    KernSide::Group(KernGroup::Side1("bars".into())),
+                    KernSide::Group(KernGroup::Side2("plus".into())),
+                    -30.0
+                ),
+            ],
+            kerns
+                .vertical_kerns
+                .iter()
+                .map(|((side1, side2), value)| (side1.clone(), side2.clone(), value.0))
+                .collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn captures_anchors() {
         let base_name = "A".into();
@@ -2233,4 +2586,96 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn reads_feature_writer_modes() {
+        let writer = |class: &str, mode: &str| {
+            let mut options = plist::Dictionary::new();
+            options.insert("mode".to_string(), mode.into());
+            let mut writer = plist::Dictionary::new();
+            writer.insert("class".to_string(), class.into());
+            writer.insert("options".to_string(), options.into());
+            plist::Value::Dictionary(writer)
+        };
+        let mut lib_plist = plist::Dictionary::new();
+        assert_eq!(
+            (FeatureWriterMode::Skip, FeatureWriterMode::Skip),
+            feature_writer_modes(&lib_plist).unwrap()
+        );
+
+        lib_plist.insert(
+            UFO2FT_FEATURE_WRITERS.to_string(),
+            plist::Value::Array(vec![
+                writer("KernFeatureWriter", "append"),
+                writer("ContextualMarkFeatureWriter", "skip"),
+            ]),
+        );
+        assert_eq!(
+            (FeatureWriterMode::Append, FeatureWriterMode::Skip),
+            feature_writer_modes(&lib_plist).unwrap()
+        );
+    }
+
+    #[test]
+    fn reads_glyph_filters() {
+        let filter = |name: &str, extra: &[(&str, plist::Value)]| {
+            let mut filter = plist::Dictionary::new();
+            filter.insert("name".to_string(), name.into());
+            for (key, value) in extra {
+                filter.insert(key.to_string(), value.clone());
+            }
+            plist::Value::Dictionary(filter)
+        };
+        let names = |names: &[&str]| {
+            plist::Value::Array(names.iter().map(|n| plist::Value::from(*n)).collect())
+        };
+        let mut kwargs = plist::Dictionary::new();
+        kwargs.insert("Slant".to_string(), plist::Value::Real(12.5));
+        kwargs.insert("Origin".to_string(), plist::Value::Integer(2.into()));
+        let mut lib_plist = plist::Dictionary::new();
+        lib_plist.insert(
+            UFO2FT_FILTERS.to_string(),
+            plist::Value::Array(vec![
+                filter("propagateAnchors", &[("pre", true.into())]),
+                filter("reverseContourDirection", &[]),
+                filter("transformations", &[("kwargs", kwargs.into())]),
+                filter("decomposeComponents", &[("include", names(&["aacute"]))]),
+                filter("sortContours", &[("exclude", names(&["a", "b"]))]),
+            ]),
+        );
+
+        let mut propagate = GlyphFilter::new(GlyphFilterKind::PropagateAnchors);
+        propagate.pre = true;
+        let mut decompose = GlyphFilter::new(GlyphFilterKind::DecomposeComponents);
+        decompose.glyphs = GlyphSelection::Include(BTreeSet::from(["aacute".into()]));
+        let mut sort = GlyphFilter::new(GlyphFilterKind::SortContours);
+        sort.glyphs = GlyphSelection::Exclude(BTreeSet::from(["a".into(), "b".into()]));
+        assert_eq!(
+            vec![
+                propagate,
+                GlyphFilter::new(GlyphFilterKind::Transformations(Transformation {
+                    slant: 12.5.into(),
+                    origin: TransformOrigin::XHeight,
+                    ..Default::default()
+                })),
+                decompose,
+                sort,
+            ],
+            glyph_filters(&lib_plist).unwrap()
+        );
+    }
+
+    #[test]
+    fn include_and_exclude_are_mutually_exclusive() {
+        let mut filter = plist::Dictionary::new();
+        filter.insert("name".to_string(), "decomposeComponents".into());
+        filter.insert("include".to_string(), plist::Value::Array(vec!["a".into()]));
+        filter.insert("exclude".to_string(), plist::Value::Array(vec!["b".into()]));
+        let mut lib_plist = plist::Dictionary::new();
+        lib_plist.insert(
+            UFO2FT_FILTERS.to_string(),
+            plist::Value::Array(vec![filter.into()]),
+        );
+        assert!(glyph_filters(&lib_plist).is_err());
+    }
 }
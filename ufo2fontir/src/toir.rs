@@ -1,4 +1,4 @@
-use std::{collections::HashMap, path::PathBuf, str::FromStr};
+use std::{collections::HashMap, fmt::Write, path::PathBuf, str::FromStr};
 
 use fontdrasil::{
     coords::{CoordConverter, DesignCoord, DesignLocation, NormalizedLocation, UserCoord},
@@ -6,11 +6,14 @@ use fontdrasil::{
 };
 use fontir::{
     error::WorkError,
-    ir::{self, AnchorBuilder, GlyphPathBuilder},
+    ir::{self, AnchorBuilder, GlyphPathBuilder, WoffMetadata},
 };
 use kurbo::{Affine, BezPath};
 use log::trace;
-use norad::designspace::{self, Dimension};
+use norad::{
+    designspace::{self, Dimension},
+    fontinfo::{WoffAttributeDirection, WoffMetadataTextRecord},
+};
 use write_fonts::types::Tag;
 
 pub(crate) fn to_design_location(
@@ -72,6 +75,7 @@ fn to_ir_component(component: &norad::Component) -> ir::Component {
             component.transform.x_offset,
             component.transform.y_offset,
         ]),
+        location: NormalizedLocation::new(),
     }
 }
 
@@ -184,6 +188,178 @@ pub fn to_ir_glyph(
     glyph.build()
 }
 
+/// Collect the WOFF data from fontinfo
+///
+/// See <https://unifiedfontobject.org/versions/ufo3/fontinfo.plist/#woff-data>
+/// and <https://www.w3.org/TR/WOFF/#Metadata> for the XML this becomes.
+pub(crate) fn to_ir_woff_metadata(font_info: &norad::FontInfo) -> WoffMetadata {
+    let mut lines = Vec::new();
+    if let Some(unique_id) = &font_info.woff_metadata_unique_id {
+        lines.push(format!(
+            "  {}/>",
+            woff_tag("uniqueid", &[("id", Some(unique_id.id.as_str()))])
+        ));
+    }
+    if let Some(vendor) = &font_info.woff_metadata_vendor {
+        let attrs = [
+            ("name", Some(vendor.name.as_str())),
+            ("url", vendor.url.as_deref()),
+            ("dir", woff_dir(&vendor.dir)),
+            ("class", vendor.class.as_deref()),
+        ];
+        lines.push(format!("  {}/>", woff_tag("vendor", &attrs)));
+    }
+    if let Some(credits) = &font_info.woff_metadata_credits {
+        lines.push("  <credits>".to_string());
+        for credit in credits.credits.iter() {
+            let attrs = [
+                ("name", Some(credit.name.as_str())),
+                ("url", credit.url.as_deref()),
+                ("role", credit.role.as_deref()),
+                ("dir", woff_dir(&credit.dir)),
+                ("class", credit.class.as_deref()),
+            ];
+            lines.push(format!("    {}/>", woff_tag("credit", &attrs)));
+        }
+        lines.push("  </credits>".to_string());
+    }
+    if let Some(description) = &font_info.woff_metadata_description {
+        let attrs = [("url", description.url.as_deref())];
+        woff_text_element(&mut lines, "description", &attrs, &description.text);
+    }
+    if let Some(license) = &font_info.woff_metadata_license {
+        let attrs = [
+            ("url", license.url.as_deref()),
+            ("id", license.id.as_deref()),
+        ];
+        woff_text_element(&mut lines, "license", &attrs, &license.text);
+    }
+    if let Some(copyright) = &font_info.woff_metadata_copyright {
+        woff_text_element(&mut lines, "copyright", &[], &copyright.text);
+    }
+    if let Some(trademark) = &font_info.woff_metadata_trademark {
+        woff_text_element(&mut lines, "trademark", &[], &trademark.text);
+    }
+    if let Some(licensee) = &font_info.woff_metadata_licensee {
+        let attrs = [
+            ("name", Some(licensee.name.as_str())),
+            ("dir", woff_dir(&licensee.dir)),
+            ("class", licensee.class.as_deref()),
+        ];
+        lines.push(format!("  {}/>", woff_tag("licensee", &attrs)));
+    }
+    for extension in font_info.woff_metadata_extensions.iter().flatten() {
+        lines.push(format!(
+            "  {}>",
+            woff_tag("extension", &[("id", extension.id.as_deref())])
+        ));
+        for name in extension.names.iter() {
+            let text = woff_text("name", &name.text, &name.language, &name.dir, &name.class);
+            lines.push(format!("    {text}"));
+        }
+        for item in extension.items.iter() {
+            lines.push(format!(
+                "    {}>",
+                woff_tag("item", &[("id", item.id.as_deref())])
+            ));
+            for name in item.names.iter() {
+                let text = woff_text("name", &name.text, &name.language, &name.dir, &name.class);
+                lines.push(format!("      {text}"));
+            }
+            for value in item.values.iter() {
+                let text = woff_text(
+                    "value",
+                    &value.text,
+                    &value.language,
+                    &value.dir,
+                    &value.class,
+                );
+                lines.push(format!("      {text}"));
+            }
+            lines.push("    </item>".to_string());
+        }
+        lines.push("  </extension>".to_string());
+    }
+
+    let version = |version: Option<u32>| version.and_then(|v| u16::try_from(v).ok());
+    WoffMetadata {
+        major_version: version(font_info.woff_major_version),
+        minor_version: version(font_info.woff_minor_version),
+        xml: (!lines.is_empty()).then(|| {
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<metadata version=\"1.0\">\n{}\n</metadata>\n",
+                lines.join("\n")
+            )
+        }),
+    }
+}
+
+/// An element whose content is text records, e.g. copyright
+fn woff_text_element(
+    lines: &mut Vec<String>,
+    element: &str,
+    attrs: &[(&str, Option<&str>)],
+    records: &[WoffMetadataTextRecord],
+) {
+    lines.push(format!("  {}>", woff_tag(element, attrs)));
+    for record in records {
+        let text = woff_text(
+            "text",
+            &record.text,
+            &record.language,
+            &record.dir,
+            &record.class,
+        );
+        lines.push(format!("    {text}"));
+    }
+    lines.push(format!("  </{element}>"));
+}
+
+/// A text record, with its language, direction and class
+fn woff_text(
+    element: &str,
+    text: &str,
+    language: &Option<String>,
+    dir: &Option<WoffAttributeDirection>,
+    class: &Option<String>,
+) -> String {
+    let attrs = [
+        ("lang", language.as_deref()),
+        ("dir", woff_dir(dir)),
+        ("class", class.as_deref()),
+    ];
+    format!(
+        "{}>{}</{element}>",
+        woff_tag(element, &attrs),
+        xml_escape(text)
+    )
+}
+
+fn woff_dir(dir: &Option<WoffAttributeDirection>) -> Option<&'static str> {
+    dir.as_ref().map(|dir| match dir {
+        WoffAttributeDirection::LeftToRight => "ltr",
+        WoffAttributeDirection::RightToLeft => "rtl",
+    })
+}
+
+/// An unclosed start tag with whichever of `attrs` have a value
+fn woff_tag(element: &str, attrs: &[(&str, Option<&str>)]) -> String {
+    let mut tag = format!("<{element}");
+    for (attr, value) in attrs {
+        if let Some(value) = value {
+            write!(tag, " {attr}=\"{}\"", xml_escape(value)).unwrap();
+        }
+    }
+    tag
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
@@ -307,4 +483,61 @@ mod tests {
             Affine::new([0.4366, -0.4366, 0.4415, 0.4425, 282.0, 5.0])
         );
     }
+
+    #[test]
+    fn woff_metadata_from_fontinfo() {
+        let font_info: norad::FontInfo = plist::from_bytes(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+  <key>woffMajorVersion</key>
+  <integer>2</integer>
+  <key>woffMetadataUniqueID</key>
+  <dict><key>id</key><string>com.example.font</string></dict>
+  <key>woffMetadataCredits</key>
+  <dict>
+    <key>credits</key>
+    <array>
+      <dict><key>name</key><string>Ann</string><key>role</key><string>Design</string></dict>
+    </array>
+  </dict>
+  <key>woffMetadataCopyright</key>
+  <dict>
+    <key>text</key>
+    <array>
+      <dict><key>text</key><string>A &amp; B</string><key>language</key><string>en</string></dict>
+    </array>
+  </dict>
+</dict>
+</plist>"#
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            WoffMetadata {
+                major_version: Some(2),
+                minor_version: None,
+                xml: Some(
+                    r#"<?xml version="1.0" encoding="UTF-8"?>
+<metadata version="1.0">
+  <uniqueid id="com.example.font"/>
+  <credits>
+    <credit name="Ann" role="Design"/>
+  </credits>
+  <copyright>
+    <text lang="en">A &amp; B</text>
+  </copyright>
+</metadata>
+"#
+                    .to_string()
+                ),
+            },
+            to_ir_woff_metadata(&font_info)
+        );
+        assert_eq!(
+            WoffMetadata::default(),
+            to_ir_woff_metadata(&norad::FontInfo::default())
+        );
+    }
 }